@@ -0,0 +1,161 @@
+//! Backward error bounds and thresholds $\theta_m$ of the Padé approximants $r_m$ to the
+//! exponential, from which the thresholds of the `Float` implementations are derived.
+//!
+//! Following [Higham 2005] and [Al-Mohy, Higham 2009], the Padé approximant satisfies $r_m(A) =
+//! e^{A + \Delta A}$ with $\Delta A = h_{2m+1}(A)$, where
+//!
+//! \begin{equation}
+//!     h_{2m+1}(x) = \log\left(e^{-x} r_m(x)\right) = \sum^\infty_{i=2m+1} c_i x^i.
+//! \end{equation}
+//!
+//! For $\lVert A \rVert_1 \le \theta$, the relative backward error is bounded by
+//!
+//! \begin{equation}
+//!     \frac{\lVert \Delta A \rVert_1}{\lVert A \rVert_1} \le \frac{\tilde{h}_{2m+1}(\theta)}{\theta},
+//!     \quad \tilde{h}_{2m+1}(x) = \sum^\infty_{i=2m+1} \lvert c_i \rvert x^i,
+//! \end{equation}
+//!
+//! and $\theta_m$ is the largest $\theta$ for which the bound does not exceed a given tolerance,
+//! usually the unit roundoff. The coefficients $c_i$ follow from $r_m(x) = p_m(x)/p_m(-x)$ and
+//! the power series of $\log p_m(x)$, and the series is truncated after `SERIES_DEGREE` terms as
+//! in the references. All calculations are done in double precision, which suffices for the
+//! tolerances of double and single precision; thresholds for other precisions can be tabulated
+//! with these functions and provided through the `Float` trait.
+//!
+//! [Higham 2005]: https://doi.org/10.1137/04061101X
+//! [Al-Mohy, Higham 2009]: https://doi.org/10.1137/09074721X
+
+/// The degree after which the series of the backward error is truncated.
+pub const SERIES_DEGREE: usize = 150;
+
+/// The number of bisections for calculating $\theta_m$, which shrink the bracket below the
+/// resolution of double precision.
+const BISECTIONS: usize = 200;
+
+/// The coefficients $c_0, c_1, \dots, c_d$ of the series of the backward error function
+/// $h_{2m+1}$ of the Padé approximant of order `m`, up to `degree` $d$. The coefficients below
+/// $2m+1$ and those of even powers vanish.
+///
+/// NOTE: Panics if `m` is zero.
+pub fn pade_backward_error_series(m: u64, degree: usize) -> Vec<f64> {
+    assert!(m > 0, "Padé order has to be positive.");
+    let m = m as usize;
+
+    // Coefficients of the numerator p_m(x) = Σ b_k x^k with b_0 = 1.
+    let mut p = vec![0.0; degree + 1];
+    let mut b = 1.0;
+    for (k, p_k) in p.iter_mut().enumerate().take(m + 1) {
+        if k > 0 {
+            b *= (m + 1 - k) as f64 / ((2 * m + 1 - k) * k) as f64;
+        }
+        *p_k = b;
+    }
+
+    // log p_m(x) = Σ l_k x^k, from k l_k = k b_k - Σ_{j=1}^{k-1} j l_j b_{k-j}.
+    let mut l = vec![0.0; degree + 1];
+    for k in 1..=degree {
+        let sum: f64 = (1..k).map(|j| j as f64 * l[j] * p[k - j]).sum();
+        l[k] = p[k] - sum / k as f64;
+    }
+
+    // h(x) = -x + log p_m(x) - log p_m(-x)
+    let mut c = vec![0.0; degree + 1];
+    for (k, c_k) in c.iter_mut().enumerate().skip(1).step_by(2) {
+        *c_k = 2.0 * l[k];
+    }
+    if degree >= 1 {
+        c[1] -= 1.0;
+    }
+    // The leading coefficients cancel analytically.
+    for c_k in c.iter_mut().take((2 * m + 1).min(degree + 1)) {
+        *c_k = 0.0;
+    }
+    c
+}
+
+/// The bound $\tilde{h}_{2m+1}(\theta)/\theta$ on the relative backward error of the Padé
+/// approximant of order `m` for matrices of 1-norm at most `theta`.
+///
+/// NOTE: Panics if `m` is zero.
+pub fn pade_backward_error_bound(m: u64, theta: f64) -> f64 {
+    let c = pade_backward_error_series(m, SERIES_DEGREE);
+    if theta == 0.0 {
+        return 0.0;
+    }
+    let h = c.iter().rev().fold(0.0, |acc, &c_i| acc * theta + c_i.abs());
+    h / theta
+}
+
+/// The threshold $\theta_m$ of the Padé approximant of order `m`, i.e. the largest 1-norm for
+/// which the bound on the relative backward error does not exceed `tolerance`. For the unit
+/// roundoffs of double and single precision, this reproduces the thresholds of the `Float`
+/// implementations.
+///
+/// NOTE: Panics if `m` is zero or `tolerance` is not positive.
+pub fn pade_theta(m: u64, tolerance: f64) -> f64 {
+    assert!(tolerance > 0.0, "Tolerance has to be positive.");
+    let c = pade_backward_error_series(m, SERIES_DEGREE);
+    let bound = |theta: f64| c.iter().rev().fold(0.0, |acc, &c_i| acc * theta + c_i.abs()) / theta;
+
+    // The bound increases monotonically, so that θ_m can be bracketed and bisected.
+    let mut lower = 0.0;
+    let mut upper = 1.0;
+    while bound(upper) <= tolerance {
+        lower = upper;
+        upper *= 2.0;
+    }
+    for _ in 0..BISECTIONS {
+        let middle = 0.5 * (lower + upper);
+        if middle == lower || middle == upper {
+            break;
+        }
+        if bound(middle) <= tolerance {
+            lower = middle;
+        } else {
+            upper = middle;
+        }
+    }
+    lower
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::Float;
+
+    #[test]
+    fn leading_coefficient_agrees_with_closed_form() {
+        for &m in &[3, 5, 7, 9, 13] {
+            let c = crate::pade_backward_error_series(m, 2 * m as usize + 1);
+            assert_relative_eq!(c[2 * m as usize + 1].abs(), crate::pade_error_coefficient(m), max_relative=1e-12);
+        }
+    }
+
+    #[test]
+    fn thetas_reproduce_tables() {
+        let u_double = f64::unit_roundoff();
+        let u_single = f64::from(f32::unit_roundoff());
+
+        // The θ₁₃ of `f64` is the smaller one of Algorithm 5.1, the bound reproduces Algorithm 3.1.
+        let double = f64::THETAS.iter().filter(|&&(m, _)| m < 13).chain(&[(13, 5.371920351148152)]);
+        for &(m, theta) in double {
+            assert_relative_eq!(crate::pade_theta(m, u_double), theta, max_relative=1e-7);
+        }
+
+        for &(m, theta) in f32::THETAS {
+            assert_relative_eq!(crate::pade_theta(m, u_single), theta, max_relative=1e-7);
+        }
+    }
+
+    #[test]
+    fn unsupported_orders_have_no_threshold() {
+        assert_eq!(f64::THETAS.last().unwrap().0, f64::MAX_PADE_ORDER);
+        assert_eq!(f32::THETAS.last().unwrap().0, f32::MAX_PADE_ORDER);
+        assert!(f64::threshold(13).is_some() && f64::frechet_threshold(13).is_some());
+        assert!(f32::threshold(7).is_some() && f32::frechet_threshold(7).is_some());
+        assert_eq!(f32::threshold(9), None);
+        assert_eq!(f32::frechet_threshold(13), None);
+        assert_eq!(f64::threshold(11), None);
+    }
+}
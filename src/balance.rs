@@ -0,0 +1,129 @@
+//! Balancing of badly scaled matrices before exponentiation, following the scaling part of
+//! LAPACK's `gebal` and [Parlett, Reinsch 1969].
+//!
+//! A diagonal similarity transformation $B = D^{-1} A D$ with powers of the radix 2 on the
+//! diagonal of $D$ is chosen to make the off-diagonal 1-norms of corresponding rows and columns
+//! of $B$ approximately equal. Since
+//!
+//! \begin{equation}
+//!     e^A = D e^B D^{-1},
+//! \end{equation}
+//!
+//! the exponential of $A$ is recovered from the exponential of $B$ without rounding errors. A
+//! smaller norm of $B$ reduces the number of squarings and the backward error relative to
+//! $\lVert A \rVert_1$. Balancing is not always beneficial, see D. S. Watkins, *A case where
+//! balancing is harmful*, ETNA 23 (2006), and is only used when requested via
+//! `ExpmOptions::balance` and when it actually reduces the 1-norm.
+//!
+//! [Parlett, Reinsch 1969]: https://doi.org/10.1007/BF02165404
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    float::onenorm,
+    Float,
+};
+
+/// Balances the n×n matrix `a`, storing $D^{-1} A D$ in `balanced` and the diagonal of $D$ in
+/// `d`. Returns whether the 1-norm of the balanced matrix is smaller than that of `a`.
+pub(crate) fn balance<T, S1, S2, S3>(a: &ArrayBase<S1, Ix2>, balanced: &mut ArrayBase<S2, Ix2>, d: &mut ArrayBase<S3, Ix1>) -> bool
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+          S3: DataMut<Elem=T>,
+{
+    let two = T::from_f64(2.0).unwrap();
+    let four = T::from_f64(4.0).unwrap();
+    let threshold = T::from_f64(0.95).unwrap();
+    let n = d.len();
+
+    balanced.assign(a);
+    d.fill(T::one());
+    let mut is_converged = false;
+    while !is_converged {
+        is_converged = true;
+        for i in 0..n {
+            let mut c = balanced.column(i).fold(T::zero(), |acc, &x| acc + x.abs()) - balanced[(i, i)].abs();
+            let r = balanced.row(i).fold(T::zero(), |acc, &x| acc + x.abs()) - balanced[(i, i)].abs();
+            let s = c + r;
+            if c == T::zero() || r == T::zero() || !s.is_finite() {
+                continue;
+            }
+
+            let mut f = T::one();
+            while c < r / two {
+                f = f * two;
+                c = c * four;
+            }
+            while c > r * two {
+                f = f / two;
+                c = c / four;
+            }
+
+            // Here c is the column norm scaled by f², so that (c + r)/f is the sum of the scaled
+            // norms. Only scalings that reduce it noticeably are accepted, which ensures
+            // termination.
+            if (c + r) / f < threshold * s {
+                is_converged = false;
+                d[i] = d[i] * f;
+                balanced.row_mut(i).mapv_inplace(|x| x / f);
+                balanced.column_mut(i).mapv_inplace(|x| x * f);
+            }
+        }
+    }
+
+    onenorm(balanced) < onenorm(a)
+}
+
+/// Transforms the exponential $e^B$ of the balanced matrix in `v` back to $D e^B D^{-1}$.
+pub(crate) fn unbalance<T, S1, S2>(d: &ArrayBase<S1, Ix1>, v: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    for ((i, j), x) in v.indexed_iter_mut() {
+        *x = *x * d[i] / d[j];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    use crate::ExpmOptions;
+
+    #[test]
+    fn balancing_reduces_squarings() {
+        // A = D T D⁻¹ with an exactly representable D, so that exp(A) = D exp(T) D⁻¹.
+        let t = arr2(&[[-1.0, 2.0, 0.5], [0.5, -3.0, 1.0], [0.25, 0.25, -0.5]]);
+        let d = arr1(&[1.0, 2f64.powi(20), 2f64.powi(-20)]);
+        let scale = |m: &Array2<f64>| {
+            let mut m = m.clone();
+            for ((i, j), x) in m.indexed_iter_mut() {
+                *x *= d[i] / d[j];
+            }
+            m
+        };
+        let a = scale(&t);
+        let mut exp_t = Array2::<f64>::zeros((3, 3));
+        crate::expm(&t, &mut exp_t);
+        let expected = scale(&exp_t);
+
+        let mut b = Array2::<f64>::zeros((3, 3));
+        let unbalanced = crate::expm_with_opts(&a, &mut b, &ExpmOptions::default());
+        let options = ExpmOptions { balance: true, ..ExpmOptions::default() };
+        let balanced = crate::expm_with_opts(&a, &mut b, &options);
+
+        assert!(balanced.balanced);
+        assert!(balanced.squarings < unbalanced.squarings);
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-13);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! A bank of precomputed propagators $e^{2^{-j} \Delta t A}$ for the dyadic fractions of a time
+//! step $\Delta t$, from which propagators over arbitrary times are composed, e.g. for
+//! interactive tools that scrub through time.
+//!
+//! The finest propagator $e^{2^{-J} \Delta t A}$ is calculated with `expm`, and the coarser ones
+//! by repeated squaring, which is what the scaling and squaring algorithm does anyway. A time $t
+//! \ge 0$ is rounded to the grid $2^{-J} \Delta t \mathbb{Z}$, and written in binary as
+//!
+//! \begin{equation}
+//!     t = \Delta t \left( \sum_{i \ge 0} b_i 2^i + \sum_{j=1}^J c_j 2^{-j} \right),
+//! \end{equation}
+//!
+//! so that $e^{tA}$ is the product of the propagators of the set bits, which all commute. The
+//! propagators $e^{2^i \Delta t A}$ for the integer part are squared on demand and kept for later
+//! requests. A propagator thus costs at most $J + \log_2(t / \Delta t)$ matrix products, and its
+//! action on a vector as many matrix-vector products, independently of the norm of $A$.
+
+use ndarray::{
+    self,
+    linalg::general_mat_vec_mul,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    float::gemm,
+    Expm,
+    Float,
+};
+
+/// Storage for a bank of propagators of an n×n matrix at dyadic fractions and multiples of a time
+/// step.
+pub struct ExpmBank<T = f64> {
+    n: usize,
+    dt: T,
+    levels: usize,
+    expm: Expm<T>,
+    /// $e^{2^{-j} \Delta t A}$ for $j = 0, \dots, J$.
+    fine: Vec<Array2<T>>,
+    /// $e^{2^i \Delta t A}$ for $i = 1, 2, \dots$, as far as requested so far.
+    coarse: Vec<Array2<T>>,
+    work: Array2<T>,
+    y: Array1<T>,
+}
+
+impl<T: Float> ExpmBank<T> {
+    /// Allocates all space for a bank of propagators of n×n matrices.
+    pub fn new(n: usize) -> Self {
+        ExpmBank {
+            n,
+            dt: T::zero(),
+            levels: 0,
+            expm: Expm::new(n),
+            fine: Vec::new(),
+            coarse: Vec::new(),
+            work: Array2::zeros((n, n)),
+            y: Array1::zeros(n),
+        }
+    }
+
+    /// Precompute the propagators $e^{2^{-j} \Delta t A}$ of the n×n matrix `a` for the time step
+    /// `dt` and $j = 0, \dots,$ `levels`, discarding all previously stored propagators.
+    ///
+    /// NOTE: Panics if `dt` is not positive, if `levels` is 64 or more, or if `a` is not n×n,
+    /// where n is the dimension of the `ExpmBank` object `build` is called on.
+    pub fn build<S>(&mut self, a: &ArrayBase<S, Ix2>, dt: T, levels: usize)
+        where S: Data<Elem=T>,
+    {
+        assert!(dt > T::zero(), "Time step `dt` has to be positive.");
+        assert!(levels < 64, "At most 63 levels are supported.");
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `ExpmBank` struct.");
+
+        let n = self.n;
+        self.dt = dt;
+        self.levels = levels;
+        self.coarse.clear();
+        self.fine.resize_with(levels + 1, || Array2::zeros((n, n)));
+
+        let finest = dt / T::from_f64(2.0).unwrap().powi(levels as i32);
+        self.work.zip_mut_with(a, |x, &y| *x = finest * y);
+        let mut propagator = std::mem::replace(&mut self.fine[levels], Array2::zeros((0, 0)));
+        self.expm.expm(&self.work, &mut propagator);
+        self.fine[levels] = propagator;
+
+        for j in (0..levels).rev() {
+            let (coarser, finer) = self.fine.split_at_mut(j + 1);
+            gemm(T::one(), &finer[0], &finer[0], T::zero(), &mut coarser[j]);
+        }
+    }
+
+    /// The resolution $2^{-J} \Delta t$ of the times for which propagators are composed.
+    pub fn resolution(&self) -> T {
+        self.dt / T::from_f64(2.0).unwrap().powi(self.levels as i32)
+    }
+
+    /// Compose the propagator $e^{tA}$ for the time `t` rounded to the resolution of the bank,
+    /// storing it in `b` and returning the rounded time.
+    ///
+    /// NOTE: Panics if `build` has not been called, if `t` is negative or too large for the
+    /// resolution, or if `b` is not n×n.
+    pub fn propagator<S>(&mut self, t: T, b: &mut ArrayBase<S, Ix2>) -> T
+        where S: DataMut<Elem=T>,
+    {
+        assert_eq!(b.dim(), (self.n, self.n), "Dimension mismatch between matrix `b` and preconfigured `ExpmBank` struct.");
+        let units = self.units(t);
+
+        b.fill(T::zero());
+        b.diag_mut().fill(T::one());
+        for level in set_bits(units) {
+            let propagator = level_propagator(&self.fine, &self.coarse, self.levels, level);
+            gemm(T::one(), propagator, b, T::zero(), &mut self.work);
+            b.assign(&self.work);
+        }
+
+        T::from_u64(units).unwrap() * self.resolution()
+    }
+
+    /// Calculate $e^{tA} v$ for the time `t` rounded to the resolution of the bank and the vector
+    /// `v`, storing the result in `w` and returning the rounded time.
+    ///
+    /// NOTE: Panics if `build` has not been called, if `t` is negative or too large for the
+    /// resolution, or if `v` or `w` don't have length n.
+    pub fn apply<S1, S2>(&mut self, t: T, v: &ArrayBase<S1, Ix1>, w: &mut ArrayBase<S2, Ix1>) -> T
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert_eq!(v.len(), self.n, "Dimension mismatch between vector `v` and preconfigured `ExpmBank` struct.");
+        assert_eq!(w.len(), self.n, "Dimension mismatch between vector `w` and preconfigured `ExpmBank` struct.");
+        let units = self.units(t);
+
+        w.assign(v);
+        for level in set_bits(units) {
+            let propagator = level_propagator(&self.fine, &self.coarse, self.levels, level);
+            general_mat_vec_mul(T::one(), propagator, w, T::zero(), &mut self.y);
+            w.assign(&self.y);
+        }
+
+        T::from_u64(units).unwrap() * self.resolution()
+    }
+
+    /// The number of resolution steps nearest to `t`, extending the coarse propagators up to the
+    /// largest bit of the integer part.
+    fn units(&mut self, t: T) -> u64 {
+        assert!(!self.fine.is_empty(), "The bank has to be built before composing propagators.");
+        assert!(t >= T::zero(), "Time `t` has to be non-negative.");
+
+        let units = (t / self.resolution()).round().to_u64().expect("Time `t` is too large for the resolution of the bank.");
+        let integer = units >> self.levels;
+        let bits = (64 - integer.leading_zeros()) as usize;
+        while self.coarse.len() + 1 < bits {
+            let mut next = Array2::zeros((self.n, self.n));
+            let last = self.coarse.last().unwrap_or(&self.fine[0]);
+            gemm(T::one(), last, last, T::zero(), &mut next);
+            self.coarse.push(next);
+        }
+        units
+    }
+}
+
+/// The levels of the set bits of `units`, where level $J - j$ stands for $2^{-j} \Delta t$ and
+/// level $J + i$ for $2^i \Delta t$.
+fn set_bits(units: u64) -> impl Iterator<Item=usize> {
+    (0..64).filter(move |&bit| units & (1 << bit) != 0)
+}
+
+/// The propagator of the bit `level` for `levels` $J$, see `set_bits`.
+fn level_propagator<'a, T>(fine: &'a [Array2<T>], coarse: &'a [Array2<T>], levels: usize, level: usize) -> &'a Array2<T> {
+    if level <= levels {
+        &fine[levels - level]
+    } else {
+        &coarse[level - levels - 1]
+    }
+}
+
+/// Calculate $e^{t_k A}$ for the n×n matrix `a` and all times $t_k$ in `times`, rounded to the
+/// resolution $2^{-J} \Delta t$ for the time step `dt` and `levels` $J$, storing the result for
+/// $t_k$ in `b[k]`. The propagators are composed from a bank built once.
+///
+/// NOTE: Panics if `dt` is not positive, if a time is negative, if `a` is not square, or if `b`
+/// is not of dimension k×n×n for k times.
+pub fn expm_bank_propagators<T, S1, S2>(a: &ArrayBase<S1, Ix2>, dt: T, levels: usize, times: &[T], b: &mut ArrayBase<S2, Ix3>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+    assert_eq!(b.dim(), (times.len(), n, n), "Dimension mismatch between tensor `b` and the times and matrix `a`.");
+
+    let mut bank = ExpmBank::new(n);
+    bank.build(a, dt, levels);
+    for (&t, mut b) in times.iter().zip(b.outer_iter_mut()) {
+        bank.propagator(t, &mut b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn composed_propagators_agree_with_expm() {
+        let a = arr2(&[[-1.0, 2.0, 0.5], [-2.0, -3.0, 1.0], [0.25, 0.25, -0.5]]);
+        let dt = 0.4;
+        // 2.375 dt, 0.125 dt, 13 dt, and a time off the grid of resolution dt / 8.
+        let times = [0.95, 0.05, 5.2, 0.3];
+        let mut b = Array3::<f64>::zeros((times.len(), 3, 3));
+        crate::expm_bank_propagators(&a, dt, 3, &times, &mut b);
+
+        let mut bank = crate::ExpmBank::new(3);
+        bank.build(&a, dt, 3);
+        let v = arr1(&[1.0, -2.0, 0.5]);
+        let mut w = Array1::<f64>::zeros(3);
+        let mut expected = Array2::<f64>::zeros((3, 3));
+        for (&t, b) in times.iter().zip(b.outer_iter()) {
+            let rounded = bank.apply(t, &v, &mut w);
+            assert_relative_eq!(rounded, (t / 0.05f64).round() * 0.05, max_relative=1e-15);
+
+            crate::expm(&(rounded * &a), &mut expected);
+            for (&x, &y) in b.iter().zip(expected.iter()) {
+                assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-14);
+            }
+            for (&x, &y) in w.iter().zip(expected.dot(&v).iter()) {
+                assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-14);
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Matrix exponentials of stacks of independent matrices.
+//!
+//! A stack of shape (batch, n, n) is mapped over its leading axis. Without additional features,
+//! a single `Expm` workspace is reused for all matrices in the stack. With the `rayon` feature
+//! enabled, the stack is processed in parallel. Rayon splits the stack into pieces of work, and
+//! each piece allocates its own workspace, which it reuses for all of its matrices. A worker
+//! thread can process several pieces, so that there are usually more workspaces than threads.
+//!
+//! For pipelines in which the matrices arrive one by one and the full batch never fits in memory,
+//! `ExpmStream` adapts an iterator of matrices into an iterator of their exponentials. It pulls
+//! at most a fixed number of matrices from the input at a time, exponentiates them, in parallel
+//! with the `rayon` feature enabled, and yields the results in input order before pulling the
+//! next matrices. At most that many inputs and results and the same number of `Expm` workspaces
+//! are held at any time. The workspaces are reused for consecutive matrices of the same
+//! dimension.
+
+use std::collections::VecDeque;
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{
+    Expm,
+    Float,
+};
+
+/// Calculate the matrix exponentials of the stack of n×n matrices `a` of shape (batch, n, n),
+/// storing the results in the stack `b` of the same shape.
+///
+/// With the `rayon` feature enabled, the matrices are exponentiated in parallel.
+///
+/// NOTE: Panics if the stacks `a` and `b` don't have matching shapes, the matrices are not
+/// square, or the matrices in `b` are not in row-major order.
+pub fn expm_batch<T, S1, S2>(a: &ArrayBase<S1, Ix3>, b: &mut ArrayBase<S2, Ix3>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    assert_eq!(a.dim(), b.dim(), "Input stacks `a` and `b` have to have matching dimensions.");
+    let (_, n, _) = a.dim();
+
+    #[cfg(feature = "rayon")]
+    {
+        let inputs: Vec<_> = a.outer_iter().collect();
+        let outputs: Vec<_> = b.outer_iter_mut().collect();
+
+        inputs.into_par_iter()
+            .zip(outputs.into_par_iter())
+            .for_each_init(|| Expm::new(n), |expm, (a, mut b)| expm.expm(&a, &mut b));
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    {
+        let mut expm = Expm::new(n);
+        for (a, mut b) in a.outer_iter().zip(b.outer_iter_mut()) {
+            expm.expm(&a, &mut b);
+        }
+    }
+}
+
+/// An iterator over the matrix exponentials of the n×n matrices of another iterator, holding at
+/// most a bounded number of matrices in flight.
+pub struct ExpmStream<T, I> {
+    matrices: I,
+    in_flight: usize,
+    workspaces: Vec<Expm<T>>,
+    results: VecDeque<Array2<T>>,
+}
+
+impl<T, I> ExpmStream<T, I>
+    where T: Float,
+          I: Iterator<Item=Array2<T>>,
+{
+    /// Creates a stream over the exponentials of `matrices`, exponentiating at most `in_flight`
+    /// matrices at a time.
+    ///
+    /// NOTE: Panics if `in_flight` is zero.
+    pub fn new<J>(matrices: J, in_flight: usize) -> Self
+        where J: IntoIterator<IntoIter=I, Item=Array2<T>>,
+    {
+        assert!(in_flight > 0, "At least one matrix has to be in flight.");
+
+        ExpmStream {
+            matrices: matrices.into_iter(),
+            in_flight,
+            workspaces: Vec::with_capacity(in_flight),
+            results: VecDeque::with_capacity(in_flight),
+        }
+    }
+
+    /// Pulls the next matrices from the input and exponentiates them into `results`.
+    fn fill(&mut self) {
+        let inputs: Vec<Array2<T>> = self.matrices.by_ref().take(self.in_flight).collect();
+        for a in &inputs[self.workspaces.len().min(inputs.len())..] {
+            self.workspaces.push(Expm::new(a.dim().0));
+        }
+
+        let exponentiate = |(a, expm): (Array2<T>, &mut Expm<T>)| {
+            let (n, _) = a.dim();
+            if expm.n != n {
+                *expm = Expm::new(n);
+            }
+            let mut b = Array2::zeros(a.dim());
+            expm.expm(&a, &mut b);
+            b
+        };
+
+        #[cfg(feature = "rayon")]
+        let results: Vec<_> = inputs.into_par_iter().zip(self.workspaces.par_iter_mut()).map(exponentiate).collect();
+
+        #[cfg(not(feature = "rayon"))]
+        let results: Vec<_> = inputs.into_iter().zip(self.workspaces.iter_mut()).map(exponentiate).collect();
+
+        self.results.extend(results);
+    }
+}
+
+impl<T, I> Iterator for ExpmStream<T, I>
+    where T: Float,
+          I: Iterator<Item=Array2<T>>,
+{
+    type Item = Array2<T>;
+
+    /// NOTE: Panics if the next matrix is not square.
+    fn next(&mut self) -> Option<Array2<T>> {
+        if self.results.is_empty() {
+            self.fill();
+        }
+        self.results.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.matrices.size_hint();
+        let pending = self.results.len();
+        (lower.saturating_add(pending), upper.and_then(|upper| upper.checked_add(pending)))
+    }
+}
+
+/// Calculate the matrix exponentials of the square `matrices` as they arrive, yielding the
+/// results in input order. With the `rayon` feature enabled, as many matrices as there are
+/// worker threads are exponentiated in parallel at a time, and otherwise a single one.
+///
+/// NOTE: The returned iterator panics if a matrix is not square.
+pub fn expm_stream<T, I>(matrices: I) -> ExpmStream<T, I::IntoIter>
+    where T: Float,
+          I: IntoIterator<Item=Array2<T>>,
+{
+    #[cfg(feature = "rayon")]
+    let in_flight = rayon::current_num_threads();
+
+    #[cfg(not(feature = "rayon"))]
+    let in_flight = 1;
+
+    ExpmStream::new(matrices, in_flight)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+
+    #[test]
+    fn batch_agrees_with_single_matrices() {
+        let batch = 6;
+        let n = 3;
+        let a = Array3::<f64>::from_shape_fn((batch, n, n), |(k, i, j)| {
+            ((k + 1) as f64) * 0.5 * (i as f64 - j as f64) + if i == j { -(k as f64) } else { 0.1 }
+        });
+        let mut b = Array3::<f64>::zeros((batch, n, n));
+
+        crate::expm_batch(&a, &mut b);
+
+        let mut expected = Array2::<f64>::zeros((n, n));
+        for k in 0..batch {
+            crate::expm(&a.index_axis(Axis(0), k), &mut expected);
+            for (&x, &y) in b.index_axis(Axis(0), k).iter().zip(expected.iter()) {
+                approx::assert_relative_eq!(x, y, max_relative=1e-13, epsilon=1e-15);
+            }
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_batch_agrees_with_sequential_expm() {
+        // Enough matrices for rayon to split the stack into several pieces of work.
+        let batch = 64;
+        let n = 4;
+        let a = Array3::<f64>::from_shape_fn((batch, n, n), |(k, i, j)| {
+            0.01 * (k as f64 + 1.0) * ((3 * i + 5 * j + k) % 7) as f64 - if i == j { 1.0 } else { 0.0 }
+        });
+        let mut b = Array3::<f64>::zeros((batch, n, n));
+
+        crate::expm_batch(&a, &mut b);
+
+        let mut expm = crate::Expm::new(n);
+        let mut expected = Array2::<f64>::zeros((n, n));
+        for k in 0..batch {
+            expm.expm(&a.index_axis(Axis(0), k), &mut expected);
+            for (&x, &y) in b.index_axis(Axis(0), k).iter().zip(expected.iter()) {
+                approx::assert_relative_eq!(x, y, max_relative=1e-13, epsilon=1e-15);
+            }
+        }
+    }
+
+    #[test]
+    fn stream_preserves_order() {
+        // Matrices of changing dimensions, so that workspaces are replaced in between.
+        let matrices: Vec<Array2<f64>> = (0..7)
+            .map(|k| Array2::from_shape_fn((2 + k % 3, 2 + k % 3), |(i, j)| (k as f64 + 1.0) * 0.3 * (i as f64 - j as f64) - if i == j { 0.5 } else { 0.0 }))
+            .collect();
+
+        let stream = crate::ExpmStream::new(matrices.clone(), 3);
+        assert_eq!(stream.size_hint(), (7, Some(7)));
+
+        let mut count = 0;
+        for (a, b) in matrices.iter().zip(stream) {
+            let mut expected = Array2::<f64>::zeros(a.dim());
+            crate::expm(a, &mut expected);
+            for (&x, &y) in b.iter().zip(expected.iter()) {
+                approx::assert_relative_eq!(x, y, max_relative=1e-13, epsilon=1e-15);
+            }
+            count += 1;
+        }
+        assert_eq!(count, matrices.len());
+        assert_eq!(crate::expm_stream(matrices).count(), 7);
+    }
+}
@@ -0,0 +1,200 @@
+//! Exponentials of centrosymmetric matrices, which satisfy $JAJ = A$ for the exchange matrix $J$
+//! with ones on the antidiagonal, i.e. $a_{ij} = a_{n-1-i,n-1-j}$.
+//!
+//! Such matrices arise from models that are symmetric under reflection, like chains, networks,
+//! and beams with symmetric boundary conditions. For $n = 2m$, a centrosymmetric matrix has the
+//! block form below, and the orthogonal matrix $K$ decouples it into two half-size problems
+//! [Cantoni, Butler 1976],
+//!
+//! \begin{equation}
+//!     A = \begin{pmatrix} B & JCJ \\ C & JBJ \end{pmatrix}, \quad
+//!     K = \frac{1}{\sqrt{2}} \begin{pmatrix} I & I \\ J & -J \end{pmatrix}, \quad
+//!     K^\top A K = \begin{pmatrix} B + JC & 0 \\ 0 & B - JC \end{pmatrix}.
+//! \end{equation}
+//!
+//! For odd $n = 2m + 1$, the middle row and column are appended to the first block. Since
+//! $e^A = K \operatorname{diag}(e^{B + JC}, e^{B - JC}) K^\top$ and $K$ has at most two nonzero
+//! entries per row and column, the transformations cost $O(n^2)$ operations, while the two
+//! exponentials together cost about a quarter of the exponential of the full matrix.
+//!
+//! [Cantoni, Butler 1976]: https://doi.org/10.1016/0024-3795(76)90101-4
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    Expm,
+    Float,
+};
+
+/// Storage for calculating exponentials of n×n matrices, exploiting centrosymmetric structure if
+/// present.
+pub struct ExpmCentrosymmetric<T = f64> {
+    n: usize,
+    expm: Expm<T>,
+    expm_even: Expm<T>,
+    expm_odd: Expm<T>,
+    even: Array2<T>,
+    odd: Array2<T>,
+    exp_even: Array2<T>,
+    exp_odd: Array2<T>,
+    rows: Vec<Vec<(usize, T)>>,
+    columns: Vec<Vec<(usize, T)>>,
+}
+
+impl<T: Float> ExpmCentrosymmetric<T> {
+    /// Allocates all space to calculate exponentials of n×n matrices, and of their two halves
+    /// in the centrosymmetric case.
+    pub fn new(n: usize) -> Self {
+        let m = n / 2;
+        let m_even = n - m;
+
+        // The nonzero entries (i, k, K_ik) of the orthogonal transformation K.
+        let s = T::from_f64(std::f64::consts::FRAC_1_SQRT_2).unwrap();
+        let mut rows = vec![Vec::with_capacity(2); n];
+        let mut columns = vec![Vec::with_capacity(2); n];
+        let mut push = |i: usize, k: usize, x: T| {
+            rows[i].push((k, x));
+            columns[k].push((i, x));
+        };
+        for k in 0..m {
+            push(k, k, s);
+            push(n - 1 - k, k, s);
+            push(k, m_even + k, s);
+            push(n - 1 - k, m_even + k, -s);
+        }
+        if m_even > m {
+            push(m, m, T::one());
+        }
+
+        ExpmCentrosymmetric {
+            n,
+            expm: Expm::new(n),
+            expm_even: Expm::new(m_even),
+            expm_odd: Expm::new(m),
+            even: Array2::zeros((m_even, m_even)),
+            odd: Array2::zeros((m, m)),
+            exp_even: Array2::zeros((m_even, m_even)),
+            exp_odd: Array2::zeros((m, m)),
+            rows,
+            columns,
+        }
+    }
+
+    /// Calculate the matrix exponential of the n×n matrix `a`, storing the result in `b`. If `a`
+    /// is centrosymmetric, the exponential is calculated from two half-size problems, otherwise
+    /// it is calculated as usual.
+    ///
+    /// NOTE: Panics if input matrices `a` and `b` don't have matching dimensions, are not square,
+    /// not in row-major order, or don't have the same dimension as the `ExpmCentrosymmetric`
+    /// object `expm` is called on.
+    pub fn expm<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        let n = self.n;
+        assert_eq!(a.dim(), (n, n), "Dimension mismatch between matrix `a` and preconfigured `ExpmCentrosymmetric` struct.");
+        assert_eq!(b.dim(), (n, n), "Dimension mismatch between matrix `b` and preconfigured `ExpmCentrosymmetric` struct.");
+
+        if !is_centrosymmetric(a) {
+            self.expm.expm(a, b);
+            return;
+        }
+
+        // Kᵀ A K, of which only the diagonal blocks are nonzero.
+        let m_even = self.even.rows();
+        for ((k, l), x) in self.even.indexed_iter_mut() {
+            *x = transform(&self.columns, a, k, l);
+        }
+        for ((k, l), x) in self.odd.indexed_iter_mut() {
+            *x = transform(&self.columns, a, m_even + k, m_even + l);
+        }
+
+        self.expm_even.expm(&self.even, &mut self.exp_even);
+        self.expm_odd.expm(&self.odd, &mut self.exp_odd);
+
+        // K diag(e^{B + JC}, e^{B - JC}) Kᵀ
+        for ((i, j), x) in b.indexed_iter_mut() {
+            let mut sum = T::zero();
+            for &(k, u) in &self.rows[i] {
+                for &(l, v) in &self.rows[j] {
+                    let d = match (k < m_even, l < m_even) {
+                        (true, true) => self.exp_even[(k, l)],
+                        (false, false) => self.exp_odd[(k - m_even, l - m_even)],
+                        _ => continue,
+                    };
+                    sum = sum + u * v * d;
+                }
+            }
+            *x = sum;
+        }
+    }
+}
+
+/// The entry (k, l) of Kᵀ A K, given the nonzero entries of the columns of K.
+fn transform<T, S>(columns: &[Vec<(usize, T)>], a: &ArrayBase<S, Ix2>, k: usize, l: usize) -> T
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    let mut sum = T::zero();
+    for &(i, u) in &columns[k] {
+        for &(j, v) in &columns[l] {
+            sum = sum + u * v * a[(i, j)];
+        }
+    }
+    sum
+}
+
+/// Whether the square matrix `a` is centrosymmetric, i.e. whether $a_{ij} = a_{n-1-i,n-1-j}$
+/// holds exactly for all entries.
+pub fn is_centrosymmetric<T, S>(a: &ArrayBase<S, Ix2>) -> bool
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    let (n, _) = a.dim();
+    a.indexed_iter().all(|((i, j), &x)| x == a[(n - 1 - i, n - 1 - j)])
+}
+
+/// Calculate the matrix exponential of the n×n matrix `a`, storing the result in matrix `b`. If
+/// `a` is centrosymmetric, the exponential is calculated from two half-size problems.
+///
+/// NOTE: Panics if input matrices `a` and `b` don't have matching dimensions, are not square,
+/// or not in row-major order.
+pub fn expm_centrosymmetric<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut expm = ExpmCentrosymmetric::new(n);
+    expm.expm(a, b);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn centrosymmetric_agrees_with_expm() {
+        for &n in &[4, 5] {
+            let c = Array2::from_shape_fn((n, n), |(i, j)| ((5 * i + 3 * j) % 7) as f64 * 0.25 - 0.75);
+            let a = Array2::from_shape_fn((n, n), |(i, j)| c[(i, j)] + c[(n - 1 - i, n - 1 - j)]);
+            assert!(crate::is_centrosymmetric(&a));
+
+            let mut b = Array2::<f64>::zeros((n, n));
+            let mut expected = Array2::<f64>::zeros((n, n));
+            crate::expm_centrosymmetric(&a, &mut b);
+            crate::expm(&a, &mut expected);
+
+            for (&x, &y) in b.iter().zip(expected.iter()) {
+                assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-13);
+            }
+        }
+    }
+}
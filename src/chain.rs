@@ -0,0 +1,253 @@
+//! Sensitivities of a state propagated through an ordered product of exponentials,
+//!
+//! \begin{equation}
+//!     x_K = e^{A_K(\theta_K)} \cdots e^{A_1(\theta_1)} x_0,
+//! \end{equation}
+//!
+//! with respect to the parameters $\theta_k$, as required e.g. for the gradients of pulse
+//! sequences in GRAPE-type optimal control, see [Khaneja et al. 2005]. The forward sweep
+//! calculates the propagators $E_k = e^{A_k}$ and, with `ExpmFrechet`, the exact derivatives
+//! $\partial E_k / \partial \theta_k = L(A_k, \partial A_k / \partial \theta_k)$, which avoids
+//! the first-order approximation of the original GRAPE algorithm. With the intermediate states
+//! $x_k = E_k x_{k-1}$, the derivative of the final state is
+//!
+//! \begin{equation}
+//!     \frac{\partial x_K}{\partial \theta_k} = E_K \cdots E_{k+1} L(A_k, \partial A_k /
+//!     \partial \theta_k) x_{k-1}.
+//! \end{equation}
+//!
+//! For a scalar objective with the cotangent $\lambda_K = \partial J / \partial x_K$, all
+//! gradients follow from a single backward sweep with the adjoint states $\lambda_{k-1} =
+//! E_k^\top \lambda_k$,
+//!
+//! \begin{equation}
+//!     \frac{\partial J}{\partial \theta_k} = \lambda_k^\top L(A_k, \partial A_k / \partial
+//!     \theta_k) x_{k-1},
+//! \end{equation}
+//!
+//! while the full Jacobian costs one propagation per parameter.
+//!
+//! [Khaneja et al. 2005]: https://doi.org/10.1016/j.jmr.2004.11.004
+
+use ndarray::{
+    self,
+    linalg::general_mat_vec_mul,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    ExpmFrechet,
+    Float,
+};
+
+/// Storage for propagating states of dimension n through products of exponentials and
+/// calculating their sensitivities.
+pub struct ExpmChain<T = f64> {
+    n: usize,
+    frechet: ExpmFrechet<T>,
+    propagators: Vec<Array2<T>>,
+    derivatives: Vec<Array2<T>>,
+    states: Vec<Array1<T>>,
+    steps: usize,
+    adjoint: Array1<T>,
+    tangent: Array1<T>,
+    work: Array1<T>,
+}
+
+impl<T: Float> ExpmChain<T> {
+    /// Allocates all space to propagate states of dimension n. The space for the propagators of
+    /// the individual steps is allocated by `forward` as needed and reused afterwards.
+    pub fn new(n: usize) -> Self {
+        ExpmChain {
+            n,
+            frechet: ExpmFrechet::new(n),
+            propagators: Vec::new(),
+            derivatives: Vec::new(),
+            states: vec![Array1::zeros(n)],
+            steps: 0,
+            adjoint: Array1::zeros(n),
+            tangent: Array1::zeros(n),
+            work: Array1::zeros(n),
+        }
+    }
+
+    /// Propagate the state `x0` through $e^{A_K} \cdots e^{A_1}$ for the n×n matrices $A_k$ in
+    /// `generators`, in the order of application, storing the final state in `x`. The n×n
+    /// matrices in `derivatives` are the derivatives $\partial A_k / \partial \theta_k$ of the
+    /// generators with respect to their parameters. The propagators, their derivatives, and the
+    /// intermediate states are kept for `gradient` and `jacobian`.
+    ///
+    /// NOTE: Panics if `generators` and `derivatives` don't have the same length, if the matrices
+    /// are not n×n or not in row-major order, or if `x0` and `x` don't have length n, where n is
+    /// the dimension of the `ExpmChain` object `forward` is called on.
+    pub fn forward<S1, S2, S3, S4>(&mut self, generators: &[&ArrayBase<S1, Ix2>], derivatives: &[&ArrayBase<S2, Ix2>], x0: &ArrayBase<S3, Ix1>, x: &mut ArrayBase<S4, Ix1>)
+        where S1: Data<Elem=T>,
+              S2: Data<Elem=T>,
+              S3: Data<Elem=T>,
+              S4: DataMut<Elem=T>,
+    {
+        let n = self.n;
+        assert_eq!(generators.len(), derivatives.len(), "Every generator requires a derivative.");
+        assert_eq!(x0.len(), n, "Dimension mismatch between state `x0` and preconfigured `ExpmChain` struct.");
+        assert_eq!(x.len(), n, "Dimension mismatch between state `x` and preconfigured `ExpmChain` struct.");
+
+        let steps = generators.len();
+        while self.propagators.len() < steps {
+            self.propagators.push(Array2::zeros((n, n)));
+            self.derivatives.push(Array2::zeros((n, n)));
+            self.states.push(Array1::zeros(n));
+        }
+        self.steps = steps;
+
+        self.states[0].assign(x0);
+        for (k, (&a, &e)) in generators.iter().zip(derivatives.iter()).enumerate() {
+            self.frechet.frechet(a, e, &mut self.propagators[k], &mut self.derivatives[k]);
+
+            let (previous, next) = self.states.split_at_mut(k + 1);
+            general_mat_vec_mul(T::one(), &self.propagators[k], &previous[k], T::zero(), &mut next[0]);
+        }
+        x.assign(&self.states[steps]);
+    }
+
+    /// Calculate the gradient $\partial J / \partial \theta_k$ of a scalar objective $J$ with
+    /// respect to the parameters of all steps of the last call to `forward` in a single
+    /// backward sweep, storing it in `gradient`. The `cotangent` is $\partial J / \partial x_K$
+    /// at the final state. Afterwards, `adjoint` contains $\partial J / \partial x_0$.
+    ///
+    /// NOTE: Panics if `cotangent` doesn't have length n, or if `gradient` doesn't have one
+    /// entry per step of the last call to `forward`.
+    pub fn gradient<S1, S2>(&mut self, cotangent: &ArrayBase<S1, Ix1>, gradient: &mut ArrayBase<S2, Ix1>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert_eq!(cotangent.len(), self.n, "Dimension mismatch between `cotangent` and preconfigured `ExpmChain` struct.");
+        assert_eq!(gradient.len(), self.steps, "The gradient requires one entry per step.");
+
+        self.adjoint.assign(cotangent);
+        for k in (0..self.steps).rev() {
+            // λ_kᵀ L_k x_{k-1}
+            general_mat_vec_mul(T::one(), &self.derivatives[k], &self.states[k], T::zero(), &mut self.work);
+            gradient[k] = self.adjoint.dot(&self.work);
+
+            // λ_{k-1} = E_kᵀ λ_k
+            general_mat_vec_mul(T::one(), &self.propagators[k].t(), &self.adjoint, T::zero(), &mut self.work);
+            std::mem::swap(&mut self.adjoint, &mut self.work);
+        }
+    }
+
+    /// The adjoint state $\partial J / \partial x_0$ of the last call to `gradient`.
+    pub fn adjoint(&self) -> ArrayView1<'_, T> {
+        self.adjoint.view()
+    }
+
+    /// Calculate the Jacobian $\partial x_K / \partial \theta_k$ of the final state with respect
+    /// to the parameters of all steps of the last call to `forward`, storing the derivative with
+    /// respect to $\theta_k$ in column k of the n×K matrix `jacobian`.
+    ///
+    /// NOTE: Panics if `jacobian` is not of dimension n×K for the K steps of the last call to
+    /// `forward`.
+    pub fn jacobian<S>(&mut self, jacobian: &mut ArrayBase<S, Ix2>)
+        where S: DataMut<Elem=T>,
+    {
+        assert_eq!(jacobian.dim(), (self.n, self.steps), "The Jacobian has to be of dimension n×K for K steps.");
+
+        for (k, mut column) in jacobian.gencolumns_mut().into_iter().enumerate() {
+            general_mat_vec_mul(T::one(), &self.derivatives[k], &self.states[k], T::zero(), &mut self.tangent);
+            for propagator in &self.propagators[k + 1..self.steps] {
+                general_mat_vec_mul(T::one(), propagator, &self.tangent, T::zero(), &mut self.work);
+                std::mem::swap(&mut self.tangent, &mut self.work);
+            }
+            column.assign(&self.tangent);
+        }
+    }
+}
+
+/// Calculate the gradient $\partial J / \partial \theta_k$ of the linear objective $J = c^\top
+/// x_K$ with the `cotangent` $c$, where $x_K = e^{A_K} \cdots e^{A_1} x_0$ for the n×n
+/// `generators` $A_k$ in the order of application, with the `derivatives` $\partial A_k /
+/// \partial \theta_k$. The gradient is stored in `gradient` and the final state in `x`.
+///
+/// NOTE: Panics if `generators` and `derivatives` don't have the same length as `gradient`, if
+/// the matrices are not square or not in row-major order, or if `x0`, `cotangent`, and `x` don't
+/// have the same dimension as the matrices.
+pub fn expm_chain_gradient<T, S1, S2, S3, S4, S5, S6>(generators: &[&ArrayBase<S1, Ix2>], derivatives: &[&ArrayBase<S2, Ix2>], x0: &ArrayBase<S3, Ix1>, cotangent: &ArrayBase<S4, Ix1>, x: &mut ArrayBase<S5, Ix1>, gradient: &mut ArrayBase<S6, Ix1>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=T>,
+          S3: Data<Elem=T>,
+          S4: Data<Elem=T>,
+          S5: DataMut<Elem=T>,
+          S6: DataMut<Elem=T>,
+{
+    let n = x0.len();
+
+    let mut chain = ExpmChain::new(n);
+    chain.forward(generators, derivatives, x0, x);
+    chain.gradient(cotangent, gradient);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    /// A_k(θ) = D + θ H_k with a common drift D and control Hamiltonians H_k.
+    fn generators(theta: &[f64]) -> (Vec<Array2<f64>>, Vec<Array2<f64>>) {
+        let drift = arr2(&[[-0.1, 1.0, 0.0], [-1.0, -0.2, 0.3], [0.0, -0.3, -0.05]]);
+        let controls = [
+            arr2(&[[0.0, 0.0, 1.0], [0.0, 0.0, 0.0], [-1.0, 0.0, 0.0]]),
+            arr2(&[[0.0, 0.5, 0.0], [-0.5, 0.0, 1.0], [0.0, -1.0, 0.0]]),
+        ];
+        let derivatives: Vec<_> = theta.iter().enumerate().map(|(k, _)| controls[k % 2].clone()).collect();
+        let generators = theta.iter().zip(derivatives.iter()).map(|(&t, h)| &drift + &(t * h)).collect();
+        (generators, derivatives)
+    }
+
+    #[test]
+    fn gradient_agrees_with_finite_differences() {
+        let theta = [0.3, -0.7, 1.2, 0.4];
+        let x0 = arr1(&[1.0, 0.0, 0.5]);
+        let c = arr1(&[0.2, -1.0, 0.7]);
+
+        let (a, e) = generators(&theta);
+        let a: Vec<_> = a.iter().collect();
+        let e: Vec<_> = e.iter().collect();
+        let mut x = Array1::<f64>::zeros(3);
+        let mut gradient = Array1::<f64>::zeros(4);
+        crate::expm_chain_gradient(&a, &e, &x0, &c, &mut x, &mut gradient);
+
+        let objective = |theta: &[f64]| {
+            let (a, _) = generators(theta);
+            let mut state = x0.clone();
+            let mut b = Array2::<f64>::zeros((3, 3));
+            for a in &a {
+                crate::expm(a, &mut b);
+                state = b.dot(&state);
+            }
+            c.dot(&state)
+        };
+        let h = 1e-6;
+        for k in 0..theta.len() {
+            let mut plus = theta;
+            let mut minus = theta;
+            plus[k] += h;
+            minus[k] -= h;
+            let difference = (objective(&plus) - objective(&minus)) / (2.0 * h);
+            assert_relative_eq!(gradient[k], difference, max_relative=1e-7, epsilon=1e-9);
+        }
+
+        // The forward Jacobian is consistent with the backward sweep.
+        let mut chain = crate::ExpmChain::new(3);
+        chain.forward(&a, &e, &x0, &mut x);
+        chain.gradient(&c, &mut gradient);
+        let adjoint = chain.adjoint().to_owned();
+        let mut jacobian = Array2::<f64>::zeros((3, 4));
+        chain.jacobian(&mut jacobian);
+        assert_eq!(chain.adjoint(), adjoint);
+        for (&x, &y) in jacobian.t().dot(&c).iter().zip(gradient.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-14);
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Exponentials of circulant and skew-circulant matrices, as they arise from semi-discretized
+//! wave and advection equations with periodic or antiperiodic boundary conditions.
+//!
+//! A circulant matrix $C$ with first column $c$ is diagonalized by the discrete Fourier
+//! transform $F$, $C = F^{-1} \operatorname{diag}(Fc) F$, so that $e^C$ is again circulant with
+//! first column $F^{-1} e^{Fc}$. A skew-circulant matrix $S$, whose entries above the diagonal
+//! change sign upon wrapping around, is similar to a circulant matrix, $S = D C D^{-1}$ with $D =
+//! \operatorname{diag}(\theta^m)$ and $\theta = e^{i\pi/n}$. Only the first column and the
+//! spectrum are needed, so that the exponential costs $O(n^2)$ operations instead of $O(n^3)$,
+//! and its action on a vector can be calculated without forming the full matrix.
+//!
+//! If the generator is antisymmetric, as for central differences of first derivatives, its
+//! spectrum is purely imaginary. The real parts of the calculated eigenvalues are then set to
+//! zero, so that the exponential is orthogonal to machine precision even over long times, and the
+//! propagation conserves the discrete energy $\lVert u \rVert_2^2$.
+//!
+//! Only the periodic and antiperiodic structures are exploited. Antisymmetric banded generators
+//! without such structure, e.g. from Dirichlet boundary conditions, have no fast transform, and
+//! their exponential is full despite the band. For them, `expm_skew_symmetric` calculates the
+//! dense exponential with $O(n^3)$ operations, ignoring the bandwidth, and re-orthogonalizes it.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+use num_complex::Complex;
+
+use crate::Float;
+
+/// The circulant structure of a matrix, which is determined by its first column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Circulant {
+    /// Entries $c_{(i - j) \bmod n}$, corresponding to periodic boundary conditions.
+    Periodic,
+    /// Entries $c_{i - j}$ on and below the diagonal and $-c_{n + i - j}$ above it, corresponding
+    /// to antiperiodic boundary conditions.
+    Antiperiodic,
+}
+
+impl Circulant {
+    /// The entry (i, j) of the matrix with first column `c`.
+    fn entry<T, S>(self, c: &ArrayBase<S, Ix1>, i: usize, j: usize) -> T
+        where T: Float,
+              S: Data<Elem=T>,
+    {
+        let n = c.len();
+        if i >= j {
+            c[i - j]
+        } else {
+            match self {
+                Circulant::Periodic => c[n + i - j],
+                Circulant::Antiperiodic => -c[n + i - j],
+            }
+        }
+    }
+}
+
+/// Storage for calculating exponentials of n×n circulant and skew-circulant matrices.
+pub struct ExpmCirculant<T = f64> {
+    n: usize,
+    roots: Vec<Complex<T>>,
+    half_roots: Vec<Complex<T>>,
+    exp_spectrum: Vec<Complex<T>>,
+    x: Vec<Complex<T>>,
+    y: Vec<Complex<T>>,
+    unit: Array1<T>,
+    first: Array1<T>,
+}
+
+impl<T: Float> ExpmCirculant<T> {
+    /// Allocates all space to calculate exponentials of n×n circulant matrices.
+    pub fn new(n: usize) -> Self {
+        let pi = T::from_f64(std::f64::consts::PI).unwrap();
+        let n_float = T::from_usize(n.max(1)).unwrap();
+
+        // ω^m = e^{-2πim/n} and θ^m = e^{iπm/n}
+        let roots = (0..n)
+            .map(|m| Complex::from_polar(&T::one(), &(-(pi + pi) * T::from_usize(m).unwrap() / n_float)))
+            .collect();
+        let half_roots = (0..n)
+            .map(|m| Complex::from_polar(&T::one(), &(pi * T::from_usize(m).unwrap() / n_float)))
+            .collect();
+
+        ExpmCirculant {
+            n,
+            roots,
+            half_roots,
+            exp_spectrum: vec![Complex::new(T::zero(), T::zero()); n],
+            x: vec![Complex::new(T::zero(), T::zero()); n],
+            y: vec![Complex::new(T::zero(), T::zero()); n],
+            unit: Array1::from_shape_fn(n, |m| if m == 0 { T::one() } else { T::zero() }),
+            first: Array1::zeros(n),
+        }
+    }
+
+    /// Calculate the matrix exponential of the n×n matrix with first column `c` and the given
+    /// circulant `structure`, storing the result in `b`.
+    ///
+    /// NOTE: Panics if `c` and `b` don't have the same dimension as the `ExpmCirculant` object
+    /// `expm` is called on.
+    pub fn expm<S1, S2>(&mut self, c: &ArrayBase<S1, Ix1>, structure: Circulant, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        let n = self.n;
+        assert_eq!(b.dim(), (n, n), "Dimension mismatch between matrix `b` and preconfigured `ExpmCirculant` struct.");
+
+        // The first column of the exponential is its action on the first unit vector.
+        let unit = std::mem::replace(&mut self.unit, Array1::zeros(0));
+        let mut first = std::mem::replace(&mut self.first, Array1::zeros(0));
+        self.propagate(c, structure, &unit, &mut first);
+
+        for ((i, j), x) in b.indexed_iter_mut() {
+            *x = structure.entry(&first, i, j);
+        }
+
+        self.unit = unit;
+        self.first = first;
+    }
+
+    /// Calculate $e^C u$ for the n×n matrix $C$ with first column `c` and the given circulant
+    /// `structure`, storing the result in `v`. The full exponential is never formed.
+    ///
+    /// NOTE: Panics if `c`, `u`, and `v` don't have the same dimension as the `ExpmCirculant`
+    /// object `propagate` is called on.
+    pub fn propagate<S1, S2, S3>(&mut self, c: &ArrayBase<S1, Ix1>, structure: Circulant, u: &ArrayBase<S2, Ix1>, v: &mut ArrayBase<S3, Ix1>)
+        where S1: Data<Elem=T>,
+              S2: Data<Elem=T>,
+              S3: DataMut<Elem=T>,
+    {
+        let n = self.n;
+        assert_eq!(c.len(), n, "Dimension mismatch between column `c` and preconfigured `ExpmCirculant` struct.");
+        assert_eq!(u.len(), n, "Dimension mismatch between vector `u` and preconfigured `ExpmCirculant` struct.");
+        assert_eq!(v.len(), n, "Dimension mismatch between vector `v` and preconfigured `ExpmCirculant` struct.");
+
+        // Eigenvalues λ = F D⁻¹ c of the similar circulant matrix.
+        twist(c, &self.half_roots, structure, &mut self.x);
+        self.dft(false);
+        let is_antisymmetric = (0..n).all(|m| structure.entry(c, m, 0) == -structure.entry(c, 0, m));
+        for (exp_lambda, &lambda) in self.exp_spectrum.iter_mut().zip(self.y.iter()) {
+            let lambda = if is_antisymmetric { Complex::new(T::zero(), lambda.im) } else { lambda };
+            *exp_lambda = lambda.exp();
+        }
+
+        // e^C u = D F⁻¹ e^Λ F D⁻¹ u
+        twist(u, &self.half_roots, structure, &mut self.x);
+        self.dft(false);
+        for (x, (&y, &exp_lambda)) in self.x.iter_mut().zip(self.y.iter().zip(self.exp_spectrum.iter())) {
+            *x = y * exp_lambda;
+        }
+        self.dft(true);
+        let n_float = T::from_usize(n.max(1)).unwrap();
+        for (m, x) in v.iter_mut().enumerate() {
+            let y = self.y[m] / n_float;
+            *x = match structure {
+                Circulant::Periodic => y.re,
+                Circulant::Antiperiodic => (y * self.half_roots[m]).re,
+            };
+        }
+    }
+
+    /// The unnormalized discrete Fourier transform of `x` into `y`, or its inverse without the
+    /// factor 1/n.
+    fn dft(&mut self, inverse: bool) {
+        let n = self.n;
+        for (j, y) in self.y.iter_mut().enumerate() {
+            let mut sum = Complex::new(T::zero(), T::zero());
+            for (m, &x) in self.x.iter().enumerate() {
+                let root = self.roots[(j * m) % n];
+                sum = sum + x * if inverse { root.conj() } else { root };
+            }
+            *y = sum;
+        }
+    }
+}
+
+/// Stores D⁻¹ x in `y`, where D = diag(θᵐ) for antiperiodic structure and D = I otherwise.
+fn twist<T, S>(x: &ArrayBase<S, Ix1>, half_roots: &[Complex<T>], structure: Circulant, y: &mut [Complex<T>])
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    for ((y, &x), &root) in y.iter_mut().zip(x.iter()).zip(half_roots.iter()) {
+        *y = match structure {
+            Circulant::Periodic => Complex::new(x, T::zero()),
+            Circulant::Antiperiodic => root.conj() * x,
+        };
+    }
+}
+
+/// Calculate the matrix exponential of the n×n matrix with first column `c` and the given
+/// circulant `structure`, storing the result in `b`.
+///
+/// NOTE: Panics if `b` is not of dimension n×n.
+pub fn expm_circulant<T, S1, S2>(c: &ArrayBase<S1, Ix1>, structure: Circulant, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let n = c.len();
+
+    let mut expm = ExpmCirculant::new(n);
+    expm.expm(c, structure, b);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    use super::{
+        Circulant,
+        ExpmCirculant,
+    };
+
+    #[test]
+    fn circulant_agrees_with_expm() {
+        let c = arr1(&[-1.0, 0.5, 0.0, 0.25, 2.0]);
+
+        for &structure in &[Circulant::Periodic, Circulant::Antiperiodic] {
+            let a = Array2::from_shape_fn((5, 5), |(i, j)| structure.entry(&c, i, j));
+            let mut b = Array2::<f64>::zeros((5, 5));
+            let mut expected = Array2::<f64>::zeros((5, 5));
+
+            crate::expm_circulant(&c, structure, &mut b);
+            crate::expm(&a, &mut expected);
+
+            for (&x, &y) in b.iter().zip(expected.iter()) {
+                assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-13);
+            }
+        }
+    }
+
+    #[test]
+    fn advection_conserves_energy() {
+        // Central differences of -∂ₓ on an antiperiodic grid, propagated over a long time.
+        let n = 16;
+        let speed = 40.0;
+        let mut c = Array1::<f64>::zeros(n);
+        c[1] = speed;
+        c[n - 1] = speed;
+        let u = Array1::from_shape_fn(n, |m| (m as f64 * 0.3).sin() + 0.1 * m as f64);
+        let mut v = Array1::<f64>::zeros(n);
+
+        let mut expm = ExpmCirculant::new(n);
+        expm.propagate(&c, Circulant::Antiperiodic, &u, &mut v);
+
+        assert_relative_eq!(v.dot(&v), u.dot(&u), max_relative=1e-13);
+    }
+}
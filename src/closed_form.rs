@@ -0,0 +1,242 @@
+//! Closed-form exponentials of small and structured matrices, for which Padé approximation and
+//! scaling and squaring are needlessly expensive.
+//!
+//! For a 2×2 matrix $A$ with eigenvalues $\mu \pm \delta$,
+//!
+//! \begin{equation}
+//!     e^A = e^\mu \left(\cosh(\delta) I + \frac{\sinh(\delta)}{\delta} (A - \mu I)\right),
+//! \end{equation}
+//!
+//! where $\delta$ may be imaginary. For real $\delta$, the coefficients are formed from
+//! $e^{\mu \pm \delta}$ instead, since $e^\mu$ may underflow while $\cosh(\delta)$ overflows.
+//! For a 3×3 antisymmetric matrix $W = [\omega]_\times$ with rotation angle $\theta = \lVert
+//! \omega \rVert_2$, Rodrigues' formula gives the rotation
+//!
+//! \begin{equation}
+//!     e^W = I + \frac{\sin\theta}{\theta} W + \frac{1 - \cos\theta}{\theta^2} W^2,
+//! \end{equation}
+//!
+//! and the exponential of a twist in $\mathfrak{se}(3)$ is the rigid body motion [Lynch, Park
+//! 2017, Section 3.3.3]
+//!
+//! \begin{equation}
+//!     \exp \begin{pmatrix} W & v \\ 0 & 0 \end{pmatrix}
+//!     = \begin{pmatrix} e^W & Vv \\ 0 & 1 \end{pmatrix}, \quad
+//!     V = I + \frac{1 - \cos\theta}{\theta^2} W + \frac{\theta - \sin\theta}{\theta^3} W^2.
+//! \end{equation}
+//!
+//! The coefficients are evaluated without cancellation for small $\theta$, using $1 - \cos\theta
+//! = 2\sin^2(\theta/2)$ and a truncated Taylor series for $(\theta - \sin\theta)/\theta^3$.
+//!
+//! [Lynch, Park 2017]: http://hades.mech.northwestern.edu/index.php/Modern_Robotics
+
+use ndarray::{
+    self,
+    prelude::*,
+    s,
+    Data,
+    DataMut,
+};
+
+use crate::Float;
+
+/// Calculate the matrix exponential of the 2×2 matrix `a`, storing the result in `b`.
+///
+/// NOTE: Panics if `a` or `b` are not of dimension 2×2.
+pub fn expm_2x2<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    assert_eq!(a.dim(), (2, 2), "Matrix `a` has to be of dimension 2×2.");
+    assert_eq!(b.dim(), (2, 2), "Matrix `b` has to be of dimension 2×2.");
+
+    let exp_a = exp_2x2([[a[(0, 0)], a[(0, 1)]], [a[(1, 0)], a[(1, 1)]]]);
+    for ((i, j), x) in b.indexed_iter_mut() {
+        *x = exp_a[i][j];
+    }
+}
+
+/// Calculate the rotation matrix $e^W$ for the 3×3 antisymmetric matrix `w`, storing the result
+/// in `b`.
+///
+/// NOTE: Panics if `w` is not antisymmetric, or if `w` or `b` are not of dimension 3×3.
+pub fn expm_so3<T, S1, S2>(w: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    assert_eq!(w.dim(), (3, 3), "Matrix `w` has to be of dimension 3×3.");
+    assert_eq!(b.dim(), (3, 3), "Matrix `b` has to be of dimension 3×3.");
+    assert!(is_antisymmetric(w), "Matrix `w` is not antisymmetric.");
+
+    let (alpha, beta, _) = rotation_coefficients(w);
+    rotation(w, alpha, beta, b);
+}
+
+/// Calculate the rigid body motion $e^\Xi$ for the 4×4 twist `xi`, whose upper left 3×3 block is
+/// antisymmetric and whose last row is zero, storing the result in `b`.
+///
+/// NOTE: Panics if `xi` is not a twist, or if `xi` or `b` are not of dimension 4×4.
+pub fn expm_se3<T, S1, S2>(xi: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    assert_eq!(xi.dim(), (4, 4), "Matrix `xi` has to be of dimension 4×4.");
+    assert_eq!(b.dim(), (4, 4), "Matrix `b` has to be of dimension 4×4.");
+    let w = xi.slice(s![..3, ..3]);
+    assert!(is_antisymmetric(&w), "Upper left block of matrix `xi` is not antisymmetric.");
+    assert!(xi.row(3).iter().all(|&x| x == T::zero()), "Last row of matrix `xi` is not zero.");
+
+    let (alpha, beta, gamma) = rotation_coefficients(&w);
+    rotation(&w, alpha, beta, &mut b.slice_mut(s![..3, ..3]));
+
+    // V v = v + β W v + γ W (W v)
+    let v = xi.slice(s![..3, 3]);
+    let wv: Array1<T> = w.dot(&v);
+    let wwv: Array1<T> = w.dot(&wv);
+    for i in 0..3 {
+        b[(i, 3)] = v[i] + beta * wv[i] + gamma * wwv[i];
+    }
+    b.row_mut(3).fill(T::zero());
+    b[(3, 3)] = T::one();
+}
+
+/// The exponential of a 2×2 matrix, given as nested arrays.
+pub(crate) fn exp_2x2<T: Float>(a: [[T; 2]; 2]) -> [[T; 2]; 2] {
+    let half = T::from_f64(0.5).unwrap();
+    let mu = half * (a[0][0] + a[1][1]);
+    let d = half * (a[0][0] - a[1][1]);
+    let delta_squared = d * d + a[0][1] * a[1][0];
+
+    // e^μ cosh(δ) and e^μ sinh(δ)/δ, continued to imaginary δ. For real δ, they are built from
+    // e^{μ+δ} and e^{μ-δ}, since e^μ may underflow while cosh(δ) and sinh(δ) overflow.
+    let (c, s) = if delta_squared > T::zero() {
+        let delta = delta_squared.sqrt();
+        let upper = (mu + delta).exp();
+        let lower = (mu - delta).exp();
+        let difference = if delta < half {
+            // e^{μ+δ} - e^{μ-δ} = e^{μ-δ} (e^{2δ} - 1) without cancellation.
+            lower * (delta + delta).exp_m1()
+        } else {
+            upper - lower
+        };
+        (half * (upper + lower), half * difference / delta)
+    } else {
+        let omega = (-delta_squared).sqrt();
+        let sinc = if omega == T::zero() { T::one() } else { omega.sin() / omega };
+        let exp_mu = mu.exp();
+        (exp_mu * omega.cos(), exp_mu * sinc)
+    };
+
+    [
+        [c + s * d, s * a[0][1]],
+        [s * a[1][0], c - s * d],
+    ]
+}
+
+fn is_antisymmetric<T, S>(w: &ArrayBase<S, Ix2>) -> bool
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    w.indexed_iter().all(|((i, j), &x)| x == -w[(j, i)])
+}
+
+/// The coefficients sin(θ)/θ, (1 - cos θ)/θ², and (θ - sin θ)/θ³ for the antisymmetric 3×3
+/// matrix `w` with rotation angle θ.
+fn rotation_coefficients<T, S>(w: &ArrayBase<S, Ix2>) -> (T, T, T)
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    let theta_squared = w[(2, 1)] * w[(2, 1)] + w[(0, 2)] * w[(0, 2)] + w[(1, 0)] * w[(1, 0)];
+    let theta = theta_squared.sqrt();
+    let half = T::from_f64(0.5).unwrap();
+
+    if theta == T::zero() {
+        return (T::one(), half, T::one() / T::from_f64(6.0).unwrap());
+    }
+
+    let alpha = theta.sin() / theta;
+    let sine_half = (half * theta).sin() / theta;
+    let beta = (sine_half + sine_half) * sine_half;
+    let gamma = if theta < T::from_f64(0.1).unwrap() {
+        // 1/3! - θ²/5! + θ⁴/7! - θ⁶/9!
+        let coefficients = [1.0 / 6.0, -1.0 / 120.0, 1.0 / 5040.0, -1.0 / 362_880.0];
+        coefficients.iter().rev().fold(T::zero(), |acc, &c| acc * theta_squared + T::from_f64(c).unwrap())
+    } else {
+        (theta - theta.sin()) / (theta_squared * theta)
+    };
+    (alpha, beta, gamma)
+}
+
+/// Stores I + α W + β W² in `b`.
+fn rotation<T, S1, S2>(w: &ArrayBase<S1, Ix2>, alpha: T, beta: T, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let w2: Array2<T> = w.dot(w);
+    for ((i, j), x) in b.indexed_iter_mut() {
+        let identity = if i == j { T::one() } else { T::zero() };
+        *x = identity + alpha * w[(i, j)] + beta * w2[(i, j)];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn closed_forms_agree_with_expm() {
+        let a = arr2(&[[0.3, -2.0], [1.5, -0.4]]);
+        let w = arr2(&[[0.0, -0.3, 0.2], [0.3, 0.0, -1.1], [-0.2, 1.1, 0.0]]);
+        let xi = arr2(&[
+            [0.0, -0.3, 0.2, 1.0],
+            [0.3, 0.0, -1.1, -2.0],
+            [-0.2, 1.1, 0.0, 0.5],
+            [0.0, 0.0, 0.0, 0.0],
+        ]);
+        let w_small = 1e-5 * &w;
+
+        type ClosedForm = fn(&Array2<f64>, &mut Array2<f64>);
+        let cases: Vec<(&Array2<f64>, ClosedForm)> = vec![
+            (&a, crate::expm_2x2),
+            (&w, crate::expm_so3),
+            (&w_small, crate::expm_so3),
+            (&xi, crate::expm_se3),
+        ];
+
+        for (m, f) in cases {
+            let mut b = Array2::<f64>::zeros(m.dim());
+            let mut expected = Array2::<f64>::zeros(m.dim());
+            f(m, &mut b);
+            crate::expm(m, &mut expected);
+
+            for (&x, &y) in b.iter().zip(expected.iter()) {
+                assert_relative_eq!(x, y, max_relative=1e-13, epsilon=1e-15);
+            }
+        }
+    }
+
+    #[test]
+    fn separated_eigenvalues_do_not_overflow() {
+        let a = arr2(&[[0.0, 1.0], [0.0, -1500.0]]);
+        let mut b = Array2::<f64>::zeros((2, 2));
+        crate::expm_2x2(&a, &mut b);
+        let expected = arr2(&[[1.0, 1.0 / 1500.0], [0.0, 0.0]]);
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-14);
+        }
+
+        // Close real eigenvalues.
+        let a = arr2(&[[1.0, 1e-9], [3e-9, 1.0]]);
+        let mut expected = Array2::<f64>::zeros((2, 2));
+        crate::expm_2x2(&a, &mut b);
+        crate::expm(&a, &mut expected);
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-14);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Estimation of the relative condition number of the matrix exponential in the 1-norm,
+//!
+//! \begin{equation}
+//!     \kappa_{\exp}(A) = \frac{\lVert L(A) \rVert_1 \lVert A \rVert_1}{\lVert e^A \rVert_1},
+//! \end{equation}
+//!
+//! following Algorithm 3.20 in [Higham 2008]. The norm of the Fréchet derivative $L(A)$ is
+//! estimated by applying the block 1-norm estimator to its Kronecker form $K(A)$, an n²×n² matrix
+//! which is never formed explicitly. Instead, each product with a probing vector $\mathrm{vec}(E)$
+//! is obtained as $\mathrm{vec}(L(A, E))$, and products with $K(A)^\top$ as $\mathrm{vec}(L(A^\top,
+//! E))$.
+//!
+//! [Higham 2008]: https://doi.org/10.1137/1.9780898717778
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+};
+use rand::RngCore;
+use std::cell::RefCell;
+
+use crate::{
+    normest::{
+        LinearOperator,
+        Normest1,
+    },
+    float::onenorm,
+    ExpmFrechet,
+    Float,
+};
+
+/// Storage for the evaluations of the Fréchet derivative, which are required as mutable
+/// workspace from within `LinearOperator::multiply_matrix`.
+struct FrechetWork<T> {
+    frechet: ExpmFrechet<T>,
+    e: Array2<T>,
+    b: Array2<T>,
+    l: Array2<T>,
+}
+
+/// The Kronecker form of the Fréchet derivative of the exponential at `a`.
+struct FrechetOperator<T> {
+    a: Array2<T>,
+    a_t: Array2<T>,
+    work: RefCell<FrechetWork<T>>,
+}
+
+impl<T: Float> LinearOperator<T> for FrechetOperator<T> {
+    fn multiply_matrix(&self, b: &mut Array2<T>, c: &mut Array2<T>, transpose: bool) {
+        let FrechetWork { frechet, e, b: exp_a, l } = &mut *self.work.borrow_mut();
+        let a = if transpose { &self.a_t } else { &self.a };
+
+        for (b_column, mut c_column) in b.gencolumns().into_iter().zip(c.gencolumns_mut()) {
+            e.iter_mut().zip(b_column.iter()).for_each(|(x, &y)| *x = y);
+            frechet.frechet(a, e, exp_a, l);
+            c_column.iter_mut().zip(l.iter()).for_each(|(x, &y)| *x = y);
+        }
+    }
+}
+
+/// Storage for estimating the condition number of the exponential of n×n matrices.
+pub struct ExpmCond<T = f64> {
+    n: usize,
+    itmax: usize,
+    operator: FrechetOperator<T>,
+    exp_a: Array2<T>,
+    normest1: Normest1<T>,
+}
+
+impl<T: Float> ExpmCond<T> {
+    /// Allocates all space to estimate the condition number for square matrices of dimension
+    /// n×n.
+    pub fn new(n: usize) -> Self {
+        let zeros = || Array2::<T>::zeros((n, n));
+
+        let work = FrechetWork {
+            frechet: ExpmFrechet::new(n),
+            e: zeros(),
+            b: zeros(),
+            l: zeros(),
+        };
+        let operator = FrechetOperator {
+            a: zeros(),
+            a_t: zeros(),
+            work: RefCell::new(work),
+        };
+
+        // The Kronecker form acts on vectors of length n².
+        let t = 2.min(n * n);
+        let itmax = 5;
+
+        ExpmCond {
+            n,
+            itmax,
+            operator,
+            exp_a: zeros(),
+            normest1: Normest1::new(n * n, t),
+        }
+    }
+
+    /// Seed the estimator of the norm of the Kronecker form with `seed`, so that repeated
+    /// estimates agree.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.normest1.set_seed(seed);
+    }
+
+    /// Reseed the estimator of the norm of the Kronecker form from the generator `rng`.
+    pub fn set_rng<R: RngCore>(&mut self, rng: &mut R) {
+        self.normest1.set_rng(rng);
+    }
+
+    /// Estimate the relative condition number of the matrix exponential of the n×n matrix `a`
+    /// in the 1-norm.
+    ///
+    /// The estimate of the norm of the Kronecker form is a lower bound that is usually accurate
+    /// within a factor of 3.
+    ///
+    /// NOTE: Panics if `a` is not square, or doesn't have the same dimension as the `ExpmCond`
+    /// object `cond` is called on.
+    pub fn cond<S>(&mut self, a: &ArrayBase<S, Ix2>) -> T
+        where S: Data<Elem=T>,
+    {
+        let (n_rows, n_cols) = a.dim();
+        assert_eq!(n_rows, n_cols, "The condition number is only defined for square matrices.");
+        assert_eq!(n_rows, self.n, "Dimension mismatch between matrix `a` and preconfigured `ExpmCond` struct.");
+
+        if self.n == 0 {
+            return T::zero();
+        }
+
+        self.operator.a.assign(a);
+        self.operator.a_t.assign(&a.t());
+
+        {
+            let FrechetWork { frechet, e, l, .. } = &mut *self.operator.work.borrow_mut();
+            e.fill(T::zero());
+            frechet.frechet(a, e, &mut self.exp_a, l);
+        }
+
+        let norm_kronecker = self.normest1.normest1_operator(&self.operator, self.itmax);
+        norm_kronecker * onenorm(a) / onenorm(&self.exp_a)
+    }
+}
+
+/// Estimate the relative condition number of the matrix exponential of the n×n matrix `a` in
+/// the 1-norm.
+///
+/// NOTE: Panics if `a` is not square.
+pub fn expm_cond<T, S>(a: &ArrayBase<S, Ix2>) -> T
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut cond = ExpmCond::new(n);
+    cond.cond(a)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    use crate::float::onenorm;
+
+    #[test]
+    fn cond_of_scaled_identity() {
+        // L(αI, E) = e^α E, so that the condition number is |α|.
+        let a = Array2::<f64>::eye(4) * -3.0;
+        assert_relative_eq!(crate::expm_cond(&a), 3.0, max_relative=1e-12);
+    }
+
+    #[test]
+    fn cond_is_bounded_by_exact_kronecker_norm() {
+        let a = arr2(&[[-1.0, 10.0, 0.0], [0.0, -1.5, 20.0], [0.0, 0.0, -2.0]]);
+        let n = 3;
+
+        // Build the Kronecker form column by column from the derivatives in the directions of
+        // the unit matrices.
+        let mut norm_kronecker = 0.0f64;
+        for k in 0..n * n {
+            let mut e = Array2::<f64>::zeros((n, n));
+            e[[k / n, k % n]] = 1.0;
+            let mut l = Array2::<f64>::zeros((n, n));
+            crate::expm_frechet(&a, &e, &mut l);
+            norm_kronecker = norm_kronecker.max(l.iter().map(|x| x.abs()).sum());
+        }
+        let mut exp_a = Array2::<f64>::zeros((n, n));
+        crate::expm(&a, &mut exp_a);
+        let exact = norm_kronecker * onenorm(&a) / onenorm(&exp_a);
+
+        let mut cond = crate::ExpmCond::new(n);
+        cond.set_seed(3);
+        let estimate = cond.cond(&a);
+        assert!(estimate <= exact * (1.0 + 1e-10));
+        assert!(estimate >= exact / 3.0);
+    }
+}
@@ -0,0 +1,311 @@
+//! The congruence $X \mapsto e^A X e^{A^\top}$ of a symmetric matrix $X$, i.e. the flow of the
+//! homogeneous part of the Lyapunov differential equation $\dot X = AX + XA^\top$.
+//!
+//! Rather than multiplying $X$ from both sides with the exponential, $X$ is decomposed as $X = V
+//! \Lambda V^\top$, and the result is formed as $W \Lambda W^\top$ with $W = e^A V$. This makes
+//! the result symmetric by construction. If $X$ is positive semidefinite, eigenvalues that are
+//! negative due to rounding are set to zero and the result is formed as $Z Z^\top$ with $Z = W
+//! \Lambda^{1/2}$, so that it is positive semidefinite as well.
+//!
+//! On top of the dense exponential, this costs a symmetric eigendecomposition of $X$, about
+//! $9n^3$ flops, and two matrix products, the same two as multiplying $X$ from both sides. This
+//! triples the cost of simply symmetrizing $e^A X e^{A^\top}$ after the exponential, but that
+//! symmetrization only repairs the symmetry: rounding errors of order $u \lVert e^A \rVert^2 \lVert X \rVert$
+//! turn the eigenvalues of a singular or nearly singular positive semidefinite $X$ negative,
+//! which breaks Cholesky factorizations and square roots of covariance matrices downstream. If
+//! only symmetry matters, calculate the exponential with `Expm` and form the product directly.
+//!
+//! For a complex matrix $A = B + iC$ and a complex Hermitian matrix $X = S + iK$, the congruence
+//! $e^A X e^{A^\dagger}$ is calculated from their real 2n×2n embeddings
+//! $\begin{pmatrix} B & -C \\ C & B \end{pmatrix}$ and
+//! $\begin{pmatrix} S & -K \\ K & S \end{pmatrix}$ as in `ExpmUnitary`. The embedding of $X$
+//! is symmetric, and positive semidefinite if $X$ is, so that the real congruence carries over
+//! both properties.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+use num_complex::Complex;
+
+use crate::{
+    float::{
+        gemm,
+        syev,
+    },
+    unitary::hermitian_embedding,
+    Expm,
+    Float,
+};
+
+/// Storage for calculating congruences with the exponential of n×n matrices.
+pub struct ExpmCongruence<T = f64> {
+    n: usize,
+    expm: Expm<T>,
+    exp_a: Array2<T>,
+    eigenvectors: Array2<T>,
+    eigenvalues: Array1<T>,
+    w: Array2<T>,
+    z: Array2<T>,
+    embedding: Option<Box<Embedding<T>>>,
+}
+
+/// Storage for the congruence of the real 2n×2n embeddings of complex n×n matrices.
+struct Embedding<T> {
+    congruence: ExpmCongruence<T>,
+    a: Array2<T>,
+    x: Array2<T>,
+    y: Array2<T>,
+}
+
+impl<T: Float> Embedding<T> {
+    fn new(n: usize) -> Self {
+        Embedding {
+            congruence: ExpmCongruence::new(2 * n),
+            a: Array2::zeros((2 * n, 2 * n)),
+            x: Array2::zeros((2 * n, 2 * n)),
+            y: Array2::zeros((2 * n, 2 * n)),
+        }
+    }
+}
+
+impl<T: Float> ExpmCongruence<T> {
+    /// Allocates all space to calculate congruences of n×n matrices.
+    pub fn new(n: usize) -> Self {
+        ExpmCongruence {
+            n,
+            expm: Expm::new(n),
+            exp_a: Array2::zeros((n, n)),
+            eigenvectors: Array2::zeros((n, n)),
+            eigenvalues: Array1::zeros(n),
+            w: Array2::zeros((n, n)),
+            z: Array2::zeros((n, n)),
+            embedding: None,
+        }
+    }
+
+    /// Calculate $e^A X e^{A^\top}$ for the n×n matrix `a` and the symmetric n×n matrix `x`,
+    /// storing the symmetric result in `y`. Only the upper triangle of `x` is referenced. If `x`
+    /// is positive semidefinite up to rounding, so is `y`.
+    ///
+    /// NOTE: Panics if the matrices don't have matching dimensions, are not square, don't have
+    /// the same dimension as the `ExpmCongruence` object `congruence` is called on, or if the
+    /// eigendecomposition of `x` does not converge.
+    pub fn congruence<S1, S2, S3>(&mut self, a: &ArrayBase<S1, Ix2>, x: &ArrayBase<S2, Ix2>, y: &mut ArrayBase<S3, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: Data<Elem=T>,
+              S3: DataMut<Elem=T>,
+    {
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `ExpmCongruence` struct.");
+        assert_eq!(a.dim(), x.dim(), "Input matrices `a` and `x` have to have matching dimensions.");
+        assert_eq!(a.dim(), y.dim(), "Input matrices `a` and `y` have to have matching dimensions.");
+
+        self.expm.expm(a, &mut self.exp_a);
+
+        self.eigenvectors.assign(x);
+        let info = syev(&mut self.eigenvectors, &mut self.eigenvalues);
+        assert_eq!(info, 0, "The eigendecomposition of `x` did not converge.");
+
+        // W = exp(A) V
+        gemm(T::one(), &self.exp_a, &self.eigenvectors, T::zero(), &mut self.w);
+
+        // Eigenvalues of a positive semidefinite X can come out slightly negative; their size is
+        // bounded by a small multiple of the unit roundoff times the largest eigenvalue.
+        let lambda_max = self.eigenvalues.fold(T::zero(), |acc, &x| acc.max(x.abs()));
+        let tolerance = T::from_usize(self.n).unwrap() * T::epsilon() * lambda_max;
+        let is_psd = self.eigenvalues.iter().all(|&lambda| lambda >= -tolerance);
+
+        // Y = (W Λ^{1/2}) (W Λ^{1/2})ᵀ if X is positive semidefinite, and Y = (W Λ) Wᵀ otherwise.
+        self.z.assign(&self.w);
+        for (mut column, &lambda) in self.z.gencolumns_mut().into_iter().zip(self.eigenvalues.iter()) {
+            let factor = if is_psd { lambda.max(T::zero()).sqrt() } else { lambda };
+            column.mapv_inplace(|x| x * factor);
+        }
+        if is_psd {
+            gemm(T::one(), &self.z, &self.z.t(), T::zero(), y);
+        } else {
+            gemm(T::one(), &self.z, &self.w.t(), T::zero(), y);
+        }
+
+        // Remove the asymmetry introduced by rounding in the product above.
+        let half = T::from_f64(0.5).unwrap();
+        self.w.assign(y);
+        y.zip_mut_with(&self.w.t(), |x, &y| *x = half * (*x + y));
+    }
+
+    /// Calculate $e^A X e^{A^\dagger}$ for the complex n×n matrix `a` and the complex Hermitian
+    /// n×n matrix `x`, storing the Hermitian result in `y`. Only the upper triangle of `x` is
+    /// referenced, and the imaginary parts of its diagonal are ignored. If `x` is positive
+    /// semidefinite up to rounding, so is `y`.
+    ///
+    /// The storage for the real 2n×2n embeddings is allocated on the first call.
+    ///
+    /// NOTE: Panics if the matrices don't have matching dimensions, are not square, don't have
+    /// the same dimension as the `ExpmCongruence` object `congruence_hermitian` is called on, or
+    /// if the eigendecomposition of `x` does not converge.
+    pub fn congruence_hermitian<S1, S2, S3>(&mut self, a: &ArrayBase<S1, Ix2>, x: &ArrayBase<S2, Ix2>, y: &mut ArrayBase<S3, Ix2>)
+        where S1: Data<Elem=Complex<T>>,
+              S2: Data<Elem=Complex<T>>,
+              S3: DataMut<Elem=Complex<T>>,
+    {
+        let n = self.n;
+        assert_eq!(a.dim(), (n, n), "Dimension mismatch between matrix `a` and preconfigured `ExpmCongruence` struct.");
+        assert_eq!(a.dim(), x.dim(), "Input matrices `a` and `x` have to have matching dimensions.");
+        assert_eq!(a.dim(), y.dim(), "Input matrices `a` and `y` have to have matching dimensions.");
+
+        let embedding = &mut **self.embedding.get_or_insert_with(|| Box::new(Embedding::new(n)));
+        complex_embedding(a, &mut embedding.a);
+        hermitian_embedding(x, &mut embedding.x);
+        embedding.congruence.congruence(&embedding.a, &embedding.x, &mut embedding.y);
+
+        // The symmetric result is [[Re Y, -Im Y], [Im Y, Re Y]] up to rounding. Averaging both
+        // copies of each block maps a positive semidefinite matrix to a positive semidefinite one.
+        let e = &embedding.y;
+        let half = T::from_f64(0.5).unwrap();
+        for i in 0..n {
+            let re = half * (e[(i, i)] + e[(n + i, n + i)]);
+            y[(i, i)] = Complex::new(re, T::zero());
+            for j in i+1..n {
+                let re = half * (e[(i, j)] + e[(n + i, n + j)]);
+                let im = half * (e[(n + i, j)] - e[(i, n + j)]);
+                y[(i, j)] = Complex::new(re, im);
+                y[(j, i)] = Complex::new(re, -im);
+            }
+        }
+    }
+}
+
+/// Stores the real 2n×2n embedding $\begin{pmatrix} B & -C \\ C & B \end{pmatrix}$ of the
+/// complex n×n matrix $A = B + iC$ in `m`.
+fn complex_embedding<T, S>(a: &ArrayBase<S, Ix2>, m: &mut Array2<T>)
+    where T: Float,
+          S: Data<Elem=Complex<T>>,
+{
+    let (n, _) = a.dim();
+    for ((i, j), &Complex { re, im }) in a.indexed_iter() {
+        m[(i, j)] = re;
+        m[(n + i, n + j)] = re;
+        m[(n + i, j)] = im;
+        m[(i, n + j)] = -im;
+    }
+}
+
+/// Calculate $e^A X e^{A^\top}$ for the n×n matrix `a` and the symmetric n×n matrix `x`, storing
+/// the symmetric result in `y`.
+///
+/// NOTE: Panics if the matrices don't have matching dimensions, are not square, or if the
+/// eigendecomposition of `x` does not converge.
+pub fn expm_congruence<T, S1, S2, S3>(a: &ArrayBase<S1, Ix2>, x: &ArrayBase<S2, Ix2>, y: &mut ArrayBase<S3, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=T>,
+          S3: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut congruence = ExpmCongruence::new(n);
+    congruence.congruence(a, x, y);
+}
+
+/// Calculate $e^A X e^{A^\dagger}$ for the complex n×n matrix `a` and the complex Hermitian n×n
+/// matrix `x`, storing the Hermitian result in `y`.
+///
+/// NOTE: Panics if the matrices don't have matching dimensions, are not square, or if the
+/// eigendecomposition of `x` does not converge.
+pub fn expm_congruence_hermitian<T, S1, S2, S3>(a: &ArrayBase<S1, Ix2>, x: &ArrayBase<S2, Ix2>, y: &mut ArrayBase<S3, Ix2>)
+    where T: Float,
+          S1: Data<Elem=Complex<T>>,
+          S2: Data<Elem=Complex<T>>,
+          S3: DataMut<Elem=Complex<T>>,
+{
+    let (n, _) = a.dim();
+
+    let mut congruence = ExpmCongruence::new(n);
+    congruence.congruence_hermitian(a, x, y);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use num_complex::Complex;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn congruence_agrees_with_explicit_products() {
+        let a = arr2(&[[-1.0, 2.0, 0.0], [0.5, -3.0, 1.0], [0.0, 0.25, -0.5]]);
+        // Symmetric and indefinite.
+        let x = arr2(&[[1.0, 2.0, 0.0], [2.0, -1.0, 0.5], [0.0, 0.5, 3.0]]);
+        let mut y = Array2::<f64>::zeros((3, 3));
+        let mut exp_a = Array2::<f64>::zeros((3, 3));
+
+        crate::expm_congruence(&a, &x, &mut y);
+        crate::expm(&a, &mut exp_a);
+        let expected = exp_a.dot(&x).dot(&exp_a.t());
+
+        for (&u, &v) in y.iter().zip(expected.iter()) {
+            assert_relative_eq!(u, v, max_relative=1e-12, epsilon=1e-14);
+        }
+        assert_eq!(y, y.t());
+    }
+
+    #[test]
+    fn congruence_preserves_positive_semidefiniteness() {
+        let a = arr2(&[[0.0, 5.0, 0.0], [-5.0, 0.0, 2.0], [1.0, 0.0, -1.0]]);
+        // Rank one and positive semidefinite.
+        let v = arr1(&[1.0, -2.0, 0.5]);
+        let x = Array2::from_shape_fn((3, 3), |(i, j)| v[i] * v[j]);
+        let mut y = Array2::<f64>::zeros((3, 3));
+
+        crate::expm_congruence(&a, &x, &mut y);
+
+        assert_eq!(y, y.t());
+        let mut eigenvalues = Array1::<f64>::zeros(3);
+        let mut eigenvectors = y.clone();
+        crate::float::syev(&mut eigenvectors, &mut eigenvalues);
+        let lambda_max = eigenvalues.fold(0.0f64, |acc, &x| acc.max(x));
+        for &lambda in eigenvalues.iter() {
+            assert!(lambda >= -10.0 * f64::EPSILON * lambda_max);
+        }
+    }
+
+    #[test]
+    fn hermitian_congruence_agrees_with_explicit_products() {
+        let n = 3;
+        let a = Array2::from_shape_fn((n, n), |(i, j)| {
+            Complex::new(0.3 * ((i + 2 * j) % 5) as f64 - 0.6, 0.4 * ((2 * i + j) % 3) as f64 - 0.4)
+        });
+        // Rank one and positive semidefinite.
+        let v = arr1(&[Complex::new(1.0, 0.5), Complex::new(-2.0, 0.0), Complex::new(0.5, -1.0)]);
+        let x = Array2::from_shape_fn((n, n), |(i, j)| v[i] * v[j].conj());
+        let mut y = Array2::<Complex<f64>>::zeros((n, n));
+
+        crate::expm_congruence_hermitian(&a, &x, &mut y);
+
+        // exp(A) from the exponential of the embedding [[Re A, -Im A], [Im A, Re A]].
+        let mut m = Array2::<f64>::zeros((2 * n, 2 * n));
+        let mut exp_m = Array2::<f64>::zeros((2 * n, 2 * n));
+        super::complex_embedding(&a, &mut m);
+        crate::expm(&m, &mut exp_m);
+        let exp_a = Array2::from_shape_fn((n, n), |(i, j)| Complex::new(exp_m[(i, j)], exp_m[(n + i, j)]));
+        let expected = exp_a.dot(&x).dot(&exp_a.t().mapv(|z| z.conj()));
+
+        for (u, v) in y.iter().zip(expected.iter()) {
+            assert_relative_eq!(u.re, v.re, max_relative=1e-12, epsilon=1e-13);
+            assert_relative_eq!(u.im, v.im, max_relative=1e-12, epsilon=1e-13);
+        }
+        for ((i, j), z) in y.indexed_iter() {
+            assert_eq!(*z, y[(j, i)].conj());
+        }
+
+        let mut eigenvectors = Array2::<f64>::zeros((2 * n, 2 * n));
+        let mut eigenvalues = Array1::<f64>::zeros(2 * n);
+        crate::unitary::hermitian_embedding(&y, &mut eigenvectors);
+        crate::float::syev(&mut eigenvectors, &mut eigenvalues);
+        let lambda_max = eigenvalues.fold(0.0f64, |acc, &x| acc.max(x));
+        for &lambda in eigenvalues.iter() {
+            assert!(lambda >= -10.0 * f64::EPSILON * lambda_max);
+        }
+    }
+}
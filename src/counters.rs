@@ -0,0 +1,69 @@
+//! Operation counts of a single evaluation, for building performance models and comparing
+//! algorithm options analytically rather than only by wall clock time.
+
+use std::ops::{
+    Add,
+    AddAssign,
+};
+
+/// Counts of the dominant operations performed by the last evaluation.
+///
+/// + `matrix_products` counts n×n matrix-matrix products, each costing $2n^3$ flops.
+/// + `linear_solves` counts LU factorizations of n×n matrices followed by the triangular solves
+///   for n right hand sides, costing $8n^3/3$ flops together.
+/// + `matvecs` counts products of n×n matrices with vectors, each costing $2n^2$ flops. These
+///   arise in the 1-norm estimates, which apply a matrix to a block of t probing vectors at once.
+/// + `orthogonalizations` counts iterations of re-orthogonalization, each costing two matrix
+///   products, which are also included in `matrix_products`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counters {
+    pub matrix_products: usize,
+    pub linear_solves: usize,
+    pub matvecs: usize,
+    pub orthogonalizations: usize,
+}
+
+impl Add for Counters {
+    type Output = Counters;
+
+    fn add(self, other: Counters) -> Counters {
+        Counters {
+            matrix_products: self.matrix_products + other.matrix_products,
+            linear_solves: self.linear_solves + other.linear_solves,
+            matvecs: self.matvecs + other.matvecs,
+            orthogonalizations: self.orthogonalizations + other.orthogonalizations,
+        }
+    }
+}
+
+impl AddAssign for Counters {
+    fn add_assign(&mut self, other: Counters) {
+        *self = *self + other;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+
+    #[test]
+    fn counters_follow_pade_order() {
+        let mut b = Array2::<f64>::zeros((3, 3));
+
+        // A nilpotent matrix of small norm is handled by the approximant of order 3, requiring A²
+        // and one more product for the odd part.
+        let a = arr2(&[[0.0, 1e-3, 0.0], [0.0, 0.0, 1e-3], [0.0, 0.0, 0.0]]);
+        let counters = crate::expm_with_counters(&a, &mut b);
+        assert_eq!(counters.matrix_products, 2);
+        assert_eq!(counters.linear_solves, 1);
+        assert!(counters.matvecs > 0);
+
+        // A large norm requires the approximant of order 13, i.e. A², A⁴, A⁶, three products
+        // for the approximant, and the squarings.
+        let a = arr2(&[[-100.0, 200.0, 0.0], [50.0, -300.0, 100.0], [0.0, 25.0, -50.0]]);
+        let counters = crate::expm_with_counters(&a, &mut b);
+        assert!(counters.matrix_products > 7);
+        assert_eq!(counters.linear_solves, 1);
+        assert_eq!(counters.orthogonalizations, 0);
+    }
+}
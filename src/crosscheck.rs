@@ -0,0 +1,249 @@
+//! Verification of the matrix exponential against an independent algorithm.
+//!
+//! The exponential is calculated twice: once with the Padé-based `expm`, and once with a
+//! truncated Taylor series combined with scaling and squaring. Both methods share no code besides
+//! the matrix products, so that a large discrepancy between them indicates a silent numerical
+//! failure of at least one of them.
+//!
+//! The Taylor series is evaluated at $2^{-s} A$ with $s$ chosen such that $\lVert 2^{-s} A
+//! \rVert_1 \le 1/2$, and truncated once the 1-norm of the next term drops below the unit
+//! roundoff relative to the partial sum.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    extended::add_compensated,
+    float::{
+        gemm,
+        onenorm,
+    },
+    Expm,
+    ExpmOptions,
+    Float,
+};
+
+/// The maximum degree of the truncated Taylor series. With $\lVert 2^{-s} A \rVert_1 \le 1/2$
+/// the truncation error is far below the unit roundoff of `f64` at this degree.
+const MAX_TAYLOR_DEGREE: usize = 30;
+
+/// Outcome of a cross-checked calculation of the matrix exponential.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CrossCheck<T> {
+    /// The relative discrepancy $\lVert B - B_\mathrm{Taylor} \rVert_1 / \lVert B \rVert_1$
+    /// between the Padé result $B$ and the Taylor result.
+    pub discrepancy: T,
+    /// The degree at which the Taylor series was truncated.
+    pub taylor_degree: usize,
+    /// The number of squarings performed in the Taylor method.
+    pub squarings: i32,
+}
+
+/// Storage for the independent calculation of the exponential with the Taylor method.
+pub(crate) struct TaylorReference<T> {
+    x: Array2<T>,
+    term: Array2<T>,
+    taylor: Array2<T>,
+    compensation: Array2<T>,
+    work: Array2<T>,
+}
+
+impl<T: Float> TaylorReference<T> {
+    /// Allocates all space to calculate the exponential of n×n matrices with the Taylor method.
+    pub(crate) fn new(n: usize) -> Self {
+        TaylorReference {
+            x: Array2::zeros((n, n)),
+            term: Array2::zeros((n, n)),
+            taylor: Array2::zeros((n, n)),
+            compensation: Array2::zeros((n, n)),
+            work: Array2::zeros((n, n)),
+        }
+    }
+
+    /// Calculates the exponential of `a` with the Taylor method and compares it to `b`. If
+    /// `compensated` is set, the terms of the series are accumulated with compensated summation.
+    pub(crate) fn compare<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &ArrayBase<S2, Ix2>, compensated: bool) -> CrossCheck<T>
+        where S1: Data<Elem=T>,
+              S2: Data<Elem=T>,
+    {
+        let (taylor_degree, squarings) = self.taylor(a, compensated);
+
+        self.work.assign(b);
+        self.work.zip_mut_with(&self.taylor, |x, &y| *x = *x - y);
+        let norm_b = onenorm(b);
+        let discrepancy = if norm_b > T::zero() {
+            onenorm(&self.work) / norm_b
+        } else {
+            onenorm(&self.work)
+        };
+
+        CrossCheck {
+            discrepancy,
+            taylor_degree,
+            squarings,
+        }
+    }
+
+    /// Calculates the exponential of `a` via scaling and squaring of a truncated Taylor series,
+    /// storing it in `self.taylor`. Returns the degree of the series and the number of squarings.
+    fn taylor<S>(&mut self, a: &ArrayBase<S, Ix2>, compensated: bool) -> (usize, i32)
+        where S: Data<Elem=T>,
+    {
+        let half = T::from_f64(0.5).unwrap();
+        let norm_a = onenorm(a);
+        let s = if norm_a > half {
+            (norm_a / half).log2().ceil().to_i32().unwrap_or(0).max(0)
+        } else {
+            0
+        };
+        let scale = T::from_f64(2.0).unwrap().powi(-s);
+        self.x.zip_mut_with(a, |x, &y| *x = scale * y);
+
+        self.taylor.fill(T::zero());
+        self.taylor.diag_mut().fill(T::one());
+        self.term.assign(&self.taylor);
+        self.compensation.fill(T::zero());
+
+        let mut degree = 0;
+        for k in 1..=MAX_TAYLOR_DEGREE {
+            // term ← term X / k
+            let k_inv = T::one() / T::from_usize(k).unwrap();
+            gemm(k_inv, &self.term, &self.x, T::zero(), &mut self.work);
+            std::mem::swap(&mut self.term, &mut self.work);
+            if compensated {
+                add_compensated(&mut self.taylor, &mut self.compensation, &self.term);
+            } else {
+                self.taylor.zip_mut_with(&self.term, |x, &y| *x = *x + y);
+            }
+            degree = k;
+
+            if onenorm(&self.term) <= T::unit_roundoff() * onenorm(&self.taylor) {
+                break;
+            }
+        }
+
+        self.taylor.zip_mut_with(&self.compensation, |x, &c| *x = *x + c);
+
+        for _ in 0..s {
+            gemm(T::one(), &self.taylor, &self.taylor, T::zero(), &mut self.work);
+            std::mem::swap(&mut self.taylor, &mut self.work);
+        }
+
+        (degree, s)
+    }
+}
+
+/// Storage for calculating cross-checked matrix exponentials.
+pub struct ExpmCrossCheck<T = f64> {
+    n: usize,
+    expm: Expm<T>,
+    options: ExpmOptions<T>,
+}
+
+impl<T: Float> ExpmCrossCheck<T> {
+    /// Allocates all space to calculate cross-checked matrix exponentials of square matrices of
+    /// dimension n×n.
+    pub fn new(n: usize) -> Self {
+        ExpmCrossCheck {
+            n,
+            expm: Expm::new(n),
+            options: ExpmOptions::default(),
+        }
+    }
+
+    /// Use `options` for the exponential calculated by `expm`. The compensated summation of the
+    /// options applies to the Taylor series of the cross-check.
+    pub fn set_options(&mut self, options: &ExpmOptions<T>) {
+        self.options = *options;
+    }
+
+    /// If `compensated` is set, the terms of the Taylor series are accumulated with compensated
+    /// summation, see `ExpmOptions::compensated_summation`.
+    pub fn set_compensated_summation(&mut self, compensated: bool) {
+        self.options.compensated_summation = compensated;
+    }
+
+    /// Calculate the matrix exponential of the n×n matrix `a` storing the result in matrix `b`,
+    /// and report the discrepancy to the result of the Taylor method.
+    ///
+    /// NOTE: Panics if input matrices `a` and `b` don't have matching dimensions, are not square,
+    /// not in row-major order, or don't have the same dimension as the `ExpmCrossCheck` object
+    /// `expm` is called on.
+    pub fn expm<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>) -> CrossCheck<T>
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `ExpmCrossCheck` struct.");
+
+        let options = ExpmOptions { cross_check: true, ..self.options };
+        let report = self.expm.expm_with_opts(a, b, &options);
+        report.cross_check.unwrap()
+    }
+}
+
+/// Calculate the matrix exponential of the n×n matrix `a` storing the result in matrix `b`, and
+/// report the discrepancy to an independent calculation with the Taylor method.
+///
+/// NOTE: Panics if input matrices `a` and `b` don't have matching dimensions, are not square, or
+/// not in row-major order.
+pub fn expm_cross_check<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>) -> CrossCheck<T>
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut cross_check = ExpmCrossCheck::new(n);
+    cross_check.expm(a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+
+    use crate::ExpmOptions;
+
+    #[test]
+    fn methods_agree_for_well_conditioned_matrices() {
+        let a = arr2(&[[-1.0, 2.0, 0.5], [0.0, -3.0, 1.0], [0.5, 0.25, -0.5]]);
+        let mut b = Array2::<f64>::zeros((3, 3));
+
+        for &scale in &[0.01, 1.0, 5.0] {
+            let report = crate::expm_cross_check(&(&a * scale), &mut b);
+            assert!(report.discrepancy < 1e-12, "discrepancy {:?} at scale {}", report, scale);
+        }
+    }
+
+    #[test]
+    fn inaccurate_results_are_flagged() {
+        // Without squarings, the approximant of order 13 is evaluated far beyond θ₁₃.
+        let a = arr2(&[[-1.0, 2.0, 0.5], [0.0, -3.0, 1.0], [0.5, 0.25, -0.5]]) * 10.0;
+        let mut b = Array2::<f64>::zeros((3, 3));
+
+        let mut cross_check = crate::ExpmCrossCheck::new(3);
+        cross_check.set_options(&ExpmOptions { max_squarings: Some(0), ..ExpmOptions::default() });
+        let report = cross_check.expm(&a, &mut b);
+        assert!(report.discrepancy > 1e-6, "discrepancy {:?}", report);
+    }
+
+    #[test]
+    fn option_reports_the_cross_check() {
+        let a = arr2(&[[-1.0, 2.0, 0.5], [0.0, -3.0, 1.0], [0.5, 0.25, -0.5]]);
+        let mut b = Array2::<f64>::zeros((3, 3));
+
+        let report = crate::expm_with_opts(&a, &mut b, &ExpmOptions::default());
+        assert_eq!(report.cross_check, None);
+
+        let options = ExpmOptions { cross_check: true, ..ExpmOptions::default() };
+        let report = crate::expm_with_opts(&a, &mut b, &options);
+        assert!(report.cross_check.unwrap().discrepancy < 1e-12);
+
+        let unscaled = ExpmOptions { max_squarings: Some(0), ..options };
+        let report = crate::expm_with_opts(&(&a * 10.0), &mut b, &unscaled);
+        assert!(report.cross_check.unwrap().discrepancy > 1e-6);
+    }
+}
@@ -0,0 +1,375 @@
+//! Transition matrices and propagation of continuous-time Markov chains (CTMCs).
+//!
+//! A CTMC with n states is described by its generator $Q$, whose off-diagonal entries are the
+//! non-negative transition rates. Two conventions are in common use:
+//!
+//! + `StochasticConvention::Row`: $Q_{ij}$ is the rate from state $i$ to state $j$, the rows of
+//!   $Q$ sum to zero, and probability distributions are row vectors evolving as
+//!   $p(t) = p(0) e^{Qt}$, making $e^{Qt}$ row-stochastic;
+//! + `StochasticConvention::Column`: $Q_{ij}$ is the rate from state $j$ to state $i$, the columns
+//!   of $Q$ sum to zero, and distributions are column vectors evolving as $p(t) = e^{Qt} p(0)$,
+//!   making $e^{Qt}$ column-stochastic.
+//!
+//! Because mixing up these conventions silently produces wrong results, all functions in this
+//! module take the convention explicitly and check that the generator conforms to it. Neither
+//! convention requires transposing the generator or the transition matrix.
+//!
+//! The Padé approximant used by `transition` can produce tiny negative probabilities and sums
+//! that deviate from 1 by rounding errors. `transition_uniformized` instead uses uniformization
+//! (Jensen's method): with $\Lambda \ge \max_i \lvert Q_{ii} \rvert$, the matrix $P = I + Q/\Lambda$
+//! is stochastic, and
+//!
+//! \begin{equation}
+//!     e^{Q\tau} = \sum_{k=0}^\infty e^{-\Lambda\tau} \frac{(\Lambda\tau)^k}{k!} P^k
+//! \end{equation}
+//!
+//! is a sum of non-negative terms. The series is evaluated for $\tau = 2^{-s} t$ with $\Lambda\tau
+//! \le 1$ and truncated once the remaining Poisson weights are bounded by the tolerance, and the
+//! result is squared $s$ times. Every step only adds and multiplies non-negative numbers, and the
+//! sums of the result are renormalized to 1 after every step, which guarantees a non-negative,
+//! stochastic transition matrix. The truncation errors of all steps add up to at most the given
+//! tolerance.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    float::gemm,
+    Expm,
+    Float,
+};
+
+/// The maximum number of terms of the uniformization series per step, which is never reached for
+/// tolerances above the underflow threshold since $\Lambda\tau \le 1$.
+const MAX_UNIFORMIZATION_TERMS: usize = 200;
+
+/// The convention of a CTMC generator and the corresponding distributions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StochasticConvention {
+    /// Rows of the generator sum to zero; distributions are row vectors, $p(t) = p(0) e^{Qt}$.
+    Row,
+    /// Columns of the generator sum to zero; distributions are column vectors,
+    /// $p(t) = e^{Qt} p(0)$.
+    Column,
+}
+
+/// Storage for calculating transition matrices and propagating distributions of CTMCs with n
+/// states.
+pub struct Ctmc<T = f64> {
+    n: usize,
+    expm: Expm<T>,
+    qt: Array2<T>,
+    transition: Array2<T>,
+    uniformized: Array2<T>,
+    term: Array2<T>,
+    work: Array2<T>,
+}
+
+impl<T: Float> Ctmc<T> {
+    /// Allocates all space for CTMCs with n states.
+    pub fn new(n: usize) -> Self {
+        Ctmc {
+            n,
+            expm: Expm::new(n),
+            qt: Array2::zeros((n, n)),
+            transition: Array2::zeros((n, n)),
+            uniformized: Array2::zeros((n, n)),
+            term: Array2::zeros((n, n)),
+            work: Array2::zeros((n, n)),
+        }
+    }
+
+    /// Calculate the transition matrix $e^{Qt}$ of the n×n generator `q` over the time `t`,
+    /// storing the result in `p`.
+    ///
+    /// NOTE: Panics if `q` is not a generator in the given `convention` (see
+    /// `check_generator`), if `q` and `p` don't have matching dimensions, or don't have the same
+    /// dimension as the `Ctmc` object `transition` is called on.
+    pub fn transition<S1, S2>(&mut self, q: &ArrayBase<S1, Ix2>, t: T, convention: StochasticConvention, p: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert_eq!(q.dim(), (self.n, self.n), "Dimension mismatch between generator `q` and preconfigured `Ctmc` struct.");
+        check_generator(q, convention);
+
+        self.qt.zip_mut_with(q, |x, &y| *x = t * y);
+        self.expm.expm(&self.qt, p);
+    }
+
+    /// Calculate the transition matrix $e^{Qt}$ of the n×n generator `q` over the time `t` by
+    /// uniformization, storing the result in `p`. The result is non-negative and its rows
+    /// (`Row`) or columns (`Column`) sum to 1 up to rounding, and it deviates from the exact
+    /// transition matrix by at most `tolerance` in the 1-norm of each row or column, up to
+    /// rounding.
+    ///
+    /// NOTE: Panics if `t` is negative, if `tolerance` is not positive, if `q` is not a generator
+    /// in the given `convention` (see `check_generator`), or if `q` and `p` don't have matching
+    /// dimensions or don't have the same dimension as the `Ctmc` object
+    /// `transition_uniformized` is called on.
+    pub fn transition_uniformized<S1, S2>(&mut self, q: &ArrayBase<S1, Ix2>, t: T, tolerance: T, convention: StochasticConvention, p: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert_eq!(q.dim(), (self.n, self.n), "Dimension mismatch between generator `q` and preconfigured `Ctmc` struct.");
+        assert_eq!(p.dim(), (self.n, self.n), "Dimension mismatch between matrix `p` and preconfigured `Ctmc` struct.");
+        assert!(t >= T::zero(), "Time `t` has to be non-negative.");
+        assert!(tolerance > T::zero(), "Tolerance has to be positive.");
+        check_generator(q, convention);
+
+        p.fill(T::zero());
+        p.diag_mut().fill(T::one());
+
+        let lambda = q.diag().fold(T::zero(), |acc, &x| acc.max(x.abs()));
+        if lambda == T::zero() || t == T::zero() {
+            return;
+        }
+
+        // P = I + Q/Λ, whose diagonal is non-negative by the choice of Λ.
+        self.uniformized.zip_mut_with(q, |x, &y| *x = y / lambda);
+        self.uniformized.diag_mut().mapv_inplace(|x| (T::one() + x).max(T::zero()));
+        for ((i, j), x) in self.uniformized.indexed_iter_mut() {
+            if i != j {
+                *x = x.max(T::zero());
+            }
+        }
+
+        let two = T::from_f64(2.0).unwrap();
+        let s = (lambda * t).log2().ceil().to_i32().unwrap_or(0).max(0);
+        let lambda_tau = lambda * t / two.powi(s);
+        let step_tolerance = tolerance / two.powi(s);
+
+        // Σ_k w_k P^k with the Poisson weights w_k = e^{-Λτ} (Λτ)^k / k!
+        let mut weight = (-lambda_tau).exp();
+        p.mapv_inplace(|x| weight * x);
+        self.term.fill(T::zero());
+        self.term.diag_mut().fill(T::one());
+        for k in 1..=MAX_UNIFORMIZATION_TERMS {
+            // The remaining weights are bounded by w_k / (1 - Λτ/(k+1)).
+            let k_float = T::from_usize(k).unwrap();
+            weight = weight * lambda_tau / k_float;
+            let tail = weight / (T::one() - lambda_tau / (k_float + T::one()));
+            if tail <= step_tolerance {
+                break;
+            }
+
+            gemm(T::one(), &self.term, &self.uniformized, T::zero(), &mut self.work);
+            std::mem::swap(&mut self.term, &mut self.work);
+            p.zip_mut_with(&self.term, |x, &y| *x = *x + weight * y);
+        }
+        normalize(p, convention);
+
+        for _ in 0..s {
+            gemm(T::one(), p, p, T::zero(), &mut self.work);
+            p.assign(&self.work);
+            normalize(p, convention);
+        }
+    }
+
+    /// Propagate the distribution `p0` over the time `t` with the n×n generator `q`, storing the
+    /// result in `p`. Depending on the `convention`, `p0` and `p` are interpreted as row or
+    /// column vectors.
+    ///
+    /// NOTE: Panics if `q` is not a generator in the given `convention`, if `p0` and `p` are not
+    /// contiguous vectors of length n, or if `q` doesn't have the same dimension as the `Ctmc`
+    /// object `propagate` is called on.
+    pub fn propagate<S1, S2, S3>(&mut self, q: &ArrayBase<S1, Ix2>, p0: &ArrayBase<S2, Ix1>, t: T, convention: StochasticConvention, p: &mut ArrayBase<S3, Ix1>)
+        where S1: Data<Elem=T>,
+              S2: Data<Elem=T>,
+              S3: DataMut<Elem=T>,
+    {
+        let n = self.n;
+        assert_eq!(p0.len(), n, "Dimension mismatch between distribution `p0` and preconfigured `Ctmc` struct.");
+        assert_eq!(p.len(), n, "Dimension mismatch between distribution `p` and preconfigured `Ctmc` struct.");
+
+        let mut transition = std::mem::replace(&mut self.transition, Array2::zeros((0, 0)));
+        self.transition(q, t, convention, &mut transition);
+
+        let shape = match convention {
+            StochasticConvention::Row => (1, n),
+            StochasticConvention::Column => (n, 1),
+        };
+        let p0 = p0.view().into_shape(shape).expect("Distribution `p0` has to be contiguous.");
+        let mut p = p.view_mut().into_shape(shape).expect("Distribution `p` has to be contiguous.");
+        match convention {
+            StochasticConvention::Row => gemm(T::one(), &p0, &transition, T::zero(), &mut p),
+            StochasticConvention::Column => gemm(T::one(), &transition, &p0, T::zero(), &mut p),
+        }
+
+        self.transition = transition;
+    }
+}
+
+/// Check that `q` is a generator in the given `convention`, i.e. that its off-diagonal entries
+/// are non-negative and that its rows (`Row`) or columns (`Column`) sum to zero up to rounding.
+///
+/// NOTE: Panics if `q` is not square or not a generator in the given `convention`. If the sums
+/// vanish in the other convention, the panic message points this out.
+pub fn check_generator<T, S>(q: &ArrayBase<S, Ix2>, convention: StochasticConvention)
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    let (n_rows, n_cols) = q.dim();
+    assert_eq!(n_rows, n_cols, "A generator has to be a square matrix.");
+
+    for ((i, j), &x) in q.indexed_iter() {
+        assert!(i == j || x >= T::zero(), "Off-diagonal entry ({}, {}) of the generator is negative.", i, j);
+    }
+
+    let conforms = |convention| sums_vanish(q, convention);
+    if !conforms(convention) {
+        let other = match convention {
+            StochasticConvention::Row => StochasticConvention::Column,
+            StochasticConvention::Column => StochasticConvention::Row,
+        };
+        if conforms(other) {
+            panic!("The generator follows the {:?} convention, but {:?} was requested.", other, convention);
+        } else {
+            panic!("The generator does not follow the {:?} convention.", convention);
+        }
+    }
+}
+
+/// Scales the rows (`Row`) or columns (`Column`) of the non-negative matrix `p` to sum to 1.
+fn normalize<T, S>(p: &mut ArrayBase<S, Ix2>, convention: StochasticConvention)
+    where T: Float,
+          S: DataMut<Elem=T>,
+{
+    let axis = match convention {
+        StochasticConvention::Row => Axis(1),
+        StochasticConvention::Column => Axis(0),
+    };
+    for mut lane in p.lanes_mut(axis) {
+        let sum = lane.fold(T::zero(), |acc, &x| acc + x);
+        if sum > T::zero() {
+            lane.mapv_inplace(|x| x / sum);
+        }
+    }
+}
+
+/// Whether the rows (`Row`) or columns (`Column`) of `q` sum to zero, relative to the magnitude
+/// of their entries.
+fn sums_vanish<T, S>(q: &ArrayBase<S, Ix2>, convention: StochasticConvention) -> bool
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    let axis = match convention {
+        StochasticConvention::Row => Axis(1),
+        StochasticConvention::Column => Axis(0),
+    };
+    let n = T::from_usize(q.rows()).unwrap();
+    let tolerance = n * T::from_f64(16.0).unwrap() * T::epsilon();
+
+    q.lanes(axis).into_iter().all(|lane| {
+        let sum = lane.fold(T::zero(), |acc, &x| acc + x);
+        let magnitude = lane.fold(T::zero(), |acc, &x| acc + x.abs());
+        sum.abs() <= tolerance * magnitude
+    })
+}
+
+/// Calculate the transition matrix $e^{Qt}$ of the n×n generator `q` over the time `t`, storing
+/// the result in `p`.
+///
+/// NOTE: Panics if `q` is not a generator in the given `convention`, or if `q` and `p` don't have
+/// matching dimensions.
+pub fn ctmc_transition<T, S1, S2>(q: &ArrayBase<S1, Ix2>, t: T, convention: StochasticConvention, p: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = q.dim();
+
+    let mut ctmc = Ctmc::new(n);
+    ctmc.transition(q, t, convention, p);
+}
+
+/// Calculate the transition matrix $e^{Qt}$ of the n×n generator `q` over the time `t` by
+/// uniformization with the given `tolerance`, storing the non-negative and stochastic result in
+/// `p`.
+///
+/// NOTE: Panics if `t` is negative, if `tolerance` is not positive, if `q` is not a generator in
+/// the given `convention`, or if `q` and `p` don't have matching dimensions.
+pub fn expm_generator<T, S1, S2>(q: &ArrayBase<S1, Ix2>, t: T, tolerance: T, convention: StochasticConvention, p: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = q.dim();
+
+    let mut ctmc = Ctmc::new(n);
+    ctmc.transition_uniformized(q, t, tolerance, convention, p);
+}
+
+/// Propagate the distribution `p0` over the time `t` with the n×n generator `q`, storing the
+/// result in `p`.
+///
+/// NOTE: Panics if `q` is not a generator in the given `convention`, or if `p0` and `p` are not
+/// contiguous vectors of length n.
+pub fn ctmc_propagate<T, S1, S2, S3>(q: &ArrayBase<S1, Ix2>, p0: &ArrayBase<S2, Ix1>, t: T, convention: StochasticConvention, p: &mut ArrayBase<S3, Ix1>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=T>,
+          S3: DataMut<Elem=T>,
+{
+    let (n, _) = q.dim();
+
+    let mut ctmc = Ctmc::new(n);
+    ctmc.propagate(q, p0, t, convention, p);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    use super::StochasticConvention;
+
+    #[test]
+    fn conventions_agree() {
+        let q_row = arr2(&[[-3.0, 2.0, 1.0], [0.5, -0.5, 0.0], [1.0, 4.0, -5.0]]);
+        let q_column = q_row.t().to_owned();
+        let p0 = arr1(&[0.2, 0.3, 0.5]);
+        let mut p_row = Array1::<f64>::zeros(3);
+        let mut p_column = Array1::<f64>::zeros(3);
+
+        crate::ctmc_propagate(&q_row, &p0, 0.7, StochasticConvention::Row, &mut p_row);
+        crate::ctmc_propagate(&q_column, &p0, 0.7, StochasticConvention::Column, &mut p_column);
+
+        for (&x, &y) in p_row.iter().zip(p_column.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-13);
+        }
+        assert_relative_eq!(p_row.sum(), 1.0, max_relative=1e-13);
+    }
+
+    #[test]
+    fn uniformization_is_stochastic() {
+        // Rates spanning several orders of magnitude.
+        let q_row = arr2(&[[-3.0, 2.0, 1.0], [0.5, -0.5, 0.0], [1e-3, 1e3, -1e3 - 1e-3]]);
+        for &(t, convention, ref q) in &[(0.7, StochasticConvention::Row, q_row.clone()), (25.0, StochasticConvention::Column, q_row.t().to_owned())] {
+            let mut p = Array2::<f64>::zeros((3, 3));
+            let mut expected = Array2::<f64>::zeros((3, 3));
+            crate::expm_generator(q, t, 1e-14, convention, &mut p);
+            crate::ctmc_transition(q, t, convention, &mut expected);
+
+            assert!(p.iter().all(|&x| x >= 0.0));
+            let axis = if convention == StochasticConvention::Row { Axis(1) } else { Axis(0) };
+            for sum in p.sum_axis(axis).iter() {
+                assert_relative_eq!(*sum, 1.0, max_relative=4.0 * f64::EPSILON);
+            }
+            for (&x, &y) in p.iter().zip(expected.iter()) {
+                assert_relative_eq!(x, y, max_relative=1e-9, epsilon=1e-12);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "follows the Row convention")]
+    fn convention_mismatch_is_detected() {
+        let q_row = arr2(&[[-3.0, 2.0, 1.0], [0.5, -0.5, 0.0], [1.0, 4.0, -5.0]]);
+        let mut p = Array2::<f64>::zeros((3, 3));
+        crate::ctmc_transition(&q_row, 1.0, StochasticConvention::Column, &mut p);
+    }
+}
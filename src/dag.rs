@@ -0,0 +1,80 @@
+//! The matrix exponential of sparse nilpotent matrices, such as (weighted) adjacency matrices of
+//! directed acyclic graphs (DAGs).
+//!
+//! The entry $(A^k)_{ij}$ of the k-th power of a DAG adjacency matrix collects the paths of
+//! length k from node i to node j. Since no path is longer than the longest path $L < n$ of the
+//! graph, $A^{L+1} = 0$ and the exponential is given exactly by the terminating series
+//!
+//! \begin{equation}
+//!     e^A = \sum_{k=0}^{L} \frac{A^k}{k!}.
+//! \end{equation}
+//!
+//! All powers are calculated with sparse products, so that the sparsity structure of the result,
+//! the transitive closure of the graph plus the diagonal, is preserved. This module is only
+//! available with the `sprs` feature.
+
+use sprs::CsMat;
+
+use crate::Float;
+
+/// Calculate the matrix exponential of the sparse nilpotent n×n matrix `a`, e.g. the adjacency
+/// matrix of a directed acyclic graph, via the exactly terminating power series. The result is
+/// returned in compressed sparse row format.
+///
+/// NOTE: Panics if `a` is not square or not nilpotent, i.e. if the graph contains a cycle.
+pub fn expm_dag<T>(a: &CsMat<T>) -> CsMat<T>
+    where T: Float + Default,
+{
+    let (n_rows, n_cols) = a.shape();
+    assert_eq!(n_rows, n_cols, "The matrix exponential is only defined for square matrices.");
+
+    let a = a.to_csr();
+    let mut sum = CsMat::<T>::eye(n_rows);
+    let mut term = a.clone();
+
+    // A nilpotent n×n matrix satisfies A^n = 0.
+    for k in 1..=n_rows {
+        if term.nnz() == 0 {
+            return sum;
+        }
+
+        let k_inv = T::one() / T::from_usize(k).unwrap();
+        term.scale(k_inv);
+        sum = &sum + &term;
+        term = &term * &a;
+    }
+
+    assert_eq!(term.nnz(), 0, "The matrix is not nilpotent; the graph contains a cycle.");
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use sprs::CsMat;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn dag_agrees_with_dense_and_stays_sparse() {
+        // A chain 0 → 1 → 2 → 3 with a shortcut 0 → 2, and an isolated node 4.
+        let a = CsMat::new((5, 5), vec![0, 2, 3, 4, 4, 4], vec![1, 2, 2, 3], vec![1.0, 3.0, 2.0, 0.5]);
+
+        let b = crate::expm_dag(&a);
+
+        // The diagonal plus all pairs connected by a path.
+        assert_eq!(b.nnz(), 5 + 6);
+
+        let mut expected = Array2::<f64>::zeros((5, 5));
+        crate::expm(&a.to_dense(), &mut expected);
+        for (&x, &y) in b.to_dense().iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-14, epsilon=1e-15);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "not nilpotent")]
+    fn cycles_are_rejected() {
+        let a = CsMat::new((2, 2), vec![0, 1, 2], vec![1, 0], vec![1.0, 1.0]);
+        crate::expm_dag(&a);
+    }
+}
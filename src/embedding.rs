@@ -0,0 +1,257 @@
+//! Estimation of a CTMC generator from a transition matrix observed over a time step, the
+//! so-called embedding problem.
+//!
+//! A stochastic matrix $P$ is embeddable if $P = e^{Q \Delta t}$ for some generator $Q$. The
+//! natural candidate is the principal logarithm $L = \log(P) / \Delta t$, whose rows (`Row`) or
+//! columns (`Column`) sum to zero since $P$ preserves the vector of ones. It may however have
+//! negative off-diagonal entries, in which case $P$ is not embeddable with the principal branch,
+//! or not exist at all if $P$ has eigenvalues on the closed negative real axis.
+//!
+//! The estimate is therefore projected onto the valid generators, following the quasi-optimal
+//! generator of A. Kreinin and M. Sidelnikova, *Regularization algorithms for transition
+//! matrices*, Algo Research Quarterly 4 (2001): each row (or column) $l$ is replaced by its
+//! Euclidean projection onto $\{x : \sum_j x_j = 0, x_j \ge 0 \text{ for } j \ne i\}$, which is
+//!
+//! \begin{equation}
+//!     x_j = \max(l_j - \lambda, 0) \text{ for } j \ne i, \qquad x_i = l_i - \lambda,
+//! \end{equation}
+//!
+//! with the unique shift $\lambda$ for which the entries sum to zero. If the principal logarithm
+//! does not exist, the first-order estimate $(P - I) / \Delta t$ is projected instead. The
+//! diagnostics report whether $P$ was embeddable, how much negative rate mass was removed, and
+//! the residual $\lVert e^{Q \Delta t} - P \rVert_1$ of the projected generator.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    float::{
+        gees,
+        onenorm,
+    },
+    Ctmc,
+    Float,
+    Logm,
+    StochasticConvention,
+};
+
+/// Diagnostics of the estimation of a generator from a transition matrix.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EmbeddingDiagnostics<T> {
+    /// Whether the principal logarithm of the transition matrix exists, i.e. whether it has no
+    /// eigenvalues on the closed negative real axis. It is also `false` if the Schur
+    /// decomposition that determines the eigenvalues does not converge.
+    pub principal_log: bool,
+    /// Whether the principal logarithm is a generator up to rounding, so that the transition
+    /// matrix is embeddable and no projection was necessary.
+    pub embeddable: bool,
+    /// The sum of the magnitudes of the negative off-diagonal rates removed by the projection.
+    pub negative_mass: T,
+    /// The residual $\lVert e^{Q \Delta t} - P \rVert_1$ of the returned generator.
+    pub residual: T,
+}
+
+/// Storage for estimating generators from n×n transition matrices.
+pub struct CtmcEmbedding<T = f64> {
+    n: usize,
+    logm: Logm<T>,
+    ctmc: Ctmc<T>,
+    schur: Array2<T>,
+    vectors: Array2<T>,
+    wr: Array1<T>,
+    wi: Array1<T>,
+    transition: Array2<T>,
+    sorted: Vec<T>,
+}
+
+impl<T: Float> CtmcEmbedding<T> {
+    /// Allocates all space to estimate generators from n×n transition matrices.
+    pub fn new(n: usize) -> Self {
+        CtmcEmbedding {
+            n,
+            logm: Logm::new(n),
+            ctmc: Ctmc::new(n),
+            schur: Array2::zeros((n, n)),
+            vectors: Array2::zeros((n, n)),
+            wr: Array1::zeros(n),
+            wi: Array1::zeros(n),
+            transition: Array2::zeros((n, n)),
+            sorted: Vec::with_capacity(n),
+        }
+    }
+
+    /// Estimate the generator $Q$ with $e^{Q \Delta t} \approx P$ from the n×n transition matrix
+    /// `p` observed over the time step `dt`, storing the result in `q`, and return diagnostics
+    /// about the embeddability of `p`. The result is always a generator in the given
+    /// `convention`.
+    ///
+    /// NOTE: Panics if `dt` is not positive, if `p` is not a stochastic matrix in the given
+    /// `convention` up to rounding, or if `p` and `q` don't have matching dimensions or don't have
+    /// the same dimension as the `CtmcEmbedding` object `generator` is called on.
+    pub fn generator<S1, S2>(&mut self, p: &ArrayBase<S1, Ix2>, dt: T, convention: StochasticConvention, q: &mut ArrayBase<S2, Ix2>) -> EmbeddingDiagnostics<T>
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert_eq!(p.dim(), (self.n, self.n), "Dimension mismatch between matrix `p` and preconfigured `CtmcEmbedding` struct.");
+        assert_eq!(q.dim(), (self.n, self.n), "Dimension mismatch between matrix `q` and preconfigured `CtmcEmbedding` struct.");
+        assert!(dt > T::zero(), "Time step `dt` has to be positive.");
+        check_stochastic(p, convention);
+
+        self.schur.assign(p);
+        // Without converged eigenvalues, the existence of the principal logarithm is unknown, and
+        // the first-order estimate is used instead.
+        let info = gees(&mut self.schur, &mut self.vectors, &mut self.wr, &mut self.wi);
+        let principal_log = info == 0 && self.wr.iter().zip(self.wi.iter()).all(|(&re, &im)| im != T::zero() || re > T::zero());
+
+        if principal_log {
+            self.logm.logm(p, q);
+        } else {
+            q.assign(p);
+            q.diag_mut().mapv_inplace(|x| x - T::one());
+        }
+        q.mapv_inplace(|x| x / dt);
+
+        // The off-diagonal entries of the logarithm are only accurate up to rounding relative to
+        // its norm.
+        let tolerance = T::from_usize(self.n).unwrap() * T::epsilon() * onenorm(q);
+        let negative_mass = q.indexed_iter()
+            .filter(|&((i, j), &x)| i != j && x < T::zero())
+            .fold(T::zero(), |acc, (_, &x)| acc - x);
+        let embeddable = principal_log && q.indexed_iter().all(|((i, j), &x)| i == j || x >= -tolerance);
+
+        let axis = match convention {
+            StochasticConvention::Row => Axis(0),
+            StochasticConvention::Column => Axis(1),
+        };
+        for (i, mut lane) in q.axis_iter_mut(axis).enumerate() {
+            project(&mut lane, i, &mut self.sorted);
+        }
+
+        self.ctmc.transition(q, dt, convention, &mut self.transition);
+        self.transition.zip_mut_with(p, |x, &y| *x = *x - y);
+        let residual = onenorm(&self.transition);
+
+        EmbeddingDiagnostics {
+            principal_log,
+            embeddable,
+            negative_mass,
+            residual,
+        }
+    }
+}
+
+/// Projects `lane` onto the vectors whose entries sum to zero and whose entries except the
+/// `diagonal` one are non-negative, using `sorted` as scratch space.
+fn project<T: Float>(lane: &mut ArrayViewMut1<T>, diagonal: usize, sorted: &mut Vec<T>) {
+    sorted.clear();
+    sorted.extend(lane.iter().enumerate().filter(|&(j, _)| j != diagonal).map(|(_, &x)| x));
+    sorted.sort_by(|x, y| y.partial_cmp(x).unwrap_or(std::cmp::Ordering::Equal));
+
+    // With the m largest off-diagonal entries kept positive, the shift is λ = (l_i + Σ_{k≤m}
+    // b_k) / (m + 1), which is consistent if it separates the kept from the clamped entries.
+    let mut sum = lane[diagonal];
+    let mut shift = sum;
+    for (m, &b) in sorted.iter().enumerate() {
+        if b <= shift {
+            break;
+        }
+        sum = sum + b;
+        shift = sum / T::from_usize(m + 2).unwrap();
+    }
+
+    for (j, x) in lane.iter_mut().enumerate() {
+        *x = if j == diagonal {
+            *x - shift
+        } else {
+            (*x - shift).max(T::zero())
+        };
+    }
+}
+
+/// Checks that `p` is non-negative and that its rows (`Row`) or columns (`Column`) sum to 1 up
+/// to rounding.
+fn check_stochastic<T, S>(p: &ArrayBase<S, Ix2>, convention: StochasticConvention)
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    assert!(p.iter().all(|&x| x >= T::zero()), "The transition matrix has negative entries.");
+
+    let (n, _) = p.dim();
+    let tolerance = T::from_usize(n.max(1)).unwrap() * T::from_f64(8.0).unwrap() * T::epsilon();
+    let axis = match convention {
+        StochasticConvention::Row => Axis(1),
+        StochasticConvention::Column => Axis(0),
+    };
+    for sum in p.sum_axis(axis).iter() {
+        assert!((*sum - T::one()).abs() <= tolerance, "The transition matrix does not follow the {:?} convention.", convention);
+    }
+}
+
+/// Estimate the generator $Q$ with $e^{Q \Delta t} \approx P$ from the n×n transition matrix `p`
+/// observed over the time step `dt`, storing the result in `q`, and return diagnostics about the
+/// embeddability of `p`.
+///
+/// NOTE: Panics if `dt` is not positive, if `p` is not a stochastic matrix in the given
+/// `convention` up to rounding, or if `p` and `q` don't have matching dimensions.
+pub fn generator_from_transition<T, S1, S2>(p: &ArrayBase<S1, Ix2>, dt: T, convention: StochasticConvention, q: &mut ArrayBase<S2, Ix2>) -> EmbeddingDiagnostics<T>
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = p.dim();
+
+    let mut embedding = CtmcEmbedding::new(n);
+    embedding.generator(p, dt, convention, q)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    use crate::StochasticConvention;
+
+    #[test]
+    fn embeddable_generator_is_recovered() {
+        let generator = arr2(&[[-3.0, 2.0, 1.0], [0.5, -0.5, 0.0], [1.0, 4.0, -5.0]]);
+        let dt = 0.3;
+        let mut p = Array2::<f64>::zeros((3, 3));
+        crate::ctmc_transition(&generator, dt, StochasticConvention::Row, &mut p);
+
+        let mut q = Array2::<f64>::zeros((3, 3));
+        let diagnostics = crate::generator_from_transition(&p, dt, StochasticConvention::Row, &mut q);
+
+        assert!(diagnostics.principal_log);
+        assert!(diagnostics.embeddable);
+        assert!(diagnostics.residual < 1e-13);
+        for (&x, &y) in q.iter().zip(generator.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-10, epsilon=1e-12);
+        }
+    }
+
+    #[test]
+    fn non_embeddable_matrices_are_projected() {
+        // The principal logarithm has a negative entry in the upper right corner.
+        let p = arr2(&[[0.9, 0.1, 0.0], [0.0, 0.9, 0.1], [0.0, 0.0, 1.0]]);
+        // Eigenvalues on the negative real axis.
+        let swap = arr2(&[[0.1, 0.9], [0.9, 0.1]]);
+
+        let mut q = Array2::<f64>::zeros((3, 3));
+        let diagnostics = crate::generator_from_transition(&p, 1.0, StochasticConvention::Row, &mut q);
+        assert!(diagnostics.principal_log);
+        assert!(!diagnostics.embeddable);
+        assert!(diagnostics.negative_mass > 1e-3);
+        assert!(diagnostics.residual > 0.0 && diagnostics.residual < 0.1);
+        crate::check_generator(&q, StochasticConvention::Row);
+
+        let mut q = Array2::<f64>::zeros((2, 2));
+        let diagnostics = crate::generator_from_transition(&swap, 1.0, StochasticConvention::Column, &mut q);
+        assert!(!diagnostics.principal_log);
+        assert!(!diagnostics.embeddable);
+        crate::check_generator(&q, StochasticConvention::Column);
+    }
+}
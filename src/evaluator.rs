@@ -0,0 +1,261 @@
+//! Exponentials $e^{tA}$ of a fixed matrix at many time points, e.g. for plotting relaxation
+//! curves, with the decomposition of $A$ calculated once.
+//!
+//! Symmetric matrices are diagonalized, $A = V \Lambda V^\top$, after which each time point only
+//! costs the back transformation
+//!
+//! \begin{equation}
+//!     e^{tA} = V e^{t \Lambda} V^\top,
+//! \end{equation}
+//!
+//! or, for the action on a vector, two matrix-vector products. All other matrices are reduced to
+//! real Schur form $A = Q T Q^\top$, and
+//!
+//! \begin{equation}
+//!     e^{tA} = Q e^{tT} Q^\top
+//! \end{equation}
+//!
+//! is evaluated with the quasi-triangular variant of `expm`, which calculates the diagonal
+//! blocks of $e^{tT}$ exactly, and so avoids repeating the Schur decomposition for every $t$.
+//! The powers $T^k$ and the norm estimates of $\lVert T^k \rVert_1$ that select the Padé order
+//! and scaling are calculated once as well. Since $\lVert (tT)^k \rVert_1 = \lvert t\rvert^k
+//! \lVert T^k \rVert_1$, each time point only costs the approximant, the squarings, and the
+//! back transformation.
+
+use ndarray::{
+    self,
+    linalg::general_mat_vec_mul,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    float::{
+        gees,
+        gemm,
+        syev,
+    },
+    plan::ScalingPlan,
+    Expm,
+    Float,
+};
+
+/// The decomposition of the matrix that the exponentials are calculated from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Decomposition {
+    /// $A = V \Lambda V^\top$ with the eigenvectors in `q` and the eigenvalues in `lambda`.
+    Eigen,
+    /// $A = Q T Q^\top$ with the Schur vectors in `q` and the Schur form planned in `plan`.
+    Schur,
+}
+
+/// Storage for a decomposition of an n×n matrix $A$, from which $e^{tA}$ is calculated for many
+/// values of $t$.
+pub struct ExpmEvaluator<T = f64> {
+    n: usize,
+    decomposition: Decomposition,
+    q: Array2<T>,
+    lambda: Array1<T>,
+    expm: Expm<T>,
+    plan: ScalingPlan<T>,
+    exp_t: Array2<T>,
+    work: Array2<T>,
+    y: Array1<T>,
+    z: Array1<T>,
+}
+
+impl<T: Float> ExpmEvaluator<T> {
+    /// Decomposes the n×n matrix `a` and allocates all space to calculate $e^{tA}$ from it. The
+    /// eigendecomposition is used if `a` is symmetric, and the real Schur decomposition otherwise.
+    ///
+    /// NOTE: Panics if `a` is not square, or if its decomposition does not converge.
+    pub fn new<S>(a: &ArrayBase<S, Ix2>) -> Self
+        where S: Data<Elem=T>,
+    {
+        let (n_rows, n_cols) = a.dim();
+        assert_eq!(n_rows, n_cols, "The exponential is only defined for square matrices.");
+        let n = n_rows;
+
+        let mut q = Array2::zeros((n, n));
+        let mut lambda = Array1::zeros(n);
+        let mut t = Array2::zeros((n, n));
+
+        let is_symmetric = a.indexed_iter().all(|((i, j), &x)| x == a[(j, i)]);
+        let decomposition = if is_symmetric {
+            q.assign(a);
+            let info = syev(&mut q, &mut lambda);
+            assert_eq!(info, 0, "The eigendecomposition of `a` did not converge.");
+            Decomposition::Eigen
+        } else {
+            let mut wi = Array1::zeros(n);
+            t.assign(a);
+            let info = gees(&mut t, &mut q, &mut lambda, &mut wi);
+            assert_eq!(info, 0, "The Schur decomposition of `a` did not converge.");
+            Decomposition::Schur
+        };
+
+        let mut expm = Expm::new(n);
+        let mut plan = ScalingPlan::new(n);
+        if decomposition == Decomposition::Schur {
+            expm.plan_triangular(&t, &mut plan);
+        }
+
+        ExpmEvaluator {
+            n,
+            decomposition,
+            q,
+            lambda,
+            expm,
+            plan,
+            exp_t: Array2::zeros((n, n)),
+            work: Array2::zeros((n, n)),
+            y: Array1::zeros(n),
+            z: Array1::zeros(n),
+        }
+    }
+
+    /// Calculate $e^{tA}$, storing the result in `b`.
+    ///
+    /// NOTE: Panics if `b` doesn't have the same dimension as the matrix the `ExpmEvaluator` was
+    /// created from.
+    pub fn eval<S>(&mut self, t: T, b: &mut ArrayBase<S, Ix2>)
+        where S: DataMut<Elem=T>,
+    {
+        assert_eq!(b.dim(), (self.n, self.n), "Dimension mismatch between matrix `b` and preconfigured `ExpmEvaluator` struct.");
+
+        match self.decomposition {
+            Decomposition::Eigen => {
+                // B = V exp(tΛ) Vᵀ
+                self.work.assign(&self.q);
+                for (mut column, &lambda) in self.work.gencolumns_mut().into_iter().zip(self.lambda.iter()) {
+                    let exp_lambda = (t * lambda).exp();
+                    column.mapv_inplace(|x| x * exp_lambda);
+                }
+                gemm(T::one(), &self.work, &self.q.t(), T::zero(), b);
+            }
+            Decomposition::Schur => {
+                self.exp_schur(t);
+                gemm(T::one(), &self.q, &self.exp_t, T::zero(), &mut self.work);
+                gemm(T::one(), &self.work, &self.q.t(), T::zero(), b);
+            }
+        }
+    }
+
+    /// Calculate $e^{tA} v$ for the vector `v`, storing the result in `w`. For symmetric
+    /// matrices, this only costs two matrix-vector products.
+    ///
+    /// NOTE: Panics if `v` or `w` don't have the same length as the dimension of the matrix the
+    /// `ExpmEvaluator` was created from.
+    pub fn eval_multiply<S1, S2>(&mut self, t: T, v: &ArrayBase<S1, Ix1>, w: &mut ArrayBase<S2, Ix1>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert_eq!(v.len(), self.n, "Dimension mismatch between vector `v` and preconfigured `ExpmEvaluator` struct.");
+        assert_eq!(w.len(), self.n, "Dimension mismatch between vector `w` and preconfigured `ExpmEvaluator` struct.");
+
+        // y = Qᵀ v
+        general_mat_vec_mul(T::one(), &self.q.t(), v, T::zero(), &mut self.y);
+
+        match self.decomposition {
+            Decomposition::Eigen => {
+                self.y.zip_mut_with(&self.lambda, |y, &lambda| *y = *y * (t * lambda).exp());
+                general_mat_vec_mul(T::one(), &self.q, &self.y, T::zero(), w);
+            }
+            Decomposition::Schur => {
+                self.exp_schur(t);
+                general_mat_vec_mul(T::one(), &self.exp_t, &self.y, T::zero(), &mut self.z);
+                general_mat_vec_mul(T::one(), &self.q, &self.z, T::zero(), w);
+            }
+        }
+    }
+
+    /// Calculates $e^{tT}$ of the Schur form $T$ in `exp_t` from the planned order and scaling.
+    fn exp_schur(&mut self, t: T) {
+        self.expm.expm_planned(&mut self.plan, t, &mut self.exp_t);
+    }
+}
+
+/// Calculate $e^{t_k A}$ for the n×n matrix `a` and all times $t_k$ in `times`, storing the
+/// result for $t_k$ in `b[k]`. The matrix is decomposed only once.
+///
+/// NOTE: Panics if `a` is not square, if `b` is not of dimension k×n×n for k times, or if the
+/// decomposition of `a` does not converge.
+pub fn expm_at_times<T, S1, S2>(a: &ArrayBase<S1, Ix2>, times: &[T], b: &mut ArrayBase<S2, Ix3>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+    assert_eq!(b.dim(), (times.len(), n, n), "Dimension mismatch between tensor `b` and the times and matrix `a`.");
+
+    let mut evaluator = ExpmEvaluator::new(a);
+    for (&t, mut b) in times.iter().zip(b.outer_iter_mut()) {
+        evaluator.eval(t, &mut b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    use super::ExpmEvaluator;
+
+    #[test]
+    fn evaluator_agrees_with_expm() {
+        let general = arr2(&[[-1.0, 2.0, 0.5], [-2.0, -3.0, 1.0], [0.25, 0.25, -0.5]]);
+        let symmetric = arr2(&[[-1.0, 2.0, 0.5], [2.0, -3.0, 1.0], [0.5, 1.0, -0.5]]);
+        let v = arr1(&[1.0, -2.0, 0.5]);
+        let times = [0.0, 0.1, 1.0, 2.5];
+
+        for a in &[general, symmetric] {
+            let mut b = Array3::<f64>::zeros((times.len(), 3, 3));
+            crate::expm_at_times(a, &times, &mut b);
+
+            let mut evaluator = ExpmEvaluator::new(a);
+            let mut expected = Array2::<f64>::zeros((3, 3));
+            let mut w = Array1::<f64>::zeros(3);
+            for (&t, b) in times.iter().zip(b.outer_iter()) {
+                crate::expm(&(t * a), &mut expected);
+                for (&x, &y) in b.iter().zip(expected.iter()) {
+                    assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-14);
+                }
+
+                evaluator.eval_multiply(t, &v, &mut w);
+                for (&x, &y) in w.iter().zip(expected.dot(&v).iter()) {
+                    assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-14);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn schur_time_points_reuse_the_plan() {
+        let a = arr2(&[
+            [-1.0, 2.0, 0.5, 0.0],
+            [-2.0, -3.0, 1.0, 2.0],
+            [0.25, 0.25, -0.5, 1.0],
+            [1.0, 0.0, -3.0, -2.0],
+        ]);
+        let mut evaluator = ExpmEvaluator::new(&a);
+        let mut b = Array2::<f64>::zeros((4, 4));
+        let mut expected = Array2::<f64>::zeros((4, 4));
+
+        for &t in &[0.01, 0.7, 4.0, -1.5] {
+            evaluator.eval(t, &mut b);
+            let counters = evaluator.expm.counters();
+            let report = evaluator.expm.report;
+            let pade_products = if report.pade_order == 13 { 3 } else { 1 };
+
+            // Neither the powers of T nor the norm estimates are recalculated.
+            assert_eq!(counters.matvecs, 0);
+            assert_eq!(counters.matrix_products, pade_products + report.squarings as usize);
+
+            crate::expm(&(t * &a), &mut expected);
+            for (&x, &y) in b.iter().zip(expected.iter()) {
+                assert_relative_eq!(x, y, max_relative=1e-11, epsilon=1e-13);
+            }
+        }
+    }
+}
@@ -0,0 +1,187 @@
+//! Locating the first time $t$ at which a functional of the state $x(t) = e^{tA} x_0$ changes
+//! sign, e.g. when a probability of a Markov chain exceeds a bound or the norm of a decaying
+//! state drops below a tolerance.
+//!
+//! Instead of calculating a new exponential for every trial time, the interval $[0, t_\mathrm{max}]$
+//! is scanned on a uniform grid of width $h$ with the single propagator $e^{hA}$. Once the sign
+//! changes between two grid points, the bracket is bisected with the dyadic propagators
+//! $e^{2^{-j} h A}$, each of which is calculated at most once per search. Every trial time thus
+//! costs a single matrix-vector product. The crossing within the final bracket is located by
+//! linear interpolation.
+//!
+//! Crossings that occur and revert between two grid points are not detected, so the grid has
+//! to resolve the time scale of the functional.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+};
+
+use crate::{
+    float::gemm,
+    Expm,
+    Float,
+};
+
+/// The number of bisections of a bracket, which shrinks it to $2^{-32} h$ before the final
+/// interpolation.
+const BISECTIONS: usize = 32;
+
+/// Storage for locating sign changes of functionals along the trajectory of n×n matrices.
+pub struct ExpmEvent<T = f64> {
+    n: usize,
+    expm: Expm<T>,
+    scaled: Array2<T>,
+    step: Array2<T>,
+    bisection_steps: Vec<Array2<T>>,
+    x: Array2<T>,
+    x_next: Array2<T>,
+}
+
+impl<T: Float> ExpmEvent<T> {
+    /// Allocates all space to scan the trajectories of n×n matrices. The propagators for the
+    /// bisection are allocated on first use.
+    pub fn new(n: usize) -> Self {
+        ExpmEvent {
+            n,
+            expm: Expm::new(n),
+            scaled: Array2::zeros((n, n)),
+            step: Array2::zeros((n, n)),
+            bisection_steps: Vec::new(),
+            x: Array2::zeros((n, 1)),
+            x_next: Array2::zeros((n, 1)),
+        }
+    }
+
+    /// Find the first time $t \in [0, t_\mathrm{max}]$ at which `f` applied to $e^{tA} x_0$
+    /// changes sign, scanning `steps` uniform intervals. To detect a threshold crossing of some
+    /// quantity $g(x)$, pass $f(x) = g(x) - \mathrm{threshold}$. Returns `None` if no sign change
+    /// is detected on the grid.
+    ///
+    /// NOTE: Panics if `a` is not square, if `x0` doesn't match its dimension, if `steps` is
+    /// zero, or if `a` doesn't have the same dimension as the `ExpmEvent` object `find` is
+    /// called on.
+    pub fn find<S1, S2, F>(&mut self, a: &ArrayBase<S1, Ix2>, x0: &ArrayBase<S2, Ix1>, t_max: T, steps: usize, mut f: F) -> Option<T>
+        where S1: Data<Elem=T>,
+              S2: Data<Elem=T>,
+              F: FnMut(ArrayView1<T>) -> T,
+    {
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `ExpmEvent` struct.");
+        assert_eq!(x0.len(), self.n, "Dimension mismatch between vector `x0` and preconfigured `ExpmEvent` struct.");
+        assert!(steps > 0, "The scan requires at least one step.");
+
+        let h = t_max / T::from_usize(steps).unwrap();
+
+        self.x.column_mut(0).assign(x0);
+        let mut g = f(self.x.column(0));
+        if g == T::zero() {
+            return Some(T::zero());
+        }
+
+        self.scaled.assign(a);
+        self.scaled.mapv_inplace(|x| h * x);
+        self.expm.expm(&self.scaled, &mut self.step);
+        let mut bisection_levels = 0;
+
+        for k in 0..steps {
+            gemm(T::one(), &self.step, &self.x, T::zero(), &mut self.x_next);
+            let g_next = f(self.x_next.column(0));
+            if g_next == T::zero() {
+                return Some(T::from_usize(k + 1).unwrap() * h);
+            }
+            if (g_next > T::zero()) == (g > T::zero()) {
+                std::mem::swap(&mut self.x, &mut self.x_next);
+                g = g_next;
+                continue;
+            }
+
+            // Bisect the bracket [t, t + h], keeping the state at its left end in `x`.
+            let mut t = T::from_usize(k).unwrap() * h;
+            let mut width = h;
+            let mut g_right = g_next;
+            for j in 0..BISECTIONS {
+                width = width / T::from_f64(2.0).unwrap();
+                if j == bisection_levels {
+                    self.bisection_step(a, width, j);
+                    bisection_levels += 1;
+                }
+
+                gemm(T::one(), &self.bisection_steps[j], &self.x, T::zero(), &mut self.x_next);
+                let g_mid = f(self.x_next.column(0));
+                if g_mid == T::zero() {
+                    return Some(t + width);
+                }
+                if (g_mid > T::zero()) == (g > T::zero()) {
+                    std::mem::swap(&mut self.x, &mut self.x_next);
+                    t = t + width;
+                    g = g_mid;
+                } else {
+                    g_right = g_mid;
+                }
+            }
+
+            return Some(t + width * g / (g - g_right));
+        }
+
+        None
+    }
+
+    /// Calculates the propagator $e^{wA}$ for the bisection at depth `j`.
+    fn bisection_step<S>(&mut self, a: &ArrayBase<S, Ix2>, width: T, j: usize)
+        where S: Data<Elem=T>,
+    {
+        if self.bisection_steps.len() <= j {
+            self.bisection_steps.push(Array2::zeros((self.n, self.n)));
+        }
+        self.scaled.assign(a);
+        self.scaled.mapv_inplace(|x| width * x);
+        self.expm.expm(&self.scaled, &mut self.bisection_steps[j]);
+    }
+}
+
+/// Find the first time $t \in [0, t_\mathrm{max}]$ at which `f` applied to $e^{tA} x_0$ changes
+/// sign, scanning `steps` uniform intervals. Returns `None` if no sign change is detected on the
+/// grid.
+///
+/// NOTE: Panics if `a` is not square, if `x0` doesn't match its dimension, or if `steps` is zero.
+pub fn expm_event<T, S1, S2, F>(a: &ArrayBase<S1, Ix2>, x0: &ArrayBase<S2, Ix1>, t_max: T, steps: usize, f: F) -> Option<T>
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=T>,
+          F: FnMut(ArrayView1<T>) -> T,
+{
+    let (n, _) = a.dim();
+
+    let mut event = ExpmEvent::new(n);
+    event.find(a, x0, t_max, steps, f)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn probability_crosses_threshold() {
+        // Two-state chain with unit rates: p₁(t) = (1 - e^{-2t}) / 2 reaches 1/4 at t = ln(2) / 2.
+        let q = arr2(&[[-1.0, 1.0], [1.0, -1.0]]);
+        let p0 = arr1(&[1.0, 0.0]);
+
+        let t = crate::expm_event(&q, &p0, 3.0, 10, |p| p[1] - 0.25).unwrap();
+
+        assert_relative_eq!(t, 2f64.ln() / 2.0, max_relative=1e-12);
+    }
+
+    #[test]
+    fn norm_decays_below_tolerance() {
+        let a = arr2(&[[-0.1, 2.0], [-2.0, -0.1]]);
+        let x0 = arr1(&[1.0, 0.0]);
+
+        // The rotation preserves the norm, which decays as e^{-t/10}.
+        let t = crate::expm_event(&a, &x0, 100.0, 200, |x: ArrayView1<f64>| x.dot(&x).sqrt() - 1e-2).unwrap();
+        assert_relative_eq!(t, 10.0 * 100f64.ln(), max_relative=1e-10);
+
+        assert!(crate::expm_event(&a, &x0, 10.0, 20, |x: ArrayView1<f64>| x.dot(&x).sqrt() - 1e-2).is_none());
+    }
+}
@@ -0,0 +1,199 @@
+//! The matrix analogue of `exp_m1`, $e^A - I$, calculated without the cancellation of forming
+//! $e^A$ first and subtracting the identity, which loses all significant digits for matrices of
+//! small norm such as $hA$ with a small time step $h$.
+//!
+//! With the splitting $p_m = V + U$ and $q_m = V - U$ of the Padé approximant into even and odd
+//! parts, the difference to the identity is available without cancellation as
+//!
+//! \begin{equation}
+//!     r_m(A) - I = q_m(A)^{-1} (p_m(A) - q_m(A)) = (V - U)^{-1} \, 2U.
+//! \end{equation}
+//!
+//! The squaring phase acts on $W = e^X - I$ directly via
+//!
+//! \begin{equation}
+//!     e^{2X} - I = (e^X - I)(e^X - I) + 2(e^X - I) = W (W + 2I),
+//! \end{equation}
+//!
+//! which only adds to $W$ and never subtracts the identity. The Padé order and scaling parameter
+//! are chosen as for `expm`, and the backward error bounds carry over since $r_m(A) - I = e^{A +
+//! \Delta A} - I$.
+//!
+//! Non-finite intermediate values and a singular denominator are handled as for `expm`: the
+//! difference is recalculated with the highest Padé order and the conservative scaling parameter
+//! from the exact 1-norm, and the outcome is recorded in the report.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    float::{
+        gemm,
+        gesv,
+    },
+    recovery::all_finite,
+    Counters,
+    Expm,
+    ExpmReport,
+    Float,
+    PadeOrders,
+};
+
+impl<T: Float> Expm<T> {
+    /// Calculate $e^A - I$ for the n×n matrix `a`, storing the result in matrix `b`.
+    ///
+    /// NOTE: Panics if input matrices `a` and `b` don't have matching dimensions, are not square,
+    /// not in row-major order, or don't have the same dimension as the `Expm` object `expm1m` is
+    /// called on.
+    pub fn expm1m<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert_eq!(a.dim(), b.dim(), "Input matrices `a` and `b` have to have matching dimensions.");
+        let (n_rows, n_cols) = a.dim();
+        assert_eq!(n_rows, n_cols, "expm1m is only implemented for square matrices.");
+        assert_eq!(n_rows, self.n, "Dimension mismatch between matrix `a` and preconfigured `Expm` struct.");
+
+        self.counters = Counters::default();
+        self.matvecs_before = self.normest1.matvecs();
+        self.report = ExpmReport::new();
+
+        let w = b;
+        self.a1.assign(a);
+
+        let (pade_order, eta, is_scaled) = match self.options.pade_order {
+            Some(m) => self.prepare_order(m),
+            None => self.select_order(),
+        };
+
+        let s = if is_scaled {
+            self.scale(a, eta, pade_order.order())
+        } else {
+            0
+        };
+
+        self.report.pade_order = pade_order.order();
+        self.report.squarings = s;
+        self.report.norm_parameter = eta;
+
+        let is_finite = self.evaluate_expm1m(pade_order, s, w);
+        if !is_finite && all_finite(a) {
+            let (pade_order, s) = self.prepare_conservative(a);
+            self.report.recovered = self.evaluate_expm1m(pade_order, s, w);
+            self.report.non_finite = !self.report.recovered;
+        } else {
+            self.report.non_finite = !is_finite;
+        }
+    }
+
+    /// Calculates $e^X - I$ for the scaled matrix $X$ in `a1` with the Padé approximant of order
+    /// `pade_order`, followed by `s` squarings, and stores it in `w`. Returns whether the result
+    /// is finite; a singular denominator fills `w` with NaN and skips the squarings.
+    fn evaluate_expm1m<S>(&mut self, pade_order: PadeOrders, s: i32, w: &mut ArrayBase<S, Ix2>) -> bool
+        where S: DataMut<Elem=T>,
+    {
+        // w = (V - U)⁻¹ 2U, using u for V - U.
+        self.pade_sums(pade_order, w);
+        self.work.assign(&self.u);
+        self.u.zip_mut_with(w, |x, &y| *x = y - *x);
+        w.zip_mut_with(&self.work, |x, &y| *x = y + y);
+        let info = gesv(&mut self.u, &mut self.pivot, w);
+        if info != 0 {
+            w.fill(T::nan());
+            return false;
+        }
+
+        let two = T::from_f64(2.0).unwrap();
+        for _ in 0..s {
+            if !all_finite(w) {
+                return false;
+            }
+            // w <- w (w + 2I) = w² + 2w
+            gemm(T::one(), w, w, T::zero(), &mut self.u);
+            self.u.zip_mut_with(w, |x, &y| *x = *x + two * y);
+            w.assign(&self.u);
+            self.counters.matrix_products += 1;
+        }
+        all_finite(w)
+    }
+}
+
+/// Calculate $e^A - I$ for the n×n matrix `a`, storing the result in matrix `b`.
+///
+/// NOTE: Panics if input matrices `a` and `b` don't have matching dimensions, are not square, or
+/// not in row-major order.
+pub fn expm1m<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut expm = Expm::new(n);
+    expm.expm1m(a, b);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    use crate::ExpmOptions;
+
+    fn matrix() -> Array2<f64> {
+        arr2(&[[-1.0, 2.0, 0.5], [0.5, -3.0, 1.0], [0.25, 0.25, -0.5]])
+    }
+
+    #[test]
+    fn expm1m_is_accurate_for_small_norms() {
+        let a = 1e-10 * matrix();
+        let mut b = Array2::<f64>::zeros((3, 3));
+        crate::expm1m(&a, &mut b);
+
+        // A + A²/2 + A³/6 is exact to working precision for such small norms.
+        let a2 = a.dot(&a);
+        let expected = &a + &(0.5 * &a2) + &(a2.dot(&a) / 6.0);
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-14);
+        }
+    }
+
+    #[test]
+    fn expm1m_agrees_with_expm() {
+        let a = 10.0 * matrix();
+        let mut b = Array2::<f64>::zeros((3, 3));
+        let mut expected = Array2::<f64>::zeros((3, 3));
+
+        let mut expm = crate::Expm::new(3);
+        expm.expm1m(&a, &mut b);
+        assert_eq!(expm.counters().linear_solves, 1);
+        crate::expm(&a, &mut expected);
+        expected.diag_mut().mapv_inplace(|x| x - 1.0);
+
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-14);
+        }
+    }
+
+    #[test]
+    fn singular_denominator_is_recovered() {
+        // The denominator q₃(x) = 120 - 60x + 12x² - x³ of the unscaled approximant of order 3
+        // evaluates to exactly zero at this x, see the test of the same name for `expm`.
+        let x = 4.644370709252172f64;
+        let a = arr2(&[[x]]);
+        let mut b = Array2::<f64>::zeros((1, 1));
+
+        let mut expm = crate::Expm::new(1);
+        expm.options = ExpmOptions { pade_order: Some(3), max_squarings: Some(0), ..ExpmOptions::default() };
+        expm.expm1m(&a, &mut b);
+
+        assert!(expm.report.recovered);
+        assert!(!expm.report.non_finite);
+        assert_eq!(expm.counters().linear_solves, 2);
+        assert_relative_eq!(b[(0, 0)], x.exp_m1(), max_relative=1e-14);
+    }
+}
@@ -0,0 +1,171 @@
+//! Squaring in double-word arithmetic, which reduces the accumulation of rounding errors during
+//! the squaring phase for large scaling parameters $s$.
+//!
+//! The squared matrix is kept as an unevaluated sum $V = V_h + V_l$ of a high and a low part in
+//! the working precision, e.g. as an `f64x2` number for `f64`. Each squaring calculates
+//!
+//! \begin{equation}
+//!     V^2 \approx V_h V_h + (V_h V_l + V_l V_h),
+//! \end{equation}
+//!
+//! where the product $V_h V_h$ is accumulated with the compensated dot product `Dot2` of [Ogita,
+//! Rump, Oishi 2005], which is as accurate as if calculated in twice the working precision, and
+//! the corrections are calculated with ordinary matrix products. The term $V_l V_l$ is below the
+//! working precision and neglected. The result is renormalized into a high and a low part, and
+//! the sum is rounded to the working precision only after the last squaring.
+//!
+//! The compensated product is not routed through BLAS and costs considerably more than an
+//! ordinary matrix product, so that this option is only worth it if the squaring phase dominates
+//! the error.
+//!
+//! The truncated Taylor series of `ExpmProduct` and `ExpmCrossCheck` can likewise accumulate
+//! their terms with compensated summation.
+//!
+//! [Ogita, Rump, Oishi 2005]: https://doi.org/10.1137/030601818
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+    Zip,
+};
+
+use crate::{
+    float::gemm,
+    recovery,
+    triangular,
+    Expm,
+    Float,
+};
+
+impl<T: Float> Expm<T> {
+    /// Undoes the scaling by squaring `v` $s$ times in double-word arithmetic, using `work` for
+    /// the low part and `u` for the corrections. For quasi-triangular matrices, the diagonal
+    /// blocks are recomputed exactly before every squaring.
+    pub(crate) fn square_extended<S>(&mut self, s: i32, v: &mut ArrayBase<S, Ix2>)
+        where S: DataMut<Elem=T>,
+    {
+        let two = T::from_f64(2.0).unwrap();
+        let n = self.n;
+
+        self.work.fill(T::zero());
+        for k in 0..s {
+            if self.is_triangular {
+                triangular::recompute_diagonal(&self.a1, two.powi(k), &self.blocks, v);
+                triangular::clear_recomputed(&self.blocks, &mut self.work);
+            }
+
+            // V_h V_l + V_l V_h in working precision.
+            gemm(T::one(), v, &self.work, T::zero(), &mut self.u);
+            gemm(T::one(), &self.work, v, T::one(), &mut self.u);
+
+            // Every entry of the corrections is read before being overwritten by the high part
+            // of the result, and the low part is no longer needed.
+            for i in 0..n {
+                for j in 0..n {
+                    let (sum, error) = dot2(v.row(i), v.column(j));
+                    let (high, low) = two_sum(sum, error + self.u[(i, j)]);
+                    self.u[(i, j)] = high;
+                    self.work[(i, j)] = low;
+                }
+            }
+            v.assign(&self.u);
+
+            // The compensated product and the two corrections.
+            self.counters.matrix_products += 3;
+            if !recovery::all_finite(v) {
+                break;
+            }
+        }
+        v.zip_mut_with(&self.work, |x, &y| *x = *x + y);
+    }
+}
+
+/// Adds `term` to `sum`, accumulating the rounding errors of the additions in `compensation`
+/// following [Neumaier 1974]. The compensated sum is `sum + compensation`.
+///
+/// [Neumaier 1974]: https://doi.org/10.1002/zamm.19740540106
+pub(crate) fn add_compensated<T, S1, S2, S3>(sum: &mut ArrayBase<S1, Ix2>, compensation: &mut ArrayBase<S2, Ix2>, term: &ArrayBase<S3, Ix2>)
+    where T: Float,
+          S1: DataMut<Elem=T>,
+          S2: DataMut<Elem=T>,
+          S3: Data<Elem=T>,
+{
+    Zip::from(sum)
+        .and(compensation)
+        .and(term)
+        .apply(|x, c, &y| {
+            let (new_sum, error) = two_sum(*x, y);
+            *x = new_sum;
+            *c = *c + error;
+        });
+}
+
+/// The sum of `a` and `b` as an unevaluated sum of the rounded sum and its rounding error.
+fn two_sum<T: Float>(a: T, b: T) -> (T, T) {
+    let sum = a + b;
+    let b_virtual = sum - a;
+    let error = (a - (sum - b_virtual)) + (b - b_virtual);
+    (sum, error)
+}
+
+/// The dot product of `x` and `y` as an unevaluated sum of the rounded dot product and an
+/// approximation of its rounding error, following algorithm `Dot2` of the paper.
+fn dot2<T: Float>(x: ArrayView1<T>, y: ArrayView1<T>) -> (T, T) {
+    let mut sum = T::zero();
+    let mut error = T::zero();
+    for (&a, &b) in x.iter().zip(y.iter()) {
+        let product = a * b;
+        let product_error = a.mul_add(b, -product);
+        let (new_sum, sum_error) = two_sum(sum, product);
+        sum = new_sum;
+        error = error + (sum_error + product_error);
+    }
+    (sum, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    use crate::{
+        Expm,
+        ExpmOptions,
+    };
+
+    #[test]
+    fn extended_squaring_agrees_with_expm() {
+        let a = arr2(&[[-100.0, 200.0, 0.0], [50.0, -300.0, 100.0], [0.0, 25.0, -50.0]]);
+        let mut b = Array2::<f64>::zeros((3, 3));
+        let mut expected = Array2::<f64>::zeros((3, 3));
+
+        let options = ExpmOptions { extended_squaring: true, ..ExpmOptions::default() };
+        let report = crate::expm_with_opts(&a, &mut b, &options);
+        crate::expm(&a, &mut expected);
+
+        assert!(report.squarings > 0);
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-10, epsilon=1e-14);
+        }
+    }
+
+    #[test]
+    fn extended_squaring_is_accurate() {
+        // Squaring 1 + 2⁻⁴⁰ forty times loses the contribution of 2⁻⁸⁰ in every step, which
+        // accumulates to a relative error of about 2⁻⁴⁰ in working precision.
+        let x = 1.0 + 2f64.powi(-40);
+        let expected = (2f64.powi(40) * 2f64.powi(-40).ln_1p()).exp();
+
+        let mut expm = Expm::<f64>::new(2);
+        let mut v = Array2::eye(2) * x;
+        expm.square_extended(40, &mut v);
+        assert_relative_eq!(v[(0, 0)], expected, max_relative=1e-15);
+        assert_eq!(v[(0, 1)], 0.0);
+
+        let mut v = Array2::eye(2) * x;
+        expm.square(40, &mut v);
+        assert!((v[(0, 0)] - expected).abs() > 1e-13 * expected);
+    }
+}
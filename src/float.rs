@@ -0,0 +1,846 @@
+//! Floating point types the matrix exponential can be calculated for.
+//!
+//! The order of the Padé approximant and the scaling parameter are chosen such that the backward
+//! error is bounded by the unit roundoff of the respective type. The thresholds $\theta_m$ below
+//! which an approximant of order $m$ suffices are thus precision dependent. The double precision
+//! values are taken from [Al-Mohy, Higham], the single precision values from [Higham 2005].
+//!
+//! Only `f32` and `f64` implement `Float`. Besides the thresholds, which `pade_theta` could
+//! provide for any unit roundoff, the trait requires the BLAS and LAPACK routines used for the
+//! matrix products, linear systems, and decompositions, which only exist for these two types.
+//! Arbitrary precision types such as `rug::Float` are moreover not `Copy`. `ndarray` itself does
+//! not require `Copy` elements, but its `LinalgScalar` trait, which bounds all BLAS-backed paths
+//! in this crate, does. The `rug` feature thus provides a separate implementation of the
+//! algorithm for `rug::Float` with generic matrix products and LU factorizations, see
+//! `ExpmMultiprecision`, whose thresholds are calculated with `pade_theta` per precision.
+//!
+//! [Al-Mohy, Higham]: http://eprints.ma.man.ac.uk/1300/1/covered/MIMS_ep2009_9.pdf
+//! [Higham 2005]: https://doi.org/10.1137/04061101X
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+    LinalgScalar,
+    ScalarOperand,
+};
+use num_traits::FromPrimitive;
+use std::fmt::Debug;
+
+use crate::as_slice_with_layout_mut;
+
+/// A real floating point type supported by `expm`.
+///
+/// Besides the precision dependent thresholds for the selection of the Padé order, implementors
+/// provide the BLAS and LAPACK routines used for the matrix products and the solution of the Padé
+/// linear system.
+pub trait Float: num_traits::Float + FromPrimitive + LinalgScalar + ScalarOperand + Debug + Send + Sync {
+    /// The Padé orders $m$ supported for this precision in increasing order, together with the
+    /// thresholds $\theta_m$ of the approximants.
+    const THETAS: &'static [(u64, f64)];
+    /// The highest Padé order, which is the one used together with scaling and squaring. Either
+    /// 7 or 13, and always the last order in `THETAS`.
+    const MAX_PADE_ORDER: u64;
+
+    /// The thresholds $\ell_m$ for evaluating the Fréchet derivative with the Padé approximants of
+    /// the orders in `THETAS`.
+    const FRECHET_THETAS: &'static [(u64, f64)];
+
+    /// The threshold $\theta_m$ for the Padé approximant of order `m`, or `None` if the order is
+    /// not supported for this precision.
+    fn threshold(m: u64) -> Option<f64> {
+        Self::THETAS.iter().find(|&&(order, _)| order == m).map(|&(_, theta)| theta)
+    }
+
+    /// The threshold $\ell_m$ for evaluating the Fréchet derivative with the Padé approximant of
+    /// order `m`, or `None` if the order is not supported for this precision.
+    fn frechet_threshold(m: u64) -> Option<f64> {
+        Self::FRECHET_THETAS.iter().find(|&&(order, _)| order == m).map(|&(_, theta)| theta)
+    }
+
+    /// The unit roundoff, defined as half the machine epsilon.
+    fn unit_roundoff() -> Self {
+        Self::epsilon() / Self::from_f64(2.0).unwrap()
+    }
+
+    /// Matrix-matrix product $C \leftarrow \alpha\,\mathrm{op}(A)\,\mathrm{op}(B) + \beta C$, see
+    /// `cblas::dgemm`.
+    ///
+    /// # Safety
+    ///
+    /// The slices have to be large enough for the given dimensions and leading dimensions.
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn gemm(
+        layout: cblas::Layout,
+        transa: cblas::Transpose,
+        transb: cblas::Transpose,
+        m: i32,
+        n: i32,
+        k: i32,
+        alpha: Self,
+        a: &[Self],
+        lda: i32,
+        b: &[Self],
+        ldb: i32,
+        beta: Self,
+        c: &mut [Self],
+        ldc: i32,
+    );
+
+    /// Solution of the linear system $AX = B$, overwriting `b` with $X$, see `lapacke::dgesv`.
+    ///
+    /// # Safety
+    ///
+    /// The slices have to be large enough for the given dimensions and leading dimensions.
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn gesv(
+        layout: lapacke::Layout,
+        n: i32,
+        nrhs: i32,
+        a: &mut [Self],
+        lda: i32,
+        ipiv: &mut [i32],
+        b: &mut [Self],
+        ldb: i32,
+    ) -> i32;
+
+    /// LU factorization of the matrix $A$ with partial pivoting, see `lapacke::dgetrf`.
+    ///
+    /// # Safety
+    ///
+    /// The slices have to be large enough for the given dimensions and leading dimensions.
+    unsafe fn getrf(
+        layout: lapacke::Layout,
+        m: i32,
+        n: i32,
+        a: &mut [Self],
+        lda: i32,
+        ipiv: &mut [i32],
+    ) -> i32;
+
+    /// Solution of the linear system $AX = B$ using the LU factorization computed by `getrf`,
+    /// overwriting `b` with $X$, see `lapacke::dgetrs`.
+    ///
+    /// # Safety
+    ///
+    /// The slices have to be large enough for the given dimensions and leading dimensions.
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn getrs(
+        layout: lapacke::Layout,
+        trans: u8,
+        n: i32,
+        nrhs: i32,
+        a: &[Self],
+        lda: i32,
+        ipiv: &[i32],
+        b: &mut [Self],
+        ldb: i32,
+    ) -> i32;
+
+    /// Eigenvalues and, if `jobz` is `b'V'`, eigenvectors of the symmetric matrix $A$, see
+    /// `lapacke::dsyev`.
+    ///
+    /// # Safety
+    ///
+    /// The slices have to be large enough for the given dimensions and leading dimensions.
+    unsafe fn syev(
+        layout: lapacke::Layout,
+        jobz: u8,
+        uplo: u8,
+        n: i32,
+        a: &mut [Self],
+        lda: i32,
+        w: &mut [Self],
+    ) -> i32;
+
+    /// Real Schur decomposition $A = Q T Q^\top$ with $T$ quasi upper triangular, see
+    /// `lapacke::dgees`. Eigenvalues are not reordered.
+    ///
+    /// # Safety
+    ///
+    /// The slices have to be large enough for the given dimensions and leading dimensions.
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn gees(
+        layout: lapacke::Layout,
+        jobvs: u8,
+        n: i32,
+        a: &mut [Self],
+        lda: i32,
+        wr: &mut [Self],
+        wi: &mut [Self],
+        vs: &mut [Self],
+        ldvs: i32,
+    ) -> i32;
+    /// Reduction $A = Q H Q^\top$ to upper Hessenberg form, see `lapacke::dgehrd`. The
+    /// orthogonal matrix $Q$ is stored as a product of elementary reflectors below the
+    /// subdiagonal of `a` and in `tau`.
+    ///
+    /// # Safety
+    ///
+    /// The slices have to be large enough for the given dimensions and leading dimensions.
+    unsafe fn gehrd(
+        layout: lapacke::Layout,
+        n: i32,
+        a: &mut [Self],
+        lda: i32,
+        tau: &mut [Self],
+    ) -> i32;
+
+    /// Forms the orthogonal matrix $Q$ from the elementary reflectors returned by `gehrd`, see
+    /// `lapacke::dorghr`.
+    ///
+    /// # Safety
+    ///
+    /// The slices have to be large enough for the given dimensions and leading dimensions.
+    unsafe fn orghr(
+        layout: lapacke::Layout,
+        n: i32,
+        a: &mut [Self],
+        lda: i32,
+        tau: &[Self],
+    ) -> i32;
+
+    /// Reorders the real Schur form $T$ such that the diagonal block starting at row `ifst` is
+    /// moved to row `ilst`, accumulating the transformation in `q`, see `lapacke::dtrexc`. The
+    /// row indices are 1-based.
+    ///
+    /// # Safety
+    ///
+    /// The slices have to be large enough for the given dimensions and leading dimensions.
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn trexc(
+        layout: lapacke::Layout,
+        n: i32,
+        t: &mut [Self],
+        ldt: i32,
+        q: &mut [Self],
+        ldq: i32,
+        ifst: &mut i32,
+        ilst: &mut i32,
+    ) -> i32;
+}
+
+impl Float for f64 {
+    const THETAS: &'static [(u64, f64)] = &[
+        (3, 1.495585217958292e-2),
+        (5, 2.53939833006323e-1),
+        (7, 9.504178996162932e-1),
+        (9, 2.097847961257068e0),
+        // (13, 5.371920351148152e0), // Alg 3.1
+        (13, 4.25), // Alg 5.1
+    ];
+    const MAX_PADE_ORDER: u64 = 13;
+
+    const FRECHET_THETAS: &'static [(u64, f64)] = &[
+        (3, 1.081338577784837e-2),
+        (5, 1.998063206978949e-1),
+        (7, 7.834608472962044e-1),
+        (9, 1.782448623969279e0),
+        (13, 4.740307543766806e0),
+    ];
+
+    unsafe fn gemm(
+        layout: cblas::Layout,
+        transa: cblas::Transpose,
+        transb: cblas::Transpose,
+        m: i32,
+        n: i32,
+        k: i32,
+        alpha: Self,
+        a: &[Self],
+        lda: i32,
+        b: &[Self],
+        ldb: i32,
+        beta: Self,
+        c: &mut [Self],
+        ldc: i32,
+    ) {
+        cblas::dgemm(layout, transa, transb, m, n, k, alpha, a, lda, b, ldb, beta, c, ldc)
+    }
+
+    unsafe fn gesv(
+        layout: lapacke::Layout,
+        n: i32,
+        nrhs: i32,
+        a: &mut [Self],
+        lda: i32,
+        ipiv: &mut [i32],
+        b: &mut [Self],
+        ldb: i32,
+    ) -> i32 {
+        lapacke::dgesv(layout, n, nrhs, a, lda, ipiv, b, ldb)
+    }
+
+    unsafe fn getrf(
+        layout: lapacke::Layout,
+        m: i32,
+        n: i32,
+        a: &mut [Self],
+        lda: i32,
+        ipiv: &mut [i32],
+    ) -> i32 {
+        lapacke::dgetrf(layout, m, n, a, lda, ipiv)
+    }
+
+    unsafe fn getrs(
+        layout: lapacke::Layout,
+        trans: u8,
+        n: i32,
+        nrhs: i32,
+        a: &[Self],
+        lda: i32,
+        ipiv: &[i32],
+        b: &mut [Self],
+        ldb: i32,
+    ) -> i32 {
+        lapacke::dgetrs(layout, trans, n, nrhs, a, lda, ipiv, b, ldb)
+    }
+
+    unsafe fn syev(
+        layout: lapacke::Layout,
+        jobz: u8,
+        uplo: u8,
+        n: i32,
+        a: &mut [Self],
+        lda: i32,
+        w: &mut [Self],
+    ) -> i32 {
+        lapacke::dsyev(layout, jobz, uplo, n, a, lda, w)
+    }
+
+    unsafe fn gees(
+        layout: lapacke::Layout,
+        jobvs: u8,
+        n: i32,
+        a: &mut [Self],
+        lda: i32,
+        wr: &mut [Self],
+        wi: &mut [Self],
+        vs: &mut [Self],
+        ldvs: i32,
+    ) -> i32 {
+        let mut sdim = 0;
+        lapacke::dgees(layout, jobvs, b'N', None, n, a, lda, &mut sdim, wr, wi, vs, ldvs)
+    }
+
+    unsafe fn gehrd(
+        layout: lapacke::Layout,
+        n: i32,
+        a: &mut [Self],
+        lda: i32,
+        tau: &mut [Self],
+    ) -> i32 {
+        lapacke::dgehrd(layout, n, 1, n, a, lda, tau)
+    }
+
+    unsafe fn orghr(
+        layout: lapacke::Layout,
+        n: i32,
+        a: &mut [Self],
+        lda: i32,
+        tau: &[Self],
+    ) -> i32 {
+        lapacke::dorghr(layout, n, 1, n, a, lda, tau)
+    }
+
+    unsafe fn trexc(
+        layout: lapacke::Layout,
+        n: i32,
+        t: &mut [Self],
+        ldt: i32,
+        q: &mut [Self],
+        ldq: i32,
+        ifst: &mut i32,
+        ilst: &mut i32,
+    ) -> i32 {
+        lapacke::dtrexc(layout, b'V', n, t, ldt, q, ldq, ifst, ilst)
+    }
+}
+
+impl Float for f32 {
+    const THETAS: &'static [(u64, f64)] = &[
+        (3, 4.258730016922831e-1),
+        (5, 1.880152677804762e0),
+        (7, 3.92572478313866e0),
+    ];
+    const MAX_PADE_ORDER: u64 = 7;
+
+    const FRECHET_THETAS: &'static [(u64, f64)] = &[
+        (3, 3.080330418453301e-1),
+        (5, 1.482532614793145e0),
+        (7, 3.248671755200478e0),
+    ];
+
+    unsafe fn gemm(
+        layout: cblas::Layout,
+        transa: cblas::Transpose,
+        transb: cblas::Transpose,
+        m: i32,
+        n: i32,
+        k: i32,
+        alpha: Self,
+        a: &[Self],
+        lda: i32,
+        b: &[Self],
+        ldb: i32,
+        beta: Self,
+        c: &mut [Self],
+        ldc: i32,
+    ) {
+        cblas::sgemm(layout, transa, transb, m, n, k, alpha, a, lda, b, ldb, beta, c, ldc)
+    }
+
+    unsafe fn gesv(
+        layout: lapacke::Layout,
+        n: i32,
+        nrhs: i32,
+        a: &mut [Self],
+        lda: i32,
+        ipiv: &mut [i32],
+        b: &mut [Self],
+        ldb: i32,
+    ) -> i32 {
+        lapacke::sgesv(layout, n, nrhs, a, lda, ipiv, b, ldb)
+    }
+
+    unsafe fn getrf(
+        layout: lapacke::Layout,
+        m: i32,
+        n: i32,
+        a: &mut [Self],
+        lda: i32,
+        ipiv: &mut [i32],
+    ) -> i32 {
+        lapacke::sgetrf(layout, m, n, a, lda, ipiv)
+    }
+
+    unsafe fn getrs(
+        layout: lapacke::Layout,
+        trans: u8,
+        n: i32,
+        nrhs: i32,
+        a: &[Self],
+        lda: i32,
+        ipiv: &[i32],
+        b: &mut [Self],
+        ldb: i32,
+    ) -> i32 {
+        lapacke::sgetrs(layout, trans, n, nrhs, a, lda, ipiv, b, ldb)
+    }
+
+    unsafe fn syev(
+        layout: lapacke::Layout,
+        jobz: u8,
+        uplo: u8,
+        n: i32,
+        a: &mut [Self],
+        lda: i32,
+        w: &mut [Self],
+    ) -> i32 {
+        lapacke::ssyev(layout, jobz, uplo, n, a, lda, w)
+    }
+
+    unsafe fn gees(
+        layout: lapacke::Layout,
+        jobvs: u8,
+        n: i32,
+        a: &mut [Self],
+        lda: i32,
+        wr: &mut [Self],
+        wi: &mut [Self],
+        vs: &mut [Self],
+        ldvs: i32,
+    ) -> i32 {
+        let mut sdim = 0;
+        lapacke::sgees(layout, jobvs, b'N', None, n, a, lda, &mut sdim, wr, wi, vs, ldvs)
+    }
+
+    unsafe fn gehrd(
+        layout: lapacke::Layout,
+        n: i32,
+        a: &mut [Self],
+        lda: i32,
+        tau: &mut [Self],
+    ) -> i32 {
+        lapacke::sgehrd(layout, n, 1, n, a, lda, tau)
+    }
+
+    unsafe fn orghr(
+        layout: lapacke::Layout,
+        n: i32,
+        a: &mut [Self],
+        lda: i32,
+        tau: &[Self],
+    ) -> i32 {
+        lapacke::sorghr(layout, n, 1, n, a, lda, tau)
+    }
+
+    unsafe fn trexc(
+        layout: lapacke::Layout,
+        n: i32,
+        t: &mut [Self],
+        ldt: i32,
+        q: &mut [Self],
+        ldq: i32,
+        ifst: &mut i32,
+        ilst: &mut i32,
+    ) -> i32 {
+        lapacke::strexc(layout, b'V', n, t, ldt, q, ldq, ifst, ilst)
+    }
+}
+
+/// Returns the memory layout and leading dimension with which a matrix of dimension `dim` and
+/// strides `strides` can be passed to BLAS, if any.
+fn blas_layout(dim: (usize, usize), strides: &[isize]) -> Option<(cblas::Layout, usize)> {
+    let (rows, cols) = dim;
+    let (row_stride, col_stride) = (strides[0], strides[1]);
+
+    if rows == 0 || cols == 0 {
+        Some((cblas::Layout::RowMajor, cols.max(1)))
+    } else if (cols == 1 || col_stride == 1) && (rows == 1 || row_stride >= cols as isize) {
+        let ld = if rows == 1 { cols } else { row_stride as usize };
+        Some((cblas::Layout::RowMajor, ld))
+    } else if (rows == 1 || row_stride == 1) && (cols == 1 || col_stride >= rows as isize) {
+        let ld = if cols == 1 { rows } else { col_stride as usize };
+        Some((cblas::Layout::ColumnMajor, ld))
+    } else {
+        None
+    }
+}
+
+/// The number of elements spanned by a matrix of dimension `dim` stored in `layout` with leading
+/// dimension `ld`.
+fn blas_len(dim: (usize, usize), layout: cblas::Layout, ld: usize) -> usize {
+    let (rows, cols) = dim;
+    if rows == 0 || cols == 0 {
+        return 0;
+    }
+    match layout {
+        cblas::Layout::RowMajor => (rows - 1) * ld + cols,
+        cblas::Layout::ColumnMajor => (cols - 1) * ld + rows,
+    }
+}
+
+/// Returns slice, layout, and leading dimension underlying a matrix `a`, which may be a view into
+/// a larger matrix.
+fn as_blas_matrix<S, T>(a: &ArrayBase<S, Ix2>) -> Option<(&[T], cblas::Layout, i32)>
+    where S: Data<Elem=T>,
+{
+    let (layout, ld) = blas_layout(a.dim(), a.strides())?;
+    let len = blas_len(a.dim(), layout, ld);
+    let a_slice = unsafe { std::slice::from_raw_parts(a.as_ptr(), len) };
+    Some((a_slice, layout, ld as i32))
+}
+
+/// Returns mutable slice, layout, and leading dimension underlying a matrix `a`, which may be a
+/// view into a larger matrix.
+fn as_blas_matrix_mut<S, T>(a: &mut ArrayBase<S, Ix2>) -> Option<(&mut [T], cblas::Layout, i32)>
+    where S: DataMut<Elem=T>,
+{
+    let (layout, ld) = blas_layout(a.dim(), a.strides())?;
+    let len = blas_len(a.dim(), layout, ld);
+    let a_slice = unsafe { std::slice::from_raw_parts_mut(a.as_mut_ptr(), len) };
+    Some((a_slice, layout, ld as i32))
+}
+
+/// Calculates $C \leftarrow \alpha A B + \beta C$.
+///
+/// The memory layout of `a` and `b` may differ from that of `c`, in which case they are passed to
+/// BLAS as transposed. This allows passing transposed views such as `a.t()`. All matrices may be
+/// views into larger matrices, as long as one of their axes is contiguous.
+///
+/// NOTE: Panics if any of the matrices has no contiguous axis or if the dimensions don't match.
+pub(crate) fn gemm<T, S1, S2, S3>(alpha: T, a: &ArrayBase<S1, Ix2>, b: &ArrayBase<S2, Ix2>, beta: T, c: &mut ArrayBase<S3, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=T>,
+          S3: DataMut<Elem=T>,
+{
+    let (m, k) = a.dim();
+    let (k_b, n) = b.dim();
+    assert_eq!(k, k_b, "Inner dimensions of matrices `a` and `b` don't match.");
+    assert_eq!((m, n), c.dim(), "Dimensions of matrix `c` don't match the product of `a` and `b`.");
+
+    let (a_slice, a_layout, lda) = as_blas_matrix(a).expect("Matrix `a` has no contiguous axis.");
+    let (b_slice, b_layout, ldb) = as_blas_matrix(b).expect("Matrix `b` has no contiguous axis.");
+    let (c_slice, layout, ldc) = as_blas_matrix_mut(c).expect("Matrix `c` has no contiguous axis.");
+
+    // A matrix stored in the other layout than `c` is the transpose of a matrix stored in the
+    // layout of `c`.
+    let transpose = |other: cblas::Layout| {
+        if other == layout {
+            cblas::Transpose::None
+        } else {
+            cblas::Transpose::Ordinary
+        }
+    };
+
+    unsafe {
+        T::gemm(
+            layout,
+            transpose(a_layout),
+            transpose(b_layout),
+            m as i32,
+            n as i32,
+            k as i32,
+            alpha,
+            a_slice,
+            lda,
+            b_slice,
+            ldb,
+            beta,
+            c_slice,
+            ldc,
+        )
+    }
+}
+
+/// Solves the linear system $AX = B$, overwriting `a` with its LU factorization and `b` with the
+/// solution $X$. Returns the `info` value of LAPACK's `gesv`.
+///
+/// NOTE: Panics if any of the matrices is not contiguous, if they are not stored in the same
+/// memory layout, or if the dimensions don't match.
+pub(crate) fn gesv<T, S1, S2>(a: &mut ArrayBase<S1, Ix2>, pivot: &mut Array1<i32>, b: &mut ArrayBase<S2, Ix2>) -> i32
+    where T: Float,
+          S1: DataMut<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n_rows, n_cols) = a.dim();
+    assert_eq!(n_rows, n_cols, "Linear system only defined for square matrices.");
+    let (b_rows, nrhs) = b.dim();
+    assert_eq!(n_rows, b_rows, "Dimension mismatch between matrices `a` and `b`.");
+    assert_eq!(n_rows, pivot.len(), "Dimension mismatch between matrix `a` and vector `pivot`.");
+    let n = n_rows as i32;
+
+    let (a_slice, a_layout) = as_slice_with_layout_mut(a).expect("Matrix `a` not contiguous.");
+    let (b_slice, b_layout) = as_slice_with_layout_mut(b).expect("Matrix `b` not contiguous.");
+    let pivot_slice = pivot.as_slice_mut().expect("Vector `pivot` not contiguous.");
+    assert_eq!(a_layout, b_layout, "Memory layout mismatch between matrices `a` and `b`.");
+
+    let ldb = match b_layout {
+        cblas::Layout::ColumnMajor => n.max(1),
+        cblas::Layout::RowMajor => (nrhs as i32).max(1),
+    };
+
+    unsafe {
+        T::gesv(
+            lapacke_layout(a_layout),
+            n,
+            nrhs as i32,
+            a_slice,
+            n.max(1),
+            pivot_slice,
+            b_slice,
+            ldb,
+        )
+    }
+}
+
+/// Calculates the LU factorization of the square matrix `a` with partial pivoting, overwriting `a`
+/// with the factors and storing the pivot indices in `pivot`. Returns the `info` value of
+/// LAPACK's `getrf`.
+///
+/// NOTE: Panics if `a` is not square and contiguous, or if the dimensions don't match.
+pub(crate) fn getrf<T, S>(a: &mut ArrayBase<S, Ix2>, pivot: &mut Array1<i32>) -> i32
+    where T: Float,
+          S: DataMut<Elem=T>,
+{
+    let (n_rows, n_cols) = a.dim();
+    assert_eq!(n_rows, n_cols, "LU factorization only implemented for square matrices.");
+    assert_eq!(n_rows, pivot.len(), "Dimension mismatch between matrix `a` and vector `pivot`.");
+    let n = n_rows as i32;
+
+    let (a_slice, a_layout) = as_slice_with_layout_mut(a).expect("Matrix `a` not contiguous.");
+    let pivot_slice = pivot.as_slice_mut().expect("Vector `pivot` not contiguous.");
+
+    unsafe {
+        T::getrf(lapacke_layout(a_layout), n, n, a_slice, n.max(1), pivot_slice)
+    }
+}
+
+/// Solves the linear system $AX = B$ given the LU factorization of `a` computed by `getrf`,
+/// overwriting `b` with the solution $X$. Returns the `info` value of LAPACK's `getrs`.
+///
+/// NOTE: Panics if any of the matrices is not contiguous, if they are not stored in the same
+/// memory layout, or if the dimensions don't match.
+pub(crate) fn getrs<T, S1, S2>(a: &ArrayBase<S1, Ix2>, pivot: &Array1<i32>, b: &mut ArrayBase<S2, Ix2>) -> i32
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n_rows, n_cols) = a.dim();
+    assert_eq!(n_rows, n_cols, "Linear system only defined for square matrices.");
+    let (b_rows, nrhs) = b.dim();
+    assert_eq!(n_rows, b_rows, "Dimension mismatch between matrices `a` and `b`.");
+    assert_eq!(n_rows, pivot.len(), "Dimension mismatch between matrix `a` and vector `pivot`.");
+    let n = n_rows as i32;
+
+    let (a_slice, a_layout, lda) = as_blas_matrix(a).expect("Matrix `a` has no contiguous axis.");
+    let (b_slice, b_layout) = as_slice_with_layout_mut(b).expect("Matrix `b` not contiguous.");
+    let pivot_slice = pivot.as_slice().expect("Vector `pivot` not contiguous.");
+    assert_eq!(a_layout, b_layout, "Memory layout mismatch between matrices `a` and `b`.");
+
+    let ldb = match b_layout {
+        cblas::Layout::ColumnMajor => n.max(1),
+        cblas::Layout::RowMajor => (nrhs as i32).max(1),
+    };
+
+    unsafe {
+        T::getrs(lapacke_layout(a_layout), b'N', n, nrhs as i32, a_slice, lda, pivot_slice, b_slice, ldb)
+    }
+}
+
+fn lapacke_layout(layout: cblas::Layout) -> lapacke::Layout {
+    match layout {
+        cblas::Layout::ColumnMajor => lapacke::Layout::ColumnMajor,
+        cblas::Layout::RowMajor => lapacke::Layout::RowMajor,
+    }
+}
+
+/// Calculates the eigendecomposition $A = V \Lambda V^\top$ of the symmetric matrix `a`,
+/// overwriting `a` with the orthogonal matrix $V$ and storing the eigenvalues in ascending order in
+/// `w`. Only the upper triangle of `a` is referenced. Returns the `info` value of LAPACK's `syev`.
+///
+/// NOTE: Panics if `a` is not square and contiguous, or if the dimensions don't match.
+pub(crate) fn syev<T, S1, S2>(a: &mut ArrayBase<S1, Ix2>, w: &mut ArrayBase<S2, Ix1>) -> i32
+    where T: Float,
+          S1: DataMut<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n_rows, n_cols) = a.dim();
+    assert_eq!(n_rows, n_cols, "Eigendecomposition only defined for square matrices.");
+    assert_eq!(n_rows, w.len(), "Dimension mismatch between matrix `a` and vector `w`.");
+    let n = n_rows as i32;
+
+    let (a_slice, a_layout) = as_slice_with_layout_mut(a).expect("Matrix `a` not contiguous.");
+    let w_slice = w.as_slice_mut().expect("Vector `w` not contiguous.");
+
+    unsafe {
+        T::syev(lapacke_layout(a_layout), b'V', b'U', n, a_slice, n.max(1), w_slice)
+    }
+}
+
+/// Calculates the real Schur decomposition $A = Q T Q^\top$ of the square matrix `a`,
+/// overwriting `a` with the quasi upper triangular matrix $T$ and storing the orthogonal matrix
+/// $Q$ in `q`. The real and imaginary parts of the eigenvalues are stored in `wr` and `wi`.
+/// Returns the `info` value of LAPACK's `gees`.
+///
+/// NOTE: Panics if `a` and `q` are not square and contiguous with the same layout, or if the
+/// dimensions don't match.
+pub(crate) fn gees<T, S1, S2, S3, S4>(a: &mut ArrayBase<S1, Ix2>, q: &mut ArrayBase<S2, Ix2>, wr: &mut ArrayBase<S3, Ix1>, wi: &mut ArrayBase<S4, Ix1>) -> i32
+    where T: Float,
+          S1: DataMut<Elem=T>,
+          S2: DataMut<Elem=T>,
+          S3: DataMut<Elem=T>,
+          S4: DataMut<Elem=T>,
+{
+    let (n_rows, n_cols) = a.dim();
+    assert_eq!(n_rows, n_cols, "Schur decomposition only defined for square matrices.");
+    assert_eq!(a.dim(), q.dim(), "Dimension mismatch between matrices `a` and `q`.");
+    assert_eq!(n_rows, wr.len(), "Dimension mismatch between matrix `a` and vector `wr`.");
+    assert_eq!(n_rows, wi.len(), "Dimension mismatch between matrix `a` and vector `wi`.");
+    let n = n_rows as i32;
+
+    let (a_slice, a_layout) = as_slice_with_layout_mut(a).expect("Matrix `a` not contiguous.");
+    let (q_slice, q_layout) = as_slice_with_layout_mut(q).expect("Matrix `q` not contiguous.");
+    assert_eq!(a_layout, q_layout, "Matrices `a` and `q` need to have the same layout.");
+    let wr_slice = wr.as_slice_mut().expect("Vector `wr` not contiguous.");
+    let wi_slice = wi.as_slice_mut().expect("Vector `wi` not contiguous.");
+
+    unsafe {
+        T::gees(lapacke_layout(a_layout), b'V', n, a_slice, n.max(1), wr_slice, wi_slice, q_slice, n.max(1))
+    }
+}
+
+/// Calculates the reduction $A = Q H Q^\top$ of the square matrix `a` to upper Hessenberg form,
+/// overwriting `a` with $H$ and storing the orthogonal matrix $Q$ in `q`. Returns the `info` value
+/// of LAPACK's `gehrd`, or of `orghr` if the former succeeded.
+///
+/// NOTE: Panics if `a` and `q` are not square and contiguous with the same layout, or if the
+/// dimensions don't match.
+pub(crate) fn gehrd<T, S1, S2>(a: &mut ArrayBase<S1, Ix2>, q: &mut ArrayBase<S2, Ix2>) -> i32
+    where T: Float,
+          S1: DataMut<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n_rows, n_cols) = a.dim();
+    assert_eq!(n_rows, n_cols, "Hessenberg reduction only defined for square matrices.");
+    assert_eq!(a.dim(), q.dim(), "Dimension mismatch between matrices `a` and `q`.");
+    let n = n_rows as i32;
+    let mut tau = vec![T::zero(); n_rows.saturating_sub(1).max(1)];
+
+    let info = {
+        let (a_slice, a_layout) = as_slice_with_layout_mut(a).expect("Matrix `a` not contiguous.");
+        let info = unsafe { T::gehrd(lapacke_layout(a_layout), n, a_slice, n.max(1), &mut tau) };
+        if info != 0 {
+            return info;
+        }
+        let (q_slice, q_layout) = as_slice_with_layout_mut(q).expect("Matrix `q` not contiguous.");
+        assert_eq!(a_layout, q_layout, "Matrices `a` and `q` need to have the same layout.");
+        q_slice.copy_from_slice(a_slice);
+        unsafe { T::orghr(lapacke_layout(q_layout), n, q_slice, n.max(1), &tau) }
+    };
+
+    // Discard the reflectors below the subdiagonal.
+    for ((i, j), x) in a.indexed_iter_mut() {
+        if i > j + 1 {
+            *x = T::zero();
+        }
+    }
+
+    info
+}
+
+/// Moves the diagonal block of the real Schur form `t` starting at row `from` to row `to`,
+/// updating the orthogonal matrix `q` of the Schur decomposition. The rows are 0-based. Returns the
+/// `info` value of LAPACK's `trexc`.
+///
+/// NOTE: Panics if `t` and `q` are not square and contiguous with the same layout, or if the
+/// dimensions don't match.
+pub(crate) fn trexc<T, S1, S2>(t: &mut ArrayBase<S1, Ix2>, q: &mut ArrayBase<S2, Ix2>, from: usize, to: usize) -> i32
+    where T: Float,
+          S1: DataMut<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n_rows, n_cols) = t.dim();
+    assert_eq!(n_rows, n_cols, "Schur form only defined for square matrices.");
+    assert_eq!(t.dim(), q.dim(), "Dimension mismatch between matrices `t` and `q`.");
+    let n = n_rows as i32;
+
+    let (t_slice, t_layout) = as_slice_with_layout_mut(t).expect("Matrix `t` not contiguous.");
+    let (q_slice, q_layout) = as_slice_with_layout_mut(q).expect("Matrix `q` not contiguous.");
+    assert_eq!(t_layout, q_layout, "Matrices `t` and `q` need to have the same layout.");
+
+    let mut ifst = from as i32 + 1;
+    let mut ilst = to as i32 + 1;
+    unsafe {
+        T::trexc(lapacke_layout(t_layout), n, t_slice, n.max(1), q_slice, n.max(1), &mut ifst, &mut ilst)
+    }
+}
+
+/// The exact 1-norm, i.e. the maximum absolute column sum of `a`.
+pub(crate) fn onenorm<T, S>(a: &ArrayBase<S, Ix2>) -> T
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    a.gencolumns()
+        .into_iter()
+        .map(|column| column.fold(T::zero(), |acc, &x| acc + x.abs()))
+        .fold(T::zero(), T::max)
+}
+
+/// The exact 1-norm of $A - \mu I$ for the n×n matrix `a` and the `shift` $\mu$.
+pub(crate) fn shifted_onenorm<T, S>(a: &ArrayBase<S, Ix2>, shift: T) -> T
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    a.gencolumns()
+        .into_iter()
+        .enumerate()
+        .map(|(j, column)| column.indexed_iter().fold(T::zero(), |acc, (i, &x)| {
+            let x = if i == j { x - shift } else { x };
+            acc + x.abs()
+        }))
+        .fold(T::zero(), T::max)
+}
@@ -0,0 +1,368 @@
+//! The Fréchet derivative of the matrix exponential,
+//!
+//! \begin{equation}
+//!     L(A, E) = \lim_{\epsilon \to 0} \frac{e^{A + \epsilon E} - e^A}{\epsilon},
+//! \end{equation}
+//!
+//! following Algorithm 6.4 in [Al-Mohy, Higham 2009b]. The derivative is obtained by
+//! differentiating the Padé approximant $r_m(A) = q_m(A)^{-1} p_m(A)$ and the squaring phase, so
+//! that $e^A$ is calculated alongside $L(A, E)$ at little extra cost.
+//!
+//! The order $m$ and the scaling parameter $s$ are chosen based on the 1-norm of $A$ and the
+//! thresholds $\ell_m$, which are given by `FRECHET_THETAS` of the `Float` trait.
+//!
+//! [Al-Mohy, Higham 2009b]: https://doi.org/10.1137/080716426
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    float::{
+        gemm,
+        getrf,
+        getrs,
+        onenorm,
+    },
+    Float,
+    PadeOrder,
+    PadeOrder_13,
+    PadeOrder_3,
+    PadeOrder_5,
+    PadeOrder_7,
+    PadeOrder_9,
+};
+
+/// Storage for calculating the Fréchet derivative of the matrix exponential.
+pub struct ExpmFrechet<T = f64> {
+    n: usize,
+    a: Array2<T>,
+    e: Array2<T>,
+    a_powers: Vec<Array2<T>>,
+    m_powers: Vec<Array2<T>>,
+    w: Array2<T>,
+    w1: Array2<T>,
+    z1: Array2<T>,
+    lw: Array2<T>,
+    lw1: Array2<T>,
+    lz1: Array2<T>,
+    u: Array2<T>,
+    v: Array2<T>,
+    lu: Array2<T>,
+    lv: Array2<T>,
+    work: Array2<T>,
+    pivot: Array1<i32>,
+}
+
+impl<T: Float> ExpmFrechet<T> {
+    /// Allocates all space to calculate the Fréchet derivative for square matrices of dimension
+    /// n×n.
+    pub fn new(n: usize) -> Self {
+        let zeros = || Array2::<T>::zeros((n, n));
+
+        // Even powers I, A², A⁴, A⁶, A⁸ and their derivatives 0, M₂, M₄, M₆, M₈.
+        let mut a_powers: Vec<_> = (0..5).map(|_| zeros()).collect();
+        a_powers[0] = Array2::eye(n);
+        let m_powers = (0..5).map(|_| zeros()).collect();
+
+        ExpmFrechet {
+            n,
+            a: zeros(),
+            e: zeros(),
+            a_powers,
+            m_powers,
+            w: zeros(),
+            w1: zeros(),
+            z1: zeros(),
+            lw: zeros(),
+            lw1: zeros(),
+            lz1: zeros(),
+            u: zeros(),
+            v: zeros(),
+            lu: zeros(),
+            lv: zeros(),
+            work: zeros(),
+            pivot: Array1::zeros(n),
+        }
+    }
+
+    /// Calculate the matrix exponential of the n×n matrix `a` and its Fréchet derivative in the
+    /// direction of the n×n matrix `e`, storing $e^A$ in `b` and $L(A, E)$ in `l`.
+    ///
+    /// NOTE: Panics if the matrices don't have matching dimensions, are not square, not in
+    /// row-major order, don't have the same dimension as the `ExpmFrechet` object `frechet` is
+    /// called on, or if the denominator of the Padé approximant is singular, which the choice of
+    /// the scaling excludes for finite `a`.
+    pub fn frechet<S1, S2, S3, S4>(&mut self, a: &ArrayBase<S1, Ix2>, e: &ArrayBase<S2, Ix2>, b: &mut ArrayBase<S3, Ix2>, l: &mut ArrayBase<S4, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: Data<Elem=T>,
+              S3: DataMut<Elem=T>,
+              S4: DataMut<Elem=T>,
+    {
+        assert_eq!(a.dim(), e.dim(), "Input matrices `a` and `e` have to have matching dimensions.");
+        assert_eq!(a.dim(), b.dim(), "Input matrices `a` and `b` have to have matching dimensions.");
+        assert_eq!(a.dim(), l.dim(), "Input matrices `a` and `l` have to have matching dimensions.");
+        let (n_rows, n_cols) = a.dim();
+        assert_eq!(n_rows, n_cols, "The Fréchet derivative is only implemented for square matrices.");
+        assert_eq!(n_rows, self.n, "Dimension mismatch between matrix `a` and preconfigured `ExpmFrechet` struct.");
+
+        let norm_a = onenorm(a);
+
+        let order = T::FRECHET_THETAS.iter()
+            .filter(|&&(m, _)| m < T::MAX_PADE_ORDER)
+            .find(|&&(_, theta)| norm_a <= T::from_f64(theta).unwrap())
+            .map(|&(m, _)| m);
+
+        let (m, s) = match order {
+            Some(m) => (m, 0),
+            None => {
+                let theta = T::from_f64(T::frechet_threshold(T::MAX_PADE_ORDER).unwrap()).unwrap();
+                let s = (norm_a / theta).log2().ceil().to_i32().unwrap_or(0).max(0);
+                (T::MAX_PADE_ORDER, s)
+            }
+        };
+
+        let scale = T::from_f64(2.0).unwrap().powi(-s);
+        self.a.zip_mut_with(a, |x, &y| *x = scale * y);
+        self.e.zip_mut_with(e, |x, &y| *x = scale * y);
+
+        if m == 13 {
+            self.pade_13();
+        } else {
+            self.pade(m);
+        }
+
+        // Solve (V - U) R = (U + V) and (V - U) L = Lu + Lv + (Lu - Lv) R, reusing the LU
+        // factorization of V - U. The solutions are written to `b` and `l`, respectively.
+        self.work.assign(&self.v);
+        self.work.zip_mut_with(&self.u, |x, &y| *x = *x - y);
+        let info = getrf(&mut self.work, &mut self.pivot);
+        assert_eq!(info, 0, "The denominator of the Padé approximant is singular.");
+
+        b.assign(&self.u);
+        b.zip_mut_with(&self.v, |x, &y| *x = *x + y);
+        assert_eq!(getrs(&self.work, &self.pivot, b), 0);
+
+        self.u.assign(&self.lu);
+        self.u.zip_mut_with(&self.lv, |x, &y| *x = *x - y);
+        l.assign(&self.lu);
+        l.zip_mut_with(&self.lv, |x, &y| *x = *x + y);
+        gemm(T::one(), &self.u, b, T::one(), l);
+        assert_eq!(getrs(&self.work, &self.pivot, l), 0);
+
+        // Squaring phase: L ← R L + L R and R ← R².
+        for _ in 0..s {
+            gemm(T::one(), b, l, T::zero(), &mut self.work);
+            gemm(T::one(), l, b, T::one(), &mut self.work);
+            l.assign(&self.work);
+            gemm(T::one(), b, b, T::zero(), &mut self.work);
+            b.assign(&self.work);
+        }
+    }
+
+    /// Calculates the even powers A², A⁴, ..., A^{2k} and their directional derivatives
+    /// M₂, M₄, ..., M_{2k}, using M_{2j} = A^{2j-2} M₂ + M_{2j-2} A².
+    fn powers(&mut self, k: usize) {
+        gemm(T::one(), &self.a, &self.a, T::zero(), &mut self.a_powers[1]);
+        gemm(T::one(), &self.a, &self.e, T::zero(), &mut self.m_powers[1]);
+        gemm(T::one(), &self.e, &self.a, T::one(), &mut self.m_powers[1]);
+
+        for j in 2..=k {
+            let (lower, upper) = self.a_powers.split_at_mut(j);
+            gemm(T::one(), &lower[j-1], &lower[1], T::zero(), &mut upper[0]);
+
+            let (lower_m, upper_m) = self.m_powers.split_at_mut(j);
+            gemm(T::one(), &lower[j-1], &lower_m[1], T::zero(), &mut upper_m[0]);
+            gemm(T::one(), &lower_m[j-1], &lower[1], T::one(), &mut upper_m[0]);
+        }
+    }
+
+    /// Evaluates U, V and their derivatives Lu, Lv for the Padé approximants of order 3 to 9.
+    fn pade(&mut self, m: u64) {
+        let coefficients = match m {
+            3 => PadeOrder_3::coefficients(),
+            5 => PadeOrder_5::coefficients(),
+            7 => PadeOrder_7::coefficients(),
+            9 => PadeOrder_9::coefficients(),
+            _ => unreachable!(),
+        };
+        let coefficient = |i: usize| T::from_f64(coefficients[i]).unwrap();
+        let k = (m as usize - 1) / 2;
+
+        self.powers(k);
+
+        // W = Σ b_{2j+1} A^{2j}, V = Σ b_{2j} A^{2j}, Lw = Σ b_{2j+1} M_{2j}, Lv = Σ b_{2j} M_{2j}
+        self.w.fill(T::zero());
+        self.v.fill(T::zero());
+        self.lw.fill(T::zero());
+        self.lv.fill(T::zero());
+        for j in 0..=k {
+            let (c_even, c_odd) = (coefficient(2*j), coefficient(2*j + 1));
+            self.w.zip_mut_with(&self.a_powers[j], |x, &y| *x = *x + c_odd * y);
+            self.v.zip_mut_with(&self.a_powers[j], |x, &y| *x = *x + c_even * y);
+            if j > 0 {
+                self.lw.zip_mut_with(&self.m_powers[j], |x, &y| *x = *x + c_odd * y);
+                self.lv.zip_mut_with(&self.m_powers[j], |x, &y| *x = *x + c_even * y);
+            }
+        }
+
+        // U = A W, Lu = A Lw + E W
+        gemm(T::one(), &self.a, &self.w, T::zero(), &mut self.u);
+        gemm(T::one(), &self.a, &self.lw, T::zero(), &mut self.lu);
+        gemm(T::one(), &self.e, &self.w, T::one(), &mut self.lu);
+    }
+
+    /// Evaluates U, V and their derivatives Lu, Lv for the Padé approximant of order 13, with
+    /// the higher powers of A factored out as in the calculation of the exponential.
+    fn pade_13(&mut self) {
+        let coefficients = PadeOrder_13::coefficients();
+        let b = |i: usize| T::from_f64(coefficients[i]).unwrap();
+
+        self.powers(3);
+
+        let (a2, a4, a6) = (&self.a_powers[1], &self.a_powers[2], &self.a_powers[3]);
+        let (m2, m4, m6) = (&self.m_powers[1], &self.m_powers[2], &self.m_powers[3]);
+        let eye = &self.a_powers[0];
+
+        // W1 = b13 A6 + b11 A4 + b9 A2, Z1 = b12 A6 + b10 A4 + b8 A2
+        combine(&mut self.w1, &[(b(13), a6), (b(11), a4), (b(9), a2)]);
+        combine(&mut self.z1, &[(b(12), a6), (b(10), a4), (b(8), a2)]);
+
+        // W = A6 W1 + W2, W2 = b7 A6 + b5 A4 + b3 A2 + b1 I
+        combine(&mut self.w, &[(b(7), a6), (b(5), a4), (b(3), a2), (b(1), eye)]);
+        gemm(T::one(), a6, &self.w1, T::one(), &mut self.w);
+
+        // V = A6 Z1 + Z2, Z2 = b6 A6 + b4 A4 + b2 A2 + b0 I
+        combine(&mut self.v, &[(b(6), a6), (b(4), a4), (b(2), a2), (b(0), eye)]);
+        gemm(T::one(), a6, &self.z1, T::one(), &mut self.v);
+
+        // Lw1 = b13 M6 + b11 M4 + b9 M2, Lz1 = b12 M6 + b10 M4 + b8 M2
+        combine(&mut self.lw1, &[(b(13), m6), (b(11), m4), (b(9), m2)]);
+        combine(&mut self.lz1, &[(b(12), m6), (b(10), m4), (b(8), m2)]);
+
+        // Lw = A6 Lw1 + M6 W1 + Lw2, Lw2 = b7 M6 + b5 M4 + b3 M2
+        combine(&mut self.lw, &[(b(7), m6), (b(5), m4), (b(3), m2)]);
+        gemm(T::one(), a6, &self.lw1, T::one(), &mut self.lw);
+        gemm(T::one(), m6, &self.w1, T::one(), &mut self.lw);
+
+        // Lv = A6 Lz1 + M6 Z1 + Lz2, Lz2 = b6 M6 + b4 M4 + b2 M2
+        combine(&mut self.lv, &[(b(6), m6), (b(4), m4), (b(2), m2)]);
+        gemm(T::one(), a6, &self.lz1, T::one(), &mut self.lv);
+        gemm(T::one(), m6, &self.z1, T::one(), &mut self.lv);
+
+        // U = A W, Lu = A Lw + E W
+        gemm(T::one(), &self.a, &self.w, T::zero(), &mut self.u);
+        gemm(T::one(), &self.a, &self.lw, T::zero(), &mut self.lu);
+        gemm(T::one(), &self.e, &self.w, T::one(), &mut self.lu);
+    }
+}
+
+/// Sets `out` to the linear combination $\sum_i c_i X_i$ of the `terms` $(c_i, X_i)$.
+fn combine<T: Float>(out: &mut Array2<T>, terms: &[(T, &Array2<T>)]) {
+    out.fill(T::zero());
+    for &(c, x) in terms {
+        out.zip_mut_with(x, |y, &x| *y = *y + c * x);
+    }
+}
+
+/// Calculate the Fréchet derivative $L(A, E)$ of the matrix exponential of the n×n matrix `a` in
+/// the direction of the n×n matrix `e`, storing the result in `l`.
+///
+/// NOTE: Panics if the matrices don't have matching dimensions, are not square, or not in
+/// row-major order.
+pub fn expm_frechet<T, S1, S2, S3>(a: &ArrayBase<S1, Ix2>, e: &ArrayBase<S2, Ix2>, l: &mut ArrayBase<S3, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=T>,
+          S3: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut b = Array2::<T>::zeros((n, n));
+    let mut frechet = ExpmFrechet::new(n);
+    frechet.frechet(a, e, &mut b, l);
+}
+
+/// Calculate the matrix exponential of the n×n matrix `a` and its Fréchet derivative in the
+/// direction of the n×n matrix `e`, storing $e^A$ in `b` and $L(A, E)$ in `l`.
+///
+/// NOTE: Panics if the matrices don't have matching dimensions, are not square, or not in
+/// row-major order.
+pub fn expm_frechet_full<T, S1, S2, S3, S4>(a: &ArrayBase<S1, Ix2>, e: &ArrayBase<S2, Ix2>, b: &mut ArrayBase<S3, Ix2>, l: &mut ArrayBase<S4, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=T>,
+          S3: DataMut<Elem=T>,
+          S4: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut frechet = ExpmFrechet::new(n);
+    frechet.frechet(a, e, b, l);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{
+        prelude::*,
+        s,
+    };
+    use approx::assert_relative_eq;
+
+    /// The Fréchet derivative is the upper right block of the exponential of the block matrix
+    /// [[A, E], [0, A]].
+    fn frechet_via_block_matrix(a: &Array2<f64>, e: &Array2<f64>) -> Array2<f64> {
+        let n = a.rows();
+        let mut block = Array2::<f64>::zeros((2*n, 2*n));
+        block.slice_mut(s![..n, ..n]).assign(a);
+        block.slice_mut(s![..n, n..]).assign(e);
+        block.slice_mut(s![n.., n..]).assign(a);
+        let mut exp_block = Array2::<f64>::zeros((2*n, 2*n));
+        crate::expm(&block, &mut exp_block);
+        exp_block.slice(s![..n, n..]).to_owned()
+    }
+
+    #[test]
+    fn frechet_agrees_with_block_matrix() {
+        let a0 = arr2(&[[-1.0, 0.5, 0.2], [0.3, -2.0, 0.7], [0.1, 0.4, -0.5]]);
+        let e = arr2(&[[0.0, 1.0, 0.0], [0.5, 0.0, -1.0], [0.0, 0.25, 0.0]]);
+
+        // Covers all Padé orders as well as the scaling and squaring phase.
+        for &scale in &[0.005, 0.1, 0.4, 1.0, 2.0, 10.0] {
+            let a = &a0 * scale;
+            let mut b = Array2::<f64>::zeros((3, 3));
+            let mut l = Array2::<f64>::zeros((3, 3));
+            crate::expm_frechet_full(&a, &e, &mut b, &mut l);
+
+            let mut expected_b = Array2::<f64>::zeros((3, 3));
+            crate::expm(&a, &mut expected_b);
+            let expected_l = frechet_via_block_matrix(&a, &e);
+
+            for (&x, &y) in b.iter().zip(expected_b.iter()) {
+                assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-15);
+            }
+            for (&x, &y) in l.iter().zip(expected_l.iter()) {
+                assert_relative_eq!(x, y, max_relative=1e-10, epsilon=1e-14);
+            }
+        }
+    }
+
+    #[test]
+    fn frechet_of_commuting_direction() {
+        // For E = A the derivative is A e^A.
+        let a = arr2(&[[0.5, 1.0], [-1.0, 0.25]]);
+        let mut l = Array2::<f64>::zeros((2, 2));
+        crate::expm_frechet(&a, &a, &mut l);
+
+        let mut b = Array2::<f64>::zeros((2, 2));
+        crate::expm(&a, &mut b);
+        let expected = a.dot(&b);
+
+        for (&x, &y) in l.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-13);
+        }
+    }
+}
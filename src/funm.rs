@@ -0,0 +1,556 @@
+//! General matrix functions via the real Schur decomposition $A = Q T Q^\top$.
+//!
+//! + `sqrtm` calculates the principal square root with the real Schur method of [Higham 1987].
+//! + `funm` applies an analytic scalar function $f$ with the Schur–Parlett algorithm of [Davies
+//!   and Higham 2003]. The diagonal blocks of $T$ are grouped into clusters, such that
+//!   eigenvalues in different clusters are farther than $\delta = 0.1$ apart, and $T$ is
+//!   reordered so that each cluster forms a contiguous atomic block. On atomic blocks with close
+//!   eigenvalues, $f$ is evaluated by its Taylor series about their mean $\sigma$,
+//!
+//! \begin{equation}
+//! f(T_{ii}) = \sum_{k=0}^\infty \frac{f^{(k)}(\sigma)}{k!} (T_{ii} - \sigma I)^k,
+//! \end{equation}
+//!
+//!   while isolated 1×1 and 2×2 blocks are evaluated directly from their eigenvalues. The
+//!   off-diagonal blocks $F_{ij}$ of $F = f(T)$ follow from the Parlett recurrence [Parlett 1976]
+//!
+//! \begin{equation}
+//! T_{ii} F_{ij} - F_{ij} T_{jj} = F_{ii} T_{ij} - T_{ij} F_{jj} + \sum_{k=i+1}^{j-1} \left(F_{ik} T_{kj} - T_{ik} F_{kj}\right),
+//! \end{equation}
+//!
+//!   which only divides by differences of eigenvalues in different clusters.
+//!
+//! Since complex eigenvalues of a real matrix come in conjugate pairs, $f$ is passed as a complex
+//! function `f(z, k)` returning the $k$-th derivative $f^{(k)}(z)$, and has to satisfy
+//! $f(\bar z) = \overline{f(z)}$, which holds for all functions with real Taylor coefficients
+//! such as $\exp$, $\cos$, or $\log$.
+//!
+//! NOTE: A 2×2 block of the real Schur form holds a pair of complex conjugate eigenvalues, which
+//! cannot be separated in real arithmetic. If such a pair is close to other eigenvalues, the
+//! Taylor series about the real mean $\sigma$ has to reach both eigenvalues of the pair and may
+//! converge slowly or not at all for functions with singularities close to $\sigma$.
+//!
+//! [Higham 1987]: https://doi.org/10.1016/0024-3795(87)90118-2
+//! [Parlett 1976]: https://doi.org/10.1016/0024-3795(76)90018-5
+//! [Davies and Higham 2003]: https://doi.org/10.1137/S0895479802410815
+
+use ndarray::{
+    self,
+    prelude::*,
+    s,
+    Data,
+    DataMut,
+};
+use num_complex::Complex;
+
+use crate::{
+    float::{
+        gees,
+        gemm,
+        trexc,
+    },
+    schur::{
+        diagonal_blocks,
+        solve_sylvester,
+        sqrt_quasi_triangular,
+    },
+    Float,
+};
+
+/// Eigenvalues closer than the blocking tolerance $\delta$ end up in the same atomic block.
+const BLOCKING_TOLERANCE: f64 = 0.1;
+
+/// The maximum number of terms of the Taylor series on an atomic block.
+const MAX_TAYLOR_TERMS: usize = 250;
+
+/// Storage for calculating functions of n×n matrices.
+pub struct Funm<T = f64> {
+    n: usize,
+    t: Array2<T>,
+    q: Array2<T>,
+    f: Array2<T>,
+    wr: Array1<T>,
+    wi: Array1<T>,
+    work: Array2<T>,
+    shifted: Array2<T>,
+    power: Array2<T>,
+    blocks: Vec<(usize, usize)>,
+    eigenvalues: Vec<(Complex<T>, Complex<T>)>,
+    labels: Vec<usize>,
+    atoms: Vec<usize>,
+}
+
+impl<T: Float> Funm<T> {
+    /// Allocates all space to calculate functions of n×n matrices.
+    pub fn new(n: usize) -> Self {
+        Funm {
+            n,
+            t: Array2::zeros((n, n)),
+            q: Array2::zeros((n, n)),
+            f: Array2::zeros((n, n)),
+            wr: Array1::zeros(n),
+            wi: Array1::zeros(n),
+            work: Array2::zeros((n, n)),
+            shifted: Array2::zeros((n, n)),
+            power: Array2::zeros((n, n)),
+            blocks: Vec::with_capacity(n),
+            eigenvalues: Vec::with_capacity(n),
+            labels: Vec::with_capacity(n),
+            atoms: Vec::with_capacity(n),
+        }
+    }
+
+    /// Calculate the principal square root of the n×n matrix `a`, storing the result in `b`.
+    ///
+    /// NOTE: Panics if `a` has an eigenvalue on the closed negative real axis, for which the
+    /// principal square root is not defined, if `a` and `b` don't have matching dimensions, are
+    /// not square, don't have the same dimension as the `Funm` object `sqrtm` is called on, or
+    /// if the Schur decomposition of `a` does not converge.
+    pub fn sqrtm<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        self.schur(a, b);
+        sqrt_quasi_triangular(&self.t, &mut self.f, &self.blocks);
+        self.back_transform(b);
+    }
+
+    /// Calculate $f(A)$ for the n×n matrix `a` and the analytic scalar function $f$, storing the
+    /// result in `b`. `f(z, k)` returns the $k$-th derivative $f^{(k)}(z)$ and has to satisfy
+    /// $f(\bar z) = \overline{f(z)}$.
+    ///
+    /// NOTE: Panics if `a` and `b` don't have matching dimensions, are not square, don't have the
+    /// same dimension as the `Funm` object `funm` is called on, if the Schur decomposition of
+    /// `a` does not converge, or if the Taylor series on an atomic block does not converge.
+    pub fn funm<S1, S2, F>(&mut self, a: &ArrayBase<S1, Ix2>, f: F, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+              F: Fn(Complex<T>, usize) -> Complex<T>,
+    {
+        self.schur(a, b);
+        self.reorder();
+        self.f.fill(T::zero());
+
+        let tolerance = T::from_f64(BLOCKING_TOLERANCE).unwrap();
+        let mut first = 0;
+        while first < self.blocks.len() {
+            let mut last = first;
+            while last + 1 < self.blocks.len() && self.atoms[last + 1] == self.atoms[first] {
+                last += 1;
+            }
+            let (i, _) = self.blocks[first];
+            let (j, q) = self.blocks[last];
+            let (lambda_1, lambda_2) = self.eigenvalues[first];
+
+            let block = self.t.slice(s![i..j+q, i..j+q]);
+            let mut f_block = self.f.slice_mut(s![i..j+q, i..j+q]);
+            if first == last && q == 1 {
+                f_block[(0, 0)] = f(lambda_1, 0).re;
+            } else if first == last && (lambda_1 - lambda_2).norm() > tolerance {
+                funm_2x2(&block, lambda_1, lambda_2, &f, &mut f_block);
+            } else {
+                funm_taylor(&block, &f, &mut f_block, &mut self.shifted, &mut self.power, &mut self.work);
+            }
+
+            first = last + 1;
+        }
+
+        for (jb, &(j, q)) in self.blocks.iter().enumerate() {
+            for ib in (0..jb).rev() {
+                // Blocks within an atomic block have been calculated from the Taylor series.
+                if self.atoms[ib] == self.atoms[jb] {
+                    continue;
+                }
+                let (i, p) = self.blocks[ib];
+
+                // C = Σ_{k=i}^{j-1} F_ik T_kj - Σ_{k=i+p}^{j+q-1} T_ik F_kj
+                let mut c = [[T::zero(); 2]; 2];
+                let mut is_zero = true;
+                for (a, row) in c.iter_mut().enumerate().take(p) {
+                    for (b, elem) in row.iter_mut().enumerate().take(q) {
+                        let mut value = T::zero();
+                        for k in i..j {
+                            value = value + self.f[(i + a, k)] * self.t[(k, j + b)];
+                        }
+                        for k in (i + p)..(j + q) {
+                            value = value - self.t[(i + a, k)] * self.f[(k, j + b)];
+                        }
+                        is_zero = is_zero && value == T::zero();
+                        *elem = value;
+                    }
+                }
+                if is_zero {
+                    continue;
+                }
+
+                // T_ii X + X (-T_jj) = C
+                let mut negated = [T::zero(); 4];
+                for (x, &y) in negated.iter_mut().zip(self.t.slice(s![j..j+q, j..j+q]).iter()) {
+                    *x = -y;
+                }
+                let negated = ArrayView2::from_shape((q, q), &negated[..q * q]).unwrap();
+                let x = solve_sylvester(&self.t.slice(s![i..i+p, i..i+p]), &negated, &c);
+                for (a, row) in x.iter().enumerate().take(p) {
+                    for (b, &elem) in row.iter().enumerate().take(q) {
+                        self.f[(i + a, j + b)] = elem;
+                    }
+                }
+            }
+        }
+
+        self.back_transform(b);
+    }
+
+    /// Calculates the real Schur form of `a` and its diagonal blocks.
+    fn schur<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: Data<Elem=T>,
+    {
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `Funm` struct.");
+        assert_eq!(a.dim(), b.dim(), "Input matrices `a` and `b` have to have matching dimensions.");
+
+        self.t.assign(a);
+        let info = gees(&mut self.t, &mut self.q, &mut self.wr, &mut self.wi);
+        assert_eq!(info, 0, "The Schur decomposition of `a` did not converge.");
+        diagonal_blocks(&self.t, &mut self.blocks);
+    }
+
+    /// Assigns each diagonal block the cluster of its eigenvalues, labelled by the index of its
+    /// first block. Two diagonal blocks are in the same cluster if they are connected by a chain
+    /// of blocks whose eigenvalues are at most `BLOCKING_TOLERANCE` apart.
+    fn cluster(&mut self) {
+        let tolerance = T::from_f64(BLOCKING_TOLERANCE).unwrap();
+
+        self.eigenvalues.clear();
+        for &(i, p) in &self.blocks {
+            let eigenvalues = if p == 1 {
+                let lambda = Complex::new(self.t[(i, i)], T::zero());
+                (lambda, lambda)
+            } else {
+                eigenvalues_2x2(&self.t.slice(s![i..i+2, i..i+2]))
+            };
+            self.eigenvalues.push(eigenvalues);
+        }
+
+        self.labels.clear();
+        self.labels.extend(0..self.blocks.len());
+        for u in 0..self.blocks.len() {
+            for v in (u + 1)..self.blocks.len() {
+                let (a_1, a_2) = self.eigenvalues[u];
+                let (b_1, b_2) = self.eigenvalues[v];
+                let is_close = [a_1, a_2].iter()
+                    .any(|&x| [b_1, b_2].iter().any(|&y| (x - y).norm() <= tolerance));
+
+                let (keep, replace) = (self.labels[u].min(self.labels[v]), self.labels[u].max(self.labels[v]));
+                if is_close && keep != replace {
+                    for label in self.labels.iter_mut().filter(|label| **label == replace) {
+                        *label = keep;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reorders the Schur form such that the diagonal blocks of each cluster are contiguous,
+    /// keeping the clusters in the order of their first block, and numbers the resulting atomic
+    /// blocks.
+    ///
+    /// If LAPACK rejects a swap of two blocks, the reordering stops early. Parts of a cluster
+    /// that don't end up next to each other then form separate atomic blocks.
+    fn reorder(&mut self) {
+        self.cluster();
+
+        // The sizes and cluster labels of the diagonal blocks in their current order.
+        let mut units: Vec<(usize, usize)> = self.blocks.iter()
+            .map(|&(_, p)| p)
+            .zip(self.labels.iter().cloned())
+            .collect();
+
+        let mut first = 0;
+        let mut row = 0;
+        'clusters: while first < units.len() {
+            let label = units[first].1;
+            let mut end = first + 1;
+            let mut end_row = row + units[first].0;
+
+            for u in (first + 1)..units.len() {
+                if units[u].1 != label {
+                    continue;
+                }
+                if u != end {
+                    let from = end_row + units[end..u].iter().map(|&(p, _)| p).sum::<usize>();
+                    if trexc(&mut self.t, &mut self.q, from, end_row) != 0 {
+                        break 'clusters;
+                    }
+                    let unit = units.remove(u);
+                    units.insert(end, unit);
+                }
+                end_row += units[end].0;
+                end += 1;
+            }
+
+            first = end;
+            row = end_row;
+        }
+
+        // Swaps can change the diagonal blocks, so that they are determined anew.
+        diagonal_blocks(&self.t, &mut self.blocks);
+        self.cluster();
+
+        self.atoms.clear();
+        for u in 0..self.labels.len() {
+            let atom = match u {
+                0 => 0,
+                _ if self.labels[u] == self.labels[u - 1] => self.atoms[u - 1],
+                _ => self.atoms[u - 1] + 1,
+            };
+            self.atoms.push(atom);
+        }
+    }
+
+    /// Calculates B = Q F Qᵀ.
+    fn back_transform<S>(&mut self, b: &mut ArrayBase<S, Ix2>)
+        where S: DataMut<Elem=T>,
+    {
+        gemm(T::one(), &self.q, &self.f, T::zero(), &mut self.work);
+        gemm(T::one(), &self.work, &self.q.t(), T::zero(), b);
+    }
+}
+
+/// The eigenvalues λ₁ and λ₂ of the 2×2 matrix `b`.
+fn eigenvalues_2x2<T, S>(b: &ArrayBase<S, Ix2>) -> (Complex<T>, Complex<T>)
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    let half = T::from_f64(0.5).unwrap();
+    let mean = half * (b[(0, 0)] + b[(1, 1)]);
+    let d = half * (b[(0, 0)] - b[(1, 1)]);
+    let discriminant = d * d + b[(0, 1)] * b[(1, 0)];
+
+    if discriminant < T::zero() {
+        let im = (-discriminant).sqrt();
+        (Complex::new(mean, im), Complex::new(mean, -im))
+    } else {
+        let re = discriminant.sqrt();
+        (Complex::new(mean + re, T::zero()), Complex::new(mean - re, T::zero()))
+    }
+}
+
+/// Evaluates `f` at the 2×2 matrix `b` with the distinct eigenvalues λ₁ and λ₂ as
+/// f(B) = f(λ₂) I + f[λ₁, λ₂] (B - λ₂ I), where f[λ₁, λ₂] is the divided difference.
+fn funm_2x2<T, S1, S2, F>(b: &ArrayBase<S1, Ix2>, lambda_1: Complex<T>, lambda_2: Complex<T>, f: &F, f_b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+          F: Fn(Complex<T>, usize) -> Complex<T>,
+{
+    let f_2 = f(lambda_2, 0);
+    let divided_difference = (f(lambda_1, 0) - f_2) / (lambda_1 - lambda_2);
+
+    for ((i, j), x) in f_b.indexed_iter_mut() {
+        let shifted = if i == j { Complex::new(b[(i, j)], T::zero()) - lambda_2 } else { Complex::new(b[(i, j)], T::zero()) };
+        let diagonal = if i == j { f_2 } else { Complex::new(T::zero(), T::zero()) };
+        *x = (diagonal + divided_difference * shifted).re;
+    }
+}
+
+/// Evaluates `f` at the m×m atomic block `t` by its Taylor series about the mean σ of the
+/// eigenvalues of `t`, using the leading m×m parts of the n×n matrices `shifted`, `power`, and
+/// `product` as workspace. The series is truncated once two consecutive terms are negligible
+/// compared to the partial sum.
+///
+/// NOTE: Panics if the series has not converged after `MAX_TAYLOR_TERMS` terms.
+fn funm_taylor<T, S1, S2, F>(
+    t: &ArrayBase<S1, Ix2>,
+    f: &F,
+    f_t: &mut ArrayBase<S2, Ix2>,
+    shifted: &mut Array2<T>,
+    power: &mut Array2<T>,
+    product: &mut Array2<T>,
+)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+          F: Fn(Complex<T>, usize) -> Complex<T>,
+{
+    let m = t.rows();
+    let sigma = t.diag().sum() / T::from_usize(m).unwrap();
+    let sigma_complex = Complex::new(sigma, T::zero());
+
+    let mut shifted = shifted.slice_mut(s![..m, ..m]);
+    shifted.assign(t);
+    for x in shifted.diag_mut() {
+        *x = *x - sigma;
+    }
+
+    let mut power = power.slice_mut(s![..m, ..m]);
+    power.fill(T::zero());
+    power.diag_mut().fill(T::one());
+    let mut product = product.slice_mut(s![..m, ..m]);
+
+    f_t.fill(T::zero());
+    f_t.diag_mut().fill(f(sigma_complex, 0).re);
+
+    let mut negligible_terms = 0;
+    for k in 1..=MAX_TAYLOR_TERMS {
+        // (T - σI)^k / k!
+        gemm(T::one() / T::from_usize(k).unwrap(), &power, &shifted, T::zero(), &mut product);
+        power.assign(&product);
+
+        let coefficient = f(sigma_complex, k).re;
+        f_t.scaled_add(coefficient, &power);
+
+        let term = coefficient.abs() * power.fold(T::zero(), |acc, &x| acc.max(x.abs()));
+        let sum = f_t.fold(T::zero(), |acc, &x| acc.max(x.abs()));
+        if term <= T::epsilon() * sum {
+            negligible_terms += 1;
+            if negligible_terms == 2 {
+                return;
+            }
+        } else {
+            negligible_terms = 0;
+        }
+    }
+
+    panic!("The Taylor series of `f` on an atomic block did not converge.");
+}
+
+/// Calculate the principal square root of the n×n matrix `a`, storing the result in `b`.
+///
+/// NOTE: Panics if `a` has an eigenvalue on the closed negative real axis, if `a` and `b` don't
+/// have matching dimensions or are not square, or if the Schur decomposition of `a` does not
+/// converge.
+pub fn sqrtm<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut funm = Funm::new(n);
+    funm.sqrtm(a, b);
+}
+
+/// Calculate $f(A)$ for the n×n matrix `a` and the analytic scalar function $f$, storing the
+/// result in `b`. `f(z, k)` returns the $k$-th derivative $f^{(k)}(z)$ and has to satisfy
+/// $f(\bar z) = \overline{f(z)}$.
+///
+/// NOTE: Panics if `a` and `b` don't have matching dimensions, are not square, if the Schur
+/// decomposition of `a` does not converge, or if the Taylor series on an atomic block does not
+/// converge.
+pub fn funm<T, S1, S2, F>(a: &ArrayBase<S1, Ix2>, f: F, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+          F: Fn(Complex<T>, usize) -> Complex<T>,
+{
+    let (n, _) = a.dim();
+
+    let mut funm = Funm::new(n);
+    funm.funm(a, f, b);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use num_complex::Complex;
+    use approx::assert_relative_eq;
+
+    fn test_matrix() -> Array2<f64> {
+        // The rotation block gives a pair of complex eigenvalues, and thus a 2×2 Schur block.
+        arr2(&[
+            [1.5, 2.0, 0.3, 0.0],
+            [-2.0, 1.5, 0.0, 0.4],
+            [0.1, 0.0, 3.0, 0.5],
+            [0.0, 0.2, 0.0, 0.5],
+        ])
+    }
+
+    /// The natural logarithm and its derivatives (-1)^(k-1) (k-1)! / z^k.
+    fn log(z: Complex<f64>, k: usize) -> Complex<f64> {
+        match k {
+            0 => z.ln(),
+            _ => (1..k).fold(z.powi(-(k as i32)), |acc, i| acc * -(i as f64)),
+        }
+    }
+
+    #[test]
+    fn sqrtm_squares_to_input() {
+        let a = test_matrix();
+        let mut b = Array2::<f64>::zeros((4, 4));
+
+        crate::sqrtm(&a, &mut b);
+
+        for (&x, &y) in b.dot(&b).iter().zip(a.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-13);
+        }
+    }
+
+    #[test]
+    fn funm_of_exp_agrees_with_expm() {
+        let a = test_matrix();
+        let mut b = Array2::<f64>::zeros((4, 4));
+        let mut expected = Array2::<f64>::zeros((4, 4));
+
+        crate::funm(&a, |z, _| z.exp(), &mut b);
+        crate::expm(&a, &mut expected);
+
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-11, epsilon=1e-12);
+        }
+
+        // Repeated eigenvalues in decoupled blocks.
+        let eye = Array2::<f64>::eye(3) * 2.0;
+        let mut log_eye = Array2::<f64>::zeros((3, 3));
+        crate::funm(&eye, log, &mut log_eye);
+        for ((i, j), &x) in log_eye.indexed_iter() {
+            assert_relative_eq!(x, if i == j { 2f64.ln() } else { 0.0 }, epsilon=1e-15);
+        }
+    }
+
+    #[test]
+    fn close_eigenvalues_are_blocked() {
+        // Eigenvalues 1 and 1 + 1e-8, for which the divided differences of the unblocked
+        // recurrence lose about half of the significant digits. They are separated by the
+        // eigenvalue 3 and have to be swapped next to each other.
+        let real = arr2(&[
+            [1.0, 1.0, 0.5],
+            [0.0, 3.0, 1.0],
+            [0.0, 0.0, 1.0 + 1e-8],
+        ]);
+        // Two close pairs of complex conjugate eigenvalues 1 ± 2i and 1 + 1e-7 ± 2i.
+        let complex = arr2(&[
+            [1.0, 2.0, 0.5, 0.3],
+            [-2.0, 1.0, 0.2, 0.4],
+            [0.0, 0.0, 1.0 + 1e-7, 2.0],
+            [0.0, 0.0, -2.0, 1.0 + 1e-7],
+        ]);
+
+        for a in &[real, complex] {
+            let n = a.rows();
+            let mut b = Array2::<f64>::zeros((n, n));
+            let mut expected = Array2::<f64>::zeros((n, n));
+
+            crate::funm(a, |z, _| z.exp(), &mut b);
+            crate::expm(a, &mut expected);
+
+            for (&x, &y) in b.iter().zip(expected.iter()) {
+                assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-13);
+            }
+        }
+
+        // log(exp(A)) = A for the close real eigenvalues.
+        let a = arr2(&[
+            [1.0, 1.0, 0.5],
+            [0.0, 1.5, 1.0],
+            [0.0, 0.0, 1.0 + 1e-8],
+        ]);
+        let mut exp_a = Array2::<f64>::zeros((3, 3));
+        let mut log_exp_a = Array2::<f64>::zeros((3, 3));
+        crate::expm(&a, &mut exp_a);
+        crate::funm(&exp_a, log, &mut log_exp_a);
+        for (&x, &y) in log_exp_a.iter().zip(a.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-13);
+        }
+    }
+}
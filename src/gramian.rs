@@ -0,0 +1,195 @@
+//! Finite-horizon controllability and observability Gramians
+//!
+//! \begin{align}
+//!     W_c(T) &= \int_0^T e^{As} B B^\top e^{A^\top s} \mathrm{d}s, \\
+//!     W_o(T) &= \int_0^T e^{A^\top s} C^\top C e^{As} \mathrm{d}s,
+//! \end{align}
+//!
+//! of the linear system $\dot x = Ax + Bu$, $y = Cx$.
+//!
+//! Both are calculated via the integrals in the `integral` module. Rounding errors make the
+//! computed integrals slightly asymmetric and can lead to tiny negative eigenvalues, which is why
+//! the results are symmetrized and projected onto the cone of positive semidefinite matrices by
+//! setting negative eigenvalues to zero.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    float::{
+        gemm,
+        syev,
+    },
+    ExpmIntegral,
+    Float,
+};
+
+/// Storage for calculating Gramians of systems with n states.
+pub struct Gramian<T = f64> {
+    n: usize,
+    integral: ExpmIntegral<T>,
+    a_t: Array2<T>,
+    m: Array2<T>,
+    eigenvectors: Array2<T>,
+    eigenvalues: Array1<T>,
+    work: Array2<T>,
+}
+
+impl<T: Float> Gramian<T> {
+    /// Allocates all space to calculate Gramians for systems with a state matrix of dimension n×n.
+    pub fn new(n: usize) -> Self {
+        let integral = ExpmIntegral::new(n);
+        let a_t = Array2::<T>::zeros((n, n));
+        let m = Array2::<T>::zeros((n, n));
+        let eigenvectors = Array2::<T>::zeros((n, n));
+        let eigenvalues = Array1::<T>::zeros(n);
+        let work = Array2::<T>::zeros((n, n));
+
+        Gramian {
+            n,
+            integral,
+            a_t,
+            m,
+            eigenvectors,
+            eigenvalues,
+            work,
+        }
+    }
+
+    /// Calculate the controllability Gramian over the horizon `t` for the n×n state matrix `a`
+    /// and the n×p input matrix `b`, storing the result in the n×n matrix `w`.
+    ///
+    /// NOTE: Panics if the dimensions of `a`, `b`, and `w` don't match, don't match the
+    /// dimension of the `Gramian` object `ctrb` is called on, or if the eigendecomposition of
+    /// the Gramian does not converge, e.g. for non-finite entries.
+    pub fn ctrb<S1, S2, S3>(&mut self, a: &ArrayBase<S1, Ix2>, b: &ArrayBase<S2, Ix2>, t: T, w: &mut ArrayBase<S3, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: Data<Elem=T>,
+              S3: DataMut<Elem=T>,
+    {
+        assert_eq!(b.rows(), self.n, "Dimension mismatch between input matrix `b` and preconfigured `Gramian` struct.");
+
+        gemm(T::one(), b, &b.t(), T::zero(), &mut self.m);
+        self.integral.integrate(a, &self.m, t, w);
+        self.project_psd(w);
+    }
+
+    /// Calculate the observability Gramian over the horizon `t` for the n×n state matrix `a`
+    /// and the q×n output matrix `c`, storing the result in the n×n matrix `w`.
+    ///
+    /// NOTE: Panics if the dimensions of `a`, `c`, and `w` don't match, don't match the
+    /// dimension of the `Gramian` object `obsv` is called on, or if the eigendecomposition of
+    /// the Gramian does not converge, e.g. for non-finite entries.
+    pub fn obsv<S1, S2, S3>(&mut self, a: &ArrayBase<S1, Ix2>, c: &ArrayBase<S2, Ix2>, t: T, w: &mut ArrayBase<S3, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: Data<Elem=T>,
+              S3: DataMut<Elem=T>,
+    {
+        assert_eq!(c.cols(), self.n, "Dimension mismatch between output matrix `c` and preconfigured `Gramian` struct.");
+
+        gemm(T::one(), &c.t(), c, T::zero(), &mut self.m);
+        self.a_t.assign(&a.t());
+        self.integral.integrate(&self.a_t, &self.m, t, w);
+        self.project_psd(w);
+    }
+
+    /// Symmetrizes `w` and sets its negative eigenvalues to zero.
+    fn project_psd<S>(&mut self, w: &mut ArrayBase<S, Ix2>)
+        where S: DataMut<Elem=T>,
+    {
+        let half = T::from_f64(0.5).unwrap();
+
+        self.eigenvectors.assign(w);
+        self.eigenvectors.zip_mut_with(&w.t(), |x, &y| *x = half * (*x + y));
+
+        let info = syev(&mut self.eigenvectors, &mut self.eigenvalues);
+        assert_eq!(info, 0, "The eigendecomposition of the Gramian did not converge.");
+
+        // W = V Λ⁺ Vᵀ, with Λ⁺ the non-negative part of Λ.
+        self.work.assign(&self.eigenvectors);
+        for (mut column, &lambda) in self.work.gencolumns_mut().into_iter().zip(self.eigenvalues.iter()) {
+            let lambda = lambda.max(T::zero());
+            column.mapv_inplace(|x| x * lambda);
+        }
+        gemm(T::one(), &self.work, &self.eigenvectors.t(), T::zero(), w);
+
+        // Remove the asymmetry introduced by rounding in the product above.
+        self.work.assign(w);
+        w.zip_mut_with(&self.work.t(), |x, &y| *x = half * (*x + y));
+    }
+}
+
+/// Calculate the controllability Gramian over the horizon `t` for the n×n state matrix `a` and
+/// the n×p input matrix `b`, storing the result in the n×n matrix `w`.
+///
+/// NOTE: Panics if the dimensions of `a`, `b`, and `w` don't match, or if the eigendecomposition
+/// of the Gramian does not converge.
+pub fn gramian_ctrb<T, S1, S2, S3>(a: &ArrayBase<S1, Ix2>, b: &ArrayBase<S2, Ix2>, t: T, w: &mut ArrayBase<S3, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=T>,
+          S3: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut gramian = Gramian::new(n);
+    gramian.ctrb(a, b, t, w);
+}
+
+/// Calculate the observability Gramian over the horizon `t` for the n×n state matrix `a` and
+/// the q×n output matrix `c`, storing the result in the n×n matrix `w`.
+///
+/// NOTE: Panics if the dimensions of `a`, `c`, and `w` don't match, or if the eigendecomposition
+/// of the Gramian does not converge.
+pub fn gramian_obsv<T, S1, S2, S3>(a: &ArrayBase<S1, Ix2>, c: &ArrayBase<S2, Ix2>, t: T, w: &mut ArrayBase<S3, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=T>,
+          S3: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut gramian = Gramian::new(n);
+    gramian.obsv(a, c, t, w);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn ctrb_and_obsv_are_dual() {
+        let a = arr2(&[[-1.0, 2.0, 0.0], [0.0, -3.0, 1.0], [0.5, 0.0, -2.0]]);
+        let b = arr2(&[[1.0], [0.0], [2.0]]);
+        let mut w_ctrb = Array2::<f64>::zeros((3, 3));
+        let mut w_obsv = Array2::<f64>::zeros((3, 3));
+
+        crate::gramian_ctrb(&a, &b, 2.5, &mut w_ctrb);
+        crate::gramian_obsv(&a.t(), &b.t(), 2.5, &mut w_obsv);
+
+        for (&x, &y) in w_ctrb.iter().zip(w_obsv.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-14);
+        }
+        assert_eq!(w_ctrb, w_ctrb.t());
+    }
+
+    #[test]
+    fn ctrb_of_uncontrollable_system_is_singular_but_psd() {
+        // The second state is neither driven by the input nor coupled to the first one.
+        let a = arr2(&[[-1.0, 0.0], [0.0, -2.0]]);
+        let b = arr2(&[[1.0], [0.0]]);
+        let mut w = Array2::<f64>::zeros((2, 2));
+
+        crate::gramian_ctrb(&a, &b, 1.0, &mut w);
+
+        assert_relative_eq!(w[[0, 0]], (1.0 - (-2.0f64).exp()) / 2.0, max_relative=1e-12);
+        assert!(w[[1, 1]] >= 0.0);
+        assert!(w[[1, 1]].abs() < 1e-15);
+        assert!(w[[0, 1]].abs() < 1e-15);
+    }
+}
@@ -0,0 +1,145 @@
+//! Integrals over the matrix exponential of the form
+//!
+//! \begin{equation}
+//!     X(T) = \int_0^T e^{As} M e^{A^\top s} \mathrm{d}s,
+//! \end{equation}
+//!
+//! as they arise for controllability and observability Gramians over finite horizons, or for the
+//! covariance of occupation times of continuous-time Markov chains.
+//!
+//! The integral is calculated following [Van Loan 1978] by exponentiating the augmented block
+//! matrix
+//!
+//! \begin{equation}
+//!     C = \begin{pmatrix} -A & M \\ 0 & A^\top \end{pmatrix} T,
+//!     \qquad
+//!     e^C = \begin{pmatrix} F_{11} & F_{12} \\ 0 & F_{22} \end{pmatrix},
+//! \end{equation}
+//!
+//! from which the integral is recovered as $X(T) = F_{22}^\top F_{12}$.
+//!
+//! [Van Loan 1978]: https://doi.org/10.1109/TAC.1978.1101743
+
+use ndarray::{
+    self,
+    prelude::*,
+    s,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    float::gemm,
+    Expm,
+    Float,
+};
+
+/// Storage for calculating integrals over the matrix exponential.
+pub struct ExpmIntegral<T = f64> {
+    n: usize,
+    expm: Expm<T>,
+    c: Array2<T>,
+    f: Array2<T>,
+}
+
+impl<T: Float> ExpmIntegral<T> {
+    /// Allocates all space to calculate the integral for square matrices of dimension n×n. The
+    /// augmented block matrix is of dimension 2n×2n.
+    pub fn new(n: usize) -> Self {
+        let expm = Expm::new(2*n);
+        let c = Array2::<T>::zeros((2*n, 2*n));
+        let f = Array2::<T>::zeros((2*n, 2*n));
+
+        ExpmIntegral {
+            n,
+            expm,
+            c,
+            f,
+        }
+    }
+
+    /// Calculate $\int_0^t e^{As} M e^{A^\top s} \mathrm{d}s$ for the n×n matrices `a` and `m`,
+    /// storing the result in matrix `b`.
+    ///
+    /// NOTE: Panics if the matrices `a`, `m`, and `b` don't have matching dimensions, are not
+    /// square, or don't have the same dimension as the `ExpmIntegral` object `integrate` is called
+    /// on.
+    pub fn integrate<S1, S2, S3>(&mut self, a: &ArrayBase<S1, Ix2>, m: &ArrayBase<S2, Ix2>, t: T, b: &mut ArrayBase<S3, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: Data<Elem=T>,
+              S3: DataMut<Elem=T>,
+    {
+        assert_eq!(a.dim(), m.dim(), "Input matrices `a` and `m` have to have matching dimensions.");
+        assert_eq!(a.dim(), b.dim(), "Input matrices `a` and `b` have to have matching dimensions.");
+        let (n_rows, n_cols) = a.dim();
+        assert_eq!(n_rows, n_cols, "The integral is only implemented for square matrices.");
+        assert_eq!(n_rows, self.n, "Dimension mismatch between matrix `a` and preconfigured `ExpmIntegral` struct.");
+
+        let n = self.n;
+
+        self.c.fill(T::zero());
+        self.c.slice_mut(s![..n, ..n]).zip_mut_with(a, |x, &y| *x = -t * y);
+        self.c.slice_mut(s![..n, n..]).zip_mut_with(m, |x, &y| *x = t * y);
+        self.c.slice_mut(s![n.., n..]).zip_mut_with(&a.t(), |x, &y| *x = t * y);
+
+        self.expm.expm(&self.c, &mut self.f);
+
+        let f12 = self.f.slice(s![..n, n..]);
+        let f22 = self.f.slice(s![n.., n..]);
+        gemm(T::one(), &f22.t(), &f12, T::zero(), b);
+    }
+}
+
+/// Calculate $\int_0^t e^{As} M e^{A^\top s} \mathrm{d}s$ for the n×n matrices `a` and `m`,
+/// storing the result in matrix `b`.
+///
+/// NOTE: Panics if the matrices `a`, `m`, and `b` don't have matching dimensions or are not
+/// square.
+pub fn expm_integral<T, S1, S2, S3>(a: &ArrayBase<S1, Ix2>, m: &ArrayBase<S2, Ix2>, t: T, b: &mut ArrayBase<S3, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=T>,
+          S3: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut integral = ExpmIntegral::new(n);
+    integral.integrate(a, m, t, b);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn integral_of_zero_generator() {
+        let n = 4;
+        let a = Array2::<f64>::zeros((n, n));
+        let m = Array2::from_shape_fn((n, n), |(i, j)| (i + 2 * j) as f64);
+        let mut b = Array2::<f64>::zeros((n, n));
+
+        crate::expm_integral(&a, &m, 1.5, &mut b);
+
+        for (&x, &y) in b.iter().zip(m.iter()) {
+            assert_relative_eq!(x, 1.5 * y, max_relative=1e-14);
+        }
+    }
+
+    #[test]
+    fn integral_of_diagonal_generator() {
+        let lambda = [-1.0, -0.5, 0.25];
+        let a = Array2::from_shape_fn((3, 3), |(i, j)| if i == j { lambda[i] } else { 0.0 });
+        let m = arr2(&[[1.0, 2.0, 0.5], [2.0, 3.0, -1.0], [0.5, -1.0, 4.0]]);
+        let t = 2.0;
+        let mut b = Array2::<f64>::zeros((3, 3));
+
+        crate::expm_integral(&a, &m, t, &mut b);
+
+        for ((i, j), &x) in b.indexed_iter() {
+            let rate = lambda[i] + lambda[j];
+            let expected = m[[i, j]] * ((rate * t).exp() - 1.0) / rate;
+            assert_relative_eq!(x, expected, max_relative=1e-12);
+        }
+    }
+}
@@ -1,31 +1,33 @@
-/// This crate contains `expm`, an implementation of Algorithm 6.1 by [Al-Mohy, Higham] in the Rust
-/// programming language. It calculates the exponential of a matrix. See the linked paper for more
-/// information.
-///
-/// An important ingredient is `normest1`, Algorithm 2.4 in [Higham, Tisseur], which estimates
-/// the 1-norm of a matrix.
-///
-/// Furthermore, to fully understand the algorithm as described in the original paper, one has to
-/// understand that the factor $\lvert C_{2m+1} \rvert$ arises during the Padé approximation of the
-/// exponential function. The derivation is described in [Gautschi 2012], pp. 363--365, and the
-/// factor reads:
-///
-/// \begin{equation}
-/// C_{n,m} = (-1)^n \frac{n!m!}{(n+m)!(n+m+1)!},
-/// \end{equation}
-///
-/// or using only the diagonal elements, $m=n$:
-///
-/// \begin{equation}
-/// C_m = (-1)^m \frac{m!m!}{(2m)!(2m+1)!}
-/// \end{equation}
-/// 
-///
-/// [Al-Mohy, Higham]: http://eprints.ma.man.ac.uk/1300/1/covered/MIMS_ep2009_9.pdf 
-/// [Higham, Tisseur]: http://eprints.ma.man.ac.uk/321/1/covered/MIMS_ep2006_145.pdf
-/// [Gautschi 2012]: https://doi.org/10.1007/978-0-8176-8259-0
+//! This crate contains `expm`, an implementation of Algorithm 6.1 by [Al-Mohy, Higham] in the Rust
+//! programming language. It calculates the exponential of a matrix. See the linked paper for more
+//! information.
+//!
+//! An important ingredient is `normest1`, Algorithm 2.4 in [Higham, Tisseur], which estimates
+//! the 1-norm of a matrix.
+//!
+//! Furthermore, to fully understand the algorithm as described in the original paper, one has to
+//! understand that the factor $\lvert C_{2m+1} \rvert$ arises during the Padé approximation of the
+//! exponential function. The derivation is described in [Gautschi 2012], pp. 363--365, and the
+//! factor reads:
+//!
+//! \begin{equation}
+//! C_{n,m} = (-1)^n \frac{n!m!}{(n+m)!(n+m+1)!},
+//! \end{equation}
+//!
+//! or using only the diagonal elements, $m=n$:
+//!
+//! \begin{equation}
+//! C_m = (-1)^m \frac{m!m!}{(2m)!(2m+1)!}
+//! \end{equation}
+//! 
+//!
+//! [Al-Mohy, Higham]: http://eprints.ma.man.ac.uk/1300/1/covered/MIMS_ep2009_9.pdf 
+//! [Higham, Tisseur]: http://eprints.ma.man.ac.uk/321/1/covered/MIMS_ep2006_145.pdf
+//! [Gautschi 2012]: https://doi.org/10.1007/978-0-8176-8259-0
+
+#[cfg(feature = "openblas-src")]
+extern crate openblas_src;
 
-use condest::Normest1;
 use ndarray::{
     self,
     prelude::*,
@@ -34,14 +36,262 @@ use ndarray::{
     Dimension,
     Zip
 };
+use rand::RngCore;
+
+mod backward_error;
+mod balance;
+mod bank;
+mod batch;
+mod centrosymmetric;
+mod chain;
+mod circulant;
+mod closed_form;
+mod cond;
+mod congruence;
+mod counters;
+mod crosscheck;
+mod ctmc;
+#[cfg(feature = "sprs")]
+mod dag;
+mod embedding;
+mod evaluator;
+mod event;
+mod expm1m;
+mod extended;
+mod float;
+mod frechet;
+mod funm;
+mod gramian;
+mod integral;
+mod logm;
+mod lumping;
+mod magnus;
+#[cfg(feature = "rug")]
+mod multiprecision;
+#[cfg(feature = "nalgebra")]
+mod nalgebra_interop;
+mod norm_est;
+mod normest;
+mod options;
+mod phi;
+mod plan;
+mod product;
+mod product_tree;
+mod recovery;
+mod schur;
+mod shift;
+mod shifted_solve;
+mod soft;
+#[cfg(feature = "sprs")]
+mod sparse_shifted_solve;
+pub mod special;
+mod step;
+mod sym;
+mod transient;
+mod triangular;
+mod trig;
+mod unitary;
+mod verified;
+
+pub use crate::backward_error::{
+    pade_backward_error_bound,
+    pade_backward_error_series,
+    pade_theta,
+    SERIES_DEGREE,
+};
+pub use crate::bank::{
+    expm_bank_propagators,
+    ExpmBank,
+};
+pub use crate::batch::{
+    expm_batch,
+    expm_stream,
+    ExpmStream,
+};
+pub use crate::centrosymmetric::{
+    expm_centrosymmetric,
+    is_centrosymmetric,
+    ExpmCentrosymmetric,
+};
+pub use crate::chain::{
+    expm_chain_gradient,
+    ExpmChain,
+};
+pub use crate::circulant::{
+    expm_circulant,
+    Circulant,
+    ExpmCirculant,
+};
+pub use crate::closed_form::{
+    expm_2x2,
+    expm_se3,
+    expm_so3,
+};
+pub use crate::cond::{
+    expm_cond,
+    ExpmCond,
+};
+pub use crate::congruence::{
+    expm_congruence,
+    expm_congruence_hermitian,
+    ExpmCongruence,
+};
+pub use crate::counters::Counters;
+pub use crate::crosscheck::{
+    expm_cross_check,
+    CrossCheck,
+    ExpmCrossCheck,
+};
+pub use crate::ctmc::{
+    check_generator,
+    ctmc_propagate,
+    ctmc_transition,
+    expm_generator,
+    Ctmc,
+    StochasticConvention,
+};
+#[cfg(feature = "sprs")]
+pub use crate::dag::expm_dag;
+pub use crate::embedding::{
+    generator_from_transition,
+    CtmcEmbedding,
+    EmbeddingDiagnostics,
+};
+pub use crate::evaluator::{
+    expm_at_times,
+    ExpmEvaluator,
+};
+pub use crate::event::{
+    expm_event,
+    ExpmEvent,
+};
+pub use crate::expm1m::expm1m;
+pub use crate::float::Float;
+pub use crate::frechet::{
+    expm_frechet,
+    expm_frechet_full,
+    ExpmFrechet,
+};
+pub use crate::funm::{
+    funm,
+    sqrtm,
+    Funm,
+};
+pub use crate::gramian::{
+    gramian_ctrb,
+    gramian_obsv,
+    Gramian,
+};
+pub use crate::integral::{
+    expm_integral,
+    ExpmIntegral,
+};
+pub use crate::logm::{
+    logm,
+    Logm,
+};
+pub use crate::lumping::{
+    lump_generator,
+    Lumping,
+    LumpingReport,
+};
+pub use crate::magnus::{
+    magnus,
+    Magnus,
+    MagnusOrder,
+};
+#[cfg(feature = "rug")]
+pub use crate::multiprecision::{
+    expm_multiprecision,
+    ExpmMultiprecision,
+    ExpmMultiprecisionReport,
+};
+#[cfg(feature = "nalgebra")]
+pub use crate::nalgebra_interop::{
+    expm_dmatrix,
+    expm_smatrix,
+};
+pub use crate::norm_est::{
+    expm_norm1_est,
+    ExpmNormEst,
+};
+pub use crate::normest::Normest1;
+pub use crate::options::{
+    expm_with_opts,
+    ExpmOptions,
+    ExpmReport,
+};
+pub use crate::phi::{
+    expm_phi,
+    expm_phi_action,
+    ExpmPhi,
+    ExpmPhiAction,
+};
+pub use crate::product::{
+    expm_product,
+    ExpmProduct,
+};
+pub use crate::product_tree::{
+    expm_prefix_products,
+    ExpmProductTree,
+};
+pub use crate::shift::ExpmShiftCache;
+pub use crate::shifted_solve::{
+    solve_shifted,
+    ShiftedSolver,
+};
+pub use crate::soft::{
+    expm_soft,
+    ExpmQuality,
+    ExpmSoftReport,
+};
+#[cfg(feature = "sprs")]
+pub use crate::sparse_shifted_solve::{
+    solve_shifted_sparse,
+    SparseShiftedSolver,
+};
+pub use crate::step::{
+    suggest_step,
+    suggest_step_from_estimates,
+    suggest_step_from_norm,
+    theta_for_tolerance,
+    StepSuggestion,
+};
+pub use crate::sym::{
+    expm_hermitian,
+    expm_sym,
+    ExpmSym,
+};
+pub use crate::transient::{
+    expm_transient_peak,
+    kreiss_lower_bound,
+    ExpmTransient,
+    TransientPeak,
+};
+pub use crate::triangular::expm_triangular;
+pub use crate::trig::{
+    coshm,
+    cosm,
+    sinhm,
+    sinm,
+    Trigm,
+};
+pub use crate::unitary::{
+    expm_skew_hermitian,
+    expm_skew_symmetric,
+    ExpmUnitary,
+};
+pub use crate::verified::{
+    expm_verified,
+    ExpmVerified,
+    MAX_VERIFIED_DIMENSION,
+};
 
-// Can we calculate these at compile time?
-const THETA_3: f64 = 1.495585217958292e-2;
-const THETA_5: f64 = 2.539398330063230e-1;
-const THETA_7: f64 = 9.504178996162932e-1;
-const THETA_9: f64 = 2.097847961257068e0;
-// const THETA_13: f64 = 5.371920351148152e0 // Alg 3.1
-const THETA_13: f64 = 4.25; // Alg 5.1
+use crate::float::{
+    gemm,
+    gesv,
+    syev,
+};
 
 const PADE_COEFF_3: [f64; 4] = [
     120., 60., 12.,
@@ -86,9 +336,9 @@ const PADE_COEFF_13: [f64; 14] = [
 ///     h(x) = \sum^\infty_{i=2m+1} C_i x^i
 /// \end{equation}
 fn pade_error_coefficient(m: u64) -> f64 {
-    use statrs::function::factorial::{binomial, factorial};
+    use crate::special::{binomial, factorial};
 
-    return 1.0 / ( binomial(2*m, m) * factorial(2*m + 1) )
+    1.0 / ( binomial(2*m, m) * factorial(2*m + 1) )
 }
 
 #[allow(non_camel_case_types)]
@@ -102,6 +352,7 @@ struct PadeOrder_9;
 #[allow(non_camel_case_types)]
 struct PadeOrder_13;
 
+#[derive(Clone, Copy)]
 enum PadeOrders {
     _3,
     _5,
@@ -110,6 +361,34 @@ enum PadeOrders {
     _13,
 }
 
+impl PadeOrders {
+    fn order(self) -> u64 {
+        match self {
+            PadeOrders::_3 => 3,
+            PadeOrders::_5 => 5,
+            PadeOrders::_7 => 7,
+            PadeOrders::_9 => 9,
+            PadeOrders::_13 => 13,
+        }
+    }
+
+    /// The Padé order `m`.
+    ///
+    /// NOTE: Panics if `m` is not one of 3, 5, 7, 9, 13, or not supported for the precision `T`.
+    fn from_order<T: Float>(m: u64) -> Self {
+        let pade_order = match m {
+            3 => PadeOrders::_3,
+            5 => PadeOrders::_5,
+            7 => PadeOrders::_7,
+            9 => PadeOrders::_9,
+            13 => PadeOrders::_13,
+            _ => panic!("Padé order {} is not supported.", m),
+        };
+        assert!(m <= T::MAX_PADE_ORDER, "Padé order {} is not supported for this precision.", m);
+        pade_order
+    }
+}
+
 trait PadeOrder {
     const ORDER: u64;
 
@@ -121,10 +400,11 @@ trait PadeOrder {
     /// Maybe possible once RFC 2000 lands? See the PR https://github.com/rust-lang/rust/pull/53645
     fn coefficients() -> &'static [f64];
 
-    fn calculate_pade_sums<S1, S2, S3>(a: &ArrayBase<S1, Ix2>, a_powers: &[&ArrayBase<S1, Ix2>], u: &mut ArrayBase<S2, Ix2>, v: &mut ArrayBase<S3, Ix2>, work: &mut ArrayBase<S2, Ix2>)
-        where S1: Data<Elem=f64>,
-              S2: DataMut<Elem=f64>,
-              S3: DataMut<Elem=f64>;
+    fn calculate_pade_sums<T, S1, S2, S3>(a: &ArrayBase<S1, Ix2>, a_powers: &[&ArrayBase<S1, Ix2>], u: &mut ArrayBase<S2, Ix2>, v: &mut ArrayBase<S3, Ix2>, work: &mut ArrayBase<S2, Ix2>)
+        where T: Float,
+              S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+              S3: DataMut<Elem=T>;
 }
 
 macro_rules! impl_padeorder {
@@ -139,22 +419,22 @@ impl PadeOrder for $ty {
         &$const_coeff
     }
 
-    fn calculate_pade_sums<S1, S2, S3>(
+    fn calculate_pade_sums<T, S1, S2, S3>(
         a: &ArrayBase<S1, Ix2>,
         a_powers: &[&ArrayBase<S1, Ix2>],
         u: &mut ArrayBase<S2, Ix2>,
         v: &mut ArrayBase<S3, Ix2>,
         work: &mut ArrayBase<S2, Ix2>,
     )
-        where S1: Data<Elem=f64>,
-              S2: DataMut<Elem=f64>,
-              S3: DataMut<Elem=f64>,
+        where T: Float,
+              S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+              S3: DataMut<Elem=T>,
     {
-        assert_eq!(a_powers.len(), ($m - 1)/2 + 1);
+        assert_eq!(a_powers.len(), (Self::ORDER as usize - 1)/2 + 1);
 
         let (n_rows, n_cols) = a.dim();
         assert_eq!(n_rows, n_cols, "Pade sum only defined for square matrices.");
-        let n = n_rows as i32;
 
         // Iterator to get 2 coefficients, c_{2i} and c_{2i+1}, and 1 matrix power at a time.
         let mut iterator = Self::coefficients().chunks_exact(2).zip(a_powers.iter());
@@ -167,7 +447,7 @@ impl PadeOrder for $ty {
         // TODO: An optimization is probably to just set u and v to zero and only assign the
         // coefficients to its diagonal, given that A_0 = A^0 = 1.
         let (c_0, c_1, a_pow) = match iterator.next().unwrap() {
-            (&[c_0, c_1], a_pow) => (c_0, c_1, a_pow),
+            (&[c_0, c_1], a_pow) => (T::from_f64(c_0).unwrap(), T::from_f64(c_1).unwrap(), a_pow),
             _ => unreachable!()
         };
 
@@ -175,9 +455,9 @@ impl PadeOrder for $ty {
         v.zip_mut_with(a_pow, |x, &y| *x = c_0 * y);
 
         // Rest of the iterator
-        while let Some(item) = iterator.next() {
+        for item in iterator {
             let (c_2k, c_2k1, a_pow) = match item {
-                (&[c_2k, c_2k1], a_pow) => (c_2k, c_2k1, a_pow),
+                (&[c_2k, c_2k1], a_pow) => (T::from_f64(c_2k).unwrap(), T::from_f64(c_2k1).unwrap(), a_pow),
                 _ => unreachable!()
             };
 
@@ -185,29 +465,7 @@ impl PadeOrder for $ty {
             v.zip_mut_with(a_pow, |x, &y| *x = *x + c_2k * y);
         }
 
-        let (a_slice, a_layout) = as_slice_with_layout(a).expect("Matrix `a` not contiguous.");
-        let (work_slice, _) = as_slice_with_layout(work).expect("Matrix `work` not contiguous.");
-        let (u_slice, u_layout) = as_slice_with_layout_mut(u).expect("Matrix `u` not contiguous.");
-        assert_eq!(a_layout, u_layout, "Memory layout mismatch between matrices; currently only row major matrices are supported.");
-        let layout = a_layout;
-        unsafe {
-            cblas::dgemm(
-                layout,
-                cblas::Transpose::None,
-                cblas::Transpose::None,
-                n,
-                n,
-                n,
-                1.0,
-                a_slice,
-                n,
-                work_slice,
-                n,
-                0.0,
-                u_slice,
-                n,
-            )
-        }
+        gemm(T::one(), a, work, T::zero(), u);
     }
 }
 
@@ -229,59 +487,51 @@ impl PadeOrder for PadeOrder_13 {
         &PADE_COEFF_13
     }
 
-    fn calculate_pade_sums<S1, S2, S3>(
+    fn calculate_pade_sums<T, S1, S2, S3>(
         a: &ArrayBase<S1, Ix2>,
         a_powers: &[&ArrayBase<S1, Ix2>],
         u: &mut ArrayBase<S2, Ix2>,
         v: &mut ArrayBase<S3, Ix2>,
         work: &mut ArrayBase<S2, Ix2>,
     )
-        where S1: Data<Elem=f64>,
-              S2: DataMut<Elem=f64>,
-              S3: DataMut<Elem=f64>,
+        where T: Float,
+              S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+              S3: DataMut<Elem=T>,
     {
-        assert_eq!(a_powers.len(), (13 - 1)/2 + 1);
+        // Only I, A², A⁴, and A⁶ are required, because the higher powers are factored out.
+        assert_eq!(a_powers.len(), 4);
 
         let (n_rows, n_cols) = a.dim();
         assert_eq!(n_rows, n_cols, "Pade sum only defined for square matrices.");
-        let n = n_rows;
 
-        let coefficients = Self::coefficients();
+        let mut coefficients = [T::zero(); 14];
+        for (c, &c_f64) in coefficients.iter_mut().zip(Self::coefficients()) {
+            *c = T::from_f64(c_f64).unwrap();
+        }
+
+        Zip::from(&mut *work)
+            .and(a_powers[1])
+            .and(a_powers[2])
+            .and(a_powers[3])
+            .apply(|x, &a2, &a4, &a6| {
+                *x = coefficients[9] * a2 + coefficients[11] * a4 + coefficients[13] * a6;
+        });
+
+        // Use `u` as temporary storage for A⁶(b_13 A⁶ + b_11 A⁴ + b_9 A²).
+        gemm(T::one(), a_powers[3], work, T::zero(), u);
 
         Zip::from(&mut *work)
+            .and(&*u)
             .and(a_powers[0])
             .and(a_powers[1])
             .and(a_powers[2])
             .and(a_powers[3])
-            .apply(|x, &a0, &a2, &a4, &a6| {
-                *x = *x + coefficients[1] * a0 + coefficients[3] * a2 + coefficients[5] * a4 + coefficients[7] * a6;
+            .apply(|x, &y, &a0, &a2, &a4, &a6| {
+                *x = y + coefficients[1] * a0 + coefficients[3] * a2 + coefficients[5] * a4 + coefficients[7] * a6;
         });
 
-        {
-            let (a_slice, a_layout) = as_slice_with_layout(a).expect("Matrix `a` not contiguous.");
-            let (work_slice, _) = as_slice_with_layout(work).expect("Matrix `work` not contiguous.");
-            let (u_slice, u_layout) = as_slice_with_layout_mut(u).expect("Matrix `u` not contiguous.");
-            assert_eq!(a_layout, u_layout, "Memory layout mismatch between matrices; currently only row major matrices are supported.");
-            let layout = a_layout;
-            unsafe {
-                cblas::dgemm(
-                    layout,
-                    cblas::Transpose::None,
-                    cblas::Transpose::None,
-                    n as i32,
-                    n as i32,
-                    n as i32,
-                    1.0,
-                    a_slice,
-                    n as i32,
-                    work_slice,
-                    n as i32,
-                    0.0,
-                    u_slice,
-                    n as i32,
-                )
-            }
-        }
+        gemm(T::one(), a, work, T::zero(), u);
 
         Zip::from(&mut *work)
             .and(a_powers[1])
@@ -291,31 +541,7 @@ impl PadeOrder for PadeOrder_13 {
                 *x = coefficients[8] * a2 + coefficients[10] * a4 + coefficients[12] * a6;
         });
 
-        {
-            let (a6_slice, a6_layout) = as_slice_with_layout(a_powers[3]).expect("Matrix `a6` not contiguous.");
-            let (work_slice, _) = as_slice_with_layout(work).expect("Matrix `work` not contiguous.");
-            let (v_slice, v_layout) = as_slice_with_layout_mut(v).expect("Matrix `v` not contiguous.");
-            assert_eq!(a6_layout, v_layout, "Memory layout mismatch between matrices; currently only row major matrices are supported.");
-            let layout = a6_layout;
-            unsafe {
-                cblas::dgemm(
-                    layout,
-                    cblas::Transpose::None,
-                    cblas::Transpose::None,
-                    n as i32,
-                    n as i32,
-                    n as i32,
-                    1.0,
-                    a6_slice,
-                    n as i32,
-                    work_slice,
-                    n as i32,
-                    0.0,
-                    v_slice,
-                    n as i32,
-                )
-            }
-        }
+        gemm(T::one(), a_powers[3], work, T::zero(), v);
 
         Zip::from(v)
             .and(a_powers[0])
@@ -329,42 +555,53 @@ impl PadeOrder for PadeOrder_13 {
 }
 
 /// Storage for calculating the matrix exponential.
-pub struct Expm {
+pub struct Expm<T = f64> {
     n: usize,
     itmax: usize,
-    eye: Array2<f64>,
-    a1: Array2<f64>,
-    a2: Array2<f64>,
-    a4: Array2<f64>,
-    a6: Array2<f64>,
-    a8: Array2<f64>,
-    a_abs: Array2<f64>,
-    u: Array2<f64>,
-    work: Array2<f64>,
+    eye: Array2<T>,
+    a1: Array2<T>,
+    a2: Array2<T>,
+    a4: Array2<T>,
+    a6: Array2<T>,
+    a8: Array2<T>,
+    a_abs: Array2<T>,
+    u: Array2<T>,
+    work: Array2<T>,
     pivot: Array1<i32>,
-    normest1: Normest1,
-    layout: cblas::Layout,
+    normest1: Normest1<T>,
+    counters: Counters,
+    matvecs_before: usize,
+    is_triangular: bool,
+    blocks: Vec<(usize, usize)>,
+    balanced: Array2<T>,
+    balancing: Array1<T>,
+    eigenvalues: Array1<T>,
+    /// The Taylor method of the cross-check, only allocated if requested in the options.
+    reference: Option<crosscheck::TaylorReference<T>>,
+    options: ExpmOptions<T>,
+    report: ExpmReport<T>,
+    /// The thresholds of the orders in `Float::THETAS` for the tolerance they were relaxed for.
+    thetas: Option<(T, [T; 5])>,
 }
 
-impl Expm {
+impl<T: Float> Expm<T> {
     /// Allocates all space to calculate the matrix exponential for a square matrix of dimension
     /// n×n.
     pub fn new(n: usize) -> Self {
-        let eye = Array2::<f64>::eye(n);
-        let a1 = Array2::<f64>::zeros((n, n));
-        let a2 = Array2::<f64>::zeros((n, n));
-        let a4 = Array2::<f64>::zeros((n, n));
-        let a6 = Array2::<f64>::zeros((n, n));
-        let a8 = Array2::<f64>::zeros((n, n));
-        let a_abs = Array2::<f64>::zeros((n, n));
-        let u = Array2::<f64>::zeros((n, n));
-        let work = Array2::<f64>::zeros((n, n));
+        let eye = Array2::<T>::eye(n);
+        let a1 = Array2::<T>::zeros((n, n));
+        let a2 = Array2::<T>::zeros((n, n));
+        let a4 = Array2::<T>::zeros((n, n));
+        let a6 = Array2::<T>::zeros((n, n));
+        let a8 = Array2::<T>::zeros((n, n));
+        let a_abs = Array2::<T>::zeros((n, n));
+        let u = Array2::<T>::zeros((n, n));
+        let work = Array2::<T>::zeros((n, n));
         let pivot = Array1::<i32>::zeros(n);
-        let layout = cblas::Layout::RowMajor;
 
         // TODO: Investigate what an optimal value for t is when estimating the 1-norm.
         // Python's SciPY uses t=2. Why?
-        let t = 2;
+        let t = 2.min(n);
         let itmax = 5;
 
         let normest1 = Normest1::new(n, t);
@@ -383,7 +620,17 @@ impl Expm {
             work,
             pivot,
             normest1,
-            layout,
+            counters: Counters::default(),
+            matvecs_before: 0,
+            is_triangular: false,
+            blocks: Vec::new(),
+            balanced: Array2::zeros((n, n)),
+            balancing: Array1::ones(n),
+            eigenvalues: Array1::zeros(n),
+            reference: None,
+            options: ExpmOptions::default(),
+            report: ExpmReport::new(),
+            thetas: None,
         }
     }
 
@@ -393,237 +640,309 @@ impl Expm {
     /// not in row-major order, or don't have the same dimension as the `Expm` object `expm` is
     /// called on.
     pub fn expm<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
-        where S1: Data<Elem=f64>,
-              S2: DataMut<Elem=f64>,
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
     {
         assert_eq!(a.dim(), b.dim(), "Input matrices `a` and `b` have to have matching dimensions.");
         let (n_rows, n_cols) = a.dim();
         assert_eq!(n_rows, n_cols, "expm is only implemented for square matrices.");
         assert_eq!(n_rows, self.n, "Dimension mismatch between matrix `a` and preconfigured `Expm` struct.");
 
+        self.counters = Counters::default();
+        self.matvecs_before = self.normest1.matvecs();
+        self.report = ExpmReport::new();
+
+        if self.options.symmetric && self.evaluate_symmetric(a, b) {
+            return;
+        }
+
+        if self.options.balance {
+            let mut balanced = std::mem::replace(&mut self.balanced, Array2::zeros((0, 0)));
+            self.report.balanced = balance::balance(a, &mut balanced, &mut self.balancing);
+            if self.report.balanced {
+                self.evaluate_recovering(&balanced, b);
+                balance::unbalance(&self.balancing, b);
+            } else {
+                self.evaluate_recovering(a, b);
+            }
+            self.balanced = balanced;
+        } else {
+            self.evaluate_recovering(a, b);
+        }
+    }
+
+    /// Calculates the exponential of the symmetric matrix `a` from its eigendecomposition,
+    /// storing the result in `b`. Returns whether the eigendecomposition converged; otherwise
+    /// `b` is left untouched.
+    fn evaluate_symmetric<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>) -> bool
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        // NOTE: a2 and work are only used as scratch space here.
+        self.a2.assign(a);
+        if syev(&mut self.a2, &mut self.eigenvalues) != 0 {
+            return false;
+        }
+        unitary::apply_spectral(&self.a2, &self.eigenvalues, |x| x.exp(), T::one(), &mut self.work, b);
+        sym::symmetrize(b, &mut self.work);
+        self.counters.matrix_products += 1;
+        self.report.symmetric = true;
+        self.report.non_finite = !recovery::all_finite(b);
+        true
+    }
+
+    /// Calculates the exponential of `a` by scaling and squaring, storing the result in `b`.
+    /// Returns whether the result is finite; the calculation ends early at a singular denominator
+    /// of the Padé approximant or at the first non-finite intermediate result.
+    fn evaluate<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>) -> bool
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
         // Rename b to v to be in line with the nomenclature of the original paper.
         let v = b;
 
         self.a1.assign(a);
 
-        let n = self.n as i32;
-
-        {
-            let (a_slice, a_layout) = as_slice_with_layout(&self.a1).expect("Matrix `a` not contiguous.");
-            let (a2_slice, _) = as_slice_with_layout_mut(&mut self.a2).expect("Matrix `a2` not contiguous.");
-            assert_eq!(a_layout, self.layout, "Memory layout mismatch between matrices; currently only row major matrices are supported.");
-            unsafe {
-                cblas::dgemm(
-                    self.layout,
-                    cblas::Transpose::None,
-                    cblas::Transpose::None,
-                    n,
-                    n,
-                    n,
-                    1.0,
-                    a_slice,
-                    n,
-                    a_slice,
-                    n,
-                    0.0,
-                    a2_slice,
-                    n as i32,
-                )
-            }
-        }
+        let (pade_order, eta, is_scaled) = match self.options.pade_order {
+            Some(m) => self.prepare_order(m),
+            None => self.select_order(),
+        };
+
+        let s = if is_scaled {
+            self.scale(a, eta, pade_order.order())
+        } else {
+            0
+        };
+
+        self.report.pade_order = pade_order.order();
+        self.report.squarings = s;
+        self.report.norm_parameter = eta;
+
+        self.solve_via_pade(pade_order, v) && recovery::all_finite(v) && self.square_checked(s, v)
+    }
 
-        let d4_estimated = self.normest1.normest1_pow(&self.a2, 2, self.itmax).powf(1.0/4.0);
-        let d6_estimated = self.normest1.normest1_pow(&self.a2, 3, self.itmax).powf(1.0/6.0);
+    /// Selects the Padé order following the paper, calculating the required powers of `a1`.
+    /// Returns the order, the norm parameter $\eta$ determining the scaling, and whether scaling
+    /// is required at all.
+    fn select_order(&mut self) -> (PadeOrders, T, bool) {
+        let root = |p: f64| T::one() / T::from_f64(p).unwrap();
+
+        gemm(T::one(), &self.a1, &self.a1, T::zero(), &mut self.a2);
+        self.counters.matrix_products += 1;
+
+        let d4_estimated = self.normest1.normest1_pow(&self.a2, 2, self.itmax).powf(root(4.0));
+        let d6_estimated = self.normest1.normest1_pow(&self.a2, 3, self.itmax).powf(root(6.0));
+        self.report.norm_overflow |= !d4_estimated.is_finite() || !d6_estimated.is_finite();
         let eta_1 = d4_estimated.max(d6_estimated);
 
-        if eta_1 <= THETA_3 && self.ell(3) == 0 {
-            println!("eta_1 condition");
-            self.solve_via_pade(PadeOrders::_3, v);
-            return;
+        if eta_1 <= self.theta(3) && self.ell(3) == 0 {
+            return (PadeOrders::_3, eta_1, false);
         }
 
-        {
-            let (a2_slice, _) = as_slice_with_layout(&self.a2).expect("Matrix `a2` not contiguous.");
-            let (a4_slice, _) = as_slice_with_layout_mut(&mut self.a4).expect("Matrix `a4` not contiguous.");
-            unsafe {
-                cblas::dgemm(
-                    self.layout,
-                    cblas::Transpose::None,
-                    cblas::Transpose::None,
-                    self.n as i32,
-                    self.n as i32,
-                    self.n as i32,
-                    1.0,
-                    a2_slice,
-                    n as i32,
-                    a2_slice,
-                    n as i32,
-                    0.0,
-                    a4_slice,
-                    n as i32,
-                )
-            }
-        }
+        gemm(T::one(), &self.a2, &self.a2, T::zero(), &mut self.a4);
+        self.counters.matrix_products += 1;
 
-        let d4_precise = self.normest1.normest1(&self.a4, self.itmax).powf(1.0/4.0);
+        let d4_precise = self.normest1.normest1(&self.a4, self.itmax).powf(root(4.0));
+        self.report.norm_overflow |= !d4_precise.is_finite();
         let eta_2 = d4_precise.max(d6_estimated);
 
-        if eta_2 <= THETA_5 && self.ell(5) == 0 {
-            println!("eta_2 condition");
-            self.solve_via_pade(PadeOrders::_5, v);
-            return;
+        if eta_2 <= self.theta(5) && self.ell(5) == 0 {
+            return (PadeOrders::_5, eta_2, false);
         }
 
-        {
-            let (a2_slice, _) = as_slice_with_layout(&self.a2).expect("Matrix `a2` not contiguous.");
-            let (a4_slice, _) = as_slice_with_layout(&self.a4).expect("Matrix `a4` not contiguous.");
-            let (a6_slice, _) = as_slice_with_layout_mut(&mut self.a6).expect("Matrix `a6` not contiguous.");
-            unsafe {
-                cblas::dgemm(
-                    self.layout,
-                    cblas::Transpose::None,
-                    cblas::Transpose::None,
-                    self.n as i32,
-                    self.n as i32,
-                    self.n as i32,
-                    1.0,
-                    a2_slice,
-                    n as i32,
-                    a4_slice,
-                    n as i32,
-                    0.0,
-                    a6_slice,
-                    n as i32,
-                )
-            }
-        }
+        gemm(T::one(), &self.a2, &self.a4, T::zero(), &mut self.a6);
+        self.counters.matrix_products += 1;
 
-        let d6_precise = self.normest1.normest1(&self.a6, self.itmax).powf(1.0/6.0);
-        let d8_estimated = self.normest1.normest1_pow(&self.a4, 2, self.itmax);
+        let d6_precise = self.normest1.normest1(&self.a6, self.itmax).powf(root(6.0));
+        let d8_estimated = self.normest1.normest1_pow(&self.a4, 2, self.itmax).powf(root(8.0));
+        self.report.norm_overflow |= !d6_precise.is_finite() || !d8_estimated.is_finite();
         let eta_3 = d6_precise.max(d8_estimated);
 
-        if eta_3 <= THETA_7 && self.ell(7) == 0 {
-            println!("eta_3 (first) condition");
-            self.solve_via_pade(PadeOrders::_7, v);
-            return;
+        if eta_3 <= self.theta(7) && self.ell(7) == 0 {
+            return (PadeOrders::_7, eta_3, false);
         }
 
-        {
-            let (a4_slice, _) = as_slice_with_layout(&self.a4).expect("Matrix `a4` not contiguous.");
-            let (a8_slice, _) = as_slice_with_layout_mut(&mut self.a8).expect("Matrix `a8` not contiguous.");
-            unsafe {
-                cblas::dgemm(
-                    self.layout,
-                    cblas::Transpose::None,
-                    cblas::Transpose::None,
-                    self.n as i32,
-                    self.n as i32,
-                    self.n as i32,
-                    1.0,
-                    a4_slice,
-                    n as i32,
-                    a4_slice,
-                    n as i32,
-                    0.0,
-                    a8_slice,
-                    n as i32,
-                )
-            }
+        // In lower precisions the approximant of order 7 is the highest one, which is then
+        // combined with scaling and squaring.
+        if T::MAX_PADE_ORDER == 7 {
+            return (PadeOrders::_7, eta_3, true);
         }
 
-        if eta_3 <= THETA_9 && self.ell(9) == 0 {
-            println!("eta_3 (second) condition");
-            self.solve_via_pade(PadeOrders::_9, v);
-            return;
+        gemm(T::one(), &self.a4, &self.a4, T::zero(), &mut self.a8);
+        self.counters.matrix_products += 1;
+
+        if eta_3 <= self.theta(9) && self.ell(9) == 0 {
+            return (PadeOrders::_9, eta_3, false);
         }
 
-        let eta_4 = d8_estimated.max(self.normest1.normest1_prod(&[&self.a4, &self.a6], self.itmax).powf(1.0/10.0));
+        let d10_estimated = self.normest1.normest1_prod(&[&self.a4, &self.a6], self.itmax).powf(root(10.0));
+        self.report.norm_overflow |= !d10_estimated.is_finite();
+        let eta_4 = d8_estimated.max(d10_estimated);
         let eta_5 = eta_3.min(eta_4);
 
-        use std::f64;
-        use std::cmp;
-        let mut s = cmp::max(f64::ceil(f64::log2(eta_5/THETA_13)) as i32, 0);
-        self.a1.mapv_inplace(|x| x / 2f64.powi(s));
-        s = s + self.ell(13);
-        self.a1.zip_mut_with(a, |x, &y| *x = y / 2f64.powi(s));
-        self.a2.mapv_inplace(|x| x / 2f64.powi(2*s));
-        self.a4.mapv_inplace(|x| x / 2f64.powi(4*s));
-        self.a6.mapv_inplace(|x| x / 2f64.powi(6*s));
+        (PadeOrders::_13, eta_5, true)
+    }
+
+    /// Calculates the powers of `a1` required by the forced Padé order `m`. Returns the order,
+    /// the estimated 1-norm of `a1` as the norm parameter, and that scaling is required.
+    ///
+    /// NOTE: Panics if `m` is not one of 3, 5, 7, 9, 13, or not supported for the precision `T`.
+    fn prepare_order(&mut self, m: u64) -> (PadeOrders, T, bool) {
+        let pade_order = PadeOrders::from_order::<T>(m);
+        self.calculate_powers(m);
 
-        self.solve_via_pade(PadeOrders::_13, v);
+        let eta = self.normest1.normest1(&self.a1, self.itmax);
+        self.report.norm_overflow |= !eta.is_finite();
 
-        // TODO: Call code fragment 2.1 in the paper if `a` is triangular, instead of the code below.
-        //
-        // NOTE: it's guaranteed that s >= 0 by its definition.
-        let (u_slice, _) = as_slice_with_layout_mut(&mut self.u).expect("Matrix `u` not contiguous.");
-
-        // NOTE: v initially contains r after `solve_via_pade`.
-        let (v_slice, _) = as_slice_with_layout_mut(v).expect("Matrix `v` not contiguous.");
-        for _ in 0..s {
-            unsafe {
-                cblas::dgemm(
-                    self.layout,
-                    cblas::Transpose::None,
-                    cblas::Transpose::None,
-                    self.n as i32,
-                    self.n as i32,
-                    self.n as i32,
-                    1.0,
-                    v_slice,
-                    n as i32,
-                    v_slice,
-                    n as i32,
-                    0.0,
-                    u_slice,
-                    n as i32,
-                )
+        (pade_order, eta, true)
+    }
+
+    /// Calculates the powers of `a1` required by the Padé approximant of order `m`, i.e. `a2`,
+    /// and depending on the order `a4`, `a6`, and `a8`.
+    fn calculate_powers(&mut self, m: u64) {
+        gemm(T::one(), &self.a1, &self.a1, T::zero(), &mut self.a2);
+        self.counters.matrix_products += 1;
+        if m >= 5 {
+            gemm(T::one(), &self.a2, &self.a2, T::zero(), &mut self.a4);
+            self.counters.matrix_products += 1;
+        }
+        if m >= 7 {
+            gemm(T::one(), &self.a2, &self.a4, T::zero(), &mut self.a6);
+            self.counters.matrix_products += 1;
+        }
+        if m == 9 {
+            gemm(T::one(), &self.a4, &self.a4, T::zero(), &mut self.a8);
+            self.counters.matrix_products += 1;
+        }
+    }
+
+    /// Seed the generator of the random probing vectors of the 1-norm estimator with `seed`,
+    /// making the selection of the Padé order and scaling reproducible.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.normest1.set_seed(seed);
+    }
+
+    /// Reseed the 1-norm estimator from the user-supplied generator `rng`, e.g. to derive the
+    /// probing vectors of many `Expm` objects from a single audited source of randomness.
+    pub fn set_rng<R: RngCore>(&mut self, rng: &mut R) {
+        self.normest1.set_rng(rng);
+    }
+
+    /// The operation counts of the last call to `expm`.
+    pub fn counters(&self) -> Counters {
+        Counters {
+            matvecs: self.normest1.matvecs() - self.matvecs_before,
+            ..self.counters
+        }
+    }
+
+    /// The threshold $\theta_m$ for the tolerance in the options, see `theta_for_tolerance`.
+    /// Relaxed thresholds are only calculated once per tolerance.
+    fn theta(&mut self, m: u64) -> T {
+        let tolerance = self.options.tolerance;
+        if tolerance <= T::unit_roundoff() || m > T::MAX_PADE_ORDER {
+            return theta_for_tolerance(m, tolerance);
+        }
+
+        let thetas = match self.thetas {
+            Some((cached, thetas)) if cached == tolerance => thetas,
+            _ => {
+                let mut thetas = [T::zero(); 5];
+                for (theta, &(order, _)) in thetas.iter_mut().zip(T::THETAS) {
+                    *theta = theta_for_tolerance(order, tolerance);
+                }
+                self.thetas = Some((tolerance, thetas));
+                thetas
             }
+        };
+        let index = T::THETAS.iter().position(|&(order, _)| order == m).expect("Unsupported Padé order.");
+        thetas[index]
+    }
+
+    /// Chooses the scaling parameter $s$ for the approximant of order `m` and scales `a1`, `a2`,
+    /// `a4`, `a6`, and for order 9 also `a8`, by the respective powers of $2^{-s}$. The scaling
+    /// parameter is limited by the maximum number of squarings in the options. Returns $s$.
+    fn scale<S>(&mut self, a: &ArrayBase<S, Ix2>, eta: T, m: u64) -> i32
+        where S: Data<Elem=T>,
+    {
+        use std::cmp;
 
-            u_slice.swap_with_slice(v_slice);
+        let two = T::from_f64(2.0).unwrap();
+        let theta = self.theta(m);
+
+        let mut s = cmp::max((eta/theta).log2().ceil().to_i32().unwrap_or(0), 0);
+        if let Some(max_squarings) = self.options.max_squarings {
+            s = s.min(max_squarings as i32);
+        }
+        self.a1.mapv_inplace(|x| x / two.powi(s));
+        s += self.ell(m as usize);
+        if let Some(max_squarings) = self.options.max_squarings {
+            s = s.min(max_squarings as i32);
+        }
+        self.a1.zip_mut_with(a, |x, &y| *x = y / two.powi(s));
+        self.a2.mapv_inplace(|x| x / two.powi(2*s));
+        self.a4.mapv_inplace(|x| x / two.powi(4*s));
+        self.a6.mapv_inplace(|x| x / two.powi(6*s));
+        if m == 9 {
+            self.a8.mapv_inplace(|x| x / two.powi(8*s));
+        }
+
+        s
+    }
+
+    /// Undoes the scaling by squaring `v` $s$ times, stopping early if a squaring is not finite.
+    /// For quasi-triangular matrices, the diagonal blocks are recomputed exactly before every
+    /// squaring following code fragment 2.1 in the paper.
+    ///
+    /// NOTE: it's guaranteed that s >= 0 by its definition.
+    fn square<S>(&mut self, s: i32, v: &mut ArrayBase<S, Ix2>)
+        where S: DataMut<Elem=T>,
+    {
+        let two = T::from_f64(2.0).unwrap();
+
+        // NOTE: v initially contains r after `solve_via_pade`, and `a1` contains $2^{-s} A$.
+        for k in 0..s {
+            if self.is_triangular {
+                triangular::recompute_diagonal(&self.a1, two.powi(k), &self.blocks, v);
+            }
+            gemm(T::one(), v, v, T::zero(), &mut self.u);
+            v.assign(&self.u);
+            self.counters.matrix_products += 1;
+            if !recovery::all_finite(v) {
+                break;
+            }
         }
     }
 
     /// A helper function (as it is called in the original paper) returning the
     /// $\max(\lceil \log_2(\alpha/u) / 2m \rceil, 0)$, where
-    /// $\alpha = \lvert c_{2m+1}\rvert \texttt{normest}(\lvert A\rvert^{2m+1})/\lVertA\rVert_1$.
+    /// $\alpha = \lvert c_{2m+1}\rvert \texttt{normest}(\lvert A\rvert^{2m+1})/\lVertA\rVert_1$,
+    /// and $u$ is the tolerance of the options.
     fn ell(&mut self, m: usize) -> i32 {
         Zip::from(&mut self.a_abs)
             .and(&self.a1)
             .apply(|x, &y| *x = y.abs());
 
-        let c2m1 = pade_error_coefficient(m as u64);
+        let c2m1 = T::from_f64(pade_error_coefficient(m as u64)).unwrap();
 
         let norm_abs_a_2m1 = self.normest1.normest1_pow(&self.a_abs, 2*m + 1, self.itmax);
         let norm_a = self.normest1.normest1(&self.a1, self.itmax);
         let alpha = c2m1.abs() * norm_abs_a_2m1 / norm_a;
 
-        // The unit roundoff, defined as half the machine epsilon.
-        let u = std::f64::EPSILON / 2.0;
+        let u = self.options.tolerance;
+        let two_m = T::from_usize(2 * m).unwrap();
 
-        use std::f64;
         use std::cmp;
 
-        cmp::max(0, f64::ceil( f64::log2(alpha/u) / (2 * m) as f64 ) as i32)
+        cmp::max(0, ((alpha/u).log2() / two_m).ceil().to_i32().unwrap_or(0))
     }
 
-    fn solve_via_pade<S>(&mut self, pade_order: PadeOrders, v: &mut ArrayBase<S, Ix2>)
-        where S: DataMut<Elem=f64>,
+    /// Evaluates the Padé approximant of order `pade_order` at `a1`, storing it in `v`. Returns
+    /// whether the denominator $q_m$ is nonsingular; otherwise `v` is filled with NaN.
+    fn solve_via_pade<S>(&mut self, pade_order: PadeOrders, v: &mut ArrayBase<S, Ix2>) -> bool
+        where S: DataMut<Elem=T>,
     {
-        use PadeOrders::*;
-
-        macro_rules! pade {
-            ($order:ty, [$(&$apow:expr),+]) => {
-                <$order as PadeOrder>::calculate_pade_sums(&self.a1, &[$(&$apow),+], &mut self.u, v, &mut self.work);
-            }
-        }
-
-        match pade_order {
-            _3  => pade!(PadeOrder_3, [&self.eye, &self.a2]),
-            _5  => pade!(PadeOrder_5, [&self.eye, &self.a2, &self.a4]),
-            _7  => pade!(PadeOrder_7, [&self.eye, &self.a2, &self.a4, &self.a6]),
-            _9  => pade!(PadeOrder_9, [&self.eye, &self.a2, &self.a4, &self.a6, &self.a8]),
-            _13 => pade!(PadeOrder_13, [&self.eye, &self.a2, &self.a4, &self.a6]),
-        };
+        self.pade_sums(pade_order, v);
 
         // Here we set v = p <- u + v and u = q <- -u + v, overwriting u and v via work.
         self.work.assign(v);
@@ -640,31 +959,37 @@ impl Expm {
                 *x = -*x + y;
         });
 
-        let (u_slice, _) = as_slice_with_layout_mut(&mut self.u).expect("Matrix `u` not contiguous.");
-        let (v_slice, _) = as_slice_with_layout_mut(v).expect("Matrix `v` not contiguous.");
-        let (pivot_slice, _) = as_slice_with_layout_mut(&mut self.pivot).expect("Vector `pivot` not contiguous.");
-
-        let n = self.n as i32;
+        let info = gesv(&mut self.u, &mut self.pivot, v);
+        if info != 0 {
+            v.fill(T::nan());
+        }
+        info == 0
+    }
 
-        let layout = {
-            match self.layout {
-                cblas::Layout::ColumnMajor => lapacke::Layout::ColumnMajor,
-                cblas::Layout::RowMajor => lapacke::Layout::RowMajor,
+    /// Calculates the odd part $U$ of the numerator of the Padé approximant in `u` and the even
+    /// part $V$ in `v`, so that $p_m = V + U$ and $q_m = V - U$.
+    fn pade_sums<S>(&mut self, pade_order: PadeOrders, v: &mut ArrayBase<S, Ix2>)
+        where S: DataMut<Elem=T>,
+    {
+        macro_rules! pade {
+            ($order:ty, [$(&$apow:expr),+]) => {
+                <$order as PadeOrder>::calculate_pade_sums(&self.a1, &[$(&$apow),+], &mut self.u, v, &mut self.work)
             }
+        }
+
+        // The approximant of order 13 requires three products, all others one.
+        self.counters.matrix_products += match pade_order {
+            PadeOrders::_13 => 3,
+            _ => 1,
         };
+        self.counters.linear_solves += 1;
 
-        // FIXME: Handle the info for error management.
-        let _ = unsafe {
-            lapacke::dgesv(
-                layout,
-                n,
-                n,
-                u_slice,
-                n,
-                pivot_slice,
-                v_slice,
-                n,
-            )
+        match pade_order {
+            PadeOrders::_3  => pade!(PadeOrder_3, [&self.eye, &self.a2]),
+            PadeOrders::_5  => pade!(PadeOrder_5, [&self.eye, &self.a2, &self.a4]),
+            PadeOrders::_7  => pade!(PadeOrder_7, [&self.eye, &self.a2, &self.a4, &self.a6]),
+            PadeOrders::_9  => pade!(PadeOrder_9, [&self.eye, &self.a2, &self.a4, &self.a6, &self.a8]),
+            PadeOrders::_13 => pade!(PadeOrder_13, [&self.eye, &self.a2, &self.a4, &self.a6]),
         };
     }
 }
@@ -674,9 +999,10 @@ impl Expm {
 /// NOTE: Panics if input matrices `a` and `b` don't have matching dimensions, are not square,
 /// not in row-major order, or don't have the same dimension as the `Expm` object `expm` is
 /// called on.
-pub fn expm<S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
-    where S1: Data<Elem=f64>,
-          S2: DataMut<Elem=f64>,
+pub fn expm<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
 {
     let (n, _) = a.dim();
 
@@ -684,18 +1010,39 @@ pub fn expm<S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
     expm.expm(a, b);
 }
 
-/// Returns slice and layout underlying an array `a`.
-fn as_slice_with_layout<S, T, D>(a: &ArrayBase<S, D>) -> Option<(&[T], cblas::Layout)>
-    where S: Data<Elem=T>,
-          D: Dimension
+/// Calculate the matrix exponential of the n×n matrix `a` storing the result in matrix `b`, and
+/// return the counts of the operations performed.
+///
+/// NOTE: Panics if input matrices `a` and `b` don't have matching dimensions, are not square,
+/// or not in row-major order.
+pub fn expm_with_counters<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>) -> Counters
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
 {
-    if let Some(a_slice) = a.as_slice() {
-        Some((a_slice, cblas::Layout::RowMajor))
-    } else if let Some(a_slice) = a.as_slice_memory_order() {
-        Some((a_slice, cblas::Layout::ColumnMajor))
-    } else {
-        None
-    }
+    let (n, _) = a.dim();
+
+    let mut expm = Expm::new(n);
+    expm.expm(a, b);
+    expm.counters()
+}
+
+/// A workspace holding all intermediate buffers required to calculate the exponential of n×n
+/// matrices. Creating it once and passing it to `expm_inplace` avoids any allocations when
+/// calculating many exponentials of matrices of the same dimension.
+pub type ExpmWorkspace<T = f64> = Expm<T>;
+
+/// Calculate the matrix exponential of the n×n matrix `a` storing the result in matrix `out`,
+/// reusing the buffers held by the workspace `work`.
+///
+/// NOTE: Panics if input matrices `a` and `out` don't have matching dimensions, are not square,
+/// not in row-major order, or don't have the same dimension as the workspace `work`.
+pub fn expm_inplace<T, S1, S2>(work: &mut ExpmWorkspace<T>, a: &ArrayBase<S1, Ix2>, out: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    work.expm(a, out);
 }
 
 /// Returns mutable slice and layout underlying an array `a`.
@@ -727,14 +1074,14 @@ fn as_slice_with_layout_mut<S, T, D>(a: &mut ArrayBase<S, D>) -> Option<(&mut [T
 mod tests {
     extern crate openblas_src;
     use ndarray::prelude::*;
-    use approx::assert_ulps_eq;
+    use approx::{assert_relative_eq, assert_ulps_eq};
 
     use crate::PadeOrder;
 
     /// Calculates the i-th coefficient arising in the [m/m] Padé approximant of the exponential
     /// function.
     fn pade_coefficient(i: u64, m: u64) -> f64 {
-        use statrs::function::factorial::factorial;
+        use crate::special::factorial;
 
         assert!(i <= m, "The i-th coefficient for a [m/m] Padé approximant is undefined for i > m.");
 
@@ -791,4 +1138,84 @@ mod tests {
             assert_ulps_eq!(elem, 1f64.exp(), max_ulps=1);
         }
     }
+
+    #[test]
+    fn exp_of_moler_van_loan_example() {
+        // The example from Moler and Van Loan, "Nineteen Dubious Ways to Compute the Exponential
+        // of a Matrix, Twenty-Five Years Later", with eigenvalues -1 and -17. Its norm requires
+        // the Padé approximant of order 13 with scaling. The reference values were calculated in
+        // 40 digit arithmetic.
+        let a = arr2(&[[-49.0, 24.0], [-64.0, 31.0]]);
+        let mut b = Array2::<f64>::zeros((2, 2));
+
+        crate::expm(&a, &mut b);
+
+        let expected = arr2(&[
+            [-0.7357587581447531, 0.5518190996580977],
+            [-1.4715175990882605, 1.1036382407155725],
+        ]);
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-12);
+        }
+    }
+
+    #[test]
+    fn exp_of_rotation_generator() {
+        // The norm is large enough to require the Padé approximant of order 13 with scaling.
+        let w = 10f64;
+        let a = arr2(&[[0.0, w], [-w, 0.0]]);
+        let mut b = Array2::<f64>::zeros((2, 2));
+
+        crate::expm(&a, &mut b);
+
+        let expected = arr2(&[[w.cos(), w.sin()], [-w.sin(), w.cos()]]);
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-13);
+        }
+    }
+
+    #[test]
+    fn exp_of_unit_f32() {
+        let n = 5;
+        let a = Array2::<f32>::eye(n);
+        let mut b = Array2::<f32>::zeros((n, n));
+
+        crate::expm(&a, &mut b);
+
+        for &elem in &b.diag() {
+            assert_ulps_eq!(elem, 1f32.exp(), max_ulps=2);
+        }
+    }
+
+    #[test]
+    fn f32_agrees_with_f64() {
+        let a = arr2(&[[-3.0, 1.5, 0.5], [2.0, -4.0, 1.0], [0.25, 3.0, -1.0]]);
+        let mut b = Array2::<f64>::zeros((3, 3));
+        crate::expm(&(&a * 4.0), &mut b);
+
+        let a_single = a.mapv(|x| x as f32);
+        let mut b_single = Array2::<f32>::zeros((3, 3));
+        crate::expm(&(&a_single * 4.0), &mut b_single);
+
+        for (&x, &y) in b.iter().zip(b_single.iter()) {
+            approx::assert_relative_eq!(x as f32, y, max_relative=1e-4);
+        }
+    }
+
+    #[test]
+    fn workspace_is_reusable() {
+        let mut work = crate::ExpmWorkspace::new(3);
+        let mut out = Array2::<f64>::zeros((3, 3));
+        let mut expected = Array2::<f64>::zeros((3, 3));
+
+        // Different norms select different Padé orders and numbers of squarings.
+        for &scale in &[0.01, 1.0, 20.0, 0.5] {
+            let a = arr2(&[[-1.0, 0.5, 0.0], [0.25, -2.0, 1.0], [0.0, 0.5, -0.5]]) * scale;
+            crate::expm_inplace(&mut work, &a, &mut out);
+            crate::expm(&a, &mut expected);
+            for (&x, &y) in out.iter().zip(expected.iter()) {
+                approx::assert_relative_eq!(x, y, max_relative=1e-13, epsilon=1e-15);
+            }
+        }
+    }
 }
@@ -0,0 +1,230 @@
+//! The principal matrix logarithm via inverse scaling and squaring.
+//!
+//! The matrix is reduced to real Schur form $A = Q T Q^\top$, and square roots of the quasi upper
+//! triangular factor are taken until $T^{1/2^k}$ is close to the identity. The logarithm then
+//! follows from
+//!
+//! \begin{equation}
+//! \log A = 2^k Q \log\left(T^{1/2^k}\right) Q^\top,
+//! \end{equation}
+//!
+//! where $\log(I + X)$ is approximated by the diagonal Padé approximant $r_m(X)$ of order $m = 7$
+//! as in [Higham 2008, Algorithm 11.10]. The approximant is evaluated in its partial fraction form
+//!
+//! \begin{equation}
+//! r_m(X) = \sum_{j=1}^m w_j X (I + x_j X)^{-1},
+//! \end{equation}
+//!
+//! with the nodes $x_j$ and weights $w_j$ of the m-point Gauss–Legendre quadrature on $[0, 1]$.
+//!
+//! The Padé machinery of the exponential is not reused here. Its evaluation splits $p_m$ into
+//! even and odd parts and relies on $q_m(x) = p_m(-x)$, which does not hold for the approximants
+//! to $\log(1 + x)$, and the partial fraction form needs no matrix powers and is better
+//! conditioned. The thresholds of `backward_error` bound the backward error $h_{2m+1}(x) =
+//! \log(e^{-x} r_m(x))$ of the approximants to $e^x$ and do not carry over either; the threshold
+//! $\theta_7$ below instead bounds the forward error $\lvert r_m(-\lVert X \rVert) - \log(1 -
+//! \lVert X \rVert) \rvert$ of Kenney and Laub, as tabulated in [Higham 2008, Table 11.1].
+//!
+//! [Higham 2008]: https://doi.org/10.1137/1.9780898717778
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    float::{
+        gees,
+        gemm,
+        gesv,
+        onenorm,
+    },
+    schur::{
+        diagonal_blocks,
+        sqrt_quasi_triangular,
+    },
+    Float,
+};
+
+/// Nodes of the 7-point Gauss–Legendre quadrature on $[0, 1]$.
+const GAUSS_LEGENDRE_NODES: [f64; 7] = [
+    0.025_446_043_828_620_736,
+    0.129_234_407_200_302_8,
+    0.297_077_424_311_301_4,
+    0.5,
+    0.702_922_575_688_698_6,
+    0.870_765_592_799_697_2,
+    0.974_553_956_171_379_3,
+];
+
+/// Weights of the 7-point Gauss–Legendre quadrature on $[0, 1]$.
+const GAUSS_LEGENDRE_WEIGHTS: [f64; 7] = [
+    0.064_742_483_084_434_85,
+    0.139_852_695_744_638_33,
+    0.190_915_025_252_559_47,
+    0.208_979_591_836_734_7,
+    0.190_915_025_252_559_47,
+    0.139_852_695_744_638_33,
+    0.064_742_483_084_434_85,
+];
+
+/// $\lVert X \rVert_1$ below which $r_7(X)$ approximates $\log(I + X)$ to double precision, see
+/// [Higham 2008, Table 11.1].
+///
+/// [Higham 2008]: https://doi.org/10.1137/1.9780898717778
+const THETA_LOG_7: f64 = 2.64e-1;
+
+/// The maximum number of square roots. Every square root halves the logarithm of the spectrum,
+/// so that this is only reached for matrices with eigenvalues extremely close to zero.
+const MAX_SQUARE_ROOTS: usize = 64;
+
+/// Storage for calculating the principal logarithm of n×n matrices.
+pub struct Logm<T = f64> {
+    n: usize,
+    t: Array2<T>,
+    q: Array2<T>,
+    r: Array2<T>,
+    wr: Array1<T>,
+    wi: Array1<T>,
+    lhs: Array2<T>,
+    rhs: Array2<T>,
+    pivot: Array1<i32>,
+    blocks: Vec<(usize, usize)>,
+}
+
+impl<T: Float> Logm<T> {
+    /// Allocates all space to calculate the logarithm of n×n matrices.
+    pub fn new(n: usize) -> Self {
+        Logm {
+            n,
+            t: Array2::zeros((n, n)),
+            q: Array2::zeros((n, n)),
+            r: Array2::zeros((n, n)),
+            wr: Array1::zeros(n),
+            wi: Array1::zeros(n),
+            lhs: Array2::zeros((n, n)),
+            rhs: Array2::zeros((n, n)),
+            pivot: Array1::zeros(n),
+            blocks: Vec::with_capacity(n),
+        }
+    }
+
+    /// Calculate the principal logarithm of the n×n matrix `a`, storing the result in `b`.
+    ///
+    /// NOTE: Panics if `a` has an eigenvalue on the closed negative real axis, for which the
+    /// principal logarithm is not defined, if `a` and `b` don't have matching dimensions, are
+    /// not square, don't have the same dimension as the `Logm` object `logm` is called on, or if
+    /// the Schur decomposition of `a` does not converge.
+    pub fn logm<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `Logm` struct.");
+        assert_eq!(a.dim(), b.dim(), "Input matrices `a` and `b` have to have matching dimensions.");
+
+        self.t.assign(a);
+        let info = gees(&mut self.t, &mut self.q, &mut self.wr, &mut self.wi);
+        assert_eq!(info, 0, "The Schur decomposition of `a` did not converge.");
+
+        for (&re, &im) in self.wr.iter().zip(self.wi.iter()) {
+            assert!(im != T::zero() || re > T::zero(), "The principal logarithm is not defined for eigenvalues on the closed negative real axis.");
+        }
+        diagonal_blocks(&self.t, &mut self.blocks);
+
+        // Take square roots until T is close enough to the identity.
+        let theta = T::from_f64(THETA_LOG_7).unwrap();
+        let mut k = 0;
+        loop {
+            // T ← T - I to measure the distance, and X = T - I once it is small enough.
+            self.t.diag_mut().mapv_inplace(|x| x - T::one());
+            if onenorm(&self.t) <= theta || k == MAX_SQUARE_ROOTS {
+                break;
+            }
+            self.t.diag_mut().mapv_inplace(|x| x + T::one());
+
+            sqrt_quasi_triangular(&self.t, &mut self.r, &self.blocks);
+            self.t.assign(&self.r);
+            k += 1;
+        }
+
+        // R = Σ_j w_j X (I + x_j X)⁻¹, with each term solving (I + x_j X) Y_j = X.
+        self.r.fill(T::zero());
+        for (&node, &weight) in GAUSS_LEGENDRE_NODES.iter().zip(GAUSS_LEGENDRE_WEIGHTS.iter()) {
+            let node = T::from_f64(node).unwrap();
+            let weight = T::from_f64(weight).unwrap();
+
+            self.lhs.assign(&self.t);
+            self.lhs.mapv_inplace(|x| node * x);
+            self.lhs.diag_mut().mapv_inplace(|x| x + T::one());
+            self.rhs.assign(&self.t);
+            // I + node (T - I) is nonsingular without eigenvalues of T on the negative real axis.
+            let info = gesv(&mut self.lhs, &mut self.pivot, &mut self.rhs);
+            assert_eq!(info, 0, "A shifted Schur factor in the quadrature is singular.");
+
+            self.r.scaled_add(weight, &self.rhs);
+        }
+
+        // log A = 2ᵏ Q R Qᵀ
+        let scale = T::from_f64(2.0).unwrap().powi(k as i32);
+        gemm(scale, &self.q, &self.r, T::zero(), &mut self.lhs);
+        gemm(T::one(), &self.lhs, &self.q.t(), T::zero(), b);
+    }
+}
+
+/// Calculate the principal logarithm of the n×n matrix `a`, storing the result in `b`.
+///
+/// NOTE: Panics if `a` has an eigenvalue on the closed negative real axis, if `a` and `b` don't
+/// have matching dimensions or are not square, or if the Schur decomposition of `a` does not
+/// converge.
+pub fn logm<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut logm = Logm::new(n);
+    logm.logm(a, b);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn logm_inverts_expm() {
+        // The rotation block gives a pair of complex eigenvalues, and thus a 2×2 Schur block.
+        let a = arr2(&[
+            [-0.5, 2.0, 0.3, 0.0],
+            [-2.0, -0.5, 0.0, 0.4],
+            [0.1, 0.0, 1.0, 0.5],
+            [0.0, 0.2, 0.0, -1.5],
+        ]);
+        let mut exp_a = Array2::<f64>::zeros((4, 4));
+        let mut log_exp_a = Array2::<f64>::zeros((4, 4));
+
+        crate::expm(&a, &mut exp_a);
+        crate::logm(&exp_a, &mut log_exp_a);
+
+        for (&x, &y) in log_exp_a.iter().zip(a.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-10, epsilon=1e-12);
+        }
+    }
+
+    #[test]
+    fn logm_of_diagonal_matrix() {
+        let mut a = Array2::<f64>::zeros((4, 4));
+        a.diag_mut().assign(&arr1(&[1e-3, 0.5, 1.0, 40.0]));
+        let mut b = Array2::<f64>::zeros((4, 4));
+
+        crate::logm(&a, &mut b);
+
+        for ((i, j), &x) in b.indexed_iter() {
+            let expected = if i == j { a[(i, i)].ln() } else { 0.0 };
+            assert_relative_eq!(x, expected, max_relative=1e-13, epsilon=1e-14);
+        }
+    }
+}
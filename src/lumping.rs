@@ -0,0 +1,243 @@
+//! Lumping of CTMCs, i.e. the aggregation of the states of a large chain into the blocks of a
+//! partition, and propagation of the coarse-grained dynamics.
+//!
+//! For a partition of the states into blocks $I_1, \dots, I_m$ and positive weights $w$, e.g. a
+//! reference or stationary distribution, the lumped generator has the rates
+//!
+//! \begin{equation}
+//!     \hat{Q}_{IJ} = \frac{\sum_{i \in I} w_i \sum_{j \in J} q_{ij}}{\sum_{i \in I} w_i},
+//! \end{equation}
+//!
+//! where $q_{ij}$ is the rate from state $i$ to state $j$, i.e. $Q_{ij}$ in the `Row` and
+//! $Q_{ji}$ in the `Column` convention. The chain is (ordinarily) lumpable if the rates
+//! $\sum_{j \in J} q_{ij}$ into every block $J$ are the same for all states $i$ of a block $I$,
+//! see J. G. Kemeny and J. L. Snell, *Finite Markov Chains* (1960), Theorem 6.3.2. The lumped
+//! generator then doesn't depend on the weights, and the aggregated distribution $\hat{p}_I =
+//! \sum_{i \in I} p_i$ evolves exactly with $\hat{Q}$. Otherwise, the lumped generator is an
+//! approximation, which preserves the weights if they are stationary, and the report contains
+//! the largest deviation of a rate from its lumped value.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    check_generator,
+    float::onenorm,
+    Ctmc,
+    Float,
+    StochasticConvention,
+};
+
+/// A report on the lumpability of a generator with respect to a partition.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LumpingReport<T> {
+    /// Whether the generator is lumpable up to rounding, so that the lumped dynamics are exact.
+    pub lumpable: bool,
+    /// The largest deviation $\lvert \sum_{j \in J} q_{ij} - \hat{Q}_{IJ} \rvert$ over all
+    /// states $i \in I$ and blocks $J$.
+    pub deviation: T,
+}
+
+/// Storage for lumping generators of CTMCs with n states into the m blocks of a partition, and
+/// for propagating the lumped dynamics.
+pub struct Lumping<T = f64> {
+    n: usize,
+    m: usize,
+    partition: Vec<usize>,
+    convention: StochasticConvention,
+    generator: Array2<T>,
+    block_rates: Array2<T>,
+    aggregated: Array1<T>,
+    ctmc: Ctmc<T>,
+    is_lumped: bool,
+}
+
+impl<T: Float> Lumping<T> {
+    /// Allocates all space to lump CTMCs with n states according to `partition`, where
+    /// `partition[i]` is the block of state $i$ and the m blocks are numbered from 0 to m-1.
+    ///
+    /// NOTE: Panics if a block is empty.
+    pub fn new(partition: &[usize]) -> Self {
+        let n = partition.len();
+        let m = partition.iter().max().map_or(0, |&block| block + 1);
+        for block in 0..m {
+            assert!(partition.contains(&block), "Block {} of the partition is empty.", block);
+        }
+
+        Lumping {
+            n,
+            m,
+            partition: partition.to_vec(),
+            convention: StochasticConvention::Row,
+            generator: Array2::zeros((m, m)),
+            block_rates: Array2::zeros((n, m)),
+            aggregated: Array1::zeros(m),
+            ctmc: Ctmc::new(m),
+            is_lumped: false,
+        }
+    }
+
+    /// Lump the n×n generator `q` with the state `weights`, keeping the m×m lumped generator for
+    /// `generator` and `propagate`, and return a report on the lumpability of `q`.
+    ///
+    /// NOTE: Panics if `q` is not a generator in the given `convention`, if the weights are
+    /// negative or vanish on a block, or if `q` and `weights` don't have the same dimension as
+    /// the partition of the `Lumping` object `lump` is called on.
+    pub fn lump<S1, S2>(&mut self, q: &ArrayBase<S1, Ix2>, convention: StochasticConvention, weights: &ArrayBase<S2, Ix1>) -> LumpingReport<T>
+        where S1: Data<Elem=T>,
+              S2: Data<Elem=T>,
+    {
+        assert_eq!(q.dim(), (self.n, self.n), "Dimension mismatch between generator `q` and preconfigured `Lumping` struct.");
+        assert_eq!(weights.len(), self.n, "Dimension mismatch between `weights` and preconfigured `Lumping` struct.");
+        assert!(weights.iter().all(|&w| w >= T::zero()), "The weights have to be non-negative.");
+        check_generator(q, convention);
+
+        // The rates from each state into each block.
+        let rates = match convention {
+            StochasticConvention::Row => q.view(),
+            StochasticConvention::Column => q.t(),
+        };
+        self.block_rates.fill(T::zero());
+        for ((i, j), &rate) in rates.indexed_iter() {
+            self.block_rates[(i, self.partition[j])] = self.block_rates[(i, self.partition[j])] + rate;
+        }
+
+        // Weighted averages over the states of each block.
+        let mut lumped = Array2::zeros((self.m, self.m));
+        let mut block_weights = Array1::zeros(self.m);
+        for (i, (rates, &w)) in self.block_rates.genrows().into_iter().zip(weights.iter()).enumerate() {
+            let block = self.partition[i];
+            lumped.row_mut(block).scaled_add(w, &rates);
+            block_weights[block] = block_weights[block] + w;
+        }
+        for (mut row, &w) in lumped.genrows_mut().into_iter().zip(block_weights.iter()) {
+            assert!(w > T::zero(), "The weights vanish on a block of the partition.");
+            row.mapv_inplace(|x| x / w);
+        }
+
+        let mut deviation = T::zero();
+        for (i, rates) in self.block_rates.genrows().into_iter().enumerate() {
+            for (&rate, &lumped_rate) in rates.iter().zip(lumped.row(self.partition[i]).iter()) {
+                deviation = deviation.max((rate - lumped_rate).abs());
+            }
+        }
+        let tolerance = T::from_usize(self.n).unwrap() * T::epsilon() * onenorm(q);
+
+        match convention {
+            StochasticConvention::Row => self.generator.assign(&lumped),
+            StochasticConvention::Column => self.generator.assign(&lumped.t()),
+        }
+        self.convention = convention;
+        self.is_lumped = true;
+
+        LumpingReport {
+            lumpable: deviation <= tolerance,
+            deviation,
+        }
+    }
+
+    /// The m×m lumped generator of the last call to `lump`, in the convention of that call.
+    pub fn generator(&self) -> ArrayView2<'_, T> {
+        self.generator.view()
+    }
+
+    /// Aggregate the distribution `p` over the n states into the distribution `p_hat` over the
+    /// m blocks, $\hat{p}_I = \sum_{i \in I} p_i$.
+    ///
+    /// NOTE: Panics if `p` doesn't have length n or `p_hat` doesn't have length m.
+    pub fn aggregate<S1, S2>(&self, p: &ArrayBase<S1, Ix1>, p_hat: &mut ArrayBase<S2, Ix1>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert_eq!(p.len(), self.n, "Dimension mismatch between distribution `p` and preconfigured `Lumping` struct.");
+        assert_eq!(p_hat.len(), self.m, "Dimension mismatch between distribution `p_hat` and preconfigured `Lumping` struct.");
+
+        p_hat.fill(T::zero());
+        for (&x, &block) in p.iter().zip(self.partition.iter()) {
+            p_hat[block] = p_hat[block] + x;
+        }
+    }
+
+    /// Aggregate the distribution `p0` over the n states and propagate it over the time `t` with
+    /// the lumped generator of the last call to `lump`, storing the distribution over the m
+    /// blocks in `p_hat`. If the generator was lumpable, this is the exact aggregation of the
+    /// propagated distribution.
+    ///
+    /// NOTE: Panics if `lump` has not been called, if `p0` doesn't have length n, or if `p_hat`
+    /// is not a contiguous vector of length m.
+    pub fn propagate<S1, S2>(&mut self, p0: &ArrayBase<S1, Ix1>, t: T, p_hat: &mut ArrayBase<S2, Ix1>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert!(self.is_lumped, "A generator has to be lumped before propagating.");
+
+        let mut aggregated = std::mem::replace(&mut self.aggregated, Array1::zeros(0));
+        self.aggregate(p0, &mut aggregated);
+        self.ctmc.propagate(&self.generator, &aggregated, t, self.convention, p_hat);
+        self.aggregated = aggregated;
+    }
+}
+
+/// Lump the n×n generator `q` according to `partition` with uniform weights within each block,
+/// storing the m×m lumped generator in `q_hat` and returning a report on the lumpability of `q`.
+///
+/// NOTE: Panics if `q` is not a generator in the given `convention`, if a block of the partition
+/// is empty, or if `q`, `partition`, and `q_hat` don't have matching dimensions.
+pub fn lump_generator<T, S1, S2>(q: &ArrayBase<S1, Ix2>, partition: &[usize], convention: StochasticConvention, q_hat: &mut ArrayBase<S2, Ix2>) -> LumpingReport<T>
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let mut lumping = Lumping::new(partition);
+    let report = lumping.lump(q, convention, &Array1::ones(partition.len()));
+    q_hat.assign(&lumping.generator());
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    use crate::StochasticConvention;
+
+    #[test]
+    fn lumpable_chain_propagates_exactly() {
+        // States 1 and 2 both leave to state 0 with rate 3.
+        let q = arr2(&[[-2.0, 1.0, 1.0], [3.0, -4.0, 1.0], [3.0, 1.0, -4.0]]);
+        let partition = [0, 1, 1];
+        let mut lumping = crate::Lumping::new(&partition);
+        let report = lumping.lump(&q, StochasticConvention::Row, &arr1(&[0.2, 0.5, 0.3]));
+
+        assert!(report.lumpable);
+        for (&x, &y) in lumping.generator().iter().zip(arr2(&[[-2.0, 2.0], [3.0, -3.0]]).iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-15);
+        }
+
+        let p0 = arr1(&[0.1, 0.6, 0.3]);
+        let mut p = Array1::<f64>::zeros(3);
+        let mut expected = Array1::<f64>::zeros(2);
+        let mut p_hat = Array1::<f64>::zeros(2);
+        crate::ctmc_propagate(&q, &p0, 0.7, StochasticConvention::Row, &mut p);
+        lumping.aggregate(&p, &mut expected);
+        lumping.propagate(&p0, 0.7, &mut p_hat);
+        for (&x, &y) in p_hat.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-13);
+        }
+    }
+
+    #[test]
+    fn deviation_is_reported() {
+        let q = arr2(&[[-2.0, 1.0, 1.0], [3.0, -4.0, 1.0], [1.0, 1.0, -2.0]]);
+        let mut q_hat = Array2::<f64>::zeros((2, 2));
+
+        let report = crate::lump_generator(&q.t(), &[0, 1, 1], StochasticConvention::Column, &mut q_hat);
+        assert!(!report.lumpable);
+        assert_relative_eq!(report.deviation, 1.0);
+        assert_eq!(q_hat, arr2(&[[-2.0, 2.0], [2.0, -2.0]]));
+    }
+}
@@ -0,0 +1,189 @@
+//! Propagators of linear systems $\dot{u} = A(t) u$ with time-dependent generators, i.e. the
+//! time-ordered exponentials
+//!
+//! \begin{equation}
+//!     U(t_1, t_0) = \mathcal{T} \exp\left(\int_{t_0}^{t_1} A(t) \, dt\right),
+//! \end{equation}
+//!
+//! calculated with the Magnus integrators of [Blanes, Casas, Oteo, Ros 2009]. The interval is
+//! divided into steps of length $h$, and the propagator of each step is approximated by the
+//! exponential $e^{\Omega}$ of a truncated Magnus series. The integrator of order 2 is the
+//! exponential midpoint rule,
+//!
+//! \begin{equation}
+//!     \Omega = h A\left(t + \frac{h}{2}\right),
+//! \end{equation}
+//!
+//! and the integrator of order 4 evaluates the generator at the Gauss–Legendre nodes $t + c_{1,2}
+//! h$ with $c_{1,2} = \frac{1}{2} \mp \frac{\sqrt{3}}{6}$,
+//!
+//! \begin{equation}
+//!     \Omega = \frac{h}{2} (A_1 + A_2) + \frac{\sqrt{3} h^2}{12} [A_2, A_1].
+//! \end{equation}
+//!
+//! Both preserve the Lie group structure of the exact propagator, e.g. its orthogonality for
+//! antisymmetric generators.
+//!
+//! [Blanes, Casas, Oteo, Ros 2009]: https://doi.org/10.1016/j.physrep.2008.11.001
+
+use ndarray::{
+    self,
+    prelude::*,
+    DataMut,
+};
+
+use crate::{
+    float::gemm,
+    Expm,
+    Float,
+};
+
+/// The order of the Magnus integrator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MagnusOrder {
+    /// The exponential midpoint rule, requiring one evaluation of the generator per step.
+    Second,
+    /// The Gauss–Legendre based integrator, requiring two evaluations of the generator and one
+    /// commutator per step.
+    Fourth,
+}
+
+/// Storage for calculating propagators of n×n time-dependent generators.
+pub struct Magnus<T = f64> {
+    n: usize,
+    expm: Expm<T>,
+    omega: Array2<T>,
+    commutator: Array2<T>,
+    step: Array2<T>,
+    work: Array2<T>,
+}
+
+impl<T: Float> Magnus<T> {
+    /// Allocates all space to calculate propagators of n×n generators.
+    pub fn new(n: usize) -> Self {
+        Magnus {
+            n,
+            expm: Expm::new(n),
+            omega: Array2::zeros((n, n)),
+            commutator: Array2::zeros((n, n)),
+            step: Array2::zeros((n, n)),
+            work: Array2::zeros((n, n)),
+        }
+    }
+
+    /// Calculate the propagator $U(t_1, t_0)$ of the generator `a` with a Magnus integrator of
+    /// the given `order`, storing the result in `u`. The interval is divided into the smallest
+    /// number of equal steps not longer than `h`; `t1` may be smaller than `t0`.
+    ///
+    /// NOTE: Panics if `h` is not positive, or if `u` or any of the matrices returned by `a`
+    /// don't have the same dimension as the `Magnus` object `propagator` is called on.
+    pub fn propagator<F, S>(&mut self, a: F, order: MagnusOrder, t0: T, t1: T, h: T, u: &mut ArrayBase<S, Ix2>)
+        where F: Fn(T) -> Array2<T>,
+              S: DataMut<Elem=T>,
+    {
+        let n = self.n;
+        assert_eq!(u.dim(), (n, n), "Dimension mismatch between matrix `u` and preconfigured `Magnus` struct.");
+        assert!(h > T::zero(), "Step size `h` has to be positive.");
+
+        u.fill(T::zero());
+        u.diag_mut().fill(T::one());
+
+        let steps = ((t1 - t0).abs() / h).ceil().max(T::one());
+        let h = (t1 - t0) / steps;
+        let half = T::from_f64(0.5).unwrap();
+        let sqrt_3 = T::from_f64(3.0).unwrap().sqrt();
+
+        for k in 0..steps.to_usize().unwrap() {
+            let t = t0 + T::from_usize(k).unwrap() * h;
+
+            match order {
+                MagnusOrder::Second => {
+                    let a_mid = a(t + half * h);
+                    assert_eq!(a_mid.dim(), (n, n), "Dimension mismatch between generator `a` and preconfigured `Magnus` struct.");
+                    self.omega.assign(&a_mid);
+                    self.omega.mapv_inplace(|x| h * x);
+                }
+                MagnusOrder::Fourth => {
+                    let c = sqrt_3 / T::from_f64(6.0).unwrap();
+                    let a_1 = a(t + (half - c) * h);
+                    let a_2 = a(t + (half + c) * h);
+                    assert_eq!(a_1.dim(), (n, n), "Dimension mismatch between generator `a` and preconfigured `Magnus` struct.");
+                    assert_eq!(a_2.dim(), (n, n), "Dimension mismatch between generator `a` and preconfigured `Magnus` struct.");
+
+                    // [A₂, A₁] = A₂ A₁ - A₁ A₂
+                    gemm(T::one(), &a_2, &a_1, T::zero(), &mut self.commutator);
+                    gemm(-T::one(), &a_1, &a_2, T::one(), &mut self.commutator);
+
+                    let factor = sqrt_3 * h * h / T::from_f64(12.0).unwrap();
+                    self.omega.assign(&a_1);
+                    self.omega.zip_mut_with(&a_2, |x, &y| *x = half * h * (*x + y));
+                    self.omega.zip_mut_with(&self.commutator, |x, &y| *x = *x + factor * y);
+                }
+            }
+
+            self.expm.expm(&self.omega, &mut self.step);
+            gemm(T::one(), &self.step, u, T::zero(), &mut self.work);
+            u.assign(&self.work);
+        }
+    }
+}
+
+/// Calculate the propagator $U(t_1, t_0)$ of the n×n generator `a` with a Magnus integrator of
+/// the given `order` and steps not longer than `h`, storing the result in `u`.
+///
+/// NOTE: Panics if `h` is not positive, or if `u` and the matrices returned by `a` are not of the
+/// same dimension n×n.
+pub fn magnus<T, F, S>(a: F, order: MagnusOrder, t0: T, t1: T, h: T, u: &mut ArrayBase<S, Ix2>)
+    where T: Float,
+          F: Fn(T) -> Array2<T>,
+          S: DataMut<Elem=T>,
+{
+    let (n, _) = u.dim();
+
+    let mut magnus = Magnus::new(n);
+    magnus.propagator(a, order, t0, t1, h, u);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    use super::MagnusOrder;
+
+    #[test]
+    fn commuting_generator_is_exact() {
+        // A(t) = f(t) B commutes with itself at all times, so U = exp(B ∫f).
+        let b = arr2(&[[-1.0, 2.0, 0.0], [0.5, -3.0, 1.0], [0.0, 0.25, -0.5]]);
+        let generator = |t: f64| (1.0 + t * t) * &b;
+        let mut u = Array2::<f64>::zeros((3, 3));
+        let mut expected = Array2::<f64>::zeros((3, 3));
+
+        crate::magnus(generator, MagnusOrder::Fourth, 0.0, 1.5, 0.1, &mut u);
+        crate::expm(&((1.5 + 1.5f64.powi(3) / 3.0) * &b), &mut expected);
+
+        for (&x, &y) in u.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-7, epsilon=1e-9);
+        }
+    }
+
+    #[test]
+    fn fourth_order_converges_faster() {
+        // A rotation with time-dependent frequency and a non-commuting damping term.
+        let generator = |t: f64| arr2(&[[-0.1 * t, 1.0 + t], [-(1.0 + t), 0.0]]);
+        let mut reference = Array2::<f64>::zeros((2, 2));
+        crate::magnus(generator, MagnusOrder::Fourth, 0.0, 2.0, 1e-3, &mut reference);
+
+        let error = |order, h| {
+            let mut u = Array2::<f64>::zeros((2, 2));
+            crate::magnus(generator, order, 0.0, 2.0, h, &mut u);
+            (&u - &reference).iter().fold(0.0f64, |acc, x| acc.max(x.abs()))
+        };
+
+        // Halving the step reduces the error by 2^p for order p.
+        let ratio_2 = error(MagnusOrder::Second, 0.1) / error(MagnusOrder::Second, 0.05);
+        let ratio_4 = error(MagnusOrder::Fourth, 0.1) / error(MagnusOrder::Fourth, 0.05);
+        assert!(ratio_2 > 3.5 && ratio_2 < 4.5, "ratio_2 = {}", ratio_2);
+        assert!(ratio_4 > 14.0 && ratio_4 < 18.0, "ratio_4 = {}", ratio_4);
+    }
+}
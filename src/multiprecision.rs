@@ -0,0 +1,403 @@
+//! Matrix exponentials in arbitrary precision with `rug::Float`, enabled by the `rug` feature.
+//!
+//! For generators of huge norm, e.g. stiff ones, the exponential can have entries that differ by
+//! many orders of magnitude or that are not representable in double precision at all, so that the
+//! result of `expm` loses all accuracy. This module implements the scaling and squaring method
+//! for matrices of `rug::Float` entries at a chosen precision of $p$ bits.
+//!
+//! `rug::Float` is not `Copy` and has no BLAS or LAPACK routines, so that it cannot implement
+//! the `Float` trait. The matrix products and the LU factorization with partial pivoting of the
+//! denominator are instead implemented directly on `ndarray` matrices of `rug::Float`.
+//!
+//! The Padé order and the scaling parameter are chosen per precision. For the unit roundoff $u =
+//! 2^{-p}$, the thresholds $\theta_m$ of the odd orders $m$ up to 29 are calculated
+//! with `pade_theta` once per workspace. They are capped at the $\theta_{13}$ of double
+//! precision, which keeps the denominator $q_m$ as well conditioned as in [Higham 2005]. Every
+//! order $m$ then requires the scaling parameter $s_m = \max(\lceil \log_2 (\lVert A \rVert_1 /
+//! \theta_m) \rceil, 0)$ and costs $(m - 1)/2 + 1$ matrix products for the approximant and $s_m$
+//! for the squarings. The order with the fewest matrix products is used. Higher precisions thus
+//! select higher orders and more squarings, and at $p = 53$ the thresholds are those of `f64`.
+//!
+//! The thresholds are calculated in double precision, which limits the precision to 1000 bits.
+//!
+//! [Higham 2005]: https://doi.org/10.1137/04061101X
+
+use std::cmp::Ordering;
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+use rug::{
+    Assign,
+    Float,
+};
+
+use crate::{
+    pade_theta,
+    Counters,
+    Float as _,
+};
+
+/// The highest Padé order considered. The backward error series of `pade_theta` is truncated
+/// after `SERIES_DEGREE` terms, well above the leading term of degree $2m+1$ of this order.
+const MAX_PADE_ORDER: u64 = 29;
+
+/// The highest supported precision in bits, whose unit roundoff is still a normal double
+/// precision number with room to spare for the bisection in `pade_theta`.
+const MAX_PRECISION: u32 = 1000;
+
+/// A report of the choices made during the calculation of an arbitrary precision exponential.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExpmMultiprecisionReport {
+    /// The order $m$ of the Padé approximant.
+    pub pade_order: u64,
+    /// The scaling parameter $s$, i.e. the number of squarings.
+    pub squarings: i32,
+    /// The counts of the operations performed.
+    pub counters: Counters,
+}
+
+/// Storage for calculating exponentials of n×n matrices of `rug::Float` at a fixed precision.
+pub struct ExpmMultiprecision {
+    n: usize,
+    precision: u32,
+    thetas: Vec<(u64, f64)>,
+    x: Array2<Float>,
+    powers: Vec<Array2<Float>>,
+    u: Array2<Float>,
+    v: Array2<Float>,
+    work: Array2<Float>,
+    coefficients: Vec<Float>,
+    factor: Float,
+    product: Float,
+}
+
+impl ExpmMultiprecision {
+    /// Allocates all space to calculate exponentials of n×n matrices with `precision` bits, and
+    /// calculates the thresholds of the Padé approximants for this precision.
+    ///
+    /// NOTE: Panics if `precision` is zero or exceeds 1000 bits.
+    pub fn new(n: usize, precision: u32) -> Self {
+        assert!(precision > 0 && precision <= MAX_PRECISION, "Precision has to be between 1 and {} bits.", MAX_PRECISION);
+
+        let tolerance = 2f64.powi(-(precision as i32));
+        let cap = pade_theta(13, f64::unit_roundoff());
+        let thetas = (3..=MAX_PADE_ORDER)
+            .step_by(2)
+            .map(|m| (m, pade_theta(m, tolerance).min(cap)))
+            .collect();
+
+        ExpmMultiprecision {
+            n,
+            precision,
+            thetas,
+            x: matrix(n, precision),
+            powers: Vec::new(),
+            u: matrix(n, precision),
+            v: matrix(n, precision),
+            work: matrix(n, precision),
+            coefficients: Vec::new(),
+            factor: Float::new(precision),
+            product: Float::new(precision),
+        }
+    }
+
+    /// The thresholds $\theta_m$ for the odd orders $m$ at the precision of this workspace.
+    pub fn thresholds(&self) -> &[(u64, f64)] {
+        &self.thetas
+    }
+
+    /// Calculate the matrix exponential of the n×n matrix `a` at the precision of this workspace,
+    /// storing the result in `b` and returning a report of the calculation. The entries of `a`
+    /// may have any precision and are rounded to the working precision, and the entries of `b`
+    /// keep their precision.
+    ///
+    /// NOTE: Panics if `a` has non-finite entries, or if `a` and `b` don't have matching
+    /// dimensions, are not square, or don't have the same dimension as the `ExpmMultiprecision`
+    /// object `expm` is called on.
+    pub fn expm<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>) -> ExpmMultiprecisionReport
+        where S1: Data<Elem=Float>,
+              S2: DataMut<Elem=Float>,
+    {
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `ExpmMultiprecision` struct.");
+        assert_eq!(a.dim(), b.dim(), "Input matrices `a` and `b` have to have matching dimensions.");
+        assert!(a.iter().all(|x| x.is_finite()), "Input matrix `a` has to be finite.");
+
+        let (m, s) = self.select(a);
+        let half_order = (m as usize - 1) / 2;
+
+        // X = 2^-s A
+        for (x, y) in self.x.iter_mut().zip(a.iter()) {
+            x.assign(y);
+            *x >>= s as u32;
+        }
+
+        // powers[k - 1] = X^{2k}
+        while self.powers.len() < half_order {
+            self.powers.push(matrix(self.n, self.precision));
+        }
+        matmul(&self.x, &self.x, &mut self.powers[0], &mut self.product);
+        for k in 1..half_order {
+            let (lower, upper) = self.powers.split_at_mut(k);
+            matmul(&lower[k - 1], &lower[0], &mut upper[0], &mut self.product);
+        }
+
+        self.calculate_coefficients(m as usize);
+
+        // V = Σ b_{2k} X^{2k} and W = Σ b_{2k+1} X^{2k} in work, with U = X W.
+        for (((i, j), v), w) in self.v.indexed_iter_mut().zip(self.work.iter_mut()) {
+            if i == j {
+                v.assign(&self.coefficients[0]);
+                w.assign(&self.coefficients[1]);
+            } else {
+                v.assign(0);
+                w.assign(0);
+            }
+        }
+        for k in 1..=half_order {
+            let power = &self.powers[k - 1];
+            for ((v, w), p) in self.v.iter_mut().zip(self.work.iter_mut()).zip(power.iter()) {
+                self.product.assign(&self.coefficients[2 * k] * p);
+                *v += &self.product;
+                self.product.assign(&self.coefficients[2 * k + 1] * p);
+                *w += &self.product;
+            }
+        }
+        matmul(&self.x, &self.work, &mut self.u, &mut self.product);
+
+        // work = p_m = V + U and v = q_m = V - U
+        for ((p, q), u) in self.work.iter_mut().zip(self.v.iter_mut()).zip(self.u.iter()) {
+            p.assign(&*q + u);
+            *q -= u;
+        }
+        let is_nonsingular = solve(&mut self.v, &mut self.work, &mut self.factor, &mut self.product);
+        assert!(is_nonsingular, "The denominator of the Padé approximant is singular.");
+
+        for _ in 0..s {
+            matmul(&self.work, &self.work, &mut self.u, &mut self.product);
+            std::mem::swap(&mut self.work, &mut self.u);
+        }
+
+        for (x, y) in b.iter_mut().zip(self.work.iter()) {
+            x.assign(y);
+        }
+
+        ExpmMultiprecisionReport {
+            pade_order: m,
+            squarings: s,
+            counters: Counters {
+                matrix_products: half_order + 1 + s as usize,
+                linear_solves: 1,
+                ..Counters::default()
+            },
+        }
+    }
+
+    /// Selects the Padé order and the scaling parameter with the fewest matrix products.
+    fn select<S>(&mut self, a: &ArrayBase<S, Ix2>) -> (u64, i32)
+        where S: Data<Elem=Float>,
+    {
+        let mut norm = Float::new(self.precision);
+        for column in a.gencolumns() {
+            self.factor.assign(0);
+            for x in column {
+                self.factor += &*x.as_abs();
+            }
+            if self.factor > norm {
+                norm.assign(&self.factor);
+            }
+        }
+        let log2_norm = if norm.is_zero() {
+            f64::NEG_INFINITY
+        } else {
+            norm.log2().to_f64()
+        };
+
+        let mut best = (0, 0, usize::max_value());
+        for &(m, theta) in &self.thetas {
+            let s = (log2_norm - theta.log2()).ceil().max(0.0) as i32;
+            let cost = (m as usize - 1) / 2 + 1 + s as usize;
+            if cost < best.2 {
+                best = (m, s, cost);
+            }
+        }
+        (best.0, best.1)
+    }
+
+    /// The coefficients $b_k = \frac{(2m - k)! \, m!}{(2m)! \, k! \, (m - k)!}$ of the numerator
+    /// of the Padé approximant of order `m`, with $b_0 = 1$.
+    fn calculate_coefficients(&mut self, m: usize) {
+        self.coefficients.clear();
+        let mut b = Float::with_val(self.precision, 1);
+        self.coefficients.push(b.clone());
+        for k in 1..=m {
+            b *= (m + 1 - k) as u32;
+            b /= ((2 * m + 1 - k) * k) as u32;
+            self.coefficients.push(b.clone());
+        }
+    }
+}
+
+/// An n×n matrix of zeros with `precision` bits.
+fn matrix(n: usize, precision: u32) -> Array2<Float> {
+    Array2::from_shape_fn((n, n), |_| Float::new(precision))
+}
+
+/// Calculates $C = AB$, using `product` for the products of the entries.
+fn matmul(a: &Array2<Float>, b: &Array2<Float>, c: &mut Array2<Float>, product: &mut Float) {
+    let n = a.rows();
+    for i in 0..n {
+        for j in 0..n {
+            c[(i, j)].assign(0);
+            for k in 0..n {
+                product.assign(&a[(i, k)] * &b[(k, j)]);
+                c[(i, j)] += &*product;
+            }
+        }
+    }
+}
+
+/// Solves $QX = P$ by LU factorization of `q` with partial pivoting, overwriting `p` with $X$.
+/// Returns whether `q` is nonsingular; otherwise `p` is left partially eliminated.
+fn solve(q: &mut Array2<Float>, p: &mut Array2<Float>, factor: &mut Float, product: &mut Float) -> bool {
+    let n = q.rows();
+    for k in 0..n {
+        let mut pivot = k;
+        for i in k + 1..n {
+            if q[(i, k)].cmp_abs(&q[(pivot, k)]) == Some(Ordering::Greater) {
+                pivot = i;
+            }
+        }
+        if q[(pivot, k)].is_zero() {
+            return false;
+        }
+        for j in 0..n {
+            q.swap((pivot, j), (k, j));
+            p.swap((pivot, j), (k, j));
+        }
+
+        for i in k + 1..n {
+            factor.assign(&q[(i, k)] / &q[(k, k)]);
+            for j in k + 1..n {
+                product.assign(&*factor * &q[(k, j)]);
+                q[(i, j)] -= &*product;
+            }
+            for j in 0..n {
+                product.assign(&*factor * &p[(k, j)]);
+                p[(i, j)] -= &*product;
+            }
+        }
+    }
+
+    for c in 0..n {
+        for i in (0..n).rev() {
+            for j in i + 1..n {
+                product.assign(&q[(i, j)] * &p[(j, c)]);
+                p[(i, c)] -= &*product;
+            }
+            p[(i, c)] /= &q[(i, i)];
+        }
+    }
+    true
+}
+
+/// Calculate the matrix exponential of the n×n matrix `a` with `precision` bits, storing the
+/// result in `b` and returning a report of the calculation.
+///
+/// NOTE: Panics if `precision` is zero or exceeds 1000 bits, if `a` has non-finite
+/// entries, or if `a` and `b` don't have matching dimensions or are not square.
+pub fn expm_multiprecision<S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>, precision: u32) -> ExpmMultiprecisionReport
+    where S1: Data<Elem=Float>,
+          S2: DataMut<Elem=Float>,
+{
+    let (n, _) = a.dim();
+
+    let mut expm = ExpmMultiprecision::new(n, precision);
+    expm.expm(a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+    use rug::Float;
+
+    use crate::Float as _;
+
+    fn to_multiprecision(a: &Array2<f64>, precision: u32) -> Array2<Float> {
+        a.mapv(|x| Float::with_val(precision, x))
+    }
+
+    #[test]
+    fn double_precision_reproduces_thresholds() {
+        let expm = crate::ExpmMultiprecision::new(2, 53);
+        for &(m, theta) in f64::THETAS.iter().filter(|&&(m, _)| m < 13) {
+            let &(_, threshold) = expm.thresholds().iter().find(|&&(order, _)| order == m).unwrap();
+            assert_relative_eq!(threshold, theta, max_relative=1e-7);
+        }
+    }
+
+    #[test]
+    fn double_precision_agrees_with_expm() {
+        let a = arr2(&[[-1.0, 2.0, 0.5], [0.5, -3.0, 1.0], [0.25, 0.25, -0.5]]) * 4.0;
+        let mut expected = Array2::<f64>::zeros((3, 3));
+        crate::expm(&a, &mut expected);
+
+        let mut b = to_multiprecision(&Array2::zeros((3, 3)), 53);
+        crate::expm_multiprecision(&to_multiprecision(&a, 53), &mut b, 53);
+
+        for (x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x.to_f64(), y, max_relative=1e-12, epsilon=1e-15);
+        }
+    }
+
+    #[test]
+    fn higher_precision_raises_the_cost() {
+        let a = to_multiprecision(&arr2(&[[0.0, 10.0], [-10.0, 0.0]]), 256);
+        let mut b = a.clone();
+
+        let double = crate::expm_multiprecision(&a, &mut b, 53);
+        let quadruple = crate::expm_multiprecision(&a, &mut b, 256);
+        assert!(quadruple.counters.matrix_products > double.counters.matrix_products);
+        assert!(quadruple.pade_order > double.pade_order);
+    }
+
+    #[test]
+    fn rotation_is_accurate_to_working_precision() {
+        let precision = 256;
+        let t = Float::with_val(precision, 10);
+        let a = arr2(&[[Float::new(precision), t.clone()], [-t.clone(), Float::new(precision)]]);
+        let mut b = a.clone();
+        crate::expm_multiprecision(&a, &mut b, precision);
+
+        let (sin, cos) = t.sin_cos(Float::new(precision));
+        let expected = arr2(&[[cos.clone(), sin.clone()], [-sin, cos]]);
+        for (x, y) in b.iter().zip(expected.iter()) {
+            let error = Float::with_val(precision, x - y).abs();
+            assert!(error < 1e-70, "error {} too large", error);
+        }
+    }
+
+    #[test]
+    fn stiff_triangular_matrix_is_accurate() {
+        // e^{-1000} underflows in double precision. For an upper triangular [[λ, c], [0, μ]], the
+        // off-diagonal entry of the exponential is c (e^λ - e^μ) / (λ - μ).
+        let precision = 200;
+        let a = to_multiprecision(&arr2(&[[-1000.0, 1000.0], [0.0, -1001.0]]), precision);
+        let mut b = a.clone();
+        let report = crate::expm_multiprecision(&a, &mut b, precision);
+        assert!(report.squarings > 0);
+
+        let lambda = Float::with_val(precision, -1000).exp();
+        let mu = Float::with_val(precision, -1001).exp();
+        let off_diagonal = Float::with_val(precision, &lambda - &mu) * 1000;
+        assert!(b[(1, 0)].is_zero());
+        for (x, y) in [&b[(0, 0)], &b[(0, 1)], &b[(1, 1)]].iter().zip(&[lambda, off_diagonal, mu]) {
+            let error = Float::with_val(precision, *x - y) / y;
+            assert!(error.abs() < 1e-45, "relative error of {} too large", x);
+        }
+    }
+}
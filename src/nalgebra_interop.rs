@@ -0,0 +1,191 @@
+//! Matrix exponentials of `nalgebra` matrices, available with the `nalgebra` feature.
+//!
+//! Dynamically sized `DMatrix` are handled by the `Expm` workspace without copying: `nalgebra`
+//! stores matrices in column-major order, so that the storage of $A$ is the row-major storage of
+//! $A^\top$, and $e^{A^\top} = (e^A)^\top$. Calculating the exponential of the reinterpreted
+//! storage thus directly yields the column-major storage of $e^A$.
+//!
+//! Statically sized `SMatrix` are exponentiated by a separate implementation of scaling and
+//! squaring following [Higham 2005], with all intermediate matrices allocated on the stack. The
+//! 1-norms of the matrix powers are calculated exactly, which is cheap for small matrices.
+//!
+//! [Higham 2005]: https://doi.org/10.1137/04061101X
+
+use nalgebra::{
+    Const,
+    DimMin,
+    DMatrix,
+    RealField,
+    SMatrix,
+};
+use ndarray::{
+    ArrayView2,
+    ArrayViewMut2,
+};
+
+use crate::{
+    Expm,
+    Float,
+    PadeOrder,
+    PadeOrder_13,
+    PadeOrder_3,
+    PadeOrder_5,
+    PadeOrder_7,
+    PadeOrder_9,
+};
+
+impl<T: Float + RealField> Expm<T> {
+    /// Calculate the matrix exponential of the n×n `nalgebra` matrix `a`, storing the result in
+    /// `b`.
+    ///
+    /// NOTE: Panics if `a` and `b` don't have matching dimensions, are not square, or don't have
+    /// the same dimension as the `Expm` object `expm_dmatrix` is called on.
+    pub fn expm_dmatrix(&mut self, a: &DMatrix<T>, b: &mut DMatrix<T>) {
+        assert_eq!(a.shape(), b.shape(), "Input matrices `a` and `b` have to have matching dimensions.");
+        let (n, n_cols) = a.shape();
+        assert_eq!(n, n_cols, "expm is only implemented for square matrices.");
+        assert_eq!(n, self.n, "Dimension mismatch between matrix `a` and preconfigured `Expm` struct.");
+
+        // The column-major storage of A is the row-major storage of Aᵀ.
+        let a_t = ArrayView2::from_shape((n, n), a.as_slice()).unwrap();
+        let mut b_t = ArrayViewMut2::from_shape((n, n), b.as_mut_slice()).unwrap();
+        self.expm(&a_t, &mut b_t);
+    }
+}
+
+/// Calculate the matrix exponential of the n×n `nalgebra` matrix `a`.
+///
+/// NOTE: Panics if `a` is not square.
+pub fn expm_dmatrix<T: Float + RealField>(a: &DMatrix<T>) -> DMatrix<T> {
+    let (n, _) = a.shape();
+
+    let mut b = DMatrix::zeros(n, n);
+    let mut expm = Expm::new(n);
+    expm.expm_dmatrix(a, &mut b);
+    b
+}
+
+/// Calculate the matrix exponential of the statically sized N×N `nalgebra` matrix `a`, with all
+/// intermediate matrices allocated on the stack.
+///
+/// NOTE: Panics if the Padé denominator is singular, which only happens for non-finite input.
+pub fn expm_smatrix<T, const N: usize>(a: &SMatrix<T, N, N>) -> SMatrix<T, N, N>
+    where T: Float + RealField,
+          Const<N>: DimMin<Const<N>, Output = Const<N>>,
+{
+    let norm_a = onenorm(a);
+
+    let order = <T as Float>::THETAS.iter()
+        .filter(|&&(m, _)| m < <T as Float>::MAX_PADE_ORDER)
+        .find(|&&(_, theta)| norm_a <= from_f64(theta))
+        .map(|&(m, _)| m);
+
+    if let Some(m) = order {
+        return pade(a, m);
+    }
+
+    let m = <T as Float>::MAX_PADE_ORDER;
+    let theta = <T as Float>::threshold(m).unwrap();
+    let s = num_traits::Float::ceil(num_traits::Float::log2(norm_a / from_f64(theta)));
+    let s = num_traits::ToPrimitive::to_i32(&s).unwrap_or(0).max(0);
+
+    let scaled = a * num_traits::Float::powi(from_f64::<T>(2.0), -s);
+    let mut r = pade(&scaled, m);
+    for _ in 0..s {
+        r = r * r;
+    }
+    r
+}
+
+/// Evaluates the Padé approximant of order `m` at `a`.
+fn pade<T, const N: usize>(a: &SMatrix<T, N, N>, m: u64) -> SMatrix<T, N, N>
+    where T: Float + RealField,
+          Const<N>: DimMin<Const<N>, Output = Const<N>>,
+{
+    let coefficients = match m {
+        3 => PadeOrder_3::coefficients(),
+        5 => PadeOrder_5::coefficients(),
+        7 => PadeOrder_7::coefficients(),
+        9 => PadeOrder_9::coefficients(),
+        13 => PadeOrder_13::coefficients(),
+        _ => unreachable!(),
+    };
+    let b = |i: usize| from_f64::<T>(coefficients[i]);
+
+    let eye = SMatrix::<T, N, N>::identity();
+    let a2 = a * a;
+    let a4 = a2 * a2;
+    let a6 = a4 * a2;
+
+    let (u, v) = if m == 13 {
+        let w = a6 * (a6 * b(13) + a4 * b(11) + a2 * b(9)) + a6 * b(7) + a4 * b(5) + a2 * b(3) + eye * b(1);
+        let z = a6 * (a6 * b(12) + a4 * b(10) + a2 * b(8)) + a6 * b(6) + a4 * b(4) + a2 * b(2) + eye * b(0);
+        (a * w, z)
+    } else {
+        let powers = [eye, a2, a4, a6, a6 * a2];
+        let mut w = SMatrix::<T, N, N>::zeros();
+        let mut v = SMatrix::<T, N, N>::zeros();
+        for (j, power) in powers.iter().enumerate().take((m as usize - 1) / 2 + 1) {
+            w += power * b(2*j + 1);
+            v += power * b(2*j);
+        }
+        (a * w, v)
+    };
+
+    (v - u).lu().solve(&(u + v)).expect("Padé denominator is singular.")
+}
+
+/// The exact 1-norm, i.e. the maximum absolute column sum of `a`.
+fn onenorm<T, const N: usize>(a: &SMatrix<T, N, N>) -> T
+    where T: Float + RealField,
+{
+    a.column_iter()
+        .map(|column| column.iter().fold(<T as num_traits::Zero>::zero(), |acc, &x| acc + num_traits::Float::abs(x)))
+        .fold(<T as num_traits::Zero>::zero(), num_traits::Float::max)
+}
+
+fn from_f64<T: Float>(x: f64) -> T {
+    <T as num_traits::FromPrimitive>::from_f64(x).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{
+        DMatrix,
+        Matrix3,
+    };
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn nalgebra_agrees_with_ndarray() {
+        let rows = [[-1.0, 2.0, 0.5], [0.0, -3.0, 1.0], [0.5, 0.25, -0.5]];
+        let a = arr2(&rows);
+
+        for &scale in &[0.01, 0.5, 1.0, 10.0] {
+            let a = &a * scale;
+            let mut expected = Array2::<f64>::zeros((3, 3));
+            crate::expm(&a, &mut expected);
+
+            let a_dynamic = DMatrix::from_fn(3, 3, |i, j| a[(i, j)]);
+            let b_dynamic = crate::expm_dmatrix(&a_dynamic);
+            let a_static = Matrix3::from_fn(|i, j| a[(i, j)]);
+            let b_static = crate::expm_smatrix(&a_static);
+
+            for ((i, j), &y) in expected.indexed_iter() {
+                assert_relative_eq!(b_dynamic[(i, j)], y, max_relative=1e-13, epsilon=1e-15);
+                assert_relative_eq!(b_static[(i, j)], y, max_relative=1e-12, epsilon=1e-15);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "only implemented for square matrices")]
+    fn non_square_dmatrix_is_rejected() {
+        let a = DMatrix::<f64>::zeros(2, 3);
+        let mut b = DMatrix::<f64>::zeros(2, 3);
+
+        let mut expm = crate::Expm::new(2);
+        expm.expm_dmatrix(&a, &mut b);
+    }
+}
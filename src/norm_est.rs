@@ -0,0 +1,150 @@
+//! Estimation of $\lVert e^A \rVert_1$ without forming the exponential, using the action of the
+//! exponential of [Al-Mohy and Higham 2011].
+//!
+//! The block 1-norm estimator only requires products of $e^A$ and $(e^A)^\top = e^{A^\top}$ with
+//! n×t blocks of probing vectors. These are calculated by the truncated Taylor series of
+//! `ExpmProduct::apply`, so that only products of $A$ with n×t blocks are performed. This is
+//! cheaper than `expm` for large n, and the estimate serves stability analyses of semigroups as
+//! well as guards against overflow and underflow before a dense exponential is calculated.
+//!
+//! [Al-Mohy and Higham 2011]: https://doi.org/10.1137/100788860
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+};
+use rand::RngCore;
+use std::cell::RefCell;
+
+use crate::{
+    normest::{
+        LinearOperator,
+        Normest1,
+    },
+    ExpmProduct,
+    Float,
+};
+
+/// The exponential $e^A$ as a linear operator, applied via the truncated Taylor series.
+struct ExponentialOperator<T> {
+    a: Array2<T>,
+    a_t: Array2<T>,
+    product: RefCell<ExpmProduct<T>>,
+}
+
+impl<T: Float> LinearOperator<T> for ExponentialOperator<T> {
+    fn multiply_matrix(&self, b: &mut Array2<T>, c: &mut Array2<T>, transpose: bool) {
+        let a = if transpose { &self.a_t } else { &self.a };
+        self.product.borrow_mut().apply(&[a], b, c);
+    }
+}
+
+/// Storage for estimating the 1-norm of the exponential of n×n matrices.
+pub struct ExpmNormEst<T = f64> {
+    n: usize,
+    itmax: usize,
+    operator: ExponentialOperator<T>,
+    normest1: Normest1<T>,
+}
+
+impl<T: Float> ExpmNormEst<T> {
+    /// Allocates all space to estimate the 1-norm of the exponential of n×n matrices.
+    pub fn new(n: usize) -> Self {
+        let t = 2.min(n);
+        let itmax = 5;
+
+        let operator = ExponentialOperator {
+            a: Array2::zeros((n, n)),
+            a_t: Array2::zeros((n, n)),
+            product: RefCell::new(ExpmProduct::new(n)),
+        };
+
+        ExpmNormEst {
+            n,
+            itmax,
+            operator,
+            normest1: Normest1::new(n, t),
+        }
+    }
+
+    /// Seed the probing vectors with `seed`. Estimates for general matrices depend on them,
+    /// while those for nonnegative exponentials are exact regardless.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.normest1.set_seed(seed);
+    }
+
+    /// Reseed the probing vectors from the generator `rng`.
+    pub fn set_rng<R: RngCore>(&mut self, rng: &mut R) {
+        self.normest1.set_rng(rng);
+    }
+
+    /// Estimate $\lVert e^A \rVert_1$ for the n×n matrix `a`. The estimate is a lower bound that
+    /// is usually accurate within a factor of 3, and exact for nonnegative $e^A$.
+    ///
+    /// NOTE: Panics if `a` is not square, or doesn't have the same dimension as the
+    /// `ExpmNormEst` object `norm1_est` is called on.
+    pub fn norm1_est<S>(&mut self, a: &ArrayBase<S, Ix2>) -> T
+        where S: Data<Elem=T>,
+    {
+        let (n_rows, n_cols) = a.dim();
+        assert_eq!(n_rows, n_cols, "The exponential is only defined for square matrices.");
+        assert_eq!(n_rows, self.n, "Dimension mismatch between matrix `a` and preconfigured `ExpmNormEst` struct.");
+
+        if self.n == 0 {
+            return T::zero();
+        }
+
+        self.operator.a.assign(a);
+        self.operator.a_t.assign(&a.t());
+        self.normest1.normest1_operator(&self.operator, self.itmax)
+    }
+}
+
+/// Estimate $\lVert e^A \rVert_1$ for the n×n matrix `a` without forming the exponential.
+///
+/// NOTE: Panics if `a` is not square.
+pub fn expm_norm1_est<T, S>(a: &ArrayBase<S, Ix2>) -> T
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut norm_est = ExpmNormEst::new(n);
+    norm_est.norm1_est(a)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+    use rand::SeedableRng;
+    use rand_xoshiro::SplitMix64;
+
+    use crate::float::onenorm;
+
+    #[test]
+    fn estimate_bounds_exact_norm() {
+        // The off-diagonal entries are nonnegative, so that the exponential is nonnegative and
+        // the estimate exact.
+        let metzler = arr2(&[[-4.0, 2.0, 0.5, 0.0], [0.5, -3.0, 1.0, 2.0], [0.25, 0.25, -0.5, 1.0], [1.0, 0.0, 3.0, -2.0]]);
+        let general = arr2(&[[0.5, -2.0, 0.5, 3.0], [0.0, 1.0, -1.0, 1.0], [-2.0, 0.5, 1.0, 0.0], [1.0, 1.0, -3.0, 2.0]]);
+        let mut exp_a = Array2::<f64>::zeros((4, 4));
+
+        crate::expm(&metzler, &mut exp_a);
+        assert_relative_eq!(crate::expm_norm1_est(&metzler), onenorm(&exp_a), max_relative=1e-12);
+
+        crate::expm(&general, &mut exp_a);
+        let exact = onenorm(&exp_a);
+        let mut norm_est = crate::ExpmNormEst::new(4);
+        norm_est.set_seed(5);
+        let estimate = norm_est.norm1_est(&general);
+        assert!(estimate <= exact * (1.0 + 1e-12));
+        assert!(estimate >= exact / 3.0);
+
+        let mut other = crate::ExpmNormEst::new(4);
+        norm_est.set_rng(&mut SplitMix64::seed_from_u64(11));
+        other.set_rng(&mut SplitMix64::seed_from_u64(11));
+        assert_eq!(norm_est.norm1_est(&general), other.norm1_est(&general));
+    }
+}
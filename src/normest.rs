@@ -0,0 +1,621 @@
+//! The block 1-norm estimator, Algorithm 2.4 in [Higham, Tisseur], generic over the floating
+//! point types supported by this crate.
+//!
+//! This is a port of the estimator found in the `condest` crate, which is restricted to `f64`.
+//!
+//! [Higham, Tisseur]: http://eprints.ma.man.ac.uk/321/1/covered/MIMS_ep2006_145.pdf
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+use rand::{
+    Rng,
+    RngCore,
+    SeedableRng,
+    thread_rng,
+};
+use rand_xoshiro::Xoshiro256StarStar;
+use std::cmp;
+
+use crate::float::{
+    gemm,
+    Float,
+};
+
+/// A trait to generalize over 1-norm estimates of a matrix `A`, matrix powers `A^m`, or matrix
+/// products `A1 * A2 * ... * An`.
+///
+/// Repeatedly applying the factors to the n×t matrix of probing vectors costs O(m*n*n*t), which
+/// is cheaper than forming the power or product explicitly at O(m*n*n*n) as long as t << n.
+pub(crate) trait LinearOperator<T: Float> {
+    /// Calculates `c = A b` or, if `transpose` is set, `c = Aᵀ b`. The contents of `b` may be
+    /// overwritten.
+    fn multiply_matrix(&self, b: &mut Array2<T>, c: &mut Array2<T>, transpose: bool);
+
+    /// The number of n×n matrices applied to each probing vector in `multiply_matrix`.
+    fn applications(&self) -> usize {
+        1
+    }
+}
+
+impl<T, S> LinearOperator<T> for ArrayBase<S, Ix2>
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    fn multiply_matrix(&self, b: &mut Array2<T>, c: &mut Array2<T>, transpose: bool) {
+        if transpose {
+            gemm(T::one(), &self.t(), b, T::zero(), c);
+        } else {
+            gemm(T::one(), self, b, T::zero(), c);
+        }
+    }
+}
+
+impl<T, S> LinearOperator<T> for [&ArrayBase<S, Ix2>]
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    fn applications(&self) -> usize {
+        self.len()
+    }
+
+    fn multiply_matrix(&self, b: &mut Array2<T>, c: &mut Array2<T>, transpose: bool) {
+        let len = self.len();
+
+        for k in 0..len {
+            // The transpose of a product is the product of the transposes in reversed order.
+            let a = if transpose { self[len - 1 - k] } else { self[k] };
+
+            // NOTE: The swap makes use of the fact that in all instances where `multiply_matrix`
+            // is used, the values potentially stored in `b` are not required anymore.
+            if k > 0 {
+                std::mem::swap(b, c);
+            }
+            a.multiply_matrix(b, c, transpose);
+        }
+    }
+}
+
+impl<T, S> LinearOperator<T> for (&ArrayBase<S, Ix2>, usize)
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    fn applications(&self) -> usize {
+        self.1
+    }
+
+    fn multiply_matrix(&self, b: &mut Array2<T>, c: &mut Array2<T>, transpose: bool) {
+        let (a, m) = *self;
+        for k in 0..m {
+            if k > 0 {
+                std::mem::swap(b, c);
+            }
+            a.multiply_matrix(b, c, transpose);
+        }
+    }
+}
+
+/// Storage for estimating the 1-norm of n×n matrices using t probing vectors.
+///
+/// The random probing vectors are drawn from a generator seeded from the thread-local generator,
+/// so that estimates may differ between runs. For reproducible estimates, the generator can be
+/// seeded with `with_seed` or `set_seed`, or reseeded from a user-supplied generator with
+/// `set_rng`.
+///
+/// The probing vectors that produced the last estimate can be retrieved with `probing_vectors`
+/// and passed back via `set_probing_vectors`, or reused automatically after enabling
+/// `set_warm_start`. For slowly changing matrices, e.g. during the iterations of an optimizer,
+/// starting from these vectors typically lets the estimator converge within one or two
+/// iterations instead of starting cold each time.
+pub struct Normest1<T = f64> {
+    n: usize,
+    t: usize,
+    rng: Xoshiro256StarStar,
+    x_matrix: Array2<T>,
+    x_current: Array2<T>,
+    x_best: Array2<T>,
+    start_from_best: bool,
+    warm_start: bool,
+    has_estimate: bool,
+    y_matrix: Array2<T>,
+    z_matrix: Array2<T>,
+    sign_matrix: Array2<T>,
+    sign_matrix_old: Array2<T>,
+    column_is_parallel: Vec<bool>,
+    indices: Vec<usize>,
+    /// The indices $j$ of the unit vectors $e_j$ in the columns of `x_matrix`.
+    column_indices: Vec<usize>,
+    indices_history: Vec<bool>,
+    indices_history_len: usize,
+    h: Vec<T>,
+    matvecs: usize,
+}
+
+impl<T: Float> Normest1<T> {
+    /// Allocates all space to estimate the 1-norm of n×n matrices using t probing vectors.
+    ///
+    /// NOTE: Panics if t is larger than n.
+    pub fn new(n: usize, t: usize) -> Self {
+        assert!(t <= n.max(1), "Cannot have more iteration columns t than columns in the matrix.");
+        let rng = Xoshiro256StarStar::from_rng(&mut thread_rng()).expect("Rng initialization failed.");
+
+        Normest1 {
+            n,
+            t,
+            rng,
+            x_matrix: Array2::zeros((n, t)),
+            x_current: Array2::zeros((n, t)),
+            x_best: Array2::zeros((n, t)),
+            start_from_best: false,
+            warm_start: false,
+            has_estimate: false,
+            y_matrix: Array2::zeros((n, t)),
+            z_matrix: Array2::zeros((n, t)),
+            sign_matrix: Array2::zeros((n, t)),
+            sign_matrix_old: Array2::zeros((n, t)),
+            column_is_parallel: vec![false; t],
+            indices: (0..n).collect(),
+            column_indices: vec![0; t],
+            indices_history: vec![false; n],
+            indices_history_len: 0,
+            h: vec![T::zero(); n],
+            matvecs: 0,
+        }
+    }
+
+    /// Allocates all space to estimate the 1-norm of n×n matrices using t probing vectors, with
+    /// the random probing vectors drawn from a generator seeded with `seed`.
+    ///
+    /// NOTE: Panics if t is larger than n.
+    pub fn with_seed(n: usize, t: usize, seed: u64) -> Self {
+        let mut normest1 = Self::new(n, t);
+        normest1.set_seed(seed);
+        normest1
+    }
+
+    /// Reseed the generator of the random probing vectors with `seed`, making all subsequent
+    /// estimates reproducible.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Xoshiro256StarStar::seed_from_u64(seed);
+    }
+
+    /// Reseed the generator of the random probing vectors from the user-supplied generator
+    /// `rng`.
+    pub fn set_rng<R: RngCore>(&mut self, rng: &mut R) {
+        self.rng = Xoshiro256StarStar::from_rng(rng).expect("Rng initialization failed.");
+    }
+
+    fn calculate<L>(&mut self, a_linear_operator: &L, itmax: usize) -> T
+        where L: LinearOperator<T> + ?Sized
+    {
+        assert!(itmax > 1, "normest1 is undefined for iterations itmax < 2");
+
+        let n = self.n;
+        let t = self.t;
+
+        if n == 0 {
+            return T::zero();
+        }
+
+        // Explicitly empty the index history and reset the order of the indices, so that the
+        // estimate does not depend on previous calls; all other quantities will be overwritten at
+        // some point.
+        self.indices_history.iter_mut().for_each(|x| *x = false);
+        self.indices_history_len = 0;
+        self.indices.iter_mut().enumerate().for_each(|(i, x)| *x = i);
+        self.sign_matrix_old.fill(T::zero());
+
+        if self.start_from_best || (self.warm_start && self.has_estimate) {
+            self.x_matrix.assign(&self.x_best);
+            self.start_from_best = false;
+        } else {
+            // “We take the first column of X to be the vector of 1s, which is the starting vector
+            // used in Algorithm 2.1. [...] The remaining columns are chosen as rand {− 1 , 1 } ,
+            // with a check for and correction of parallel columns, exactly as for S in the body
+            // of the algorithm.”
+            {
+                let rng = &mut self.rng;
+                self.x_matrix.mapv_inplace(|_| random_sign(rng));
+                self.x_matrix.column_mut(0).fill(T::one());
+            }
+
+            self.column_is_parallel.iter_mut().for_each(|x| *x = false);
+            find_parallel_columns_in(&self.x_matrix, &mut self.column_is_parallel);
+            for (i, &is_parallel) in self.column_is_parallel.iter().enumerate() {
+                if is_parallel {
+                    resample_column(&mut self.x_matrix, i, &mut self.rng);
+                }
+            }
+
+            // Set all columns to unit vectors
+            let n_float = T::from_usize(n).unwrap();
+            self.x_matrix.mapv_inplace(|x| x / n_float);
+        }
+
+        let mut estimate = T::zero();
+        let mut best_index;
+
+        'optimization_loop: for k in 0..itmax {
+
+            // Y = A X
+            //
+            // NOTE: The operator may overwrite X, which is why it is copied to keep track of the
+            // probing vectors producing the best estimate.
+            self.x_current.assign(&self.x_matrix);
+            a_linear_operator.multiply_matrix(&mut self.x_matrix, &mut self.y_matrix, false);
+            self.matvecs += a_linear_operator.applications() * t;
+
+            // est = max{‖Y(:,j)‖₁ : j = 1:t}
+            let (max_norm_index, max_norm) = matrix_onenorm_with_index(&self.y_matrix);
+
+            // if est > est_old or k=2
+            // > if k ≥ 2 and est ≤ est_old, est = est_old, goto (5), end
+            if k > 0 && max_norm <= estimate {
+                break 'optimization_loop
+            }
+            estimate = max_norm;
+            // NOTE: Only meaningful for k > 0, when the columns of X are unit vectors.
+            best_index = self.column_indices[max_norm_index];
+            std::mem::swap(&mut self.x_best, &mut self.x_current);
+
+            // S = sign(Y)
+            self.sign_matrix.zip_mut_with(&self.y_matrix, |s, &y| *s = if y >= T::zero() { T::one() } else { -T::one() });
+
+            // > If every column of S is parallel to a column of Sold, goto (6), end
+            if k > 0 && are_all_columns_parallel_between(&self.sign_matrix_old, &self.sign_matrix) {
+                break 'optimization_loop;
+            }
+
+            if t > 1 {
+                // > Ensure that no column of S is parallel to another column of S
+                // > or to a column of Sold by replacing columns of S by rand{-1,+1}
+                self.column_is_parallel.iter_mut().for_each(|x| *x = false);
+                find_parallel_columns_in(&self.sign_matrix, &mut self.column_is_parallel);
+                if k > 0 {
+                    find_parallel_columns_between(&self.sign_matrix, &self.sign_matrix_old, &mut self.column_is_parallel);
+                }
+                for (i, &is_parallel) in self.column_is_parallel.iter().enumerate() {
+                    if is_parallel {
+                        resample_column(&mut self.sign_matrix, i, &mut self.rng);
+                    }
+                }
+            }
+
+            // > est_old = est, Sold = S
+            //
+            // NOTE: The sign matrix is stored at this point already, so that it can serve as
+            // additional workspace when multiplying with A^m or A1 A2 ... An.
+            self.sign_matrix_old.assign(&self.sign_matrix);
+
+            // Z = A^T S
+            a_linear_operator.multiply_matrix(&mut self.sign_matrix, &mut self.z_matrix, true);
+            self.matvecs += a_linear_operator.applications() * t;
+
+            // hᵢ= ‖Z(i,:)‖_∞
+            let mut max_h = T::zero();
+            for (row, h_element) in self.z_matrix.genrows().into_iter().zip(self.h.iter_mut()) {
+                let h = row.fold(T::zero(), |acc, &x| acc.max(x.abs()));
+                max_h = max_h.max(h);
+                *h_element = h;
+            }
+
+            if k > 0 && max_h == self.h[best_index] {
+                break 'optimization_loop
+            }
+
+            // > Sort h so that h_1 >= ... >= h_n and re-order correspondingly.
+            {
+                let h_ref = &self.h;
+                self.indices.sort_unstable_by(|&i, &j| h_ref[j].partial_cmp(&h_ref[i]).unwrap_or(cmp::Ordering::Equal));
+            }
+
+            self.x_matrix.fill(T::zero());
+
+            // > Replace ind(1:t) by the first t indices in ind(1:n) that are not in ind_hist.
+            // > X(:, j) = e_ind_j, j = 1:t
+            // > ind_hist = [ind_hist ind(1:t)]
+            //
+            // If less than t unused indices remain, historical indices are used to fill up the
+            // columns of `x_matrix`.
+            let fresh_indices = cmp::min(t, n - self.indices_history_len);
+            if fresh_indices == 0 {
+                break 'optimization_loop;
+            }
+            let mut current_column_fresh = 0;
+            let mut current_column_historical = fresh_indices;
+            let mut all_first_t_in_history = true;
+
+            for (position, &i) in self.indices.iter().enumerate() {
+                if current_column_fresh >= fresh_indices && position >= t {
+                    break;
+                }
+                if !self.indices_history[i] {
+                    if current_column_fresh < fresh_indices {
+                        if position < t {
+                            all_first_t_in_history = false;
+                        }
+                        self.x_matrix[(i, current_column_fresh)] = T::one();
+                        self.column_indices[current_column_fresh] = i;
+                        current_column_fresh += 1;
+                        self.indices_history[i] = true;
+                        self.indices_history_len += 1;
+                    }
+                } else if current_column_historical < t {
+                    self.x_matrix[(i, current_column_historical)] = T::one();
+                    self.column_indices[current_column_historical] = i;
+                    current_column_historical += 1;
+                }
+            }
+
+            // > if ind(1:t) is contained in ind_hist, goto (6), end
+            if all_first_t_in_history {
+                break 'optimization_loop;
+            }
+        }
+
+        self.has_estimate = true;
+
+        estimate
+    }
+
+    /// The n×t block of probing vectors that produced the most recent estimate.
+    pub fn probing_vectors(&self) -> ArrayView2<'_, T> {
+        self.x_best.view()
+    }
+
+    /// Start the next estimate from the n×t block of probing vectors `x`, typically obtained from
+    /// `probing_vectors` after estimating the norm of a similar matrix. The columns of `x` are
+    /// normalized to unit 1-norm; vanishing columns are replaced by the vector of ones.
+    ///
+    /// NOTE: Panics if `x` is not of dimension n×t.
+    pub fn set_probing_vectors<S>(&mut self, x: &ArrayBase<S, Ix2>)
+        where S: Data<Elem=T>,
+    {
+        assert_eq!(x.dim(), (self.n, self.t), "Probing vectors have to be of dimension n×t.");
+
+        self.x_best.assign(x);
+        let n_float = T::from_usize(self.n).unwrap();
+        for mut column in self.x_best.gencolumns_mut() {
+            let norm = column.fold(T::zero(), |acc, &x| acc + x.abs());
+            if norm > T::zero() {
+                column.mapv_inplace(|x| x / norm);
+            } else {
+                column.fill(T::one() / n_float);
+            }
+        }
+        self.start_from_best = true;
+    }
+
+    /// If `warm_start` is set, each estimate starts from the probing vectors that produced the
+    /// previous estimate instead of a fresh random block.
+    pub fn set_warm_start(&mut self, warm_start: bool) {
+        self.warm_start = warm_start;
+    }
+
+    /// The total number of matrix-vector products performed since the creation of the estimator.
+    pub(crate) fn matvecs(&self) -> usize {
+        self.matvecs
+    }
+
+    /// Estimate the 1-norm of matrix `a` using up to `itmax` iterations.
+    pub fn normest1<S>(&mut self, a: &ArrayBase<S, Ix2>, itmax: usize) -> T
+        where S: Data<Elem=T>,
+    {
+        self.calculate(a, itmax)
+    }
+
+    /// Estimate the 1-norm of a marix `a` to the power `m` up to `itmax` iterations.
+    pub fn normest1_pow<S>(&mut self, a: &ArrayBase<S, Ix2>, m: usize, itmax: usize) -> T
+        where S: Data<Elem=T>,
+    {
+        self.calculate(&(a, m), itmax)
+    }
+
+    /// Estimate the 1-norm of a product of matrices `a1 a2 ... an` up to `itmax` iterations.
+    pub fn normest1_prod<S>(&mut self, aprod: &[&ArrayBase<S, Ix2>], itmax: usize) -> T
+        where S: Data<Elem=T>,
+    {
+        self.calculate(aprod, itmax)
+    }
+
+    /// Estimate the 1-norm of a general linear operator `a_linear_operator` acting on vectors
+    /// of length n up to `itmax` iterations.
+    pub(crate) fn normest1_operator<L>(&mut self, a_linear_operator: &L, itmax: usize) -> T
+        where L: LinearOperator<T> + ?Sized
+    {
+        self.calculate(a_linear_operator, itmax)
+    }
+}
+
+fn random_sign<T: Float, R: Rng>(rng: &mut R) -> T {
+    if rng.gen::<bool>() { T::one() } else { -T::one() }
+}
+
+fn resample_column<T, S, R>(a: &mut ArrayBase<S, Ix2>, i: usize, rng: &mut R)
+    where T: Float,
+          S: DataMut<Elem=T>,
+          R: Rng,
+{
+    a.column_mut(i).mapv_inplace(|_| random_sign(rng));
+}
+
+fn matrix_onenorm_with_index<T, S>(a: &ArrayBase<S, Ix2>) -> (usize, T)
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    let mut max_norm = T::zero();
+    let mut max_norm_index = 0;
+    for (i, column) in a.gencolumns().into_iter().enumerate() {
+        let norm = column.fold(T::zero(), |acc, &x| acc + x.abs());
+        if norm > max_norm {
+            max_norm = norm;
+            max_norm_index = i;
+        }
+    }
+    (max_norm_index, max_norm)
+}
+
+/// Two vectors with elements ±1 of length n are parallel if the absolute value of their inner
+/// product is n.
+fn is_parallel<T, S1, S2>(a: &ArrayBase<S1, Ix1>, b: &ArrayBase<S2, Ix1>) -> bool
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=T>,
+{
+    a.dot(b).abs() == T::from_usize(a.len()).unwrap()
+}
+
+/// Marks every column of the sign matrix `a` that is parallel to a preceding column.
+fn find_parallel_columns_in<T, S>(a: &ArrayBase<S, Ix2>, column_is_parallel: &mut [bool])
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    let n_cols = a.cols();
+    for i in 0..n_cols {
+        if column_is_parallel[i] { continue; }
+        for (j, is_parallel_j) in column_is_parallel.iter_mut().enumerate().skip(i+1) {
+            if is_parallel(&a.column(i), &a.column(j)) {
+                *is_parallel_j = true;
+            }
+        }
+    }
+}
+
+/// Marks every column of the sign matrix `a` that is parallel to any column of `b`.
+fn find_parallel_columns_between<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &ArrayBase<S2, Ix2>, column_is_parallel: &mut [bool])
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=T>,
+{
+    for (i, column) in a.gencolumns().into_iter().enumerate() {
+        if column_is_parallel[i] { continue; }
+        column_is_parallel[i] = b.gencolumns().into_iter().any(|other| is_parallel(&column, &other));
+    }
+}
+
+/// Checks whether every column of the sign matrix `b` is parallel to some column of `a`.
+fn are_all_columns_parallel_between<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &ArrayBase<S2, Ix2>) -> bool
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=T>,
+{
+    b.gencolumns().into_iter().all(|column| {
+        a.gencolumns().into_iter().any(|other| is_parallel(&column, &other))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    use crate::float::onenorm;
+
+    use super::Normest1;
+
+    #[test]
+    fn exact_for_nonnegative_matrices() {
+        let n = 6;
+        let a = Array2::from_shape_fn((n, n), |(i, j)| ((3 * i + 5 * j) % 7) as f64);
+        let mut normest1 = Normest1::with_seed(n, 2, 1);
+
+        assert_relative_eq!(normest1.normest1(&a, 5), onenorm(&a), max_relative=1e-14);
+        assert_relative_eq!(normest1.normest1_pow(&a, 3, 5), onenorm(&a.dot(&a).dot(&a)), max_relative=1e-14);
+        assert_relative_eq!(normest1.normest1_prod(&[&a, &a.t().to_owned()], 5), onenorm(&a.dot(&a.t())), max_relative=1e-14);
+    }
+
+    #[test]
+    fn lower_bound_for_general_matrices() {
+        let n = 8;
+        let a = Array2::from_shape_fn((n, n), |(i, j)| (((7 * i + 3 * j) % 11) as f32) - 5.0);
+        let exact = a.gencolumns().into_iter().map(|c| c.fold(0.0, |acc, &x| acc + x.abs())).fold(0.0, f32::max);
+        let mut normest1 = Normest1::with_seed(n, 2, 1);
+
+        let estimate = normest1.normest1(&a, 5);
+        assert!(estimate <= exact * (1.0 + 1e-6));
+        assert!(estimate >= exact / 3.0);
+    }
+
+    #[test]
+    fn seeded_estimates_are_reproducible() {
+        use rand::SeedableRng;
+        use rand_xoshiro::SplitMix64;
+
+        let n = 10;
+        let a = Array2::from_shape_fn((n, n), |(i, j)| (((7 * i + 3 * j) % 11) as f64) - 5.0);
+
+        let mut first = Normest1::with_seed(n, 3, 42);
+        let mut second = Normest1::new(n, 3);
+        second.set_seed(42);
+        assert_eq!(first.normest1(&a, 5), second.normest1(&a, 5));
+        assert_eq!(first.probing_vectors(), second.probing_vectors());
+
+        first.set_rng(&mut SplitMix64::seed_from_u64(7));
+        second.set_rng(&mut SplitMix64::seed_from_u64(7));
+        assert_eq!(first.normest1(&a, 5), second.normest1(&a, 5));
+        assert_eq!(first.probing_vectors(), second.probing_vectors());
+    }
+
+    #[test]
+    fn estimates_do_not_depend_on_previous_calls() {
+        let n = 10;
+        let a = Array2::from_shape_fn((n, n), |(i, j)| (((7 * i + 3 * j) % 11) as f64) - 5.0);
+        let b = Array2::from_shape_fn((n, n), |(i, j)| (((5 * i + 2 * j) % 13) as f64) - 6.0);
+
+        let mut used = Normest1::with_seed(n, 3, 1);
+        used.normest1(&a, 5);
+        used.set_seed(42);
+        let mut fresh = Normest1::with_seed(n, 3, 42);
+
+        assert_eq!(used.normest1(&b, 5), fresh.normest1(&b, 5));
+        assert_eq!(used.probing_vectors(), fresh.probing_vectors());
+    }
+
+    #[test]
+    fn warm_start_does_not_lose_previous_estimate() {
+        let a = arr2(&[[1.0, -2.0, 0.5, 3.0], [0.0, 4.0, -1.0, 1.0], [-2.0, 0.5, 1.0, 0.0], [1.0, 1.0, -3.0, 2.0]]);
+        let mut normest1 = Normest1::with_seed(4, 2, 1);
+
+        let cold = normest1.normest1(&a, 5);
+        let x = normest1.probing_vectors().to_owned();
+
+        // Starting from the previous probing vectors, the first iteration already reproduces the
+        // previous estimate.
+        let mut other = Normest1::new(4, 2);
+        other.set_probing_vectors(&x);
+        let warm = other.normest1(&a, 2);
+        assert!(warm >= cold);
+        assert!(warm <= onenorm(&a));
+
+        normest1.set_warm_start(true);
+        let perturbed = &a * 1.01;
+        let warm_perturbed = normest1.normest1(&perturbed, 2);
+        assert!(warm_perturbed >= 1.01 * cold * (1.0 - 1e-12));
+    }
+
+    #[test]
+    fn warm_start_saves_matvecs() {
+        // With a single probing vector, the cold start is deterministic. All entries are
+        // multiples of 1/32, so that the products are exact.
+        let n = 8;
+        let a = Array2::from_shape_fn((n, n), |(i, j)| (((3 * i + 4 * j) % 11) as f64) - 5.0);
+        let perturbed = Array2::from_shape_fn((n, n), |(i, j)| a[(i, j)] + if (i + j) % 3 == 0 { 1.0 / 16.0 } else { -1.0 / 32.0 });
+
+        let mut warm = Normest1::new(n, 1);
+        warm.normest1(&a, 5);
+        warm.set_warm_start(true);
+        let matvecs_before = warm.matvecs();
+        let warm_estimate = warm.normest1(&perturbed, 5);
+        let warm_matvecs = warm.matvecs() - matvecs_before;
+
+        let mut cold = Normest1::new(n, 1);
+        let cold_estimate = cold.normest1(&perturbed, 5);
+
+        assert!(warm_matvecs < cold.matvecs(), "warm {} vs cold {} matvecs", warm_matvecs, cold.matvecs());
+        assert_eq!(warm_estimate, onenorm(&perturbed));
+        assert!(cold_estimate <= warm_estimate);
+    }
+}
@@ -0,0 +1,240 @@
+//! Options controlling the accuracy and cost of `expm`, and a report of what the algorithm did.
+//!
+//! The tolerance replaces the unit roundoff in the selection of the Padé order and the scaling
+//! parameter: the thresholds $\theta_m$ are relaxed as in `theta_for_tolerance`, and the
+//! correction $\ell$ of the scaling parameter compares $\alpha$ to the tolerance. A forced Padé
+//! order skips the selection and only chooses the scaling parameter. The report contains the
+//! chosen order and scaling, and the bound $\tilde{h}_{2m+1}(\theta)/\theta$ on the relative
+//! backward error evaluated at $\theta = 2^{-s} \eta$, where $\eta$ is the norm parameter that
+//! determined the selection. For the orders selected without scaling, $\eta$ may underestimate
+//! $\lVert A \rVert_1$, and the bound then holds with $\rho(A)$-like quantities in place of the
+//! norm, see [Al-Mohy, Higham 2009, Theorem 4.2]. With balancing, the norm parameter and the
+//! bound refer to the balanced matrix.
+//!
+//! [Al-Mohy, Higham 2009]: https://doi.org/10.1137/09074721X
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    crosscheck::TaylorReference,
+    pade_backward_error_bound,
+    Counters,
+    CrossCheck,
+    Expm,
+    Float,
+};
+
+/// Options for the calculation of the matrix exponential.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExpmOptions<T> {
+    /// The target relative backward error. Defaults to the unit roundoff of `T`.
+    pub tolerance: T,
+    /// The maximum number of squarings. Limiting the squarings may increase the backward error
+    /// beyond the tolerance. Defaults to no limit.
+    pub max_squarings: Option<u32>,
+    /// The Padé order to use instead of the automatic selection, one of 3, 5, 7, 9, or 13.
+    /// Defaults to the automatic selection.
+    pub pade_order: Option<u64>,
+    /// Whether to accumulate the squarings in double-word arithmetic, which is more accurate for
+    /// many squarings but considerably slower. Defaults to `false`.
+    pub extended_squaring: bool,
+    /// Whether to balance the matrix by a diagonal similarity transformation before the
+    /// calculation. The balanced matrix is only used if its 1-norm is smaller. Defaults to
+    /// `false`.
+    pub balance: bool,
+    /// Whether truncated Taylor series accumulate their terms with compensated summation, which
+    /// reduces the rounding errors for many terms at the cost of a few more additions per term.
+    /// Only `ExpmProduct::apply` and the reference calculation of the cross-check evaluate such
+    /// series. Defaults to `false`.
+    pub compensated_summation: bool,
+    /// Whether the matrix is symmetric, so that the exponential is calculated from its
+    /// eigendecomposition as in `ExpmSym` instead of by scaling and squaring. Only the upper
+    /// triangle is then referenced, and the other options are ignored unless the
+    /// eigendecomposition does not converge. Defaults to `false`.
+    pub symmetric: bool,
+    /// Whether to verify the result against an independent calculation with a truncated Taylor
+    /// series as in `ExpmCrossCheck`, reporting the discrepancy in the report. This more than
+    /// doubles the cost, and the products of the cross-check are not included in the counters.
+    /// Defaults to `false`.
+    pub cross_check: bool,
+}
+
+impl<T: Float> Default for ExpmOptions<T> {
+    fn default() -> Self {
+        ExpmOptions {
+            tolerance: T::unit_roundoff(),
+            max_squarings: None,
+            pade_order: None,
+            extended_squaring: false,
+            balance: false,
+            compensated_summation: false,
+            symmetric: false,
+            cross_check: false,
+        }
+    }
+}
+
+/// A report of the choices made during the calculation of a matrix exponential.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExpmReport<T> {
+    /// The order $m$ of the Padé approximant.
+    pub pade_order: u64,
+    /// The scaling parameter $s$, i.e. the number of squarings.
+    pub squarings: i32,
+    /// The norm parameter $\eta$ of the unscaled matrix that determined the order and scaling.
+    pub norm_parameter: T,
+    /// The bound on the relative backward error of the Padé approximant at $2^{-s} \eta$.
+    pub backward_error: T,
+    /// Whether any of the norm estimates overflowed.
+    pub norm_overflow: bool,
+    /// Whether the exponential was calculated from the balanced matrix.
+    pub balanced: bool,
+    /// Whether the exponential was calculated from the eigendecomposition of the symmetric
+    /// matrix. The order, scaling, norm parameter, and backward error are then zero.
+    pub symmetric: bool,
+    /// Whether non-finite intermediate values or a singular denominator of the Padé approximant
+    /// appeared, and the recalculation with a conservative scaling parameter from the exact 1-norm
+    /// produced a finite result. A recalculation that fails as well leaves this `false` and sets
+    /// `non_finite` instead. In both cases the order, scaling, and norm parameter describe the
+    /// recalculation.
+    pub recovered: bool,
+    /// Whether the result contains non-finite values, e.g. because the exponential overflows. A
+    /// denominator of the Padé approximant that stays singular after the recalculation leaves
+    /// the result filled with NaN.
+    pub non_finite: bool,
+    /// The counts of the operations performed.
+    pub counters: Counters,
+    /// The comparison to the Taylor method if the cross-check was requested in the options.
+    pub cross_check: Option<CrossCheck<T>>,
+}
+
+impl<T: Float> ExpmReport<T> {
+    pub(crate) fn new() -> Self {
+        ExpmReport {
+            pade_order: 0,
+            squarings: 0,
+            norm_parameter: T::zero(),
+            backward_error: T::zero(),
+            norm_overflow: false,
+            balanced: false,
+            symmetric: false,
+            recovered: false,
+            non_finite: false,
+            counters: Counters::default(),
+            cross_check: None,
+        }
+    }
+}
+
+impl<T: Float> Expm<T> {
+    /// Calculate the matrix exponential of the n×n matrix `a` with the given `options`, storing
+    /// the result in matrix `b` and returning a report of the calculation.
+    ///
+    /// NOTE: Panics if the forced Padé order is not supported for the precision `T`, or if input
+    /// matrices `a` and `b` don't have matching dimensions, are not square, not in row-major
+    /// order, or don't have the same dimension as the `Expm` object `expm_with_opts` is called
+    /// on.
+    pub fn expm_with_opts<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>, options: &ExpmOptions<T>) -> ExpmReport<T>
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        self.options = *options;
+        self.expm(a, b);
+        self.options = ExpmOptions::default();
+
+        let mut report = self.report;
+        if !report.symmetric {
+            let theta = report.norm_parameter / T::from_f64(2.0).unwrap().powi(report.squarings);
+            let bound = pade_backward_error_bound(report.pade_order, theta.to_f64().unwrap_or(f64::INFINITY));
+            report.backward_error = T::from_f64(bound).unwrap();
+        }
+        report.counters = self.counters();
+        if options.cross_check {
+            let n = self.n;
+            let reference = self.reference.get_or_insert_with(|| TaylorReference::new(n));
+            report.cross_check = Some(reference.compare(a, b, options.compensated_summation));
+        }
+        report
+    }
+}
+
+/// Calculate the matrix exponential of the n×n matrix `a` with the given `options`, storing the
+/// result in matrix `b` and returning a report of the calculation.
+///
+/// NOTE: Panics if the forced Padé order is not supported for the precision `T`, or if input
+/// matrices `a` and `b` don't have matching dimensions, are not square, or not in row-major
+/// order.
+pub fn expm_with_opts<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>, options: &ExpmOptions<T>) -> ExpmReport<T>
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut expm = Expm::new(n);
+    expm.expm_with_opts(a, b, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    use crate::ExpmOptions;
+
+    fn matrix() -> Array2<f64> {
+        arr2(&[[-10.0, 20.0, 0.0], [5.0, -30.0, 10.0], [0.0, 2.5, -5.0]])
+    }
+
+    #[test]
+    fn default_report_describes_calculation() {
+        let a = matrix();
+        let mut b = Array2::<f64>::zeros((3, 3));
+        let mut expected = Array2::<f64>::zeros((3, 3));
+
+        let report = crate::expm_with_opts(&a, &mut b, &ExpmOptions::default());
+        crate::expm(&a, &mut expected);
+
+        assert_eq!(b, expected);
+        assert_eq!(report.pade_order, 13);
+        assert!(report.squarings > 0);
+        assert!(report.backward_error <= f64::EPSILON / 2.0);
+        assert!(!report.norm_overflow);
+        assert_eq!(report.counters.linear_solves, 1);
+    }
+
+    #[test]
+    fn options_trade_accuracy_for_cost() {
+        let a = matrix();
+        let mut expected = Array2::<f64>::zeros((3, 3));
+        crate::expm(&a, &mut expected);
+
+        let mut b = Array2::<f64>::zeros((3, 3));
+        let default = crate::expm_with_opts(&a, &mut b, &ExpmOptions::default());
+
+        let coarse = ExpmOptions { tolerance: 1e-8, ..ExpmOptions::default() };
+        let report = crate::expm_with_opts(&a, &mut b, &coarse);
+        assert!(report.counters.matrix_products < default.counters.matrix_products);
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-6, epsilon=1e-10);
+        }
+
+        let forced = ExpmOptions { pade_order: Some(7), ..ExpmOptions::default() };
+        let report = crate::expm_with_opts(&a, &mut b, &forced);
+        assert_eq!(report.pade_order, 7);
+        assert!(report.squarings > default.squarings);
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-14);
+        }
+
+        let limited = ExpmOptions { max_squarings: Some(1), ..ExpmOptions::default() };
+        let report = crate::expm_with_opts(&a, &mut b, &limited);
+        assert_eq!(report.squarings, 1);
+        assert!(report.backward_error > default.backward_error);
+    }
+}
@@ -0,0 +1,254 @@
+//! The $\varphi$-functions of exponential integrators,
+//!
+//! \begin{equation}
+//!     \varphi_0(z) = e^z, \qquad \varphi_k(z) = \int_0^1 e^{(1-s)z} \frac{s^{k-1}}{(k-1)!} \mathrm{d}s
+//!     = \sum_{j=0}^\infty \frac{z^j}{(j+k)!},
+//! \end{equation}
+//!
+//! evaluated at matrices $hA$. Following [Sidje 1998] and [Al-Mohy and Higham 2011], they are
+//! read off the exponential of an augmented block matrix, so that they inherit the backward error
+//! control of `expm`. For the matrices $\varphi_1(hA), \dots, \varphi_p(hA)$, the
+//! $(p+1)n \times (p+1)n$ matrix
+//!
+//! \begin{equation}
+//!     C = \begin{pmatrix}
+//!         hA & I & & \\
+//!            & 0 & \ddots & \\
+//!            &   & \ddots & I \\
+//!            &   &        & 0
+//!     \end{pmatrix}
+//! \end{equation}
+//!
+//! is exponentiated, whose first block row is $(e^{hA}, \varphi_1(hA), \dots, \varphi_p(hA))$. For
+//! the action on a vector $v$, the much smaller $(n+p) \times (n+p)$ matrix
+//!
+//! \begin{equation}
+//!     \tilde C = \begin{pmatrix} hA & v e_1^\top \\ 0 & J_p \end{pmatrix}
+//! \end{equation}
+//!
+//! with the p×p shift matrix $J_p$ suffices, where column $k$ of the upper right block of
+//! $e^{\tilde C}$ is $\varphi_k(hA) v$, and its upper left block is $e^{hA}$ itself, whose product
+//! with $v$ is the action of the exponential.
+//!
+//! [Sidje 1998]: https://doi.org/10.1145/285861.285868
+//! [Al-Mohy and Higham 2011]: https://doi.org/10.1137/100788860
+
+use ndarray::{
+    self,
+    linalg::general_mat_vec_mul,
+    prelude::*,
+    s,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    Expm,
+    Float,
+};
+
+/// Storage for calculating the matrices $\varphi_0(hA), \dots, \varphi_p(hA)$ for n×n matrices.
+pub struct ExpmPhi<T = f64> {
+    n: usize,
+    p: usize,
+    expm: Expm<T>,
+    c: Array2<T>,
+    f: Array2<T>,
+}
+
+impl<T: Float> ExpmPhi<T> {
+    /// Allocates all space to calculate $\varphi_0, \dots, \varphi_p$ for n×n matrices. The
+    /// augmented block matrix is of dimension (p+1)n×(p+1)n.
+    pub fn new(n: usize, p: usize) -> Self {
+        let m = (p + 1) * n;
+        ExpmPhi {
+            n,
+            p,
+            expm: Expm::new(m),
+            c: Array2::zeros((m, m)),
+            f: Array2::zeros((m, m)),
+        }
+    }
+
+    /// Calculate $\varphi_k(hA)$ for the n×n matrix `a` and $k = 0, \dots, p$, storing
+    /// $\varphi_k(hA)$ in `b[k]`. In particular, `b[0]` is $e^{hA}$.
+    ///
+    /// NOTE: Panics if `a` is not square, if `b` is not of shape (p+1)×n×n, or if `a` doesn't
+    /// have the same dimension as the `ExpmPhi` object `phi` is called on.
+    pub fn phi<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, h: T, b: &mut ArrayBase<S2, Ix3>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        let n = self.n;
+        let p = self.p;
+        assert_eq!(a.dim(), (n, n), "Dimension mismatch between matrix `a` and preconfigured `ExpmPhi` struct.");
+        assert_eq!(b.dim(), (p + 1, n, n), "Output `b` has to be of shape (p+1)×n×n.");
+
+        self.c.fill(T::zero());
+        self.c.slice_mut(s![..n, ..n]).zip_mut_with(a, |x, &y| *x = h * y);
+        for k in 0..p {
+            self.c.slice_mut(s![k*n..(k+1)*n, (k+1)*n..(k+2)*n]).diag_mut().fill(T::one());
+        }
+
+        self.expm.expm(&self.c, &mut self.f);
+
+        for (k, mut phi_k) in b.outer_iter_mut().enumerate() {
+            phi_k.assign(&self.f.slice(s![..n, k*n..(k+1)*n]));
+        }
+    }
+}
+
+/// Storage for calculating the vectors $\varphi_0(hA) v, \dots, \varphi_p(hA) v$ for n×n
+/// matrices.
+pub struct ExpmPhiAction<T = f64> {
+    n: usize,
+    p: usize,
+    expm: Expm<T>,
+    c: Array2<T>,
+    f: Array2<T>,
+}
+
+impl<T: Float> ExpmPhiAction<T> {
+    /// Allocates all space to calculate the action of $\varphi_0, \dots, \varphi_p$ for n×n
+    /// matrices. The augmented block matrix is of dimension (n+p)×(n+p).
+    pub fn new(n: usize, p: usize) -> Self {
+        let m = n + p;
+        ExpmPhiAction {
+            n,
+            p,
+            expm: Expm::new(m),
+            c: Array2::zeros((m, m)),
+            f: Array2::zeros((m, m)),
+        }
+    }
+
+    /// Calculate $\varphi_k(hA) v$ for the n×n matrix `a`, the vector `v`, and $k = 0, \dots,
+    /// p$, storing $\varphi_k(hA) v$ in `w[k]`. In particular, `w[0]` is $e^{hA} v$.
+    ///
+    /// NOTE: Panics if `a` is not square, if `v` doesn't match its dimension, if `w` is not of
+    /// shape (p+1)×n, or if `a` doesn't have the same dimension as the `ExpmPhiAction` object
+    /// `phi_action` is called on.
+    pub fn phi_action<S1, S2, S3>(&mut self, a: &ArrayBase<S1, Ix2>, h: T, v: &ArrayBase<S2, Ix1>, w: &mut ArrayBase<S3, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: Data<Elem=T>,
+              S3: DataMut<Elem=T>,
+    {
+        let n = self.n;
+        let p = self.p;
+        assert_eq!(a.dim(), (n, n), "Dimension mismatch between matrix `a` and preconfigured `ExpmPhiAction` struct.");
+        assert_eq!(v.len(), n, "Dimension mismatch between vector `v` and preconfigured `ExpmPhiAction` struct.");
+        assert_eq!(w.dim(), (p + 1, n), "Output `w` has to be of shape (p+1)×n.");
+
+        self.c.fill(T::zero());
+        self.c.slice_mut(s![..n, ..n]).zip_mut_with(a, |x, &y| *x = h * y);
+        if p > 0 {
+            self.c.slice_mut(s![..n, n]).assign(v);
+        }
+        for k in 1..p {
+            self.c[(n + k - 1, n + k)] = T::one();
+        }
+
+        self.expm.expm(&self.c, &mut self.f);
+
+        for k in 1..=p {
+            w.row_mut(k).assign(&self.f.slice(s![..n, n + k - 1]));
+        }
+
+        // Forming e^{hA} v as v + hA φ₁(hA) v instead would cancel for stiff, decaying hA.
+        general_mat_vec_mul(T::one(), &self.f.slice(s![..n, ..n]), v, T::zero(), &mut w.row_mut(0));
+    }
+}
+
+/// Calculate $\varphi_k(hA)$ for the n×n matrix `a` and $k = 0, \dots, p$, storing
+/// $\varphi_k(hA)$ in `b[k]`, where p+1 is the length of the first axis of `b`.
+///
+/// NOTE: Panics if `a` is not square, or if `b` is not of shape (p+1)×n×n.
+pub fn expm_phi<T, S1, S2>(a: &ArrayBase<S1, Ix2>, h: T, b: &mut ArrayBase<S2, Ix3>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+    let (p_plus_one, _, _) = b.dim();
+    assert!(p_plus_one > 0, "Output `b` has to hold at least φ₀.");
+
+    let mut phi = ExpmPhi::new(n, p_plus_one - 1);
+    phi.phi(a, h, b);
+}
+
+/// Calculate $\varphi_k(hA) v$ for the n×n matrix `a`, the vector `v`, and $k = 0, \dots, p$,
+/// storing $\varphi_k(hA) v$ in `w[k]`, where p+1 is the number of rows of `w`.
+///
+/// NOTE: Panics if `a` is not square, if `v` doesn't match its dimension, or if `w` is not of
+/// shape (p+1)×n.
+pub fn expm_phi_action<T, S1, S2, S3>(a: &ArrayBase<S1, Ix2>, h: T, v: &ArrayBase<S2, Ix1>, w: &mut ArrayBase<S3, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=T>,
+          S3: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+    let (p_plus_one, _) = w.dim();
+    assert!(p_plus_one > 0, "Output `w` has to hold at least φ₀(hA) v.");
+
+    let mut phi = ExpmPhiAction::new(n, p_plus_one - 1);
+    phi.phi_action(a, h, v, w);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn phi_of_diagonal_matrix() {
+        let lambda = [-2.0, -0.5, 1e-3];
+        let a = Array2::from_shape_fn((3, 3), |(i, j)| if i == j { lambda[i] } else { 0.0 });
+        let h = 0.7;
+        let mut b = Array3::<f64>::zeros((3, 3, 3));
+
+        crate::expm_phi(&a, h, &mut b);
+
+        for (i, &l) in lambda.iter().enumerate() {
+            let z: f64 = h * l;
+            let phi = [z.exp(), z.exp_m1() / z, (z.exp_m1() - z) / (z * z)];
+            for (k, &expected) in phi.iter().enumerate() {
+                assert_relative_eq!(b[(k, i, i)], expected, max_relative=1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn phi_action_agrees_with_phi_matrices() {
+        let a = arr2(&[[-1.0, 2.0, 0.0], [0.5, -3.0, 1.0], [0.0, 0.25, -0.5]]);
+        let v = arr1(&[1.0, -2.0, 0.5]);
+        let h = 1.5;
+        let mut b = Array3::<f64>::zeros((4, 3, 3));
+        let mut w = Array2::<f64>::zeros((4, 3));
+
+        crate::expm_phi(&a, h, &mut b);
+        crate::expm_phi_action(&a, h, &v, &mut w);
+
+        for (phi_k, w_k) in b.outer_iter().zip(w.outer_iter()) {
+            for (&x, &y) in w_k.iter().zip(phi_k.dot(&v).iter()) {
+                assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-14);
+            }
+        }
+    }
+
+    #[test]
+    fn phi_action_of_stiff_matrix() {
+        let a = Array2::<f64>::eye(2);
+        let v = arr1(&[1.0, -2.0]);
+        let mut w = Array2::<f64>::zeros((2, 2));
+
+        crate::expm_phi_action(&a, -700.0, &v, &mut w);
+
+        for (&x, &y) in w.row(0).iter().zip(v.iter()) {
+            assert_relative_eq!(x, (-700f64).exp() * y, max_relative=1e-12);
+        }
+        for (&x, &y) in w.row(1).iter().zip(v.iter()) {
+            assert_relative_eq!(x, y / 700.0, max_relative=1e-12);
+        }
+    }
+}
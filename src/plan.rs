@@ -0,0 +1,243 @@
+//! Scaling plans for exponentials $e^{tA}$ of a fixed matrix at many times $t$.
+//!
+//! All quantities that determine the Padé order and the scaling parameter are homogeneous in $t$:
+//! the powers satisfy $(tA)^k = t^k A^k$, the norm parameters $d_k = \lVert A^k \rVert_1^{1/k}$
+//! of [Al-Mohy and Higham 2009] scale as $d_k(tA) = \lvert t\rvert d_k(A)$, and the quantity
+//! $\alpha_m = \lvert c_{2m+1}\rvert \lVert \lvert A\rvert^{2m+1} \rVert_1 / \lVert A\rVert_1$
+//! of the helper $\ell$ scales as $\alpha_m(tA) = \lvert t\rvert^{2m} \alpha_m(A)$. A plan
+//! therefore calculates the powers $A^2$, $A^4$, $A^6$, $A^8$ and all norm estimates once. For
+//! every $t$, the order and scaling are then selected from scalars, and the scaled powers are
+//! obtained by scalar multiplication, leaving only the evaluation of the approximant and the
+//! squarings.
+//!
+//! [Al-Mohy and Higham 2009]: https://doi.org/10.1137/09074721X
+
+use ndarray::{
+    self,
+    prelude::*,
+    s,
+    Data,
+    DataMut,
+    Zip,
+};
+
+use crate::{
+    float::gemm,
+    options::ExpmReport,
+    pade_error_coefficient,
+    recovery,
+    schur::diagonal_blocks,
+    triangular::recompute_diagonal,
+    Counters,
+    Expm,
+    Float,
+    PadeOrders,
+};
+
+/// The Padé orders in the order of the norm estimates `alpha` of a `ScalingPlan`.
+const ORDERS: [u64; 5] = [3, 5, 7, 9, 13];
+
+/// The powers and norm estimates of an n×n matrix $A$ that determine the Padé order and scaling
+/// of $e^{tA}$ for any $t$.
+pub(crate) struct ScalingPlan<T> {
+    a: Array2<T>,
+    a2: Array2<T>,
+    a4: Array2<T>,
+    a6: Array2<T>,
+    a8: Array2<T>,
+    /// The estimate of $\lVert A\rVert_1$.
+    norm: T,
+    /// The norm parameters $d_4$, $d_6$, $d_8$, and $d_{10}$.
+    d: [T; 4],
+    /// The quantities $\alpha_m$ of the helper $\ell$ for the orders in `ORDERS`.
+    alpha: [T; 5],
+    is_triangular: bool,
+    blocks: Vec<(usize, usize)>,
+    scaled: Array2<T>,
+}
+
+impl<T: Float> ScalingPlan<T> {
+    /// Allocates all space to plan the exponentials of n×n matrices.
+    pub(crate) fn new(n: usize) -> Self {
+        ScalingPlan {
+            a: Array2::zeros((n, n)),
+            a2: Array2::zeros((n, n)),
+            a4: Array2::zeros((n, n)),
+            a6: Array2::zeros((n, n)),
+            a8: Array2::zeros((n, n)),
+            norm: T::zero(),
+            d: [T::zero(); 4],
+            alpha: [T::zero(); 5],
+            is_triangular: false,
+            blocks: Vec::new(),
+            scaled: Array2::zeros((n, n)),
+        }
+    }
+}
+
+impl<T: Float> Expm<T> {
+    /// Calculate the powers and norm estimates of the n×n matrix `a` for the exponentials
+    /// $e^{ta}$, storing them in `plan`.
+    ///
+    /// NOTE: Panics if `a` doesn't have the same dimension as the `Expm` object `plan` is called
+    /// on.
+    pub(crate) fn plan<S>(&mut self, a: &ArrayBase<S, Ix2>, plan: &mut ScalingPlan<T>)
+        where S: Data<Elem=T>,
+    {
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `Expm` struct.");
+
+        let root = |p: f64| T::one() / T::from_f64(p).unwrap();
+
+        plan.a.assign(a);
+        plan.is_triangular = false;
+        gemm(T::one(), &plan.a, &plan.a, T::zero(), &mut plan.a2);
+        gemm(T::one(), &plan.a2, &plan.a2, T::zero(), &mut plan.a4);
+        gemm(T::one(), &plan.a2, &plan.a4, T::zero(), &mut plan.a6);
+        gemm(T::one(), &plan.a4, &plan.a4, T::zero(), &mut plan.a8);
+
+        plan.norm = self.normest1.normest1(&plan.a, self.itmax);
+        plan.d = [
+            self.normest1.normest1(&plan.a4, self.itmax).powf(root(4.0)),
+            self.normest1.normest1(&plan.a6, self.itmax).powf(root(6.0)),
+            self.normest1.normest1(&plan.a8, self.itmax).powf(root(8.0)),
+            self.normest1.normest1_prod(&[&plan.a4, &plan.a6], self.itmax).powf(root(10.0)),
+        ];
+
+        Zip::from(&mut self.a_abs)
+            .and(&plan.a)
+            .apply(|x, &y| *x = y.abs());
+        for (alpha, &m) in plan.alpha.iter_mut().zip(ORDERS.iter()) {
+            let c2m1 = T::from_f64(pade_error_coefficient(m)).unwrap();
+            let norm_abs_a_2m1 = self.normest1.normest1_pow(&self.a_abs, 2 * m as usize + 1, self.itmax);
+            *alpha = c2m1.abs() * norm_abs_a_2m1 / plan.norm;
+        }
+    }
+
+    /// Calculate the powers and norm estimates of the upper quasi-triangular n×n matrix `a` for
+    /// the exponentials $e^{ta}$, storing them in `plan`. The diagonal blocks of $e^{ta}$ are
+    /// recalculated exactly as in `expm_triangular`.
+    ///
+    /// NOTE: Panics if `a` is not upper quasi-triangular, or doesn't have the same dimension as
+    /// the `Expm` object `plan_triangular` is called on.
+    pub(crate) fn plan_triangular<S>(&mut self, a: &ArrayBase<S, Ix2>, plan: &mut ScalingPlan<T>)
+        where S: Data<Elem=T>,
+    {
+        self.plan(a, plan);
+
+        diagonal_blocks(a, &mut plan.blocks);
+        for &(k, p) in &plan.blocks {
+            for row in k..k+p {
+                assert!(a.row(row).slice(s![..k]).iter().all(|&x| x == T::zero()), "Matrix `a` is not upper quasi-triangular.");
+            }
+        }
+        plan.is_triangular = true;
+    }
+
+    /// Calculate $e^{ta}$ for the matrix $a$ of the `plan`, storing the result in `b`. Only the
+    /// approximant and the squarings are evaluated; balancing is not applied. Results that are
+    /// not finite are recalculated from scratch by `expm`, or `expm_triangular`, of $ta$.
+    ///
+    /// NOTE: Panics if `b` doesn't have the same dimension as the `Expm` object `expm_planned`
+    /// is called on.
+    pub(crate) fn expm_planned<S>(&mut self, plan: &mut ScalingPlan<T>, t: T, b: &mut ArrayBase<S, Ix2>)
+        where S: DataMut<Elem=T>,
+    {
+        assert_eq!(b.dim(), (self.n, self.n), "Dimension mismatch between matrix `b` and preconfigured `Expm` struct.");
+
+        self.counters = Counters::default();
+        self.matvecs_before = self.normest1.matvecs();
+        self.report = ExpmReport::new();
+
+        let scale = t.abs();
+        let (pade_order, eta, is_scaled) = match self.options.pade_order {
+            Some(m) => (PadeOrders::from_order::<T>(m), plan.norm, true),
+            None => self.select_planned_order(plan, scale),
+        };
+        let m = pade_order.order();
+
+        let mut s = 0;
+        if is_scaled {
+            let two = T::from_f64(2.0).unwrap();
+            s = (scale * eta / self.theta(m)).log2().ceil().to_i32().unwrap_or(0).max(0);
+            if let Some(max_squarings) = self.options.max_squarings {
+                s = s.min(max_squarings as i32);
+            }
+            s += self.planned_ell(plan, m, scale / two.powi(s));
+            if let Some(max_squarings) = self.options.max_squarings {
+                s = s.min(max_squarings as i32);
+            }
+        }
+
+        self.report.pade_order = m;
+        self.report.squarings = s;
+        self.report.norm_parameter = scale * eta;
+
+        // The powers of the scaled matrix 2^{-s} t A follow from the planned powers of A.
+        let factor = t / T::from_f64(2.0).unwrap().powi(s);
+        let (factor_2, factor_4, factor_6, factor_8) = (factor.powi(2), factor.powi(4), factor.powi(6), factor.powi(8));
+        self.a1.zip_mut_with(&plan.a, |x, &y| *x = factor * y);
+        self.a2.zip_mut_with(&plan.a2, |x, &y| *x = factor_2 * y);
+        self.a4.zip_mut_with(&plan.a4, |x, &y| *x = factor_4 * y);
+        self.a6.zip_mut_with(&plan.a6, |x, &y| *x = factor_6 * y);
+        if m == 9 {
+            self.a8.zip_mut_with(&plan.a8, |x, &y| *x = factor_8 * y);
+        }
+
+        std::mem::swap(&mut self.blocks, &mut plan.blocks);
+        self.is_triangular = plan.is_triangular;
+        let is_finite = self.solve_via_pade(pade_order, b)
+            && recovery::all_finite(b)
+            && self.square_checked(s, b);
+        self.is_triangular = false;
+        std::mem::swap(&mut self.blocks, &mut plan.blocks);
+
+        if !is_finite {
+            plan.scaled.zip_mut_with(&plan.a, |x, &y| *x = t * y);
+            if plan.is_triangular {
+                self.expm_triangular(&plan.scaled, b);
+            } else {
+                self.expm(&plan.scaled, b);
+            }
+        } else if plan.is_triangular {
+            recompute_diagonal(&plan.a, t, &plan.blocks, b);
+        }
+    }
+
+    /// Selects the Padé order for $e^{ta}$ with $\lvert t\rvert$ = `scale` as `select_order`
+    /// does, but from the norm estimates of the `plan`. Returns the order, the norm parameter of
+    /// the unscaled matrix $a$, and whether scaling is required.
+    fn select_planned_order(&mut self, plan: &ScalingPlan<T>, scale: T) -> (PadeOrders, T, bool) {
+        let [d4, d6, d8, d10] = plan.d;
+        let eta_1 = d4.max(d6);
+        let eta_3 = d6.max(d8);
+        let eta_5 = eta_3.min(d8.max(d10));
+
+        if scale * eta_1 <= self.theta(3) && self.planned_ell(plan, 3, scale) == 0 {
+            return (PadeOrders::_3, eta_1, false);
+        }
+        if scale * eta_1 <= self.theta(5) && self.planned_ell(plan, 5, scale) == 0 {
+            return (PadeOrders::_5, eta_1, false);
+        }
+        if scale * eta_3 <= self.theta(7) && self.planned_ell(plan, 7, scale) == 0 {
+            return (PadeOrders::_7, eta_3, false);
+        }
+        if T::MAX_PADE_ORDER == 7 {
+            return (PadeOrders::_7, eta_3, true);
+        }
+        if scale * eta_3 <= self.theta(9) && self.planned_ell(plan, 9, scale) == 0 {
+            return (PadeOrders::_9, eta_3, false);
+        }
+
+        (PadeOrders::_13, eta_5, true)
+    }
+
+    /// The helper $\ell$ of order `m` for the matrix `scale` times the matrix of the `plan`,
+    /// i.e. $\max(\lceil \log_2(\text{scale}^{2m} \alpha_m/u) / 2m \rceil, 0)$.
+    fn planned_ell(&self, plan: &ScalingPlan<T>, m: u64, scale: T) -> i32 {
+        let index = ORDERS.iter().position(|&order| order == m).expect("Unsupported Padé order.");
+        let two_m = T::from_u64(2 * m).unwrap();
+        let log2_alpha = (plan.alpha[index] / self.options.tolerance).log2() + two_m * scale.log2();
+
+        (log2_alpha / two_m).ceil().to_i32().unwrap_or(0).max(0)
+    }
+}
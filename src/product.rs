@@ -0,0 +1,284 @@
+//! Ordered products of exponentials $e^{A_1} e^{A_2} \cdots e^{A_k}$, as they arise in splitting
+//! methods and pulse sequences.
+//!
+//! The product can either be formed explicitly, reusing a single set of buffers for all factors,
+//! or be applied to an n×m block of vectors $X$ without forming any dense exponential. In the
+//! latter case, the factors are applied from the right, each one by a truncated Taylor series
+//! following [Al-Mohy and Higham 2011]: the factor is shifted by $\mu = \operatorname{tr}(A)/n$ to
+//! reduce its norm, and split into $s$ steps such that $\lVert A - \mu I \rVert_1 / s \le 1$,
+//!
+//! \begin{equation}
+//!     e^A X = \left(e^{\mu/s} T\left(\frac{A - \mu I}{s}\right)\right)^s X,
+//! \end{equation}
+//!
+//! where the Taylor polynomial $T$ is truncated once two consecutive terms are negligible
+//! relative to the partial sum. This only requires products of n×n matrices with n×m blocks.
+//!
+//! [Al-Mohy and Higham 2011]: https://doi.org/10.1137/100788860
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    extended::add_compensated,
+    float::{
+        gemm,
+        onenorm,
+    },
+    Expm,
+    ExpmOptions,
+    Float,
+};
+
+/// The maximum degree of the Taylor polynomial per step. For $\lVert A \rVert_1 / s \le 1$, the
+/// truncation error drops below the unit roundoff in double precision after about 18 terms.
+const MAX_TAYLOR_DEGREE: usize = 40;
+
+/// Storage for calculating ordered products of exponentials of n×n matrices.
+pub struct ExpmProduct<T = f64> {
+    n: usize,
+    expm: Expm<T>,
+    exp_a: Array2<T>,
+    work: Array2<T>,
+    shifted: Array2<T>,
+    block_term: Array2<T>,
+    block_work: Array2<T>,
+    block_sum: Array2<T>,
+    block_compensation: Array2<T>,
+    options: ExpmOptions<T>,
+}
+
+impl<T: Float> ExpmProduct<T> {
+    /// Allocates all space to calculate products of exponentials of n×n matrices. The buffers
+    /// for applying products to blocks of vectors are allocated on first use.
+    pub fn new(n: usize) -> Self {
+        ExpmProduct {
+            n,
+            expm: Expm::new(n),
+            exp_a: Array2::zeros((n, n)),
+            work: Array2::zeros((n, n)),
+            shifted: Array2::zeros((n, n)),
+            block_term: Array2::zeros((n, 0)),
+            block_work: Array2::zeros((n, 0)),
+            block_sum: Array2::zeros((n, 0)),
+            block_compensation: Array2::zeros((n, 0)),
+            options: ExpmOptions::default(),
+        }
+    }
+
+    /// Use `options` for the exponentials formed by `product`, and for the Taylor series in
+    /// `apply`, of which only the compensated summation applies.
+    pub fn set_options(&mut self, options: &ExpmOptions<T>) {
+        self.options = *options;
+    }
+
+    /// If `compensated` is set, the terms of the Taylor series in `apply` are accumulated with
+    /// compensated summation, see `ExpmOptions::compensated_summation`.
+    pub fn set_compensated_summation(&mut self, compensated: bool) {
+        self.options.compensated_summation = compensated;
+    }
+
+    /// Calculate $e^{A_1} e^{A_2} \cdots e^{A_k}$ for the n×n matrices in `factors`, storing the
+    /// result in `b`. An empty product is the identity.
+    ///
+    /// NOTE: Panics if the factors and `b` are not square, or don't have the same dimension as
+    /// the `ExpmProduct` object `product` is called on.
+    pub fn product<S1, S2>(&mut self, factors: &[&ArrayBase<S1, Ix2>], b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert_eq!(b.dim(), (self.n, self.n), "Dimension mismatch between matrix `b` and preconfigured `ExpmProduct` struct.");
+
+        match factors.split_first() {
+            None => {
+                b.fill(T::zero());
+                b.diag_mut().fill(T::one());
+            }
+            Some((first, rest)) => {
+                self.expm.expm_with_opts(*first, b, &self.options);
+                for a in rest {
+                    self.expm.expm_with_opts(*a, &mut self.exp_a, &self.options);
+                    gemm(T::one(), b, &self.exp_a, T::zero(), &mut self.work);
+                    b.assign(&self.work);
+                }
+            }
+        }
+    }
+
+    /// Calculate $e^{A_1} e^{A_2} \cdots e^{A_k} X$ for the n×n matrices in `factors` and the
+    /// n×m block `x`, storing the result in `y`. No exponential is formed explicitly; a single
+    /// vector is passed as an n×1 block.
+    ///
+    /// NOTE: Panics if the factors are not square, if `x` and `y` don't have matching
+    /// dimensions, or if they don't have the same number of rows as the `ExpmProduct` object
+    /// `apply` is called on.
+    pub fn apply<S1, S2, S3>(&mut self, factors: &[&ArrayBase<S1, Ix2>], x: &ArrayBase<S2, Ix2>, y: &mut ArrayBase<S3, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: Data<Elem=T>,
+              S3: DataMut<Elem=T>,
+    {
+        let n = self.n;
+        assert_eq!(x.dim(), y.dim(), "Blocks `x` and `y` have to have matching dimensions.");
+        assert_eq!(x.rows(), n, "Dimension mismatch between block `x` and preconfigured `ExpmProduct` struct.");
+
+        let m = x.cols();
+        if self.block_sum.dim() != (n, m) {
+            self.block_term = Array2::zeros((n, m));
+            self.block_work = Array2::zeros((n, m));
+            self.block_sum = Array2::zeros((n, m));
+            self.block_compensation = Array2::zeros((n, m));
+        }
+
+        y.assign(x);
+        for a in factors.iter().rev() {
+            self.apply_exponential(*a, y);
+        }
+    }
+
+    /// Overwrites the block `y` with $e^A Y$ using the truncated Taylor series.
+    fn apply_exponential<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, y: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        let n = self.n;
+        assert_eq!(a.dim(), (n, n), "Dimension mismatch between matrix `a` and preconfigured `ExpmProduct` struct.");
+        if n == 0 {
+            return;
+        }
+
+        // Ã = A - μI with μ = tr(A)/n
+        let mu = a.diag().fold(T::zero(), |acc, &x| acc + x) / T::from_usize(n).unwrap();
+        self.shifted.assign(a);
+        self.shifted.diag_mut().mapv_inplace(|x| x - mu);
+
+        let steps = onenorm(&self.shifted).ceil().max(T::one());
+        let eta = (mu / steps).exp();
+        let tolerance = T::unit_roundoff();
+
+        for _ in 0..steps.to_usize().unwrap() {
+            self.block_sum.assign(y);
+            self.block_term.assign(y);
+            self.block_compensation.fill(T::zero());
+            let mut previous_norm = onenorm(&self.block_term);
+
+            for j in 1..=MAX_TAYLOR_DEGREE {
+                let factor = T::one() / (steps * T::from_usize(j).unwrap());
+                gemm(factor, &self.shifted, &self.block_term, T::zero(), &mut self.block_work);
+                std::mem::swap(&mut self.block_term, &mut self.block_work);
+                if self.options.compensated_summation {
+                    add_compensated(&mut self.block_sum, &mut self.block_compensation, &self.block_term);
+                } else {
+                    self.block_sum.zip_mut_with(&self.block_term, |x, &y| *x = *x + y);
+                }
+
+                let norm = onenorm(&self.block_term);
+                if previous_norm + norm <= tolerance * onenorm(&self.block_sum) {
+                    break;
+                }
+                previous_norm = norm;
+            }
+
+            self.block_sum.zip_mut_with(&self.block_compensation, |x, &c| *x = *x + c);
+            y.zip_mut_with(&self.block_sum, |x, &f| *x = eta * f);
+        }
+    }
+}
+
+/// Calculate $e^{A_1} e^{A_2} \cdots e^{A_k}$ for the n×n matrices in `factors`, storing the
+/// result in `b`.
+///
+/// NOTE: Panics if the factors and `b` are not square or don't have matching dimensions.
+pub fn expm_product<T, S1, S2>(factors: &[&ArrayBase<S1, Ix2>], b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = b.dim();
+
+    let mut product = ExpmProduct::new(n);
+    product.product(factors, b);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    use crate::ExpmOptions;
+    use super::ExpmProduct;
+
+    fn factors() -> Vec<Array2<f64>> {
+        vec![
+            arr2(&[[-1.0, 2.0, 0.0], [0.5, -3.0, 1.0], [0.0, 0.25, -0.5]]),
+            arr2(&[[0.0, 5.0, 0.0], [-5.0, 0.0, 2.0], [1.0, 0.0, -1.0]]),
+            arr2(&[[0.3, 0.0, -0.2], [0.1, 0.2, 0.0], [0.0, -0.4, 0.1]]),
+        ]
+    }
+
+    #[test]
+    fn product_agrees_with_explicit_products() {
+        let factors = factors();
+        let references: Vec<_> = factors.iter().collect();
+        let mut b = Array2::<f64>::zeros((3, 3));
+
+        crate::expm_product(&references, &mut b);
+
+        let mut expected = Array2::<f64>::eye(3);
+        let mut exp_a = Array2::<f64>::zeros((3, 3));
+        for a in &factors {
+            crate::expm(a, &mut exp_a);
+            expected = expected.dot(&exp_a);
+        }
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-13, epsilon=1e-14);
+        }
+    }
+
+    #[test]
+    fn apply_agrees_with_product() {
+        let factors = factors();
+        let references: Vec<_> = factors.iter().collect();
+        let x = arr2(&[[1.0, 0.0], [-2.0, 1.0], [0.5, 3.0]]);
+        let mut y = Array2::<f64>::zeros((3, 2));
+        let mut b = Array2::<f64>::zeros((3, 3));
+
+        let mut product = ExpmProduct::new(3);
+        product.product(&references, &mut b);
+
+        for &compensated in &[false, true] {
+            product.set_compensated_summation(compensated);
+            product.apply(&references, &x, &mut y);
+
+            for (&u, &v) in y.iter().zip(b.dot(&x).iter()) {
+                assert_relative_eq!(u, v, max_relative=1e-13, epsilon=1e-14);
+            }
+        }
+    }
+
+    #[test]
+    fn compensated_summation_rounds_correctly() {
+        // For the nilpotent shift Ã = [[0, 1, 0], [0, 0, 1], [0, 0, 0]] with trace zero, a single
+        // step is taken, and the terms of the first component of e^Ã y are exactly representable:
+        // 1, 3/4 ulp, and 5/8 ulp with ulp = 2^-52. The exact sum 1 + 11/8 ulp rounds to 1 + ulp.
+        // Plain summation rounds 1 + 3/4 ulp up to 1 + ulp, and 1 + 13/8 ulp up to 1 + 2 ulp,
+        // whereas compensated summation carries both rounding errors and rounds only once.
+        let ulp = f64::EPSILON;
+        let a = arr2(&[[0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [0.0, 0.0, 0.0]]);
+        let x = arr2(&[[1.0], [0.75 * ulp], [1.25 * ulp]]);
+        let mut y = Array2::<f64>::zeros((3, 1));
+
+        let mut product = ExpmProduct::new(3);
+        product.apply(&[&a], &x, &mut y);
+        assert_eq!(y[(0, 0)], 1.0 + 2.0 * ulp);
+
+        product.set_options(&ExpmOptions { compensated_summation: true, ..ExpmOptions::default() });
+        product.apply(&[&a], &x, &mut y);
+        assert_eq!(y[(0, 0)], 1.0 + ulp);
+        assert_eq!(y[(1, 0)], 2.0 * ulp);
+        assert_eq!(y[(2, 0)], 1.25 * ulp);
+    }
+}
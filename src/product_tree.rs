@@ -0,0 +1,304 @@
+//! Products of consecutive factors of a long chain of exponentials,
+//!
+//! \begin{equation}
+//!     P_{i,j} = e^{A_{j-1}} \cdots e^{A_{i+1}} e^{A_i},
+//! \end{equation}
+//!
+//! with the factors in the order of application as in `ExpmChain`, so that later factors multiply
+//! from the left. The exponentials are calculated once and stored as the leaves of a binary tree
+//! whose inner nodes hold the products of their subtrees. Every product $P_{i,j}$ is then
+//! assembled from at most $2 \log_2 k$ nodes of the tree, instead of $j - i - 1$ products of
+//! leaves, and replacing a single factor, e.g. a pulse during an optimization, only updates the
+//! $\log_2 k$ nodes above it. All $k$ prefixes $P_{0,j}$ or suffixes $P_{j,k}$ are calculated
+//! from each other with one product each.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    float::gemm,
+    Expm,
+    Float,
+};
+
+/// Storage for a tree of products of the exponentials of k n×n matrices.
+pub struct ExpmProductTree<T = f64> {
+    n: usize,
+    len: usize,
+    size: usize,
+    expm: Expm<T>,
+    nodes: Vec<Array2<T>>,
+    left: Array2<T>,
+    right: Array2<T>,
+    work: Array2<T>,
+    products: usize,
+}
+
+impl<T: Float> ExpmProductTree<T> {
+    /// Allocates all space for the exponentials of n×n matrices. The space for the tree is
+    /// allocated by `build` as needed.
+    pub fn new(n: usize) -> Self {
+        ExpmProductTree {
+            n,
+            len: 0,
+            size: 0,
+            expm: Expm::new(n),
+            nodes: Vec::new(),
+            left: Array2::zeros((n, n)),
+            right: Array2::zeros((n, n)),
+            work: Array2::zeros((n, n)),
+            products: 0,
+        }
+    }
+
+    /// The number of factors of the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the tree has no factors.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of matrix products performed since the last call to `build`, excluding those
+    /// of the exponentials themselves.
+    pub fn products(&self) -> usize {
+        self.products
+    }
+
+    /// Build the tree from the exponentials of the n×n matrices in `factors`, in the order of
+    /// application.
+    ///
+    /// NOTE: Panics if the factors are not n×n, where n is the dimension of the
+    /// `ExpmProductTree` object `build` is called on.
+    pub fn build<S>(&mut self, factors: &[&ArrayBase<S, Ix2>])
+        where S: Data<Elem=T>,
+    {
+        let n = self.n;
+        self.len = factors.len();
+        self.size = self.len.next_power_of_two();
+        self.products = 0;
+
+        // Padding leaves beyond the factors are the identity.
+        self.nodes.resize_with(2 * self.size, || Array2::zeros((n, n)));
+        for leaf in self.size..2 * self.size {
+            let k = leaf - self.size;
+            if k < self.len {
+                let mut node = std::mem::replace(&mut self.nodes[leaf], Array2::zeros((0, 0)));
+                self.expm.expm(factors[k], &mut node);
+                self.nodes[leaf] = node;
+            } else {
+                self.nodes[leaf].fill(T::zero());
+                self.nodes[leaf].diag_mut().fill(T::one());
+            }
+        }
+        for node in (1..self.size).rev() {
+            self.combine(node);
+        }
+    }
+
+    /// Replace the factor `k` by the exponential of the n×n matrix `a`, updating the products
+    /// above it.
+    ///
+    /// NOTE: Panics if `k` is not smaller than the number of factors, or if `a` is not n×n.
+    pub fn update<S>(&mut self, k: usize, a: &ArrayBase<S, Ix2>)
+        where S: Data<Elem=T>,
+    {
+        assert!(k < self.len, "Factor {} is out of range for {} factors.", k, self.len);
+
+        let mut node = self.size + k;
+        let mut leaf = std::mem::replace(&mut self.nodes[node], Array2::zeros((0, 0)));
+        self.expm.expm(a, &mut leaf);
+        self.nodes[node] = leaf;
+        while node > 1 {
+            node /= 2;
+            self.combine(node);
+        }
+    }
+
+    /// Calculate the product $P_{i,j} = e^{A_{j-1}} \cdots e^{A_i}$ of the factors `start` to
+    /// `end` (exclusive), storing the result in `b`. The empty product is the identity.
+    ///
+    /// NOTE: Panics if `start` is larger than `end`, if `end` is larger than the number of
+    /// factors, or if `b` is not n×n.
+    pub fn product<S>(&mut self, start: usize, end: usize, b: &mut ArrayBase<S, Ix2>)
+        where S: DataMut<Elem=T>,
+    {
+        assert!(start <= end && end <= self.len, "The range {}..{} is out of range for {} factors.", start, end, self.len);
+        assert_eq!(b.dim(), (self.n, self.n), "Dimension mismatch between matrix `b` and preconfigured `ExpmProductTree` struct.");
+
+        // `left` collects the nodes at the start of the range, which multiply from the right,
+        // and `right` those at the end, which multiply from the left.
+        self.left.fill(T::zero());
+        self.left.diag_mut().fill(T::one());
+        self.right.fill(T::zero());
+        self.right.diag_mut().fill(T::one());
+
+        let mut lower = start + self.size;
+        let mut upper = end + self.size;
+        while lower < upper {
+            if lower % 2 == 1 {
+                gemm(T::one(), &self.nodes[lower], &self.left, T::zero(), &mut self.work);
+                std::mem::swap(&mut self.left, &mut self.work);
+                self.products += 1;
+                lower += 1;
+            }
+            if upper % 2 == 1 {
+                upper -= 1;
+                gemm(T::one(), &self.right, &self.nodes[upper], T::zero(), &mut self.work);
+                std::mem::swap(&mut self.right, &mut self.work);
+                self.products += 1;
+            }
+            lower /= 2;
+            upper /= 2;
+        }
+
+        gemm(T::one(), &self.right, &self.left, T::zero(), b);
+        self.products += 1;
+    }
+
+    /// Calculate all prefix products $P_{0,j+1} = e^{A_j} \cdots e^{A_0}$, storing the product
+    /// of the first j+1 factors in `b[j]`.
+    ///
+    /// NOTE: Panics if `b` is not of dimension k×n×n for the k factors.
+    pub fn prefixes<S>(&mut self, b: &mut ArrayBase<S, Ix3>)
+        where S: DataMut<Elem=T>,
+    {
+        assert_eq!(b.dim(), (self.len, self.n, self.n), "The prefixes have to be of dimension k×n×n for k factors.");
+
+        for j in 0..self.len {
+            let (done, mut rest) = b.view_mut().split_at(Axis(0), j);
+            let mut prefix = rest.index_axis_mut(Axis(0), 0);
+            if j == 0 {
+                prefix.assign(&self.nodes[self.size]);
+            } else {
+                gemm(T::one(), &self.nodes[self.size + j], &done.index_axis(Axis(0), j - 1), T::zero(), &mut prefix);
+                self.products += 1;
+            }
+        }
+    }
+
+    /// Calculate all suffix products $P_{j,k} = e^{A_{k-1}} \cdots e^{A_j}$, storing the product
+    /// of the factors from j on in `b[j]`.
+    ///
+    /// NOTE: Panics if `b` is not of dimension k×n×n for the k factors.
+    pub fn suffixes<S>(&mut self, b: &mut ArrayBase<S, Ix3>)
+        where S: DataMut<Elem=T>,
+    {
+        assert_eq!(b.dim(), (self.len, self.n, self.n), "The suffixes have to be of dimension k×n×n for k factors.");
+
+        for j in (0..self.len).rev() {
+            let (mut rest, done) = b.view_mut().split_at(Axis(0), j + 1);
+            let mut suffix = rest.index_axis_mut(Axis(0), j);
+            if j + 1 == self.len {
+                suffix.assign(&self.nodes[self.size + j]);
+            } else {
+                gemm(T::one(), &done.index_axis(Axis(0), 0), &self.nodes[self.size + j], T::zero(), &mut suffix);
+                self.products += 1;
+            }
+        }
+    }
+
+    /// Recomputes the inner `node` as the product of its children, the later one on the left.
+    fn combine(&mut self, node: usize) {
+        gemm(T::one(), &self.nodes[2 * node + 1], &self.nodes[2 * node], T::zero(), &mut self.work);
+        self.nodes[node].assign(&self.work);
+        self.products += 1;
+    }
+}
+
+/// Calculate all prefix products $e^{A_j} \cdots e^{A_0}$ of the exponentials of the n×n
+/// matrices in `factors`, in the order of application, storing the product of the first j+1
+/// factors in `b[j]`.
+///
+/// NOTE: Panics if the factors are not square, or if `b` is not of dimension k×n×n for the k
+/// factors.
+pub fn expm_prefix_products<T, S1, S2>(factors: &[&ArrayBase<S1, Ix2>], b: &mut ArrayBase<S2, Ix3>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (_, n, _) = b.dim();
+
+    let mut tree = ExpmProductTree::new(n);
+    tree.build(factors);
+    tree.prefixes(b);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    fn factor(k: usize) -> Array2<f64> {
+        let k = k as f64;
+        arr2(&[[-0.1 * k, 0.3, 0.0], [-0.3, 0.05 * k, 0.2 - 0.1 * k], [0.1, 0.1 * k, -0.2]])
+    }
+
+    /// e^{A_{end-1}} ⋯ e^{A_start} by successive products.
+    fn direct(factors: &[Array2<f64>], start: usize, end: usize) -> Array2<f64> {
+        let mut product = Array2::<f64>::eye(3);
+        let mut b = Array2::<f64>::zeros((3, 3));
+        for a in &factors[start..end] {
+            crate::expm(a, &mut b);
+            product = b.dot(&product);
+        }
+        product
+    }
+
+    #[test]
+    fn ranges_agree_with_direct_products() {
+        let k = 11;
+        let mut factors: Vec<_> = (0..k).map(factor).collect();
+        let mut tree = crate::ExpmProductTree::new(3);
+        tree.build(&factors.iter().collect::<Vec<_>>());
+        assert_eq!(tree.len(), k);
+
+        let mut b = Array2::<f64>::zeros((3, 3));
+        for &(start, end) in &[(0, k), (3, 4), (2, 9), (5, 5), (1, 11)] {
+            tree.product(start, end, &mut b);
+            for (&x, &y) in b.iter().zip(direct(&factors, start, end).iter()) {
+                assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-14);
+            }
+        }
+
+        factors[6] = factor(20);
+        tree.update(6, &factors[6]);
+        tree.product(2, 9, &mut b);
+        for (&x, &y) in b.iter().zip(direct(&factors, 2, 9).iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-14);
+        }
+
+        let mut prefixes = Array3::<f64>::zeros((k, 3, 3));
+        let mut suffixes = Array3::<f64>::zeros((k, 3, 3));
+        tree.prefixes(&mut prefixes);
+        tree.suffixes(&mut suffixes);
+        for j in 0..k {
+            for (&x, &y) in prefixes.index_axis(Axis(0), j).iter().zip(direct(&factors, 0, j + 1).iter()) {
+                assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-14);
+            }
+            for (&x, &y) in suffixes.index_axis(Axis(0), j).iter().zip(direct(&factors, j, k).iter()) {
+                assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-14);
+            }
+        }
+    }
+
+    #[test]
+    fn range_products_are_logarithmic() {
+        let k = 64;
+        let factors: Vec<_> = (0..k).map(factor).collect();
+        let mut tree = crate::ExpmProductTree::new(3);
+        tree.build(&factors.iter().collect::<Vec<_>>());
+        assert_eq!(tree.products(), k - 1);
+
+        let mut b = Array2::<f64>::zeros((3, 3));
+        tree.product(1, 63, &mut b);
+        // At most two nodes per level and the final combination.
+        assert!(tree.products() - (k - 1) <= 2 * 6 + 1);
+    }
+}
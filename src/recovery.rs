@@ -0,0 +1,177 @@
+//! Recovery from non-finite intermediate values.
+//!
+//! The scaling parameter of `expm` is chosen from estimates of the norms of powers of $A$. If
+//! these estimates overflow or severely underestimate the norms, the Padé approximant is
+//! evaluated too far from the origin, the denominator $q_m$ becomes ill-conditioned, and the
+//! approximant or the subsequent squarings produce infinities or NaNs although the exponential
+//! itself is finite. Such values are detected after the Padé approximant and after every
+//! squaring, which ends the calculation early. A forced order with a limited number of squarings
+//! may even make $q_m$ exactly singular, which is treated the same way.
+//!
+//! The exponential is then recalculated with the highest Padé order $m$ of the precision, 13 for
+//! `f64` and 7 for `f32`, and a conservative scaling parameter derived from the exact 1-norm
+//! instead of the estimates,
+//!
+//! \begin{equation}
+//!     s = \max\left(\left\lceil \log_2 \frac{\lVert A \rVert_1}{\theta_m} \right\rceil,
+//!     0\right),
+//! \end{equation}
+//!
+//! whose powers of $2^{-s} A$ cannot overflow. A finite result of the recalculation is recorded
+//! as recovered in the report; if it is still not finite, the exponential most likely overflows
+//! and only `non_finite` is set.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    float::onenorm,
+    Expm,
+    Float,
+    PadeOrders,
+};
+
+impl<T: Float> Expm<T> {
+    /// Calculates the exponential of `a` with `evaluate`, and recalculates it with
+    /// `evaluate_conservative` if non-finite values appeared although `a` is finite.
+    pub(crate) fn evaluate_recovering<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        let is_finite = self.evaluate(a, b);
+        if !is_finite && all_finite(a) {
+            self.report.recovered = self.evaluate_conservative(a, b);
+            self.report.non_finite = !self.report.recovered;
+        } else {
+            self.report.non_finite = !is_finite;
+        }
+    }
+
+    /// Calculates the exponential of `a` with the highest Padé order of the precision `T` and the
+    /// scaling parameter from the exact 1-norm, ignoring the maximum number of squarings in the
+    /// options. Returns whether the result is finite.
+    fn evaluate_conservative<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>) -> bool
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        let v = b;
+        let (pade_order, s) = self.prepare_conservative(a);
+        self.solve_via_pade(pade_order, v) && all_finite(v) && self.square_checked(s, v)
+    }
+
+    /// Scales `a` into `a1` with the scaling parameter from the exact 1-norm for the highest Padé
+    /// order of the precision `T`, calculates the powers of the scaled matrix, and records both
+    /// in the report. Returns the order and the scaling parameter.
+    pub(crate) fn prepare_conservative<S>(&mut self, a: &ArrayBase<S, Ix2>) -> (PadeOrders, i32)
+        where S: Data<Elem=T>,
+    {
+        let two = T::from_f64(2.0).unwrap();
+
+        let m = T::MAX_PADE_ORDER;
+        let pade_order = PadeOrders::from_order::<T>(m);
+
+        let norm = onenorm(a);
+        let theta = self.theta(m);
+        let s = (norm / theta).log2().ceil().to_i32().unwrap_or(0).max(0);
+
+        self.a1.zip_mut_with(a, |x, &y| *x = y / two.powi(s));
+        self.calculate_powers(m);
+
+        self.report.pade_order = m;
+        self.report.squarings = s;
+        self.report.norm_parameter = norm;
+
+        (pade_order, s)
+    }
+
+    /// Squares `v` $s$ times with the squaring selected in the options, and returns whether the
+    /// result is finite. The squarings stop early at the first non-finite intermediate result.
+    pub(crate) fn square_checked<S>(&mut self, s: i32, v: &mut ArrayBase<S, Ix2>) -> bool
+        where S: DataMut<Elem=T>,
+    {
+        if self.options.extended_squaring {
+            self.square_extended(s, v);
+        } else {
+            self.square(s, v);
+        }
+        all_finite(v)
+    }
+}
+
+/// Whether all entries of `a` are finite.
+pub(crate) fn all_finite<T, S>(a: &ArrayBase<S, Ix2>) -> bool
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    a.iter().all(|x| x.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    use crate::ExpmOptions;
+
+    #[test]
+    fn overflowing_norm_estimates_are_recovered() {
+        // The square of the matrix overflows, so that the norm estimates determining the scaling
+        // are infinite, although the exponential is [[0, 0], [1e-200, 1]] up to underflow.
+        let a = arr2(&[[-1e200, 0.0], [1.0, 0.0]]);
+        let mut b = Array2::<f64>::zeros((2, 2));
+        let report = crate::expm_with_opts(&a, &mut b, &ExpmOptions::default());
+
+        assert!(report.norm_overflow);
+        assert!(report.recovered);
+        assert!(!report.non_finite);
+        assert!(report.squarings > 600);
+
+        let expected = arr2(&[[0.0, 0.0], [1e-200, 1.0]]);
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-10);
+        }
+    }
+
+    #[test]
+    fn singular_denominator_is_recovered() {
+        // The denominator q₃(x) = 120 - 60x + 12x² - x³ of the unscaled approximant of order 3
+        // evaluates to exactly zero at this x.
+        let x = 4.644370709252172f64;
+        let a = arr2(&[[x]]);
+        let mut b = Array2::<f64>::zeros((1, 1));
+        let options = ExpmOptions { pade_order: Some(3), max_squarings: Some(0), ..ExpmOptions::default() };
+        let report = crate::expm_with_opts(&a, &mut b, &options);
+
+        assert!(report.recovered);
+        assert!(!report.non_finite);
+        assert_relative_eq!(b[(0, 0)], x.exp(), max_relative=1e-14);
+    }
+
+    #[test]
+    fn overflowing_exponential_is_reported() {
+        // The recalculation runs but overflows as well, so that it does not count as recovered.
+        let a = arr2(&[[800.0, 0.0], [0.0, 1.0]]);
+        let mut b = Array2::<f64>::zeros((2, 2));
+        let report = crate::expm_with_opts(&a, &mut b, &ExpmOptions::default());
+
+        assert!(report.non_finite);
+        assert!(!report.recovered);
+        assert_eq!(report.pade_order, 13);
+    }
+
+    #[test]
+    fn overflowing_exponential_is_reported_f32() {
+        // The recalculation uses the approximant of order 7, the highest one for single precision.
+        let a = arr2(&[[100f32, 0.0], [0.0, 1.0]]);
+        let mut b = Array2::<f32>::zeros((2, 2));
+        let report = crate::expm_with_opts(&a, &mut b, &ExpmOptions::default());
+
+        assert!(report.non_finite);
+        assert!(!report.recovered);
+        assert_eq!(report.pade_order, 7);
+    }
+}
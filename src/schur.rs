@@ -0,0 +1,160 @@
+//! Helpers for matrix functions based on the real Schur decomposition $A = Q T Q^\top$.
+//!
+//! $T$ is quasi upper triangular, with 1×1 diagonal blocks for real eigenvalues and 2×2 diagonal
+//! blocks for pairs of complex conjugate eigenvalues. The square root of $T$ is calculated with
+//! the real Schur method of [Higham 1987], which solves for $R$ with $R^2 = T$ block by block.
+//!
+//! [Higham 1987]: https://doi.org/10.1016/0024-3795(87)90118-2
+
+use ndarray::{
+    self,
+    prelude::*,
+    s,
+    Data,
+    DataMut,
+};
+
+use crate::Float;
+
+/// Finds the diagonal blocks of the quasi upper triangular matrix `t`, storing their first index
+/// and size in `blocks`.
+pub(crate) fn diagonal_blocks<T, S>(t: &ArrayBase<S, Ix2>, blocks: &mut Vec<(usize, usize)>)
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    let n = t.rows();
+    blocks.clear();
+
+    let mut i = 0;
+    while i < n {
+        if i + 1 < n && t[(i + 1, i)] != T::zero() {
+            blocks.push((i, 2));
+            i += 2;
+        } else {
+            blocks.push((i, 1));
+            i += 1;
+        }
+    }
+}
+
+/// Calculates the principal square root `r` of the quasi upper triangular matrix `t`, whose
+/// diagonal blocks are given by `blocks`. The square root has the same block structure as `t`.
+///
+/// NOTE: Panics if `t` has an eigenvalue on the closed negative real axis, for which the
+/// principal square root is not defined.
+pub(crate) fn sqrt_quasi_triangular<T, S1, S2>(t: &ArrayBase<S1, Ix2>, r: &mut ArrayBase<S2, Ix2>, blocks: &[(usize, usize)])
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    r.fill(T::zero());
+
+    for &(i, p) in blocks {
+        if p == 1 {
+            let x = t[(i, i)];
+            assert!(x > T::zero(), "The principal square root is not defined for eigenvalues on the closed negative real axis.");
+            r[(i, i)] = x.sqrt();
+        } else {
+            // For a 2×2 matrix B without eigenvalues on the closed negative real axis,
+            // √B = (B + δI) / τ with δ = √det(B) and τ = √(tr(B) + 2δ).
+            let b = t.slice(s![i..i+2, i..i+2]);
+            let det = b[(0, 0)] * b[(1, 1)] - b[(0, 1)] * b[(1, 0)];
+            let trace = b[(0, 0)] + b[(1, 1)];
+            assert!(det > T::zero(), "The principal square root is not defined for eigenvalues on the closed negative real axis.");
+            let delta = det.sqrt();
+            let tau = (trace + delta + delta).sqrt();
+            let mut r_block = r.slice_mut(s![i..i+2, i..i+2]);
+            r_block.assign(&b);
+            r_block[(0, 0)] = r_block[(0, 0)] + delta;
+            r_block[(1, 1)] = r_block[(1, 1)] + delta;
+            r_block.mapv_inplace(|x| x / tau);
+        }
+    }
+
+    // Off-diagonal blocks from R_ii R_ij + R_ij R_jj = T_ij - Σ_{i<k<j} R_ik R_kj, column by column
+    // and from the diagonal upwards.
+    for (jb, &(j, q)) in blocks.iter().enumerate() {
+        for ib in (0..jb).rev() {
+            let (i, p) = blocks[ib];
+
+            let mut c = [[T::zero(); 2]; 2];
+            for (a, row) in c.iter_mut().enumerate().take(p) {
+                for (b, elem) in row.iter_mut().enumerate().take(q) {
+                    let mut value = t[(i + a, j + b)];
+                    for k in (i + p)..j {
+                        value = value - r[(i + a, k)] * r[(k, j + b)];
+                    }
+                    *elem = value;
+                }
+            }
+
+            let x = solve_sylvester(&r.slice(s![i..i+p, i..i+p]), &r.slice(s![j..j+q, j..j+q]), &c);
+            for a in 0..p {
+                for b in 0..q {
+                    r[(i + a, j + b)] = x[a][b];
+                }
+            }
+        }
+    }
+}
+
+/// Solves the Sylvester equation $A X + X B = C$ for blocks $A$, $B$ of size at most 2×2 via its
+/// Kronecker form $(I \otimes A + B^\top \otimes I) \mathrm{vec}(X) = \mathrm{vec}(C)$.
+pub(crate) fn solve_sylvester<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &ArrayBase<S2, Ix2>, c: &[[T; 2]; 2]) -> [[T; 2]; 2]
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=T>,
+{
+    let p = a.rows();
+    let q = b.rows();
+    let size = p * q;
+
+    // vec(X) stacks the columns of X, so that X[r][s] is at position s p + r.
+    let mut m = [[T::zero(); 4]; 4];
+    let mut rhs = [T::zero(); 4];
+    for s in 0..q {
+        for r in 0..p {
+            let row = s * p + r;
+            rhs[row] = c[r][s];
+            for k in 0..p {
+                m[row][s * p + k] = m[row][s * p + k] + a[(r, k)];
+            }
+            for k in 0..q {
+                m[row][k * p + r] = m[row][k * p + r] + b[(k, s)];
+            }
+        }
+    }
+
+    // Gaussian elimination with partial pivoting.
+    for col in 0..size {
+        let pivot = (col..size)
+            .max_by(|&x, &y| m[x][col].abs().partial_cmp(&m[y][col].abs()).unwrap_or(std::cmp::Ordering::Equal))
+            .unwrap();
+        m.swap(col, pivot);
+        rhs.swap(col, pivot);
+        let pivot_row = m[col];
+        for row in (col + 1)..size {
+            let factor = m[row][col] / pivot_row[col];
+            for (x, &y) in m[row].iter_mut().zip(pivot_row.iter()).take(size).skip(col) {
+                *x = *x - factor * y;
+            }
+            rhs[row] = rhs[row] - factor * rhs[col];
+        }
+    }
+    let mut solution = [T::zero(); 4];
+    for row in (0..size).rev() {
+        let mut value = rhs[row];
+        for k in (row + 1)..size {
+            value = value - m[row][k] * solution[k];
+        }
+        solution[row] = value / m[row][row];
+    }
+
+    let mut x = [[T::zero(); 2]; 2];
+    for s in 0..q {
+        for r in 0..p {
+            x[r][s] = solution[s * p + r];
+        }
+    }
+    x
+}
@@ -0,0 +1,272 @@
+//! A cache for families of matrices that differ only by a multiple of the identity.
+//!
+//! Since $cI$ commutes with every matrix, $e^{A + cI} = e^c e^A$. Scans over a regularization
+//! parameter or a chemical potential thus only require a single exponential $e^A$, all others
+//! follow by scaling. The cache recognizes such families automatically by comparing a matrix to
+//! the cached base matrix, or callers can declare the shift explicitly.
+//!
+//! Forming $A + cI$ rounds the diagonal, so that the recognized shift is the mean difference of
+//! the diagonals, and the individual differences may deviate from it by a few ulps of the
+//! largest diagonal entry. This amounts to a backward error comparable to that of `expm` itself.
+//! If $e^c$ over- or underflows, or the scaled exponential is not finite, although $e^{A + cI}$
+//! may well be representable, the exponential is calculated directly instead.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    recovery::all_finite,
+    Expm,
+    Float,
+};
+
+/// The tolerance for the deviations of the diagonal differences from the shift, in units of the
+/// machine epsilon relative to the largest diagonal entry.
+const SHIFT_ULPS: f64 = 4.0;
+
+/// Storage for the exponential of a base matrix $A$, from which the exponentials of shifted
+/// matrices $A + cI$ are obtained.
+pub struct ExpmShiftCache<T = f64> {
+    n: usize,
+    expm: Expm<T>,
+    base: Array2<T>,
+    exp_base: Array2<T>,
+    shifted: Array2<T>,
+    is_filled: bool,
+    hits: usize,
+    misses: usize,
+}
+
+impl<T: Float> ExpmShiftCache<T> {
+    /// Allocates all space to cache exponentials of n×n matrices.
+    pub fn new(n: usize) -> Self {
+        ExpmShiftCache {
+            n,
+            expm: Expm::new(n),
+            base: Array2::zeros((n, n)),
+            exp_base: Array2::zeros((n, n)),
+            shifted: Array2::zeros((n, n)),
+            is_filled: false,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Calculate the matrix exponential of the n×n matrix `a`, storing the result in `b`. If `a`
+    /// differs from the cached base matrix only by a multiple of the identity, the result is
+    /// obtained by scaling the cached exponential, unless the scaling over- or underflows.
+    /// Otherwise, `a` becomes the new base matrix.
+    ///
+    /// NOTE: Panics if input matrices `a` and `b` don't have matching dimensions, are not square,
+    /// not in row-major order, or don't have the same dimension as the `ExpmShiftCache` object
+    /// `expm` is called on.
+    pub fn expm<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `ExpmShiftCache` struct.");
+
+        match self.shift_of(a) {
+            Some(c) if self.serve(c, b) => {
+                self.hits += 1;
+            }
+            _ => {
+                self.fill(a);
+                b.assign(&self.exp_base);
+            }
+        }
+    }
+
+    /// Calculate the matrix exponential of $A + cI$ for the n×n base matrix `a` and the shift
+    /// `c`, storing the result in `b`. The exponential of `a` is only calculated if `a` is not
+    /// the cached base matrix, and that of $A + cI$ only if scaling by $e^c$ over- or
+    /// underflows.
+    ///
+    /// NOTE: Panics if input matrices `a` and `b` don't have matching dimensions, are not square,
+    /// not in row-major order, or don't have the same dimension as the `ExpmShiftCache` object
+    /// `expm_shifted` is called on.
+    pub fn expm_shifted<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, c: T, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `ExpmShiftCache` struct.");
+
+        let is_cached = self.is_filled && self.base == *a;
+        if !is_cached {
+            self.fill(a);
+        }
+        if self.serve(c, b) {
+            if is_cached {
+                self.hits += 1;
+            }
+        } else {
+            self.shifted.assign(a);
+            self.shifted.diag_mut().mapv_inplace(|x| x + c);
+            self.expm.expm(&self.shifted, b);
+            self.misses += 1;
+        }
+    }
+
+    /// The number of exponentials served from the cache.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// The number of exponentials that had to be calculated.
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// Returns c if `a` equals the cached base matrix plus cI, up to the rounding of the
+    /// diagonal.
+    fn shift_of<S>(&self, a: &ArrayBase<S, Ix2>) -> Option<T>
+        where S: Data<Elem=T>,
+    {
+        if !self.is_filled {
+            return None;
+        }
+        if self.n == 0 {
+            return Some(T::zero());
+        }
+
+        let same_off_diagonal = a.indexed_iter()
+            .zip(self.base.iter())
+            .all(|(((i, j), &x), &y)| i == j || x == y);
+        if !same_off_diagonal {
+            return None;
+        }
+
+        let diagonals = || a.diag().into_iter().zip(self.base.diag().into_iter());
+        let n = T::from_usize(self.n).unwrap();
+        let c = diagonals().fold(T::zero(), |sum, (&x, &y)| sum + (x - y)) / n;
+        let scale = diagonals().fold(T::zero(), |max, (&x, &y)| max.max(x.abs()).max(y.abs()));
+        let tolerance = T::from_f64(SHIFT_ULPS).unwrap() * T::epsilon() * scale;
+        let is_shift = diagonals().all(|(&x, &y)| ((x - y) - c).abs() <= tolerance);
+
+        if is_shift { Some(c) } else { None }
+    }
+
+    fn fill<S>(&mut self, a: &ArrayBase<S, Ix2>)
+        where S: Data<Elem=T>,
+    {
+        self.base.assign(a);
+        self.expm.expm(&self.base, &mut self.exp_base);
+        self.is_filled = true;
+        self.misses += 1;
+    }
+
+    /// Stores $e^c e^A$ in `b`. Returns false if $e^c$ over- or underflows, or the product is not
+    /// finite.
+    fn serve<S>(&self, c: T, b: &mut ArrayBase<S, Ix2>) -> bool
+        where S: DataMut<Elem=T>,
+    {
+        let exp_c = c.exp();
+        if !exp_c.is_finite() || exp_c < T::min_positive_value() {
+            return false;
+        }
+        b.zip_mut_with(&self.exp_base, |x, &y| *x = exp_c * y);
+        all_finite(b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    use super::ExpmShiftCache;
+
+    #[test]
+    fn shifted_matrices_are_recognized() {
+        let a = arr2(&[[-1.0, 2.0, 0.0], [0.5, -3.0, 1.0], [0.0, 0.25, -0.5]]);
+        let mut cache = ExpmShiftCache::new(3);
+        let mut b = Array2::<f64>::zeros((3, 3));
+        let mut expected = Array2::<f64>::zeros((3, 3));
+
+        for &c in &[0.0, 0.5, -2.0, 3.0] {
+            let shifted = &a + &(Array2::eye(3) * c);
+            cache.expm(&shifted, &mut b);
+            crate::expm(&shifted, &mut expected);
+            for (&x, &y) in b.iter().zip(expected.iter()) {
+                assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-14);
+            }
+        }
+        assert_eq!(cache.misses(), 1);
+        assert_eq!(cache.hits(), 3);
+
+        // A different off-diagonal requires a new exponential.
+        let mut other = a.clone();
+        other[(0, 1)] = 1.0;
+        cache.expm(&other, &mut b);
+        assert_eq!(cache.misses(), 2);
+    }
+
+    #[test]
+    fn declared_shifts_reuse_the_base() {
+        let a = arr2(&[[0.0, 1.0], [-1.0, 0.0]]);
+        let mut cache = ExpmShiftCache::new(2);
+        let mut b = Array2::<f64>::zeros((2, 2));
+
+        cache.expm_shifted(&a, 0.0, &mut b);
+        let b0 = b.clone();
+        cache.expm_shifted(&a, 1.0, &mut b);
+
+        assert_eq!(cache.misses(), 1);
+        for (&x, &y) in b.iter().zip(b0.iter()) {
+            assert_relative_eq!(x, 1f64.exp() * y, max_relative=1e-15);
+        }
+    }
+
+    #[test]
+    fn rounded_shifts_are_recognized() {
+        // The diagonal differences of a + 0.1 I and a deviate from 0.1 in the last bits.
+        let a = arr2(&[[-1.0, 2.0, 0.0], [0.5, -3.0, 1.0], [0.0, 0.25, -0.5]]);
+        let shifted = &a + &(Array2::eye(3) * 0.1);
+        assert!(shifted.diag().iter().zip(a.diag().iter()).any(|(&x, &y)| x - y != 0.1));
+
+        let mut cache = ExpmShiftCache::new(3);
+        let mut b = Array2::<f64>::zeros((3, 3));
+        let mut expected = Array2::<f64>::zeros((3, 3));
+        cache.expm(&a, &mut b);
+        cache.expm(&shifted, &mut b);
+        crate::expm(&shifted, &mut expected);
+
+        assert_eq!(cache.misses(), 1);
+        assert_eq!(cache.hits(), 1);
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-14);
+        }
+    }
+
+    #[test]
+    fn overflowing_and_underflowing_shifts_are_calculated_directly() {
+        let mut cache = ExpmShiftCache::new(2);
+        let mut b = Array2::<f64>::zeros((2, 2));
+        let mut expected = Array2::<f64>::zeros((2, 2));
+
+        // e^800 overflows, although exp(a + 800 I) ≈ e^700 does not.
+        let a = arr2(&[[-100.0, 1.0], [0.0, -100.0]]);
+        cache.expm_shifted(&a, 800.0, &mut b);
+        crate::expm(&(&a + &(Array2::eye(2) * 800.0)), &mut expected);
+        assert_eq!(cache.misses(), 2);
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-12);
+        }
+
+        // e^-800 underflows, although exp(a - 800 I) ≈ e^-100 does not.
+        let a = arr2(&[[700.0, 1.0], [0.0, 700.0]]);
+        let shifted = &a + &(Array2::eye(2) * -800.0);
+        cache.expm(&a, &mut b);
+        cache.expm(&shifted, &mut b);
+        crate::expm(&shifted, &mut expected);
+        assert_eq!(cache.misses(), 4);
+        assert_eq!(cache.hits(), 0);
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-12);
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! Solutions of shifted linear systems $(A - \sigma_i I) x = b$ for a real matrix $A$ and a set of
+//! complex shifts $\sigma_i$, as required by rational approximations of the exponential in
+//! partial fraction form, e.g. Chebyshev rational approximations or contour integrals,
+//!
+//! \begin{equation}
+//!     r(A) b = c_0 b + \sum_i c_i (A - \sigma_i I)^{-1} b.
+//! \end{equation}
+//!
+//! The matrix is reduced once to upper Hessenberg form $A = Q H Q^\top$, which is invariant
+//! under shifts, so that
+//!
+//! \begin{equation}
+//!     (A - \sigma I)^{-1} b = Q (H - \sigma I)^{-1} Q^\top b.
+//! \end{equation}
+//!
+//! The LU factorization of the shifted Hessenberg matrix with partial pivoting only eliminates
+//! the subdiagonal and costs $O(n^2)$ instead of $O(n^3)$ flops per shift. Factorizations are
+//! kept for repeated solves with the same shift. Since $A$ is real, the factorization for a shift
+//! $\sigma$ also solves the system for the conjugate shift $\bar{\sigma}$ via $(A - \bar{\sigma}
+//! I) x = b \Leftrightarrow (A - \sigma I) \bar{x} = \bar{b}$, halving the work for shifts in
+//! conjugate pairs.
+//!
+//! The reduction is dense. Large sparse matrices, whose sparsity a Hessenberg reduction would
+//! destroy, are factorized per shift by `SparseShiftedSolver` with the `sprs` feature.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+use num_complex::Complex;
+
+use crate::{
+    float::gehrd,
+    Float,
+};
+
+/// The LU factorization of a shifted upper Hessenberg matrix $H - \sigma I$, storing the
+/// multipliers on the subdiagonal and whether rows $k$ and $k+1$ were interchanged in step $k$.
+struct HessenbergLu<T> {
+    shift: Complex<T>,
+    lu: Array2<Complex<T>>,
+    is_swapped: Vec<bool>,
+}
+
+/// Storage for solving shifted linear systems $(A - \sigma I) x = b$ for a real n×n matrix $A$
+/// and many complex shifts $\sigma$.
+pub struct ShiftedSolver<T = f64> {
+    n: usize,
+    h: Array2<T>,
+    q: Array2<T>,
+    factorizations: Vec<HessenbergLu<T>>,
+    y: Array1<Complex<T>>,
+    factorization_count: usize,
+}
+
+impl<T: Float> ShiftedSolver<T> {
+    /// Allocates all space to solve shifted systems for n×n matrices, except for the
+    /// factorizations, which are allocated per shift.
+    pub fn new(n: usize) -> Self {
+        ShiftedSolver {
+            n,
+            h: Array2::zeros((n, n)),
+            q: Array2::eye(n),
+            factorizations: Vec::new(),
+            y: Array1::from_elem(n, Complex::new(T::zero(), T::zero())),
+            factorization_count: 0,
+        }
+    }
+
+    /// Reduce the n×n matrix `a` to upper Hessenberg form, replacing any previously reduced
+    /// matrix and discarding all factorizations.
+    ///
+    /// NOTE: Panics if `a` is not square, or doesn't have the same dimension as the
+    /// `ShiftedSolver` object `reduce` is called on.
+    pub fn reduce<S>(&mut self, a: &ArrayBase<S, Ix2>)
+        where S: Data<Elem=T>,
+    {
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `ShiftedSolver` struct.");
+
+        self.h.assign(a);
+        // The reduction has no convergence criterion and only fails for invalid arguments.
+        assert_eq!(gehrd(&mut self.h, &mut self.q), 0);
+        self.factorizations.clear();
+    }
+
+    /// Factorize $H - \sigma I$ for all `shifts` in advance, reusing existing factorizations of
+    /// the shifts or their conjugates.
+    pub fn factorize(&mut self, shifts: &[Complex<T>]) {
+        for &shift in shifts {
+            let _ = self.factorization(shift);
+        }
+    }
+
+    /// The number of factorizations calculated since the creation of the `ShiftedSolver`.
+    pub fn factorizations(&self) -> usize {
+        self.factorization_count
+    }
+
+    /// Solve $(A - \sigma I) x = b$ for the reduced matrix $A$ and the shift $\sigma$ =
+    /// `shift`, overwriting `b` with the solution $x$. If $A - \sigma I$ is singular, the
+    /// solution contains non-finite entries.
+    ///
+    /// NOTE: Panics if `b` doesn't have the same length as the dimension of the `ShiftedSolver`
+    /// object `solve` is called on.
+    pub fn solve<S>(&mut self, shift: Complex<T>, b: &mut ArrayBase<S, Ix1>)
+        where S: DataMut<Elem=Complex<T>>,
+    {
+        assert_eq!(b.len(), self.n, "Dimension mismatch between vector `b` and preconfigured `ShiftedSolver` struct.");
+
+        let (index, is_conjugate) = self.factorization(shift);
+        let conjugate = |x: Complex<T>| if is_conjugate { x.conj() } else { x };
+
+        // y = Qᵀ b
+        for (y_i, q_column) in self.y.iter_mut().zip(self.q.gencolumns()) {
+            *y_i = q_column.iter().zip(b.iter()).fold(Complex::new(T::zero(), T::zero()), |acc, (&q, &x)| acc + conjugate(x) * q);
+        }
+
+        self.factorizations[index].solve(&mut self.y);
+
+        // x = Q y
+        for (x, q_row) in b.iter_mut().zip(self.q.genrows()) {
+            let sum = q_row.iter().zip(self.y.iter()).fold(Complex::new(T::zero(), T::zero()), |acc, (&q, &y)| acc + y * q);
+            *x = conjugate(sum);
+        }
+    }
+
+    /// Returns the index of the factorization for `shift`, calculating it if neither the shift
+    /// nor its conjugate have been factorized, and whether it belongs to the conjugate shift.
+    fn factorization(&mut self, shift: Complex<T>) -> (usize, bool) {
+        if let Some(index) = self.factorizations.iter().position(|f| f.shift == shift) {
+            return (index, false);
+        }
+        if let Some(index) = self.factorizations.iter().position(|f| f.shift == shift.conj()) {
+            return (index, true);
+        }
+
+        self.factorizations.push(HessenbergLu::new(&self.h, shift));
+        self.factorization_count += 1;
+        (self.factorizations.len() - 1, false)
+    }
+}
+
+impl<T: Float> HessenbergLu<T> {
+    /// Factorizes $H - \sigma I$ for the upper Hessenberg matrix `h`.
+    fn new(h: &Array2<T>, shift: Complex<T>) -> Self {
+        let (n, _) = h.dim();
+        let mut lu = h.mapv(|x| Complex::new(x, T::zero()));
+        lu.diag_mut().mapv_inplace(|x| x - shift);
+        let mut is_swapped = vec![false; n.saturating_sub(1)];
+
+        for (k, swapped) in is_swapped.iter_mut().enumerate() {
+            if lu[(k + 1, k)].norm() > lu[(k, k)].norm() {
+                for j in k..n {
+                    lu.swap((k, j), (k + 1, j));
+                }
+                *swapped = true;
+            }
+            let multiplier = lu[(k + 1, k)] / lu[(k, k)];
+            lu[(k + 1, k)] = multiplier;
+            for j in k+1..n {
+                let u = lu[(k, j)];
+                lu[(k + 1, j)] = lu[(k + 1, j)] - multiplier * u;
+            }
+        }
+
+        HessenbergLu { shift, lu, is_swapped }
+    }
+
+    /// Overwrites `y` with $(H - \sigma I)^{-1} y$.
+    fn solve(&self, y: &mut Array1<Complex<T>>) {
+        let n = y.len();
+
+        for (k, &swapped) in self.is_swapped.iter().enumerate() {
+            if swapped {
+                y.swap(k, k + 1);
+            }
+            let y_k = y[k];
+            y[k + 1] = y[k + 1] - self.lu[(k + 1, k)] * y_k;
+        }
+
+        for i in (0..n).rev() {
+            let sum = (i+1..n).fold(y[i], |acc, j| acc - self.lu[(i, j)] * y[j]);
+            y[i] = sum / self.lu[(i, i)];
+        }
+    }
+}
+
+/// Solve $(A - \sigma_i I) x_i = b$ for the real n×n matrix `a` and all `shifts` $\sigma_i$,
+/// storing the solution $x_i$ in the i-th column of `x`.
+///
+/// NOTE: Panics if `a` is not square, if `b` is not of length n, or if `x` is not of dimension
+/// n×k for k shifts.
+pub fn solve_shifted<T, S1, S2, S3>(a: &ArrayBase<S1, Ix2>, shifts: &[Complex<T>], b: &ArrayBase<S2, Ix1>, x: &mut ArrayBase<S3, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=Complex<T>>,
+          S3: DataMut<Elem=Complex<T>>,
+{
+    let (n, _) = a.dim();
+    assert_eq!(x.dim(), (n, shifts.len()), "Dimension mismatch between matrix `x` and the shifts and matrix `a`.");
+
+    let mut solver = ShiftedSolver::new(n);
+    solver.reduce(a);
+    for (&shift, mut x) in shifts.iter().zip(x.gencolumns_mut()) {
+        x.assign(b);
+        solver.solve(shift, &mut x);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use num_complex::Complex;
+    use approx::assert_relative_eq;
+
+    use super::ShiftedSolver;
+
+    #[test]
+    fn shifted_solutions_have_small_residuals() {
+        let a = arr2(&[
+            [-1.0, 2.0, 0.5, 0.0],
+            [0.5, -3.0, 1.0, 2.0],
+            [0.25, 0.25, -0.5, 1.0],
+            [1.0, 0.0, 3.0, -2.0],
+        ]);
+        let b = arr1(&[Complex::new(1.0, 0.0), Complex::new(-2.0, 0.5), Complex::new(0.0, 1.0), Complex::new(3.0, 0.0)]);
+        let shifts = [Complex::new(-1.5, 2.0), Complex::new(-1.5, -2.0), Complex::new(0.3, 0.0), Complex::new(4.0, 10.0)];
+        let mut x = Array2::<Complex<f64>>::zeros((4, 4));
+        crate::solve_shifted(&a, &shifts, &b, &mut x);
+
+        for (&shift, x) in shifts.iter().zip(x.gencolumns()) {
+            for i in 0..4 {
+                let ax = (0..4).fold(Complex::new(0.0, 0.0), |acc, j| acc + x[j] * a[(i, j)]);
+                let residual = ax - x[i] * shift - b[i];
+                assert_relative_eq!(residual.norm(), 0.0, epsilon=1e-13);
+            }
+        }
+    }
+
+    #[test]
+    fn factorizations_are_reused() {
+        let a = arr2(&[[-1.0, 2.0, 0.5], [0.5, -3.0, 1.0], [0.25, 0.25, -0.5]]);
+        let shift = Complex::new(-0.5, 1.5);
+        let b = arr1(&[Complex::new(1.0, 0.0), Complex::new(0.0, 1.0), Complex::new(-1.0, 2.0)]);
+
+        let mut solver = ShiftedSolver::new(3);
+        solver.reduce(&a);
+        solver.factorize(&[shift]);
+
+        let mut x = b.clone();
+        solver.solve(shift, &mut x);
+        let mut x_conjugate = b.mapv(|x| x.conj());
+        solver.solve(shift.conj(), &mut x_conjugate);
+        assert_eq!(solver.factorizations(), 1);
+
+        // For real A and conjugate b, the solutions for conjugate shifts are conjugate.
+        for (&x, &y) in x.iter().zip(x_conjugate.iter()) {
+            assert_relative_eq!(x.re, y.re, max_relative=1e-14);
+            assert_relative_eq!(x.im, -y.im, max_relative=1e-14);
+        }
+    }
+}
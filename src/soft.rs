@@ -0,0 +1,159 @@
+//! A soft-failing variant of `expm_with_opts` for pipelines, in which discarding a sample is
+//! acceptable but aborting is not.
+//!
+//! The calculation always stores a best-effort result and classifies it by a quality flag: the
+//! result has `Converged` if it is finite and the bound on the relative backward error meets the
+//! tolerance of the options, `Degraded` if it is finite but the bound exceeds the tolerance, e.g.
+//! because the number of squarings was limited, and `Failed` if it contains non-finite values,
+//! e.g. because the input is not finite or the exponential overflows. The estimated error is the
+//! bound on the relative backward error from the report, and infinite for failed results, so
+//! that downstream code can weight or drop samples without inspecting the report.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    recovery::all_finite,
+    Expm,
+    ExpmOptions,
+    ExpmReport,
+    Float,
+};
+
+/// The quality of a result of `expm_soft`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExpmQuality {
+    /// The result is finite and the backward error bound meets the tolerance.
+    Converged,
+    /// The result is finite, but the backward error bound exceeds the tolerance.
+    Degraded,
+    /// The result contains non-finite values.
+    Failed,
+}
+
+/// The quality of a result of `expm_soft`, the estimated error, and the report of the
+/// calculation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExpmSoftReport<T> {
+    /// The quality flag of the result.
+    pub quality: ExpmQuality,
+    /// The estimated relative backward error, which is infinite for failed results.
+    pub error: T,
+    /// The report of the calculation. If the input is not finite, nothing is calculated and the
+    /// report is empty apart from `non_finite`.
+    pub report: ExpmReport<T>,
+}
+
+impl<T: Float> Expm<T> {
+    /// Calculate the matrix exponential of the n×n matrix `a` with the given `options`, storing
+    /// a best-effort result in matrix `b` and returning its quality and estimated error. Unlike
+    /// `expm_with_opts`, non-finite input does not reach the calculation: `b` is then filled with
+    /// NaN and the result flagged as failed.
+    ///
+    /// NOTE: Panics if the forced Padé order is not supported for the precision `T`, or if input
+    /// matrices `a` and `b` don't have matching dimensions, are not square, not in row-major
+    /// order, or don't have the same dimension as the `Expm` object `expm_soft` is called on.
+    pub fn expm_soft<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>, options: &ExpmOptions<T>) -> ExpmSoftReport<T>
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert_eq!(a.dim(), b.dim(), "Input matrices `a` and `b` have to have matching dimensions.");
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `Expm` struct.");
+
+        if !all_finite(a) {
+            b.fill(T::nan());
+            let mut report = ExpmReport::new();
+            report.non_finite = true;
+            return ExpmSoftReport {
+                quality: ExpmQuality::Failed,
+                error: T::infinity(),
+                report,
+            };
+        }
+
+        let report = self.expm_with_opts(a, b, options);
+        let (quality, error) = if report.non_finite {
+            (ExpmQuality::Failed, T::infinity())
+        } else if report.backward_error > options.tolerance {
+            (ExpmQuality::Degraded, report.backward_error)
+        } else {
+            (ExpmQuality::Converged, report.backward_error)
+        };
+
+        ExpmSoftReport {
+            quality,
+            error,
+            report,
+        }
+    }
+}
+
+/// Calculate the matrix exponential of the n×n matrix `a` with the given `options`, storing a
+/// best-effort result in matrix `b` and returning its quality and estimated error.
+///
+/// NOTE: Panics if the forced Padé order is not supported for the precision `T`, or if input
+/// matrices `a` and `b` don't have matching dimensions, are not square, or not in row-major
+/// order.
+pub fn expm_soft<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>, options: &ExpmOptions<T>) -> ExpmSoftReport<T>
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut expm = Expm::new(n);
+    expm.expm_soft(a, b, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+
+    use crate::{
+        ExpmOptions,
+        ExpmQuality,
+    };
+
+    #[test]
+    fn results_are_classified() {
+        let a = arr2(&[[-10.0, 20.0, 0.0], [5.0, -30.0, 10.0], [0.0, 2.5, -5.0]]);
+        let mut b = Array2::<f64>::zeros((3, 3));
+
+        let result = crate::expm_soft(&a, &mut b, &ExpmOptions::default());
+        assert_eq!(result.quality, ExpmQuality::Converged);
+        assert!(result.error <= f64::EPSILON / 2.0);
+
+        let limited = ExpmOptions { max_squarings: Some(0), ..ExpmOptions::default() };
+        let result = crate::expm_soft(&a, &mut b, &limited);
+        assert_eq!(result.quality, ExpmQuality::Degraded);
+        assert!(result.error > f64::EPSILON / 2.0 && result.error.is_finite());
+        assert!(b.iter().all(|x| x.is_finite()));
+
+        let overflowing = arr2(&[[800.0, 0.0], [0.0, 1.0]]);
+        let mut b = Array2::<f64>::zeros((2, 2));
+        let result = crate::expm_soft(&overflowing, &mut b, &ExpmOptions::default());
+        assert_eq!(result.quality, ExpmQuality::Failed);
+        assert!(result.error.is_infinite());
+
+        let nan = arr2(&[[f64::NAN, 0.0], [0.0, 1.0]]);
+        let result = crate::expm_soft(&nan, &mut b, &ExpmOptions::default());
+        assert_eq!(result.quality, ExpmQuality::Failed);
+        assert!(result.report.non_finite);
+        assert!(b.iter().all(|x| x.is_nan()));
+    }
+
+    #[test]
+    fn overflowing_f32_results_fail() {
+        let a = arr2(&[[100f32, 0.0], [0.0, 1.0]]);
+        let mut b = Array2::<f32>::zeros((2, 2));
+
+        let result = crate::expm_soft(&a, &mut b, &ExpmOptions::default());
+        assert_eq!(result.quality, ExpmQuality::Failed);
+        assert!(result.error.is_infinite());
+        assert!(result.report.non_finite);
+    }
+}
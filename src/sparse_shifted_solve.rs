@@ -0,0 +1,311 @@
+//! Solutions of shifted linear systems $(A - \sigma_i I) x = b$ for a real sparse matrix $A$ and
+//! a set of complex shifts $\sigma_i$, the sparse counterpart of the Hessenberg based solver in
+//! `shifted_solve`.
+//!
+//! A Hessenberg reduction fills in a sparse matrix completely. Instead, every shifted matrix
+//! $A - \sigma I$ is factorized directly with a sparse LU factorization with row partial
+//! pivoting, $P (A - \sigma I) = L U$, which only creates fill-in where elimination requires it.
+//! As in the dense case, factorizations are kept for repeated solves, and the factorization for
+//! a shift $\sigma$ also solves the system for the conjugate shift $\bar{\sigma}$ because $A$ is
+//! real. This module is only available with the `sprs` feature.
+
+use std::collections::{
+    BTreeMap,
+    BTreeSet,
+};
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+use num_complex::Complex;
+use sprs::CsMat;
+
+use crate::Float;
+
+/// The sparse LU factorization $P (A - \sigma I) = L U$. In step k, row `pivots[k]` was chosen
+/// as pivot row, with the diagonal entry `diagonal[k]` of $U$ and the remaining entries `upper[k]`
+/// of that row of $U$. `multipliers[k]` holds the rows eliminated in step k and their multipliers.
+struct SparseLu<T> {
+    shift: Complex<T>,
+    pivots: Vec<usize>,
+    diagonal: Vec<Complex<T>>,
+    upper: Vec<Vec<(usize, Complex<T>)>>,
+    multipliers: Vec<Vec<(usize, Complex<T>)>>,
+}
+
+/// Storage for solving shifted linear systems $(A - \sigma I) x = b$ for a real sparse n×n matrix
+/// $A$ and many complex shifts $\sigma$.
+pub struct SparseShiftedSolver<T = f64> {
+    a: CsMat<T>,
+    factorizations: Vec<SparseLu<T>>,
+    y: Vec<Complex<T>>,
+    factorization_count: usize,
+}
+
+impl<T: Float + Default> SparseShiftedSolver<T> {
+    /// Stores the sparse n×n matrix `a` in compressed sparse row format and allocates the work
+    /// space for the solves. The factorizations are allocated per shift.
+    ///
+    /// NOTE: Panics if `a` is not square.
+    pub fn new(a: &CsMat<T>) -> Self {
+        let (n_rows, n_cols) = a.shape();
+        assert_eq!(n_rows, n_cols, "Shifted systems are only defined for square matrices.");
+
+        SparseShiftedSolver {
+            a: a.to_csr(),
+            factorizations: Vec::new(),
+            y: vec![Complex::new(T::zero(), T::zero()); n_rows],
+            factorization_count: 0,
+        }
+    }
+
+    /// Factorize $A - \sigma I$ for all `shifts` in advance, reusing existing factorizations of
+    /// the shifts or their conjugates.
+    pub fn factorize(&mut self, shifts: &[Complex<T>]) {
+        for &shift in shifts {
+            let _ = self.factorization(shift);
+        }
+    }
+
+    /// The number of factorizations calculated since the creation of the `SparseShiftedSolver`.
+    pub fn factorizations(&self) -> usize {
+        self.factorization_count
+    }
+
+    /// The number of nonzero entries of $L$ and $U$ in the factorization for `shift` or its
+    /// conjugate, or `None` if neither has been factorized.
+    pub fn factorization_nnz(&self, shift: Complex<T>) -> Option<usize> {
+        self.factorizations.iter()
+            .find(|f| f.shift == shift || f.shift == shift.conj())
+            .map(|f| {
+                let upper: usize = f.upper.iter().map(Vec::len).sum();
+                let lower: usize = f.multipliers.iter().map(Vec::len).sum();
+                f.diagonal.len() + upper + lower
+            })
+    }
+
+    /// Solve $(A - \sigma I) x = b$ for the stored matrix $A$ and the shift $\sigma$ = `shift`,
+    /// overwriting `b` with the solution $x$. If $A - \sigma I$ is singular, the solution
+    /// contains non-finite entries.
+    ///
+    /// NOTE: Panics if `b` doesn't have the same length as the dimension of the
+    /// `SparseShiftedSolver` object `solve` is called on.
+    pub fn solve<S>(&mut self, shift: Complex<T>, b: &mut ArrayBase<S, Ix1>)
+        where S: DataMut<Elem=Complex<T>>,
+    {
+        assert_eq!(b.len(), self.y.len(), "Dimension mismatch between vector `b` and preconfigured `SparseShiftedSolver` struct.");
+
+        let (index, is_conjugate) = self.factorization(shift);
+        let conjugate = |x: Complex<T>| if is_conjugate { x.conj() } else { x };
+
+        for (y, &x) in self.y.iter_mut().zip(b.iter()) {
+            *y = conjugate(x);
+        }
+
+        let lu = &self.factorizations[index];
+        lu.solve(&mut self.y);
+
+        for (x, &y) in b.iter_mut().zip(self.y.iter()) {
+            *x = conjugate(y);
+        }
+    }
+
+    /// Returns the index of the factorization for `shift`, calculating it if neither the shift
+    /// nor its conjugate have been factorized, and whether it belongs to the conjugate shift.
+    fn factorization(&mut self, shift: Complex<T>) -> (usize, bool) {
+        if let Some(index) = self.factorizations.iter().position(|f| f.shift == shift) {
+            return (index, false);
+        }
+        if let Some(index) = self.factorizations.iter().position(|f| f.shift == shift.conj()) {
+            return (index, true);
+        }
+
+        self.factorizations.push(SparseLu::new(&self.a, shift));
+        self.factorization_count += 1;
+        (self.factorizations.len() - 1, false)
+    }
+}
+
+impl<T: Float + Default> SparseLu<T> {
+    /// Factorizes $A - \sigma I$ for the sparse matrix `a` in compressed sparse row format.
+    fn new(a: &CsMat<T>, shift: Complex<T>) -> Self {
+        let (n, _) = a.shape();
+        let zero = Complex::new(T::zero(), T::zero());
+
+        // The active rows of the matrix being eliminated, and for every column the active rows
+        // with a (structurally) nonzero entry in it, i.e. the pivot candidates.
+        let mut rows: Vec<BTreeMap<usize, Complex<T>>> = vec![BTreeMap::new(); n];
+        let mut columns: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); n];
+        for (&x, (i, j)) in a.iter() {
+            let entry = rows[i].entry(j).or_insert(zero);
+            *entry = *entry + Complex::new(x, T::zero());
+            columns[j].insert(i);
+        }
+        for (i, row) in rows.iter_mut().enumerate() {
+            let entry = row.entry(i).or_insert(zero);
+            *entry = *entry - shift;
+            columns[i].insert(i);
+        }
+
+        let mut is_pivoted = vec![false; n];
+        let mut pivots = Vec::with_capacity(n);
+        let mut diagonal = Vec::with_capacity(n);
+        let mut upper = Vec::with_capacity(n);
+        let mut multipliers = Vec::with_capacity(n);
+
+        for k in 0..n {
+            let candidates = std::mem::take(&mut columns[k]);
+
+            // Without candidates the matrix is singular; any active row yields a zero pivot.
+            let pivot = candidates.iter()
+                .cloned()
+                .max_by(|&r, &s| rows[r][&k].norm().partial_cmp(&rows[s][&k].norm()).unwrap())
+                .unwrap_or_else(|| is_pivoted.iter().position(|&p| !p).unwrap());
+            is_pivoted[pivot] = true;
+
+            let mut pivot_row = std::mem::take(&mut rows[pivot]);
+            for &j in pivot_row.keys() {
+                columns[j].remove(&pivot);
+            }
+            let pivot_value = pivot_row.remove(&k).unwrap_or(zero);
+            let pivot_row: Vec<_> = pivot_row.into_iter().collect();
+
+            let mut eliminated = Vec::with_capacity(candidates.len());
+            for r in candidates.into_iter().filter(|&r| r != pivot) {
+                let multiplier = rows[r].remove(&k).unwrap() / pivot_value;
+                for &(j, u) in &pivot_row {
+                    let entry = rows[r].entry(j).or_insert_with(|| {
+                        columns[j].insert(r);
+                        zero
+                    });
+                    *entry = *entry - multiplier * u;
+                }
+                eliminated.push((r, multiplier));
+            }
+
+            pivots.push(pivot);
+            diagonal.push(pivot_value);
+            upper.push(pivot_row);
+            multipliers.push(eliminated);
+        }
+
+        SparseLu { shift, pivots, diagonal, upper, multipliers }
+    }
+
+    /// Overwrites `y` with $(A - \sigma I)^{-1} y$.
+    fn solve(&self, y: &mut [Complex<T>]) {
+        // Forward elimination on the original row positions.
+        for (&pivot, eliminated) in self.pivots.iter().zip(self.multipliers.iter()) {
+            let y_pivot = y[pivot];
+            for &(r, multiplier) in eliminated {
+                y[r] = y[r] - multiplier * y_pivot;
+            }
+        }
+
+        // The pivot row of step k holds the right hand side for the unknown k. Permuting in
+        // place lets back substitution overwrite `y` with the solution.
+        let permuted: Vec<_> = self.pivots.iter().map(|&pivot| y[pivot]).collect();
+        y.copy_from_slice(&permuted);
+
+        for k in (0..y.len()).rev() {
+            let sum = self.upper[k].iter().fold(y[k], |acc, &(j, u)| acc - u * y[j]);
+            y[k] = sum / self.diagonal[k];
+        }
+    }
+}
+
+/// Solve $(A - \sigma_i I) x_i = b$ for the real sparse n×n matrix `a` and all `shifts`
+/// $\sigma_i$, storing the solution $x_i$ in the i-th column of `x`.
+///
+/// NOTE: Panics if `a` is not square, if `b` is not of length n, or if `x` is not of dimension
+/// n×k for k shifts.
+pub fn solve_shifted_sparse<T, S1, S2>(a: &CsMat<T>, shifts: &[Complex<T>], b: &ArrayBase<S1, Ix1>, x: &mut ArrayBase<S2, Ix2>)
+    where T: Float + Default,
+          S1: Data<Elem=Complex<T>>,
+          S2: DataMut<Elem=Complex<T>>,
+{
+    let (n, _) = a.shape();
+    assert_eq!(x.dim(), (n, shifts.len()), "Dimension mismatch between matrix `x` and the shifts and matrix `a`.");
+
+    let mut solver = SparseShiftedSolver::new(a);
+    for (&shift, mut x) in shifts.iter().zip(x.gencolumns_mut()) {
+        x.assign(b);
+        solver.solve(shift, &mut x);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use num_complex::Complex;
+    use sprs::CsMat;
+    use approx::assert_relative_eq;
+
+    use super::SparseShiftedSolver;
+
+    /// A sparse 6×6 matrix with a zero diagonal entry, so that the factorization has to pivot.
+    fn sparse_matrix() -> CsMat<f64> {
+        CsMat::new(
+            (6, 6),
+            vec![0, 2, 5, 7, 9, 12, 14],
+            vec![0, 5, 0, 1, 2, 3, 4, 2, 3, 0, 4, 5, 1, 5],
+            vec![-2.0, 1.0, 0.5, 0.0, 3.0, 1.0, -0.5, 0.25, -1.0, 2.0, -3.0, 0.5, 1.5, -0.25],
+        )
+    }
+
+    #[test]
+    fn sparse_solutions_agree_with_dense() {
+        let a = sparse_matrix();
+        let b = arr1(&[
+            Complex::new(1.0, 0.0), Complex::new(-2.0, 0.5), Complex::new(0.0, 1.0),
+            Complex::new(3.0, 0.0), Complex::new(0.5, -0.5), Complex::new(-1.0, 2.0),
+        ]);
+        let shifts = [Complex::new(-1.5, 2.0), Complex::new(-1.5, -2.0), Complex::new(0.3, 0.0), Complex::new(4.0, 10.0)];
+
+        let mut x = Array2::<Complex<f64>>::zeros((6, 4));
+        crate::solve_shifted_sparse(&a, &shifts, &b, &mut x);
+        let mut expected = Array2::<Complex<f64>>::zeros((6, 4));
+        crate::solve_shifted(&a.to_dense(), &shifts, &b, &mut expected);
+
+        for (x, y) in x.iter().zip(expected.iter()) {
+            assert_relative_eq!((x - y).norm(), 0.0, epsilon=1e-13);
+        }
+    }
+
+    #[test]
+    fn sparse_factorizations_are_reused_and_stay_sparse() {
+        // A tridiagonal matrix factorizes without fill-in beyond the second superdiagonal.
+        let n: usize = 50;
+        let mut indptr = vec![0];
+        let mut indices = Vec::new();
+        let mut data = Vec::new();
+        for i in 0..n {
+            for j in i.saturating_sub(1)..(i + 2).min(n) {
+                indices.push(j);
+                data.push(if i == j { -2.0 } else { 1.0 });
+            }
+            indptr.push(indices.len());
+        }
+        let a = CsMat::new((n, n), indptr, indices, data);
+        let shift = Complex::new(-0.5, 1.5);
+        let b = Array1::from_shape_fn(n, |i| Complex::new(i as f64, 1.0));
+
+        let mut solver = SparseShiftedSolver::new(&a);
+        solver.factorize(&[shift]);
+        assert!(solver.factorization_nnz(shift).unwrap() <= 4 * n);
+
+        let mut x = b.clone();
+        solver.solve(shift, &mut x);
+        let mut x_conjugate = b.mapv(|x| x.conj());
+        solver.solve(shift.conj(), &mut x_conjugate);
+        assert_eq!(solver.factorizations(), 1);
+
+        for (&x, &y) in x.iter().zip(x_conjugate.iter()) {
+            assert_relative_eq!(x.re, y.re, max_relative=1e-14);
+            assert_relative_eq!(x.im, -y.im, max_relative=1e-14);
+        }
+    }
+}
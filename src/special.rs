@@ -0,0 +1,208 @@
+//! Special functions used throughout the crate, e.g. for the coefficients of Padé approximants
+//! and Taylor series, and exposed for downstream numerical code.
+//!
+//! The factorials $0!, \dots, 170!$, which are all factorials representable in double precision,
+//! are tabulated on first use in a thread-safe `OnceLock`. The gamma function is evaluated with
+//! the Lanczos approximation for $g = 7$ and 9 coefficients, see [Press et al. 2007, Section
+//! 6.1], which is accurate to about 15 significant digits, and exactly at the positive integers.
+//! Binomial and multinomial coefficients are available in floating point, in logarithmic form
+//! for large arguments, and as exact integers via `binomial_checked`.
+//!
+//! [Press et al. 2007]: http://numerical.recipes/
+
+use std::f64::consts::PI;
+use std::sync::OnceLock;
+
+/// The largest $n$ for which $n!$ is finite in double precision.
+pub const MAX_FACTORIAL: u64 = 170;
+
+const LANCZOS_G: f64 = 7.0;
+const LANCZOS_COEFFICIENTS: [f64; 9] = [
+    0.999_999_999_999_809_9,
+    676.520_368_121_885_1,
+    -1_259.139_216_722_402_8,
+    771.323_428_777_653_1,
+    -176.615_029_162_140_6,
+    12.507_343_278_686_905,
+    -0.138_571_095_265_720_12,
+    9.984_369_578_019_572e-6,
+    1.505_632_735_149_311_6e-7,
+];
+
+/// The tabulated factorials $0!, \dots, 170!$.
+fn factorials() -> &'static [f64; MAX_FACTORIAL as usize + 1] {
+    static FACTORIALS: OnceLock<[f64; MAX_FACTORIAL as usize + 1]> = OnceLock::new();
+    FACTORIALS.get_or_init(|| {
+        let mut table = [1.0; MAX_FACTORIAL as usize + 1];
+        for i in 1..table.len() {
+            table[i] = table[i - 1] * i as f64;
+        }
+        table
+    })
+}
+
+/// The factorial $n!$, which is infinite for $n > 170$.
+pub fn factorial(n: u64) -> f64 {
+    if n > MAX_FACTORIAL {
+        f64::INFINITY
+    } else {
+        factorials()[n as usize]
+    }
+}
+
+/// The natural logarithm of the factorial, $\ln n!$.
+pub fn ln_factorial(n: u64) -> f64 {
+    if n > MAX_FACTORIAL {
+        ln_gamma(n as f64 + 1.0)
+    } else {
+        factorials()[n as usize].ln()
+    }
+}
+
+/// The binomial coefficient $\binom{n}{k}$, which vanishes for $k > n$. It is exact as long as
+/// the result is an integer representable in double precision.
+pub fn binomial(n: u64, k: u64) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    match binomial_checked(n, k) {
+        Some(b) => b as f64,
+        None => ln_binomial(n, k).exp(),
+    }
+}
+
+/// The natural logarithm of the binomial coefficient, $\ln \binom{n}{k}$, which is $-\infty$ for
+/// $k > n$.
+pub fn ln_binomial(n: u64, k: u64) -> f64 {
+    if k > n {
+        f64::NEG_INFINITY
+    } else {
+        ln_factorial(n) - ln_factorial(k) - ln_factorial(n - k)
+    }
+}
+
+/// The binomial coefficient $\binom{n}{k}$ as an exact integer, or `None` if it overflows
+/// `u128`.
+pub fn binomial_checked(n: u64, k: u64) -> Option<u128> {
+    if k > n {
+        return Some(0);
+    }
+    let k = k.min(n - k);
+
+    // C(n, i+1) = C(n, i) (n - i) / (i + 1), where dividing by g = gcd(C(n, i), i + 1) first
+    // keeps all intermediate results exact and no larger than necessary.
+    let mut b: u128 = 1;
+    for i in 0..u128::from(k) {
+        let numerator = u128::from(n) - i;
+        let denominator = i + 1;
+        let g = gcd(b, denominator);
+        b = (b / g).checked_mul(numerator / (denominator / g))?;
+    }
+    Some(b)
+}
+
+/// The multinomial coefficient $n! / (k_1! \cdots k_m!)$ for $n = k_1 + \dots + k_m$.
+pub fn multinomial(ks: &[u64]) -> f64 {
+    let mut n = 0;
+    let mut coefficient = 1.0;
+    for &k in ks {
+        n += k;
+        coefficient *= binomial(n, k);
+    }
+    coefficient
+}
+
+/// The gamma function $\Gamma(x)$, with $\Gamma(n + 1) = n!$ for the non-negative integers $n$.
+/// The poles at the non-positive integers are returned as NaN.
+pub fn gamma(x: f64) -> f64 {
+    if x <= 0.0 && x == x.floor() {
+        return f64::NAN;
+    }
+    if x == x.floor() && x <= (MAX_FACTORIAL + 1) as f64 {
+        return factorial(x as u64 - 1);
+    }
+    if x < 0.5 {
+        // Reflection formula Γ(x) Γ(1 - x) = π / sin(πx)
+        return PI / ((PI * x).sin() * gamma(1.0 - x));
+    }
+
+    // The power is split, since it overflows before the exponential is applied for large x.
+    let (t, sum) = lanczos(x);
+    let power = t.powf(0.5 * (x - 0.5));
+    (2.0 * PI).sqrt() * power * ((-t).exp() * power) * sum
+}
+
+/// The natural logarithm of the absolute value of the gamma function, $\ln \lvert \Gamma(x)
+/// \rvert$, which is infinite at the non-positive integers.
+pub fn ln_gamma(x: f64) -> f64 {
+    if x <= 0.0 && x == x.floor() {
+        return f64::INFINITY;
+    }
+    if x == x.floor() && x <= (MAX_FACTORIAL + 1) as f64 {
+        return ln_factorial(x as u64 - 1);
+    }
+    if x < 0.5 {
+        return (PI / (PI * x).sin().abs()).ln() - ln_gamma(1.0 - x);
+    }
+
+    let (t, sum) = lanczos(x);
+    0.5 * (2.0 * PI).ln() + (x - 0.5) * t.ln() - t + sum.ln()
+}
+
+/// The shifted argument $t = x + g - 1/2$ and the series of the Lanczos approximation for $x \ge
+/// 1/2$.
+fn lanczos(x: f64) -> (f64, f64) {
+    let x = x - 1.0;
+    let sum = LANCZOS_COEFFICIENTS
+        .iter()
+        .enumerate()
+        .skip(1)
+        .fold(LANCZOS_COEFFICIENTS[0], |acc, (i, &c)| acc + c / (x + i as f64));
+    (x + LANCZOS_G + 0.5, sum)
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    #[test]
+    fn gamma_agrees_with_factorials() {
+        assert_eq!(factorial(0), 1.0);
+        assert_eq!(factorial(10), 3_628_800.0);
+        assert_eq!(factorial(171), f64::INFINITY);
+        assert_relative_eq!(gamma(0.5), PI.sqrt(), max_relative=1e-14);
+        assert_relative_eq!(gamma(-0.5), -2.0 * PI.sqrt(), max_relative=1e-14);
+        assert_relative_eq!(gamma(4.5), 3.5 * 2.5 * 1.5 * 0.5 * PI.sqrt(), max_relative=1e-14);
+        assert!(gamma(-2.0).is_nan());
+        assert_relative_eq!(gamma(150.5), ln_gamma(150.5).exp(), max_relative=1e-12);
+        assert_relative_eq!(gamma(170.5), 169.5 * gamma(169.5), max_relative=1e-13);
+        assert_relative_eq!(gamma(170.5), ln_gamma(170.5).exp(), max_relative=1e-12);
+        assert_relative_eq!(ln_gamma(200.5) - ln_gamma(199.5), 199.5f64.ln(), max_relative=1e-12);
+        assert_relative_eq!(ln_factorial(300), ln_factorial(299) + 300f64.ln(), max_relative=1e-14);
+    }
+
+    #[test]
+    fn binomials_are_exact() {
+        assert_eq!(binomial_checked(10, 3), Some(120));
+        assert_eq!(binomial_checked(3, 10), Some(0));
+        assert_eq!(binomial_checked(67, 33), Some(14_226_520_737_620_288_370));
+        assert_eq!(binomial_checked(130, 65), Some(95_067_625_827_960_698_145_584_333_020_095_113_100));
+        assert_eq!(binomial_checked(200, 100), None);
+
+        assert_eq!(binomial(26, 13), 10_400_600.0);
+        assert_relative_eq!(binomial(200, 100), ln_binomial(200, 100).exp(), max_relative=1e-12);
+        assert_eq!(multinomial(&[2, 3, 1]), 60.0);
+    }
+}
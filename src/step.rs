@@ -0,0 +1,212 @@
+//! Suggestion of time steps for the repeated propagation $x_{k+1} = e^{A \Delta t} x_k$.
+//!
+//! When the same exponential is applied many times, it pays off to choose the step $\Delta t$
+//! such that $e^{A \Delta t}$ can be evaluated by a single Padé approximant without any squarings.
+//! Following [Al-Mohy, Higham 2009], the Padé approximant $r_m$ of order $m$ evaluated at a
+//! matrix with $\lVert A \Delta t \rVert_1 \le \theta_m$ has a relative backward error
+//! bounded by the unit roundoff. For accuracies $\epsilon$ coarser than the unit roundoff, the
+//! thresholds are relaxed with the full series of the relative backward error, see
+//! `pade_theta`, in proportion to the tabulated thresholds, so that they grow continuously with
+//! the tolerance.
+//!
+//! If the matrix itself is available, the spectrum is first shifted by $\mu = \mathrm{tr}(A) / n$,
+//! as in the preprocessing step of [Higham 2005]. The shift accounts for the part of the spectral
+//! abscissa that is shared by all eigenvalues, $e^{A \Delta t} = e^{\mu \Delta t} e^{(A - \mu I)
+//! \Delta t}$, and often reduces the norm considerably, allowing for larger steps. Without the
+//! matrix, estimates of $\lVert A \rVert_1$, of the spectral abscissa $\alpha$, and of $\lVert A -
+//! \alpha I \rVert_1$ serve the same purpose, e.g. for generators whose eigenvalues cluster to the
+//! left of the abscissa.
+//!
+//! [Al-Mohy, Higham 2009]: https://doi.org/10.1137/09074721X
+//! [Higham 2005]: https://doi.org/10.1137/04061101X
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+};
+
+use crate::{
+    float::{
+        onenorm,
+        shifted_onenorm,
+    },
+    pade_theta,
+    Float,
+};
+
+/// A suggested time step for the propagation over a horizon `t` in `steps` steps of equal size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StepSuggestion<T> {
+    /// The suggested time step, which divides the horizon into `steps` equal steps.
+    pub dt: T,
+    /// The number of steps required to cover the horizon.
+    pub steps: usize,
+    /// The Padé order that reaches the requested accuracy at `dt` without squarings.
+    pub order: u64,
+    /// The scalar shift $\mu$, which has to be applied as $e^{A \Delta t} = e^{\mu \Delta t}
+    /// e^{(A - \mu I) \Delta t}$. Zero if only a norm estimate was provided.
+    pub shift: T,
+    /// The number of squarings needed if instead the whole horizon is covered in a single step.
+    pub squarings: i32,
+}
+
+/// The threshold $\theta_m$ for the Padé approximant of order `m` up to which the relative
+/// backward error is bounded by `tolerance`.
+///
+/// For tolerances at or below the unit roundoff of `T`, this is the threshold used by `expm`.
+/// Above, the threshold is relaxed by the ratio of the thresholds `pade_theta` at the tolerance
+/// and at the unit roundoff, but at most to `pade_theta` at the tolerance, which keeps the
+/// backward error bound below the tolerance.
+///
+/// NOTE: Panics if `m` is not one of 3, 5, 7, 9, 13, or not supported for the precision `T`.
+pub fn theta_for_tolerance<T: Float>(m: u64, tolerance: T) -> T {
+    assert!([3, 5, 7, 9, 13].contains(&m), "Padé order {} is not supported.", m);
+    let theta = T::threshold(m).unwrap_or_else(|| panic!("Padé order {} is not supported for this precision.", m));
+    let theta = T::from_f64(theta).unwrap();
+
+    if tolerance <= T::unit_roundoff() {
+        return theta;
+    }
+
+    // Tabulated thresholds may be smaller than the bound allows, e.g. θ₁₃ of [Al-Mohy, Higham
+    // 2009], and are relaxed in proportion.
+    let unit_roundoff = T::unit_roundoff().to_f64().unwrap();
+    let relaxed = pade_theta(m, tolerance.to_f64().unwrap());
+    let proportional = relaxed / pade_theta(m, unit_roundoff) * theta.to_f64().unwrap();
+    T::from_f64(proportional.min(relaxed)).unwrap().max(theta)
+}
+
+/// Suggest a time step for the propagation with the n×n matrix `a` over the horizon `t`, such
+/// that each step reaches a relative backward error of `tolerance` without squarings.
+///
+/// NOTE: Panics if `a` is not square.
+pub fn suggest_step<T, S>(a: &ArrayBase<S, Ix2>, t: T, tolerance: T) -> StepSuggestion<T>
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    let (n_rows, n_cols) = a.dim();
+    assert_eq!(n_rows, n_cols, "Step suggestions are only available for square matrices.");
+
+    if n_rows == 0 {
+        return suggest_step_from_norm(T::zero(), t, tolerance);
+    }
+
+    let shift = a.diag().fold(T::zero(), |acc, &x| acc + x) / T::from_usize(n_rows).unwrap();
+
+    let norm_a = onenorm(a);
+    let norm_shifted = shifted_onenorm(a, shift);
+    suggest_shifted_step(norm_a, shift, norm_shifted, t, tolerance)
+}
+
+/// Suggest a time step for the propagation over the horizon `t` from estimates of the 1-norm
+/// `norm_a` of the matrix $A$, its spectral abscissa `abscissa` $\alpha$, and the 1-norm
+/// `norm_shifted` of $A - \alpha I$, e.g. from `Normest1`, such that each step reaches a relative
+/// backward error of `tolerance` without squarings. The shift by the abscissa is suggested if it
+/// reduces the norm.
+pub fn suggest_step_from_estimates<T: Float>(norm_a: T, abscissa: T, norm_shifted: T, t: T, tolerance: T) -> StepSuggestion<T> {
+    suggest_shifted_step(norm_a, abscissa, norm_shifted, t, tolerance)
+}
+
+/// Suggest a time step for the propagation with a matrix of 1-norm (or 1-norm estimate)
+/// `norm_a` over the horizon `t`, such that each step reaches a relative backward error of
+/// `tolerance` without squarings.
+pub fn suggest_step_from_norm<T: Float>(norm_a: T, t: T, tolerance: T) -> StepSuggestion<T> {
+    let order = T::MAX_PADE_ORDER;
+    let theta = theta_for_tolerance(order, tolerance);
+    let norm = norm_a * t.abs();
+
+    if norm <= theta {
+        return StepSuggestion {
+            dt: t,
+            steps: 1,
+            order: smallest_sufficient_order(norm, tolerance),
+            shift: T::zero(),
+            squarings: 0,
+        };
+    }
+
+    let steps = (norm / theta).ceil().to_usize().unwrap_or(usize::MAX);
+    let squarings = (norm / theta).log2().ceil().to_i32().unwrap_or(0).max(0);
+    let dt = t / T::from_usize(steps).unwrap();
+
+    StepSuggestion {
+        dt,
+        steps,
+        order,
+        shift: T::zero(),
+        squarings,
+    }
+}
+
+/// The suggestion for the matrix of 1-norm `norm_a`, or for the matrix shifted by `shift` of
+/// 1-norm `norm_shifted` if that is smaller.
+fn suggest_shifted_step<T: Float>(norm_a: T, shift: T, norm_shifted: T, t: T, tolerance: T) -> StepSuggestion<T> {
+    if norm_shifted < norm_a {
+        StepSuggestion {
+            shift,
+            ..suggest_step_from_norm(norm_shifted, t, tolerance)
+        }
+    } else {
+        suggest_step_from_norm(norm_a, t, tolerance)
+    }
+}
+
+/// The smallest Padé order whose threshold is not exceeded by `norm`.
+fn smallest_sufficient_order<T: Float>(norm: T, tolerance: T) -> u64 {
+    T::THETAS.iter()
+        .map(|&(m, _)| m)
+        .find(|&m| norm <= theta_for_tolerance(m, tolerance))
+        .unwrap_or(T::MAX_PADE_ORDER)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+
+    #[test]
+    fn steps_avoid_squarings() {
+        let a = arr2(&[[-1.0, 3.0], [0.5, -2.0]]);
+        let suggestion = crate::suggest_step(&a, 10.0, f64::EPSILON / 2.0);
+
+        let norm = (&a - &(Array2::eye(2) * suggestion.shift)).gencolumns().into_iter()
+            .map(|c| c.iter().map(|x: &f64| x.abs()).sum::<f64>())
+            .fold(0.0, f64::max);
+        assert!(norm * suggestion.dt <= <f64 as crate::Float>::threshold(13).unwrap());
+        assert!((suggestion.dt * suggestion.steps as f64 - 10.0).abs() < 1e-12);
+        assert_eq!(suggestion.order, 13);
+        assert!(suggestion.squarings > 0);
+    }
+
+    #[test]
+    fn coarser_tolerance_allows_larger_steps() {
+        let fine = crate::suggest_step_from_norm(100.0f64, 1.0, f64::EPSILON / 2.0);
+        let coarse = crate::suggest_step_from_norm(100.0f64, 1.0, 1e-6);
+        assert!(coarse.steps < fine.steps);
+        assert!(coarse.dt > fine.dt);
+
+        // The relaxed thresholds are continuous and respect the tolerance.
+        for &m in &[3, 5, 7, 9, 13] {
+            let unit_roundoff = f64::EPSILON / 2.0;
+            let theta = crate::theta_for_tolerance(m, unit_roundoff);
+            let relaxed = crate::theta_for_tolerance(m, 1.01 * unit_roundoff);
+            assert!(relaxed >= theta && relaxed < 1.01 * theta);
+            for &tolerance in &[1e-12, 1e-8, 1e-4] {
+                let theta = crate::theta_for_tolerance(m, tolerance);
+                assert!(crate::pade_backward_error_bound(m, theta) <= tolerance);
+            }
+        }
+    }
+
+    #[test]
+    fn abscissa_shift_allows_larger_steps() {
+        // A = -50 I + N with a small N: the eigenvalues cluster at the abscissa -50.
+        let unshifted = crate::suggest_step_from_norm(51.0f64, 1.0, 1e-10);
+        let shifted = crate::suggest_step_from_estimates(51.0f64, -50.0, 1.0, 1.0, 1e-10);
+        assert_eq!(shifted.shift, -50.0);
+        assert!(shifted.steps < unshifted.steps);
+
+        let unhelpful = crate::suggest_step_from_estimates(51.0f64, -50.0, 60.0, 1.0, 1e-10);
+        assert_eq!(unhelpful, unshifted);
+    }
+}
@@ -0,0 +1,235 @@
+//! The matrix exponential of real symmetric matrices via their eigendecomposition,
+//!
+//! \begin{equation}
+//!     e^A = V e^\Lambda V^\top, \quad A = V \Lambda V^\top.
+//! \end{equation}
+//!
+//! Because $V$ is orthogonal, this is accurate to rounding irrespective of the norm of $A$, and
+//! avoids the squaring phase which can amplify rounding errors for ill-conditioned cases.
+//!
+//! A complex Hermitian matrix $A = S + iK$ is represented by the real symmetric 2n×2n matrix
+//! $M = \begin{pmatrix} S & -K \\ K & S \end{pmatrix}$ as in `ExpmUnitary`, and $e^M$ has the
+//! same block structure with $\operatorname{Re} e^A$ and $\operatorname{Im} e^A$ in place of $S$
+//! and $K$. The eigendecomposition of $M$ costs about eight times that of a real n×n matrix.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+use num_complex::Complex;
+
+use crate::{
+    float::syev,
+    unitary::{
+        apply_spectral,
+        equalize_pairs,
+        hermitian_embedding,
+    },
+    Float,
+};
+
+/// Storage for calculating the matrix exponential of real symmetric and complex Hermitian
+/// matrices.
+pub struct ExpmSym<T = f64> {
+    n: usize,
+    eigenvectors: Array2<T>,
+    eigenvalues: Array1<T>,
+    work: Array2<T>,
+    embedding: Array2<T>,
+    embedding_eigenvalues: Array1<T>,
+    embedding_work: Array2<T>,
+    embedding_exp: Array2<T>,
+}
+
+impl<T: Float> ExpmSym<T> {
+    /// Allocates all space to calculate the matrix exponential for symmetric and Hermitian
+    /// matrices of dimension n×n.
+    pub fn new(n: usize) -> Self {
+        ExpmSym {
+            n,
+            eigenvectors: Array2::zeros((n, n)),
+            eigenvalues: Array1::zeros(n),
+            work: Array2::zeros((n, n)),
+            embedding: Array2::zeros((2 * n, 2 * n)),
+            embedding_eigenvalues: Array1::zeros(2 * n),
+            embedding_work: Array2::zeros((2 * n, 2 * n)),
+            embedding_exp: Array2::zeros((2 * n, 2 * n)),
+        }
+    }
+
+    /// Calculate the matrix exponential of the symmetric n×n matrix `a` storing the result in
+    /// matrix `b`. Only the upper triangle of `a` is referenced.
+    ///
+    /// NOTE: Panics if input matrices `a` and `b` don't have matching dimensions, are not square,
+    /// don't have the same dimension as the `ExpmSym` object `expm` is called on, or if the
+    /// eigendecomposition of `a` does not converge, e.g. for non-finite entries.
+    pub fn expm<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert_eq!(a.dim(), b.dim(), "Input matrices `a` and `b` have to have matching dimensions.");
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `ExpmSym` struct.");
+
+        self.eigenvectors.assign(a);
+        let info = syev(&mut self.eigenvectors, &mut self.eigenvalues);
+        assert_eq!(info, 0, "The eigendecomposition of `a` did not converge.");
+
+        // B = V exp(Λ) Vᵀ
+        apply_spectral(&self.eigenvectors, &self.eigenvalues, |x| x.exp(), T::one(), &mut self.work, b);
+        symmetrize(b, &mut self.work);
+    }
+
+    /// Calculate the matrix exponential of the complex Hermitian n×n matrix `a` storing the
+    /// result in matrix `b`. Only the upper triangle of `a` is referenced, and the imaginary
+    /// parts of its diagonal are ignored.
+    ///
+    /// NOTE: Panics if input matrices `a` and `b` don't have matching dimensions, are not square,
+    /// don't have the same dimension as the `ExpmSym` object `expm_hermitian` is called on, or if
+    /// the eigendecomposition of `a` does not converge, e.g. for non-finite entries.
+    pub fn expm_hermitian<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=Complex<T>>,
+              S2: DataMut<Elem=Complex<T>>,
+    {
+        let n = self.n;
+        assert_eq!(a.dim(), b.dim(), "Input matrices `a` and `b` have to have matching dimensions.");
+        assert_eq!(a.dim(), (n, n), "Dimension mismatch between matrix `a` and preconfigured `ExpmSym` struct.");
+
+        hermitian_embedding(a, &mut self.embedding);
+        let info = syev(&mut self.embedding, &mut self.embedding_eigenvalues);
+        assert_eq!(info, 0, "The eigendecomposition of `a` did not converge.");
+
+        // Split eigenvalue pairs would let exp(M) depart from the block structure.
+        equalize_pairs(&mut self.embedding_eigenvalues);
+        apply_spectral(&self.embedding, &self.embedding_eigenvalues, |x| x.exp(), T::one(), &mut self.embedding_work, &mut self.embedding_exp);
+
+        // exp(M) = [[Re, -Im], [Im, Re]]. Averaging both copies of each block and the result with
+        // its conjugate transpose removes the departure from the structure introduced by rounding.
+        let e = &self.embedding_exp;
+        let half = T::from_f64(0.5).unwrap();
+        let quarter = T::from_f64(0.25).unwrap();
+        for i in 0..n {
+            let re = half * (e[(i, i)] + e[(n + i, n + i)]);
+            b[(i, i)] = Complex::new(re, T::zero());
+            for j in i+1..n {
+                let re = quarter * (e[(i, j)] + e[(n + i, n + j)] + e[(j, i)] + e[(n + j, n + i)]);
+                let im = quarter * (e[(n + i, j)] - e[(i, n + j)] - e[(n + j, i)] + e[(j, n + i)]);
+                b[(i, j)] = Complex::new(re, im);
+                b[(j, i)] = Complex::new(re, -im);
+            }
+        }
+    }
+}
+
+/// Removes the asymmetry of `b` introduced by rounding, using `work` as scratch space.
+pub(crate) fn symmetrize<T, S>(b: &mut ArrayBase<S, Ix2>, work: &mut Array2<T>)
+    where T: Float,
+          S: DataMut<Elem=T>,
+{
+    let half = T::from_f64(0.5).unwrap();
+    work.assign(b);
+    b.zip_mut_with(&work.t(), |x, &y| *x = half * (*x + y));
+}
+
+/// Calculate the matrix exponential of the symmetric n×n matrix `a` storing the result in matrix
+/// `b`. Only the upper triangle of `a` is referenced.
+///
+/// NOTE: Panics if input matrices `a` and `b` don't have matching dimensions, are not square, or
+/// if the eigendecomposition of `a` does not converge.
+pub fn expm_sym<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut expm = ExpmSym::new(n);
+    expm.expm(a, b);
+}
+
+/// Calculate the matrix exponential of the complex Hermitian n×n matrix `a` storing the result in
+/// matrix `b`. Only the upper triangle of `a` is referenced, and the imaginary parts of its
+/// diagonal are ignored.
+///
+/// NOTE: Panics if input matrices `a` and `b` don't have matching dimensions, are not square, or
+/// if the eigendecomposition of `a` does not converge.
+pub fn expm_hermitian<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=Complex<T>>,
+          S2: DataMut<Elem=Complex<T>>,
+{
+    let (n, _) = a.dim();
+
+    let mut expm = ExpmSym::new(n);
+    expm.expm_hermitian(a, b);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{
+        prelude::*,
+        s,
+    };
+    use num_complex::Complex;
+    use approx::assert_relative_eq;
+
+    use crate::ExpmOptions;
+
+    #[test]
+    fn sym_agrees_with_pade() {
+        let a = arr2(&[[-2.0, 1.0, 0.5], [1.0, -3.0, 0.25], [0.5, 0.25, 1.0]]);
+        let mut b = Array2::<f64>::zeros((3, 3));
+        let mut expected = Array2::<f64>::zeros((3, 3));
+
+        crate::expm_sym(&a, &mut b);
+        crate::expm(&a, &mut expected);
+
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-13, epsilon=1e-15);
+        }
+        assert_eq!(b, b.t());
+    }
+
+    #[test]
+    fn hermitian_agrees_with_real_embedding() {
+        let a = arr2(&[
+            [Complex::new(-1.0, 0.0), Complex::new(0.5, -0.75), Complex::new(0.0, 0.25)],
+            [Complex::new(0.5, 0.75), Complex::new(-2.0, 0.0), Complex::new(0.25, 1.0)],
+            [Complex::new(0.0, -0.25), Complex::new(0.25, -1.0), Complex::new(3.0, 0.0)],
+        ]);
+        let mut b = Array2::<Complex<f64>>::zeros((3, 3));
+
+        crate::expm_hermitian(&a, &mut b);
+
+        // exp([[S, -K], [K, S]]) has the real part in its upper left and the imaginary part in
+        // its lower left block.
+        let mut block = Array2::<f64>::zeros((6, 6));
+        block.slice_mut(s![..3, ..3]).assign(&a.mapv(|x| x.re));
+        block.slice_mut(s![3.., 3..]).assign(&a.mapv(|x| x.re));
+        block.slice_mut(s![..3, 3..]).assign(&a.mapv(|x| -x.im));
+        block.slice_mut(s![3.., ..3]).assign(&a.mapv(|x| x.im));
+        let mut exp_block = Array2::<f64>::zeros((6, 6));
+        crate::expm(&block, &mut exp_block);
+        for ((b, &re), &im) in b.iter().zip(exp_block.slice(s![..3, ..3]).iter()).zip(exp_block.slice(s![3.., ..3]).iter()) {
+            assert_relative_eq!(b.re, re, max_relative=1e-13, epsilon=1e-14);
+            assert_relative_eq!(b.im, im, max_relative=1e-13, epsilon=1e-14);
+        }
+        assert_eq!(b, b.t().mapv(|x| x.conj()));
+    }
+
+    #[test]
+    fn symmetric_option_uses_eigendecomposition() {
+        let a = arr2(&[[-2.0, 1.0, 0.5], [1.0, -3.0, 0.25], [0.5, 0.25, 1.0]]);
+        let mut b = Array2::<f64>::zeros((3, 3));
+        let mut expected = Array2::<f64>::zeros((3, 3));
+
+        let options = ExpmOptions { symmetric: true, ..ExpmOptions::default() };
+        let report = crate::expm_with_opts(&a, &mut b, &options);
+        crate::expm_sym(&a, &mut expected);
+
+        assert!(report.symmetric);
+        assert_eq!(report.counters.linear_solves, 0);
+        assert_eq!(b, expected);
+    }
+}
@@ -0,0 +1,277 @@
+//! Transient growth of the semigroup $e^{tA}$, i.e. the behaviour of $\lVert e^{tA} \rVert_1$ for
+//! $t \ge 0$.
+//!
+//! Even if all eigenvalues of $A$ lie in the left half plane, $\lVert e^{tA} \rVert$ can grow by
+//! orders of magnitude before it decays, when $A$ is far from normal, see L. N. Trefethen and M.
+//! Embree, *Spectra and Pseudospectra* (2005). The growth at a given time is estimated with
+//! `ExpmNormEst`, which only requires the action of $e^{tA}$ on a few vectors. The transient peak
+//! is located by sampling a time interval and refining the best sample with a golden section
+//! search.
+//!
+//! Independently of any time stepping, the resolvent gives a Kreiss-type lower bound: from
+//! $(zI - A)^{-1} = \int_0^\infty e^{-zt} e^{tA} \, dt$ for real $z > 0$ follows
+//!
+//! \begin{equation}
+//!     \sup_{t \ge 0} \lVert e^{tA} \rVert_1 \ge z \lVert (zI - A)^{-1} \rVert_1,
+//! \end{equation}
+//!
+//! which also holds trivially if the semigroup is unbounded. The resolvent norm is estimated
+//! with the block 1-norm estimator from an LU factorization of $zI - A$.
+//!
+//! All estimates are based on the block 1-norm estimator and are therefore lower bounds, which
+//! are usually accurate within a factor of 3, and exact for nonnegative matrices.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+};
+use rand::RngCore;
+
+use crate::{
+    float::{
+        getrf,
+        getrs,
+    },
+    normest::{
+        LinearOperator,
+        Normest1,
+    },
+    ExpmNormEst,
+    Float,
+};
+
+/// The number of golden section steps refining the peak, which shrink the bracket by a factor of
+/// about $10^{-8}$.
+const GOLDEN_SECTION_STEPS: usize = 40;
+
+/// The location and height of the transient peak of $\lVert e^{tA} \rVert_1$.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransientPeak<T> {
+    /// The time $t$ of the peak.
+    pub time: T,
+    /// The estimate of $\lVert e^{tA} \rVert_1$ at the peak.
+    pub growth: T,
+}
+
+/// The resolvent $(zI - A)^{-1}$ as a linear operator, given by the LU factorizations of $zI - A$
+/// and its transpose.
+struct ResolventOperator<T> {
+    lu: Array2<T>,
+    pivot: Array1<i32>,
+    lu_t: Array2<T>,
+    pivot_t: Array1<i32>,
+}
+
+impl<T: Float> LinearOperator<T> for ResolventOperator<T> {
+    fn multiply_matrix(&self, b: &mut Array2<T>, c: &mut Array2<T>, transpose: bool) {
+        c.assign(b);
+        // The factorizations are checked for singularity when they are calculated, so that
+        // only invalid arguments could make the solve fail.
+        let info = if transpose {
+            getrs(&self.lu_t, &self.pivot_t, c)
+        } else {
+            getrs(&self.lu, &self.pivot, c)
+        };
+        assert_eq!(info, 0);
+    }
+}
+
+/// Storage for analysing the transient growth of $e^{tA}$ for n×n matrices.
+pub struct ExpmTransient<T = f64> {
+    n: usize,
+    itmax: usize,
+    norm_est: ExpmNormEst<T>,
+    scaled: Array2<T>,
+    resolvent: ResolventOperator<T>,
+    normest1: Normest1<T>,
+}
+
+impl<T: Float> ExpmTransient<T> {
+    /// Allocates all space to analyse the transient growth for n×n matrices.
+    pub fn new(n: usize) -> Self {
+        let resolvent = ResolventOperator {
+            lu: Array2::zeros((n, n)),
+            pivot: Array1::zeros(n),
+            lu_t: Array2::zeros((n, n)),
+            pivot_t: Array1::zeros(n),
+        };
+
+        ExpmTransient {
+            n,
+            itmax: 5,
+            norm_est: ExpmNormEst::new(n),
+            scaled: Array2::zeros((n, n)),
+            resolvent,
+            normest1: Normest1::new(n, 2.min(n)),
+        }
+    }
+
+    /// Seed both the estimator of the growth and that of the resolvent norms with `seed`.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.norm_est.set_seed(seed);
+        self.normest1.set_seed(seed);
+    }
+
+    /// Reseed both estimators from the generator `rng`, drawing the growth estimator's seed
+    /// first.
+    pub fn set_rng<R: RngCore>(&mut self, rng: &mut R) {
+        self.norm_est.set_rng(rng);
+        self.normest1.set_rng(rng);
+    }
+
+    /// Estimate $\lVert e^{tA} \rVert_1$ for the n×n matrix `a`.
+    ///
+    /// NOTE: Panics if `a` is not square, or doesn't have the same dimension as the
+    /// `ExpmTransient` object `growth` is called on.
+    pub fn growth<S>(&mut self, a: &ArrayBase<S, Ix2>, t: T) -> T
+        where S: Data<Elem=T>,
+    {
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `ExpmTransient` struct.");
+
+        self.scaled.zip_mut_with(a, |x, &y| *x = t * y);
+        self.norm_est.norm1_est(&self.scaled)
+    }
+
+    /// Locate the peak of $\lVert e^{tA} \rVert_1$ for the n×n matrix `a` in the interval $[0,
+    /// t_{\max}]$. The interval is sampled at `samples` equidistant times, and the neighbourhood
+    /// of the largest sample is refined by a golden section search, which assumes that the
+    /// growth is unimodal there.
+    ///
+    /// NOTE: Panics if `t_max` is not positive, if `samples` is zero, or if `a` is not square or
+    /// doesn't have the same dimension as the `ExpmTransient` object `peak` is called on.
+    pub fn peak<S>(&mut self, a: &ArrayBase<S, Ix2>, t_max: T, samples: usize) -> TransientPeak<T>
+        where S: Data<Elem=T>,
+    {
+        assert!(t_max > T::zero(), "Time `t_max` has to be positive.");
+        assert!(samples > 0, "At least one sample is required.");
+
+        let dt = t_max / T::from_usize(samples).unwrap();
+        let mut best = TransientPeak { time: T::zero(), growth: self.growth(a, T::zero()) };
+        for k in 1..=samples {
+            let t = T::from_usize(k).unwrap() * dt;
+            let growth = self.growth(a, t);
+            if growth > best.growth {
+                best = TransientPeak { time: t, growth };
+            }
+        }
+
+        // Golden section search on the samples adjacent to the best one.
+        let ratio = (T::from_f64(5.0).unwrap().sqrt() - T::one()) / T::from_f64(2.0).unwrap();
+        let mut lower = (best.time - dt).max(T::zero());
+        let mut upper = (best.time + dt).min(t_max);
+        let mut t_1 = upper - ratio * (upper - lower);
+        let mut t_2 = lower + ratio * (upper - lower);
+        let mut growth_1 = self.growth(a, t_1);
+        let mut growth_2 = self.growth(a, t_2);
+        for _ in 0..GOLDEN_SECTION_STEPS {
+            if growth_1 > growth_2 {
+                upper = t_2;
+                t_2 = t_1;
+                growth_2 = growth_1;
+                t_1 = upper - ratio * (upper - lower);
+                growth_1 = self.growth(a, t_1);
+            } else {
+                lower = t_1;
+                t_1 = t_2;
+                growth_1 = growth_2;
+                t_2 = lower + ratio * (upper - lower);
+                growth_2 = self.growth(a, t_2);
+            }
+        }
+
+        for &(time, growth) in &[(t_1, growth_1), (t_2, growth_2)] {
+            if growth > best.growth {
+                best = TransientPeak { time, growth };
+            }
+        }
+        best
+    }
+
+    /// The lower bound $\max_z z \lVert (zI - A)^{-1} \rVert_1$ on $\sup_{t \ge 0} \lVert e^{tA}
+    /// \rVert_1$ for the n×n matrix `a`, maximized over the positive real values `z`. Values
+    /// that are not positive are skipped; if $zI - A$ is singular, the bound is infinite.
+    ///
+    /// NOTE: Panics if `a` is not square, or doesn't have the same dimension as the
+    /// `ExpmTransient` object `kreiss_lower_bound` is called on.
+    pub fn kreiss_lower_bound<S>(&mut self, a: &ArrayBase<S, Ix2>, z: &[T]) -> T
+        where S: Data<Elem=T>,
+    {
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `ExpmTransient` struct.");
+
+        let mut bound = T::zero();
+        if self.n == 0 {
+            return bound;
+        }
+
+        for &z in z.iter().filter(|&&z| z > T::zero()) {
+            let ResolventOperator { lu, pivot, lu_t, pivot_t } = &mut self.resolvent;
+            lu.zip_mut_with(a, |x, &y| *x = -y);
+            lu.diag_mut().mapv_inplace(|x| x + z);
+            lu_t.assign(&lu.t());
+
+            if getrf(lu, pivot) > 0 || getrf(lu_t, pivot_t) > 0 {
+                return T::infinity();
+            }
+
+            let norm = self.normest1.normest1_operator(&self.resolvent, self.itmax);
+            bound = bound.max(z * norm);
+        }
+        bound
+    }
+}
+
+/// Locate the peak of $\lVert e^{tA} \rVert_1$ for the n×n matrix `a` in the interval $[0,
+/// t_{\max}]$, sampled at `samples` equidistant times and refined by a golden section search.
+///
+/// NOTE: Panics if `t_max` is not positive, if `samples` is zero, or if `a` is not square.
+pub fn expm_transient_peak<T, S>(a: &ArrayBase<S, Ix2>, t_max: T, samples: usize) -> TransientPeak<T>
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut transient = ExpmTransient::new(n);
+    transient.peak(a, t_max, samples)
+}
+
+/// The lower bound $\max_z z \lVert (zI - A)^{-1} \rVert_1$ on $\sup_{t \ge 0} \lVert e^{tA}
+/// \rVert_1$ for the n×n matrix `a`, maximized over the positive real values `z`.
+///
+/// NOTE: Panics if `a` is not square.
+pub fn kreiss_lower_bound<T, S>(a: &ArrayBase<S, Ix2>, z: &[T]) -> T
+    where T: Float,
+          S: Data<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut transient = ExpmTransient::new(n);
+    transient.kreiss_lower_bound(a, z)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    fn matrix() -> Array2<f64> {
+        // The exponential is nonnegative, and the 1-norm is attained by the second column,
+        // 10 e^{-t} - 9 e^{-2t}, which peaks at t = ln(9/5) with the value 25/9.
+        arr2(&[[-1.0, 10.0], [0.0, -2.0]])
+    }
+
+    #[test]
+    fn peak_is_located() {
+        let peak = crate::expm_transient_peak(&matrix(), 5.0, 20);
+        assert_relative_eq!(peak.time, 1.8f64.ln(), max_relative=1e-4);
+        assert_relative_eq!(peak.growth, 25.0 / 9.0, max_relative=1e-10);
+    }
+
+    #[test]
+    fn kreiss_bound_is_below_peak() {
+        // z ‖(zI - A)⁻¹‖₁ = z (z + 11) / ((z + 1)(z + 2)) is largest at z = 2.
+        let bound = crate::kreiss_lower_bound(&matrix(), &[-1.0, 0.5, 1.0, 2.0, 4.0]);
+        assert_relative_eq!(bound, 26.0 / 12.0, max_relative=1e-12);
+        assert!(bound <= 25.0 / 9.0);
+    }
+}
@@ -0,0 +1,204 @@
+//! The exponential of upper quasi-triangular matrices, such as the factor $T$ of a real Schur
+//! decomposition $A = Q T Q^\top$.
+//!
+//! Squaring the Padé approximant $r(2^{-s} T)$ repeatedly amplifies its rounding errors, which
+//! is particularly harmful to the diagonal of $e^T$ when the entries of $T$ vary widely in
+//! magnitude. Following [Al-Mohy and Higham 2009, Code Fragment 2.1], the diagonal blocks of
+//! $e^{2^{-i} T}$ are instead recomputed exactly before every squaring and once more at the end.
+//! For 1×1 blocks these are the scalar exponentials, and for 2×2 blocks with eigenvalues $\mu \pm
+//! \delta$
+//!
+//! \begin{equation}
+//!     e^B = e^\mu \left(\cosh(\delta) I + \frac{\sinh(\delta)}{\delta} (B - \mu I)\right).
+//! \end{equation}
+//!
+//! Between two adjacent 1×1 blocks, the superdiagonal entry follows from Parlett's formula for
+//! 2×2 upper triangular matrices, evaluated in the form [Higham 2008, (10.42)]
+//!
+//! \begin{equation}
+//!     \left(e^T\right)_{i,i+1} = t_{i,i+1} e^{(t_{ii} + t_{i+1,i+1})/2}
+//!     \frac{\sinh\left((t_{i+1,i+1} - t_{ii})/2\right)}{(t_{i+1,i+1} - t_{ii})/2},
+//! \end{equation}
+//!
+//! which avoids the cancellation of the divided difference for close diagonal entries. With $z =
+//! \lvert t_{i+1,i+1} - t_{ii} \rvert / 2$, the product is evaluated as $e^{\max(t_{ii},
+//! t_{i+1,i+1})} (1 - e^{-2z}) / (2z)$, so that neither factor overflows or underflows on its
+//! own for widely separated diagonal entries. The remaining off-diagonal entries follow from the
+//! squarings.
+//!
+//! [Al-Mohy and Higham 2009]: https://doi.org/10.1137/09074721X
+//! [Higham 2008]: https://doi.org/10.1137/1.9780898717778
+
+use ndarray::{
+    self,
+    prelude::*,
+    s,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    closed_form::exp_2x2,
+    schur::diagonal_blocks,
+    Expm,
+    Float,
+};
+
+impl<T: Float> Expm<T> {
+    /// Calculate the matrix exponential of the upper quasi-triangular n×n matrix `a`, storing
+    /// the result in matrix `b`. The diagonal blocks of `a` are 1×1 or 2×2, the latter being
+    /// identified by nonzero subdiagonal entries.
+    ///
+    /// NOTE: Panics if `a` is not upper quasi-triangular, if input matrices `a` and `b` don't
+    /// have matching dimensions, are not square, not in row-major order, or don't have the same
+    /// dimension as the `Expm` object `expm_triangular` is called on.
+    pub fn expm_triangular<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        let mut blocks = std::mem::take(&mut self.blocks);
+        diagonal_blocks(a, &mut blocks);
+        for &(k, p) in &blocks {
+            for row in k..k+p {
+                assert!(a.row(row).slice(s![..k]).iter().all(|&x| x == T::zero()), "Matrix `a` is not upper quasi-triangular.");
+            }
+        }
+        self.blocks = blocks;
+
+        self.is_triangular = true;
+        self.expm(a, b);
+        self.is_triangular = false;
+
+        recompute_diagonal(a, T::one(), &self.blocks, b);
+    }
+}
+
+/// Calculate the matrix exponential of the upper quasi-triangular n×n matrix `a`, storing the
+/// result in matrix `b`.
+///
+/// NOTE: Panics if `a` is not upper quasi-triangular, or if input matrices `a` and `b` don't have
+/// matching dimensions, are not square, or not in row-major order.
+pub fn expm_triangular<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut expm = Expm::new(n);
+    expm.expm_triangular(a, b);
+}
+
+/// Overwrites the diagonal blocks of `v` with the exact exponentials of the diagonal blocks of
+/// `factor * t`, the superdiagonal entries between adjacent 1×1 blocks with their exact values,
+/// and the entries below the diagonal blocks with zeros.
+pub(crate) fn recompute_diagonal<T, S1, S2>(t: &ArrayBase<S1, Ix2>, factor: T, blocks: &[(usize, usize)], v: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let half = T::from_f64(0.5).unwrap();
+
+    for (jb, &(j, q)) in blocks.iter().enumerate() {
+        for row in j..j+q {
+            v.row_mut(row).slice_mut(s![..j]).fill(T::zero());
+        }
+
+        if q == 1 {
+            v[(j, j)] = (factor * t[(j, j)]).exp();
+        } else {
+            let exp_b = exp_2x2([
+                [factor * t[(j, j)], factor * t[(j, j + 1)]],
+                [factor * t[(j + 1, j)], factor * t[(j + 1, j + 1)]],
+            ]);
+            for (r, row) in exp_b.iter().enumerate() {
+                for (c, &x) in row.iter().enumerate() {
+                    v[(j + r, j + c)] = x;
+                }
+            }
+        }
+
+        if jb + 1 < blocks.len() && q == 1 && blocks[jb + 1].1 == 1 {
+            let x = factor * t[(j, j)];
+            let y = factor * t[(j + 1, j + 1)];
+            // e^{(x+y)/2} sinh(z)/z = e^{max(x,y)} (1 - e^{-2|z|})/(2|z|) for z = (y-x)/2.
+            let z = (half * (y - x)).abs();
+            let divided_difference = if z == T::zero() {
+                x.exp()
+            } else {
+                x.max(y).exp() * -(-(z + z)).exp_m1() / (z + z)
+            };
+            v[(j, j + 1)] = factor * t[(j, j + 1)] * divided_difference;
+        }
+    }
+}
+
+/// Zeros the entries of `v` that `recompute_diagonal` overwrites, i.e. the diagonal blocks, the
+/// superdiagonal entries between adjacent 1×1 blocks, and the entries below the diagonal blocks.
+pub(crate) fn clear_recomputed<T, S>(blocks: &[(usize, usize)], v: &mut ArrayBase<S, Ix2>)
+    where T: Float,
+          S: DataMut<Elem=T>,
+{
+    for (jb, &(j, q)) in blocks.iter().enumerate() {
+        for row in j..j+q {
+            v.row_mut(row).slice_mut(s![..j+q]).fill(T::zero());
+        }
+        if jb + 1 < blocks.len() && q == 1 && blocks[jb + 1].1 == 1 {
+            v[(j, j + 1)] = T::zero();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn triangular_agrees_with_expm() {
+        // A 2×2 block with complex eigenvalues, followed by two 1×1 blocks.
+        let t = arr2(&[
+            [-0.5, 2.0, 0.3, 1.0],
+            [-1.5, -0.5, 0.7, 0.4],
+            [0.0, 0.0, 1.0, 0.5],
+            [0.0, 0.0, 0.0, -1.5],
+        ]);
+        let mut b = Array2::<f64>::zeros((4, 4));
+        let mut expected = Array2::<f64>::zeros((4, 4));
+
+        crate::expm_triangular(&t, &mut b);
+        crate::expm(&t, &mut expected);
+
+        for (&x, &y) in b.iter().zip(expected.iter()) {
+            assert_relative_eq!(x, y, max_relative=1e-12, epsilon=1e-14);
+        }
+    }
+
+    #[test]
+    fn separated_diagonal_entries_do_not_overflow() {
+        let t = arr2(&[[0.0, 1.0], [0.0, -1500.0]]);
+        let mut b = Array2::<f64>::zeros((2, 2));
+
+        crate::expm_triangular(&t, &mut b);
+
+        assert_eq!(b[(0, 0)], 1.0);
+        assert_eq!(b[(1, 1)], 0.0);
+        assert_relative_eq!(b[(0, 1)], 1.0 / 1500.0, max_relative=1e-14);
+    }
+
+    #[test]
+    fn triangular_diagonal_is_exact() {
+        // Widely varying entries require many squarings, whose rounding errors swamp the small
+        // diagonal entries of the dense result.
+        let t = arr2(&[[-60.0, 1e4], [0.0, -60.5]]);
+        let mut b = Array2::<f64>::zeros((2, 2));
+
+        crate::expm_triangular(&t, &mut b);
+
+        assert_relative_eq!(b[(0, 0)], (-60f64).exp(), max_relative=1e-15);
+        assert_relative_eq!(b[(1, 1)], (-60.5f64).exp(), max_relative=1e-15);
+        assert_eq!(b[(1, 0)], 0.0);
+        let expected = 1e4 * (-60.25f64).exp() * (0.25f64.sinh() / 0.25);
+        assert_relative_eq!(b[(0, 1)], expected, max_relative=1e-14);
+    }
+}
@@ -0,0 +1,326 @@
+//! The trigonometric and hyperbolic matrix functions $\cos A$, $\sin A$, $\cosh A$, and $\sinh A$,
+//! e.g. for second-order systems $\ddot{y} = -A y$, whose solution involves $\cos(t\sqrt{A})$
+//! and $\sin(t\sqrt{A})$.
+//!
+//! All four functions are calculated in real arithmetic by scaling and recovery with the
+//! double-angle formulas, analogously to the scaling and squaring of `expm`, see [Higham 2008,
+//! Chapter 12]. With $X = 2^{-s} A$ scaled to $\lVert X \rVert_1 \le 1$, the truncated Taylor
+//! series
+//!
+//! \begin{equation}
+//!     C_0 = \sum_{k=0}^K \frac{(\sigma X^2)^k}{(2k)!}, \qquad
+//!     S_0 = X \sum_{k=0}^K \frac{(\sigma X^2)^k}{(2k+1)!},
+//! \end{equation}
+//!
+//! with $\sigma = -1$ for the trigonometric and $\sigma = 1$ for the hyperbolic functions, are
+//! evaluated by Horner's scheme in $X^2$. The degree $K$ is the smallest one for which the
+//! truncation error is below the unit roundoff. The scaling is then undone by the double-angle
+//! formulas, which are the same for both families,
+//!
+//! \begin{equation}
+//!     C_{i+1} = 2 C_i^2 - I, \qquad S_{i+1} = 2 S_i C_i,
+//! \end{equation}
+//!
+//! so that $C_s$ and $S_s$ approximate $\cos A$ and $\sin A$, or $\cosh A$ and $\sinh A$. The
+//! sine and hyperbolic sine require the cosines of all steps and cost one additional product per
+//! step.
+//!
+//! [Higham 2008]: https://doi.org/10.1137/1.9780898717778
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+};
+
+use crate::{
+    float::{
+        gemm,
+        onenorm,
+    },
+    special::factorial,
+    Float,
+};
+
+/// The bound on $\lVert 2^{-s} A \rVert_1$ up to which the Taylor series are evaluated.
+const THETA: f64 = 1.0;
+
+/// Storage for calculating the trigonometric and hyperbolic functions of n×n matrices.
+pub struct Trigm<T = f64> {
+    n: usize,
+    x: Array2<T>,
+    x2: Array2<T>,
+    c: Array2<T>,
+    s: Array2<T>,
+    work: Array2<T>,
+}
+
+impl<T: Float> Trigm<T> {
+    /// Allocates all space to calculate the trigonometric and hyperbolic functions of n×n
+    /// matrices.
+    pub fn new(n: usize) -> Self {
+        Trigm {
+            n,
+            x: Array2::zeros((n, n)),
+            x2: Array2::zeros((n, n)),
+            c: Array2::zeros((n, n)),
+            s: Array2::zeros((n, n)),
+            work: Array2::zeros((n, n)),
+        }
+    }
+
+    /// Calculate $\cos A$ for the n×n matrix `a`, storing the result in `b`.
+    ///
+    /// NOTE: Panics if `a` and `b` don't have matching dimensions, or don't have the same
+    /// dimension as the `Trigm` object `cosm` is called on.
+    pub fn cosm<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        self.evaluate(a, false, false);
+        b.assign(&self.c);
+    }
+
+    /// Calculate $\sin A$ for the n×n matrix `a`, storing the result in `b`.
+    ///
+    /// NOTE: Panics if `a` and `b` don't have matching dimensions, or don't have the same
+    /// dimension as the `Trigm` object `sinm` is called on.
+    pub fn sinm<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        self.evaluate(a, false, true);
+        b.assign(&self.s);
+    }
+
+    /// Calculate $\cos A$ and $\sin A$ together for the n×n matrix `a`, storing the results in
+    /// `c` and `s`, respectively.
+    ///
+    /// NOTE: Panics if `a`, `c`, and `s` don't have matching dimensions, or don't have the same
+    /// dimension as the `Trigm` object `cos_sinm` is called on.
+    pub fn cos_sinm<S1, S2, S3>(&mut self, a: &ArrayBase<S1, Ix2>, c: &mut ArrayBase<S2, Ix2>, s: &mut ArrayBase<S3, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+              S3: DataMut<Elem=T>,
+    {
+        self.evaluate(a, false, true);
+        c.assign(&self.c);
+        s.assign(&self.s);
+    }
+
+    /// Calculate $\cosh A$ for the n×n matrix `a`, storing the result in `b`.
+    ///
+    /// NOTE: Panics if `a` and `b` don't have matching dimensions, or don't have the same
+    /// dimension as the `Trigm` object `coshm` is called on.
+    pub fn coshm<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        self.evaluate(a, true, false);
+        b.assign(&self.c);
+    }
+
+    /// Calculate $\sinh A$ for the n×n matrix `a`, storing the result in `b`.
+    ///
+    /// NOTE: Panics if `a` and `b` don't have matching dimensions, or don't have the same
+    /// dimension as the `Trigm` object `sinhm` is called on.
+    pub fn sinhm<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        self.evaluate(a, true, true);
+        b.assign(&self.s);
+    }
+
+    /// Calculate $\cosh A$ and $\sinh A$ together for the n×n matrix `a`, storing the results
+    /// in `c` and `s`, respectively.
+    ///
+    /// NOTE: Panics if `a`, `c`, and `s` don't have matching dimensions, or don't have the same
+    /// dimension as the `Trigm` object `cosh_sinhm` is called on.
+    pub fn cosh_sinhm<S1, S2, S3>(&mut self, a: &ArrayBase<S1, Ix2>, c: &mut ArrayBase<S2, Ix2>, s: &mut ArrayBase<S3, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+              S3: DataMut<Elem=T>,
+    {
+        self.evaluate(a, true, true);
+        c.assign(&self.c);
+        s.assign(&self.s);
+    }
+
+    /// Calculates the cosine in `c` and, if `with_sine` is set, the sine in `s`, of the
+    /// trigonometric or, if `hyperbolic` is set, the hyperbolic family.
+    fn evaluate<S>(&mut self, a: &ArrayBase<S, Ix2>, hyperbolic: bool, with_sine: bool)
+        where S: Data<Elem=T>,
+    {
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `Trigm` struct.");
+
+        let two = T::from_f64(2.0).unwrap();
+        let theta = T::from_f64(THETA).unwrap();
+        let sigma = if hyperbolic { T::one() } else { -T::one() };
+
+        let norm = onenorm(a);
+        let s = (norm / theta).log2().ceil().to_i32().unwrap_or(0).max(0);
+        self.x.zip_mut_with(a, |x, &y| *x = y / two.powi(s));
+
+        // σX²
+        gemm(sigma, &self.x, &self.x, T::zero(), &mut self.x2);
+
+        let degree = taylor_degree::<T>();
+
+        // Horner's scheme for Σ (σX²)^k / (2k)!, with the divisors (2k)(2k-1) of each step.
+        self.c.fill(T::zero());
+        self.c.diag_mut().fill(T::one());
+        for k in (1..=degree).rev() {
+            let divisor = T::from_usize((2 * k) * (2 * k - 1)).unwrap();
+            gemm(T::one() / divisor, &self.x2, &self.c, T::zero(), &mut self.work);
+            self.work.diag_mut().mapv_inplace(|x| x + T::one());
+            std::mem::swap(&mut self.c, &mut self.work);
+        }
+
+        if with_sine {
+            // X Σ (σX²)^k / (2k+1)!, with the divisors (2k+1)(2k) of each step.
+            self.s.fill(T::zero());
+            self.s.diag_mut().fill(T::one());
+            for k in (1..=degree).rev() {
+                let divisor = T::from_usize((2 * k + 1) * (2 * k)).unwrap();
+                gemm(T::one() / divisor, &self.x2, &self.s, T::zero(), &mut self.work);
+                self.work.diag_mut().mapv_inplace(|x| x + T::one());
+                std::mem::swap(&mut self.s, &mut self.work);
+            }
+            gemm(T::one(), &self.x, &self.s, T::zero(), &mut self.work);
+            std::mem::swap(&mut self.s, &mut self.work);
+        }
+
+        // Double-angle recovery: S ← 2SC, C ← 2C² - I.
+        for _ in 0..s {
+            if with_sine {
+                gemm(two, &self.s, &self.c, T::zero(), &mut self.work);
+                std::mem::swap(&mut self.s, &mut self.work);
+            }
+            gemm(two, &self.c, &self.c, T::zero(), &mut self.work);
+            self.work.diag_mut().mapv_inplace(|x| x - T::one());
+            std::mem::swap(&mut self.c, &mut self.work);
+        }
+    }
+}
+
+/// The smallest degree $K$ for which the truncation errors of the Taylor series at $\lVert X
+/// \rVert_1 \le \theta$, bounded by twice their first omitted term $\theta^{2K+2}/(2K+2)!$, are
+/// below the unit roundoff of `T`.
+fn taylor_degree<T: Float>() -> usize {
+    let u = T::unit_roundoff().to_f64().unwrap();
+    (1..).find(|&k| 2.0 * THETA.powi(2 * k as i32 + 2) / factorial(2 * k as u64 + 2) <= u).unwrap()
+}
+
+/// Calculate $\cos A$ for the n×n matrix `a`, storing the result in `b`.
+///
+/// NOTE: Panics if `a` and `b` don't have matching dimensions, or are not square.
+pub fn cosm<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut trigm = Trigm::new(n);
+    trigm.cosm(a, b);
+}
+
+/// Calculate $\sin A$ for the n×n matrix `a`, storing the result in `b`.
+///
+/// NOTE: Panics if `a` and `b` don't have matching dimensions, or are not square.
+pub fn sinm<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut trigm = Trigm::new(n);
+    trigm.sinm(a, b);
+}
+
+/// Calculate $\cosh A$ for the n×n matrix `a`, storing the result in `b`.
+///
+/// NOTE: Panics if `a` and `b` don't have matching dimensions, or are not square.
+pub fn coshm<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut trigm = Trigm::new(n);
+    trigm.coshm(a, b);
+}
+
+/// Calculate $\sinh A$ for the n×n matrix `a`, storing the result in `b`.
+///
+/// NOTE: Panics if `a` and `b` don't have matching dimensions, or are not square.
+pub fn sinhm<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut trigm = Trigm::new(n);
+    trigm.sinhm(a, b);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+    use approx::assert_relative_eq;
+
+    /// A matrix function together with its scalar counterpart.
+    type Case = (fn(&Array2<f64>, &mut Array2<f64>), fn(f64) -> f64);
+
+    #[test]
+    fn trigonometric_functions_of_triangular_matrix() {
+        // For upper triangular 2×2 matrices, f(A)₁₂ = A₁₂ (f(a) - f(d)) / (a - d).
+        let (a, d, b) = (3.0f64, -2.5f64, 1.5);
+        let matrix = arr2(&[[a, b], [0.0, d]]);
+        let mut result = Array2::<f64>::zeros((2, 2));
+
+        let functions: [Case; 4] = [
+            (crate::cosm, f64::cos),
+            (crate::sinm, f64::sin),
+            (crate::coshm, f64::cosh),
+            (crate::sinhm, f64::sinh),
+        ];
+        for &(matrix_function, f) in &functions {
+            matrix_function(&matrix, &mut result);
+            let expected = arr2(&[[f(a), b * (f(a) - f(d)) / (a - d)], [0.0, f(d)]]);
+            for (&x, &y) in result.iter().zip(expected.iter()) {
+                assert_relative_eq!(x, y, max_relative=1e-13, epsilon=1e-14);
+            }
+        }
+    }
+
+    #[test]
+    fn hyperbolic_functions_agree_with_expm() {
+        let a = arr2(&[[-1.0, 2.0, 0.5], [-2.0, -3.0, 1.0], [4.0, 0.25, 2.5]]);
+        let mut exp_plus = Array2::<f64>::zeros((3, 3));
+        let mut exp_minus = Array2::<f64>::zeros((3, 3));
+        crate::expm(&a, &mut exp_plus);
+        crate::expm(&(-&a), &mut exp_minus);
+
+        let mut c = Array2::<f64>::zeros((3, 3));
+        let mut s = Array2::<f64>::zeros((3, 3));
+        let mut trigm = crate::Trigm::new(3);
+        trigm.cosh_sinhm(&a, &mut c, &mut s);
+        for ((&c, &s), (&p, &m)) in c.iter().zip(s.iter()).zip(exp_plus.iter().zip(exp_minus.iter())) {
+            assert_relative_eq!(c, (p + m) / 2.0, max_relative=1e-12, epsilon=1e-12);
+            assert_relative_eq!(s, (p - m) / 2.0, max_relative=1e-12, epsilon=1e-12);
+        }
+
+        // cos² A + sin² A = I
+        trigm.cos_sinm(&a, &mut c, &mut s);
+        let identity = c.dot(&c) + s.dot(&s);
+        for (&x, &y) in identity.iter().zip(Array2::<f64>::eye(3).iter()) {
+            assert_relative_eq!(x, y, epsilon=1e-11);
+        }
+    }
+}
@@ -0,0 +1,379 @@
+//! Structure-preserving exponentials of skew-symmetric and skew-Hermitian generators.
+//!
+//! Long squaring chains let the result of `expm` drift away from the orthogonal (unitary) group.
+//! This module offers two ways to keep the result on the group to machine precision:
+//!
+//! + For a real symmetric Hamiltonian $H$, the propagator $e^{-iHt} = V \cos(\Lambda t) V^\top -
+//!   i V \sin(\Lambda t) V^\top$ is calculated from the eigendecomposition $H = V \Lambda V^\top$,
+//!   and its real and imaginary parts are returned separately. A complex Hermitian Hamiltonian
+//!   $H = S + iK$, with $S$ symmetric and $K$ skew-symmetric, is represented by the real
+//!   symmetric 2n×2n matrix
+//!
+//!   \begin{equation}
+//!   M = \begin{pmatrix} S & -K \\ K & S \end{pmatrix},
+//!   \end{equation}
+//!
+//!   in which multiplication by $i$ becomes multiplication by $J = \begin{pmatrix} 0 & -I \\ I &
+//!   0 \end{pmatrix}$. Since $J$ commutes with $M$, the representation of $e^{-iHt}$ is
+//!   $\cos(Mt) - J \sin(Mt)$, calculated from the eigendecomposition of $M$ at four times the
+//!   cost of the real case.
+//! + For a real skew-symmetric $A$, $e^A$ is calculated with `expm` and subsequently
+//!   re-orthogonalized with the Newton–Schulz iteration $Q \leftarrow \frac{1}{2} Q (3I -
+//!   Q^\top Q)$, which converges quadratically to the orthogonal polar factor of $Q$.
+
+use ndarray::{
+    self,
+    prelude::*,
+    s,
+    Data,
+    DataMut,
+    Zip,
+};
+use num_complex::Complex;
+
+use crate::{
+    float::{
+        gemm,
+        onenorm,
+        syev,
+    },
+    Counters,
+    Expm,
+    Float,
+};
+
+/// The maximum number of Newton–Schulz iterations. Starting from the result of `expm`, which is
+/// orthogonal up to a small multiple of the unit roundoff, two iterations typically suffice.
+const MAX_NEWTON_SCHULZ_ITERATIONS: usize = 8;
+
+/// Storage for calculating structure-preserving exponentials of n×n generators.
+pub struct ExpmUnitary<T = f64> {
+    n: usize,
+    expm: Expm<T>,
+    eigenvectors: Array2<T>,
+    eigenvalues: Array1<T>,
+    work: Array2<T>,
+    gram: Array2<T>,
+    embedding: Array2<T>,
+    embedding_eigenvalues: Array1<T>,
+    embedding_work: Array2<T>,
+    embedding_cos: Array2<T>,
+    embedding_sin: Array2<T>,
+    counters: Counters,
+}
+
+impl<T: Float> ExpmUnitary<T> {
+    /// Allocates all space to calculate exponentials of n×n generators.
+    pub fn new(n: usize) -> Self {
+        ExpmUnitary {
+            n,
+            expm: Expm::new(n),
+            eigenvectors: Array2::zeros((n, n)),
+            eigenvalues: Array1::zeros(n),
+            work: Array2::zeros((n, n)),
+            gram: Array2::zeros((n, n)),
+            embedding: Array2::zeros((2 * n, 2 * n)),
+            embedding_eigenvalues: Array1::zeros(2 * n),
+            embedding_work: Array2::zeros((2 * n, 2 * n)),
+            embedding_cos: Array2::zeros((2 * n, 2 * n)),
+            embedding_sin: Array2::zeros((2 * n, 2 * n)),
+            counters: Counters::default(),
+        }
+    }
+
+    /// Calculate the unitary propagator $e^{-iHt}$ of the real symmetric n×n Hamiltonian `h`,
+    /// storing its real part in `re` and its imaginary part in `im`. Only the upper triangle of
+    /// `h` is referenced.
+    ///
+    /// NOTE: Panics if `h`, `re`, and `im` don't have matching dimensions, are not square, don't
+    /// have the same dimension as the `ExpmUnitary` object `propagator` is called on, or if the
+    /// eigendecomposition of `h` does not converge.
+    pub fn propagator<S1, S2, S3>(&mut self, h: &ArrayBase<S1, Ix2>, t: T, re: &mut ArrayBase<S2, Ix2>, im: &mut ArrayBase<S3, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+              S3: DataMut<Elem=T>,
+    {
+        assert_eq!(h.dim(), (self.n, self.n), "Dimension mismatch between matrix `h` and preconfigured `ExpmUnitary` struct.");
+        assert_eq!(h.dim(), re.dim(), "Input matrices `h` and `re` have to have matching dimensions.");
+        assert_eq!(h.dim(), im.dim(), "Input matrices `h` and `im` have to have matching dimensions.");
+
+        self.eigenvectors.assign(h);
+        let info = syev(&mut self.eigenvectors, &mut self.eigenvalues);
+        assert_eq!(info, 0, "The eigendecomposition of `h` did not converge.");
+
+        // Re = V cos(Λt) Vᵀ, Im = -V sin(Λt) Vᵀ
+        apply_spectral(&self.eigenvectors, &self.eigenvalues, |x| (x * t).cos(), T::one(), &mut self.work, re);
+        apply_spectral(&self.eigenvectors, &self.eigenvalues, |x| (x * t).sin(), -T::one(), &mut self.work, im);
+        self.counters = Counters { matrix_products: 2, ..Counters::default() };
+    }
+
+    /// Calculate the unitary propagator $e^{-iHt}$ of the complex Hermitian n×n Hamiltonian `h`,
+    /// storing it in `u`. Only the upper triangle of `h` is referenced, and the imaginary parts
+    /// of its diagonal are ignored.
+    ///
+    /// NOTE: Panics if `h` and `u` don't have matching dimensions, are not square, don't have the
+    /// same dimension as the `ExpmUnitary` object `propagator_hermitian` is called on, or if the
+    /// eigendecomposition of `h` does not converge.
+    pub fn propagator_hermitian<S1, S2>(&mut self, h: &ArrayBase<S1, Ix2>, t: T, u: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=Complex<T>>,
+              S2: DataMut<Elem=Complex<T>>,
+    {
+        let n = self.n;
+        assert_eq!(h.dim(), (n, n), "Dimension mismatch between matrix `h` and preconfigured `ExpmUnitary` struct.");
+        assert_eq!(h.dim(), u.dim(), "Input matrices `h` and `u` have to have matching dimensions.");
+
+        hermitian_embedding(h, &mut self.embedding);
+        let info = syev(&mut self.embedding, &mut self.embedding_eigenvalues);
+        assert_eq!(info, 0, "The eigendecomposition of `h` did not converge.");
+
+        // For large t the phases of split eigenvalue pairs drift apart, so that the result no
+        // longer commutes with J.
+        equalize_pairs(&mut self.embedding_eigenvalues);
+
+        apply_spectral(&self.embedding, &self.embedding_eigenvalues, |x| (x * t).cos(), T::one(), &mut self.embedding_work, &mut self.embedding_cos);
+        apply_spectral(&self.embedding, &self.embedding_eigenvalues, |x| (x * t).sin(), T::one(), &mut self.embedding_work, &mut self.embedding_sin);
+        self.counters = Counters { matrix_products: 16, ..Counters::default() };
+
+        // cos(Mt) - J sin(Mt) represents Re + i Im with Re = C_re + S_im and Im = C_im - S_re,
+        // where the real parts are the upper left and the imaginary parts the lower left blocks.
+        let c_re = self.embedding_cos.slice(s![..n, ..n]);
+        let c_im = self.embedding_cos.slice(s![n.., ..n]);
+        let s_re = self.embedding_sin.slice(s![..n, ..n]);
+        let s_im = self.embedding_sin.slice(s![n.., ..n]);
+        Zip::from(u)
+            .and(&c_re)
+            .and(&c_im)
+            .and(&s_re)
+            .and(&s_im)
+            .apply(|u, &c_re, &c_im, &s_re, &s_im| *u = Complex::new(c_re + s_im, c_im - s_re));
+    }
+
+    /// Calculate the matrix exponential of the real skew-symmetric n×n matrix `a`, storing the
+    /// orthogonal result in `b`.
+    ///
+    /// NOTE: Panics if `a` and `b` don't have matching dimensions, are not square, not in
+    /// row-major order, or don't have the same dimension as the `ExpmUnitary` object
+    /// `expm_skew_symmetric` is called on.
+    pub fn expm_skew_symmetric<S1, S2>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+    {
+        assert_eq!(a.dim(), (self.n, self.n), "Dimension mismatch between matrix `a` and preconfigured `ExpmUnitary` struct.");
+
+        self.expm.expm(a, b);
+        self.counters = self.expm.counters();
+        self.orthogonalize(b);
+    }
+
+    /// The operation counts of the last call to `propagator`, `propagator_hermitian`, or
+    /// `expm_skew_symmetric`.
+    pub fn counters(&self) -> Counters {
+        self.counters
+    }
+
+    /// Re-orthogonalizes `q` with the Newton–Schulz iteration, until the departure from
+    /// orthogonality $\lVert Q^\top Q - I \rVert_1$ no longer decreases.
+    fn orthogonalize<S>(&mut self, q: &mut ArrayBase<S, Ix2>)
+        where S: DataMut<Elem=T>,
+    {
+        let half = T::from_f64(0.5).unwrap();
+        let two = T::from_f64(2.0).unwrap();
+        let mut departure = T::infinity();
+
+        for _ in 0..MAX_NEWTON_SCHULZ_ITERATIONS {
+            // G = QᵀQ - I
+            gemm(T::one(), &q.t(), q, T::zero(), &mut self.gram);
+            self.counters.matrix_products += 1;
+            self.gram.diag_mut().mapv_inplace(|x| x - T::one());
+
+            let new_departure = onenorm(&self.gram);
+            if new_departure >= departure || new_departure == T::zero() {
+                break;
+            }
+            departure = new_departure;
+
+            // Q ← ½ Q (3I - QᵀQ) = ½ Q (2I - G)
+            self.gram.mapv_inplace(|x| -x);
+            self.gram.diag_mut().mapv_inplace(|x| x + two);
+            gemm(half, q, &self.gram, T::zero(), &mut self.work);
+            q.assign(&self.work);
+            self.counters.matrix_products += 1;
+            self.counters.orthogonalizations += 1;
+        }
+    }
+}
+
+/// Fills `m` with the real symmetric 2n×2n matrix $M = \begin{pmatrix} S & -K \\ K & S
+/// \end{pmatrix}$ representing the complex Hermitian n×n matrix $H = S + iK$. Only the upper
+/// triangle of `h` is referenced, and the imaginary parts of its diagonal are ignored.
+pub(crate) fn hermitian_embedding<T, S>(h: &ArrayBase<S, Ix2>, m: &mut Array2<T>)
+    where T: Float,
+          S: Data<Elem=Complex<T>>,
+{
+    let (n, _) = h.dim();
+    for i in 0..n {
+        m[(i, i)] = h[(i, i)].re;
+        m[(n + i, n + i)] = h[(i, i)].re;
+        m[(i, n + i)] = T::zero();
+        m[(n + i, i)] = T::zero();
+        for j in i+1..n {
+            let Complex { re, im } = h[(i, j)];
+            for &(r, c, x) in &[(i, j, re), (n + i, n + j, re), (j, n + i, im), (n + j, i, -im)] {
+                m[(r, c)] = x;
+                m[(c, r)] = x;
+            }
+        }
+    }
+}
+
+/// Equalizes the ascending eigenvalues `lambda` of a Hermitian embedding pairwise. Every
+/// eigenvalue of $H$ is a double eigenvalue of $M$, but rounding splits the pairs.
+pub(crate) fn equalize_pairs<T: Float>(lambda: &mut Array1<T>) {
+    let two = T::from_f64(2.0).unwrap();
+    for pair in lambda.as_slice_mut().unwrap().chunks_mut(2) {
+        let mean = (pair[0] + pair[1]) / two;
+        pair[0] = mean;
+        pair[1] = mean;
+    }
+}
+
+/// Calculates $\alpha V f(\Lambda) V^\top$ for the eigenvectors `v` and eigenvalues `lambda`,
+/// storing the result in `out`.
+pub(crate) fn apply_spectral<T, F, S>(v: &Array2<T>, lambda: &Array1<T>, f: F, alpha: T, work: &mut Array2<T>, out: &mut ArrayBase<S, Ix2>)
+    where T: Float,
+          F: Fn(T) -> T,
+          S: DataMut<Elem=T>,
+{
+    work.assign(v);
+    for (mut column, &lambda) in work.gencolumns_mut().into_iter().zip(lambda.iter()) {
+        let f_lambda = f(lambda);
+        column.mapv_inplace(|x| x * f_lambda);
+    }
+    gemm(alpha, work, &v.t(), T::zero(), out);
+}
+
+/// Calculate the unitary propagator $e^{-iHt}$ of the complex Hermitian n×n Hamiltonian `h`,
+/// storing it in `u`.
+///
+/// NOTE: Panics if `h` and `u` don't have matching dimensions, are not square, or if the
+/// eigendecomposition of `h` does not converge.
+pub fn expm_skew_hermitian<T, S1, S2>(h: &ArrayBase<S1, Ix2>, t: T, u: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=Complex<T>>,
+          S2: DataMut<Elem=Complex<T>>,
+{
+    let (n, _) = h.dim();
+
+    let mut expm = ExpmUnitary::new(n);
+    expm.propagator_hermitian(h, t, u);
+}
+
+/// Calculate the matrix exponential of the real skew-symmetric n×n matrix `a`, storing the
+/// orthogonal result in `b`.
+///
+/// NOTE: Panics if `a` and `b` don't have matching dimensions, are not square, or not in
+/// row-major order.
+pub fn expm_skew_symmetric<T, S1, S2>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut expm = ExpmUnitary::new(n);
+    expm.expm_skew_symmetric(a, b);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{
+        prelude::*,
+        s,
+    };
+    use num_complex::Complex;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn propagator_is_unitary() {
+        let h = arr2(&[[1.0, 0.5, 0.0], [0.5, -2.0, 0.25], [0.0, 0.25, 3.0]]);
+        let mut re = Array2::<f64>::zeros((3, 3));
+        let mut im = Array2::<f64>::zeros((3, 3));
+
+        let mut unitary = crate::ExpmUnitary::new(3);
+        unitary.propagator(&h, 1000.0, &mut re, &mut im);
+
+        // U†U = (Reᵀ Re + Imᵀ Im) + i (Reᵀ Im - Imᵀ Re) = I
+        let real = re.t().dot(&re) + im.t().dot(&im);
+        let imag = re.t().dot(&im) - im.t().dot(&re);
+        for ((i, j), &x) in real.indexed_iter() {
+            assert_relative_eq!(x, if i == j { 1.0 } else { 0.0 }, epsilon=1e-13);
+        }
+        for &x in imag.iter() {
+            assert_relative_eq!(x, 0.0, epsilon=1e-13);
+        }
+
+        // For short times, the propagator agrees with exp of the real block form
+        // [[0, Ht], [-Ht, 0]], whose blocks are Re and Im.
+        let t = 0.3;
+        unitary.propagator(&h, t, &mut re, &mut im);
+        let mut block = Array2::<f64>::zeros((6, 6));
+        block.slice_mut(s![..3, 3..]).assign(&(&h * t));
+        block.slice_mut(s![3.., ..3]).assign(&(&h * -t));
+        let mut exp_block = Array2::<f64>::zeros((6, 6));
+        crate::expm(&block, &mut exp_block);
+        for (&x, &y) in re.iter().zip(exp_block.slice(s![..3, ..3]).iter()) {
+            assert_relative_eq!(x, y, epsilon=1e-14);
+        }
+        for (&x, &y) in im.iter().zip(exp_block.slice(s![3.., ..3]).iter()) {
+            assert_relative_eq!(x, y, epsilon=1e-14);
+        }
+    }
+
+    #[test]
+    fn hermitian_propagator_is_unitary() {
+        let h = arr2(&[
+            [Complex::new(1.0, 0.0), Complex::new(0.5, -0.75), Complex::new(0.0, 0.25)],
+            [Complex::new(0.5, 0.75), Complex::new(-2.0, 0.0), Complex::new(0.25, 1.0)],
+            [Complex::new(0.0, -0.25), Complex::new(0.25, -1.0), Complex::new(3.0, 0.0)],
+        ]);
+        let mut u = Array2::<Complex<f64>>::zeros((3, 3));
+
+        crate::expm_skew_hermitian(&h, 1000.0, &mut u);
+
+        let gram = u.t().mapv(|x| x.conj()).dot(&u);
+        for ((i, j), &x) in gram.indexed_iter() {
+            assert_relative_eq!((x - if i == j { 1.0 } else { 0.0 }).norm(), 0.0, epsilon=1e-13);
+        }
+
+        // For short times, the propagator agrees with exp of the real block form of -iHt =
+        // Kt - iSt, [[Kt, St], [-St, Kt]], whose left blocks are Re and Im.
+        let t = 0.3;
+        crate::expm_skew_hermitian(&h, t, &mut u);
+        let mut block = Array2::<f64>::zeros((6, 6));
+        block.slice_mut(s![..3, ..3]).assign(&h.mapv(|x| x.im * t));
+        block.slice_mut(s![3.., 3..]).assign(&h.mapv(|x| x.im * t));
+        block.slice_mut(s![..3, 3..]).assign(&h.mapv(|x| x.re * t));
+        block.slice_mut(s![3.., ..3]).assign(&h.mapv(|x| -x.re * t));
+        let mut exp_block = Array2::<f64>::zeros((6, 6));
+        crate::expm(&block, &mut exp_block);
+        for ((u, &re), &im) in u.iter().zip(exp_block.slice(s![..3, ..3]).iter()).zip(exp_block.slice(s![3.., ..3]).iter()) {
+            assert_relative_eq!(u.re, re, epsilon=1e-14);
+            assert_relative_eq!(u.im, im, epsilon=1e-14);
+        }
+    }
+
+    #[test]
+    fn skew_symmetric_is_orthogonal() {
+        let a = arr2(&[[0.0, 40.0, -15.0], [-40.0, 0.0, 30.0], [15.0, -30.0, 0.0]]);
+        let mut b = Array2::<f64>::zeros((3, 3));
+
+        let mut unitary = crate::ExpmUnitary::new(3);
+        unitary.expm_skew_symmetric(&a, &mut b);
+        assert!(unitary.counters().orthogonalizations > 0);
+
+        let gram = b.t().dot(&b);
+        for ((i, j), &x) in gram.indexed_iter() {
+            assert_relative_eq!(x, if i == j { 1.0 } else { 0.0 }, epsilon=4.0 * f64::EPSILON);
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! Rigorous per-entry error bounds for the matrix exponential of small matrices.
+//!
+//! The result of `expm` is accompanied by an enclosure $[L, U]$ with $L \le e^A \le U$
+//! entrywise, from which absolute and relative error bounds of every entry follow. The enclosure
+//! is calculated in interval arithmetic: with $B = 2^{-s} A$ scaled to $\lVert B \rVert_\infty \le
+//! 1/2$, the Taylor polynomial
+//!
+//! \begin{equation}
+//!     T_K(B) = \sum_{k=0}^K \frac{B^k}{k!}
+//! \end{equation}
+//!
+//! is evaluated with interval matrices, the truncation error is enclosed entrywise by
+//!
+//! \begin{equation}
+//!     \lvert (e^B - T_K(B))_{ij} \rvert \le \lVert e^B - T_K(B) \rVert_\infty
+//!     \le \frac{\lVert B \rVert_\infty^{K+1}}{(K+1)!} \frac{1}{1 - \lVert B \rVert_\infty / (K+2)},
+//! \end{equation}
+//!
+//! and the enclosure of $e^B$ is squared $s$ times. Directed rounding is emulated by widening
+//! the result of every operation outwards by two units in the last place plus the smallest
+//! normal number, which covers the rounding errors of round-to-nearest including underflow. The
+//! scalar interval operations cost $O(n^3)$ per product without BLAS, so that this mode is only
+//! available for n ≤ `MAX_VERIFIED_DIMENSION`. The interval squarings widen the enclosure by a
+//! factor that grows with $s$, so that the bounds are tight for moderate norms and become
+//! pessimistic for matrices of large norm.
+
+use ndarray::{
+    self,
+    prelude::*,
+    Data,
+    DataMut,
+    Zip,
+};
+
+use crate::{
+    Expm,
+    Float,
+};
+
+/// The largest dimension for which verified error bounds are calculated.
+pub const MAX_VERIFIED_DIMENSION: usize = 32;
+
+/// The degree of the Taylor polynomial. For $\lVert B \rVert_\infty \le 1/2$ the truncation error
+/// is below $10^{-26}$.
+const TAYLOR_DEGREE: usize = 20;
+
+/// Storage for calculating the matrix exponential of n×n matrices with rigorous error bounds.
+pub struct ExpmVerified<T = f64> {
+    n: usize,
+    expm: Expm<T>,
+    b_lower: Array2<T>,
+    b_upper: Array2<T>,
+    term_lower: Array2<T>,
+    term_upper: Array2<T>,
+    lower: Array2<T>,
+    upper: Array2<T>,
+    work_lower: Array2<T>,
+    work_upper: Array2<T>,
+}
+
+impl<T: Float> ExpmVerified<T> {
+    /// Allocates all space to calculate verified exponentials of n×n matrices.
+    ///
+    /// NOTE: Panics if n is larger than `MAX_VERIFIED_DIMENSION`.
+    pub fn new(n: usize) -> Self {
+        assert!(n <= MAX_VERIFIED_DIMENSION, "Verified error bounds are only available for dimensions up to {}.", MAX_VERIFIED_DIMENSION);
+        let zeros = || Array2::<T>::zeros((n, n));
+
+        ExpmVerified {
+            n,
+            expm: Expm::new(n),
+            b_lower: zeros(),
+            b_upper: zeros(),
+            term_lower: zeros(),
+            term_upper: zeros(),
+            lower: zeros(),
+            upper: zeros(),
+            work_lower: zeros(),
+            work_upper: zeros(),
+        }
+    }
+
+    /// Calculate the matrix exponential of the n×n matrix `a`, storing the result in `b`, and
+    /// rigorous bounds on the absolute and relative errors of each entry of `b` in `abs_error`
+    /// and `rel_error`. The relative error bound of a vanishing entry is infinite, and all
+    /// bounds are infinite if the enclosure overflows.
+    ///
+    /// NOTE: Panics if the matrices don't have matching dimensions, are not square, not in
+    /// row-major order, or don't have the same dimension as the `ExpmVerified` object `expm` is
+    /// called on.
+    pub fn expm<S1, S2, S3, S4>(&mut self, a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>, abs_error: &mut ArrayBase<S3, Ix2>, rel_error: &mut ArrayBase<S4, Ix2>)
+        where S1: Data<Elem=T>,
+              S2: DataMut<Elem=T>,
+              S3: DataMut<Elem=T>,
+              S4: DataMut<Elem=T>,
+    {
+        let n = self.n;
+        assert_eq!(abs_error.dim(), (n, n), "Dimension mismatch between matrix `abs_error` and preconfigured `ExpmVerified` struct.");
+        assert_eq!(rel_error.dim(), (n, n), "Dimension mismatch between matrix `rel_error` and preconfigured `ExpmVerified` struct.");
+
+        self.expm.expm(a, b);
+        self.enclose(a);
+
+        Zip::from(abs_error)
+            .and(rel_error)
+            .and(&*b)
+            .and(&self.lower)
+            .and(&self.upper)
+            .apply(|abs_error, rel_error, &x, &lower, &upper| {
+                let error = up(upper - x).max(up(x - lower));
+                *abs_error = if error.is_nan() { T::infinity() } else { error };
+                *rel_error = if x == T::zero() { T::infinity() } else { up(*abs_error / x.abs()) };
+            });
+    }
+
+    /// The enclosure $[L, U]$ of $e^A$ calculated by the last call to `expm`.
+    pub fn enclosure(&self) -> (ArrayView2<'_, T>, ArrayView2<'_, T>) {
+        (self.lower.view(), self.upper.view())
+    }
+
+    /// Calculates the enclosure of $e^A$ in `lower` and `upper`.
+    fn enclose<S>(&mut self, a: &ArrayBase<S, Ix2>)
+        where S: Data<Elem=T>,
+    {
+        let half = T::from_f64(0.5).unwrap();
+        let two = T::from_f64(2.0).unwrap();
+
+        // Choose s such that the upper bound of ‖2⁻ˢ A‖∞ is at most 1/2.
+        let norm_a = row_norm_upper(a, a);
+        let mut s = if norm_a > half {
+            (two * norm_a).log2().ceil().to_i32().unwrap_or(0).max(0)
+        } else {
+            0
+        };
+        let mut rho;
+        loop {
+            let factor = two.powi(-s);
+            Zip::from(&mut self.b_lower).and(&mut self.b_upper).and(a).apply(|lower, upper, &x| {
+                *lower = down(x * factor);
+                *upper = up(x * factor);
+            });
+            rho = row_norm_upper(&self.b_lower, &self.b_upper);
+            if rho <= half || !rho.is_finite() {
+                break;
+            }
+            s += 1;
+        }
+
+        // Σ_{k≤K} Bᵏ/k!
+        set_identity(&mut self.lower);
+        set_identity(&mut self.upper);
+        set_identity(&mut self.term_lower);
+        set_identity(&mut self.term_upper);
+        for k in 1..=TAYLOR_DEGREE {
+            interval_product(&self.term_lower, &self.term_upper, &self.b_lower, &self.b_upper, &mut self.work_lower, &mut self.work_upper);
+            let k = T::from_usize(k).unwrap();
+            Zip::from(&mut self.term_lower).and(&mut self.term_upper).and(&self.work_lower).and(&self.work_upper)
+                .apply(|term_lower, term_upper, &lower, &upper| {
+                    *term_lower = down(lower / k);
+                    *term_upper = up(upper / k);
+                });
+            Zip::from(&mut self.lower).and(&mut self.upper).and(&self.term_lower).and(&self.term_upper)
+                .apply(|lower, upper, &term_lower, &term_upper| {
+                    *lower = down(*lower + term_lower);
+                    *upper = up(*upper + term_upper);
+                });
+        }
+
+        // ‖B‖^{K+1}/(K+1)! / (1 - ‖B‖/(K+2))
+        let mut remainder = T::one();
+        for j in 1..=TAYLOR_DEGREE + 1 {
+            remainder = up(up(remainder * rho) / T::from_usize(j).unwrap());
+        }
+        let denominator = down(T::one() - up(rho / T::from_usize(TAYLOR_DEGREE + 2).unwrap()));
+        remainder = up(remainder / denominator);
+        self.lower.mapv_inplace(|x| down(x - remainder));
+        self.upper.mapv_inplace(|x| up(x + remainder));
+
+        for _ in 0..s {
+            interval_product(&self.lower, &self.upper, &self.lower, &self.upper, &mut self.work_lower, &mut self.work_upper);
+            std::mem::swap(&mut self.lower, &mut self.work_lower);
+            std::mem::swap(&mut self.upper, &mut self.work_upper);
+        }
+    }
+}
+
+/// Rounds the result `x` of a floating point operation upwards, so that it is not smaller than
+/// the exact result.
+fn up<T: Float>(x: T) -> T {
+    let two = T::from_f64(2.0).unwrap();
+    x + (two * T::epsilon() * x.abs() + T::min_positive_value())
+}
+
+/// Rounds the result `x` of a floating point operation downwards, so that it is not larger than
+/// the exact result.
+fn down<T: Float>(x: T) -> T {
+    let two = T::from_f64(2.0).unwrap();
+    x - (two * T::epsilon() * x.abs() + T::min_positive_value())
+}
+
+fn set_identity<T: Float>(a: &mut Array2<T>) {
+    a.fill(T::zero());
+    a.diag_mut().fill(T::one());
+}
+
+/// An upper bound on the ∞-norm of all matrices in the interval matrix [`lower`, `upper`].
+fn row_norm_upper<T, S1, S2>(lower: &ArrayBase<S1, Ix2>, upper: &ArrayBase<S2, Ix2>) -> T
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: Data<Elem=T>,
+{
+    lower.genrows().into_iter().zip(upper.genrows())
+        .map(|(lower, upper)| lower.iter().zip(upper.iter()).fold(T::zero(), |acc, (&l, &u)| up(acc + l.abs().max(u.abs()))))
+        .fold(T::zero(), T::max)
+}
+
+/// Encloses the product of the interval matrices [`a_lower`, `a_upper`] and [`b_lower`,
+/// `b_upper`] in [`c_lower`, `c_upper`].
+fn interval_product<T: Float>(a_lower: &Array2<T>, a_upper: &Array2<T>, b_lower: &Array2<T>, b_upper: &Array2<T>, c_lower: &mut Array2<T>, c_upper: &mut Array2<T>) {
+    let (n, _) = a_lower.dim();
+    for i in 0..n {
+        for j in 0..n {
+            let mut lower = T::zero();
+            let mut upper = T::zero();
+            for k in 0..n {
+                let (al, au, bl, bu) = (a_lower[(i, k)], a_upper[(i, k)], b_lower[(k, j)], b_upper[(k, j)]);
+                let products = [al * bl, al * bu, au * bl, au * bu];
+                let product_lower = products.iter().fold(T::infinity(), |acc, &p| acc.min(p));
+                let product_upper = products.iter().fold(T::neg_infinity(), |acc, &p| acc.max(p));
+                lower = down(lower + down(product_lower));
+                upper = up(upper + up(product_upper));
+            }
+            c_lower[(i, j)] = lower;
+            c_upper[(i, j)] = upper;
+        }
+    }
+}
+
+/// Calculate the matrix exponential of the n×n matrix `a`, storing the result in `b`, and
+/// rigorous bounds on the absolute and relative errors of each entry of `b` in `abs_error` and
+/// `rel_error`.
+///
+/// NOTE: Panics if n is larger than `MAX_VERIFIED_DIMENSION`, or if the matrices don't have
+/// matching dimensions, are not square, or not in row-major order.
+pub fn expm_verified<T, S1, S2, S3, S4>(a: &ArrayBase<S1, Ix2>, b: &mut ArrayBase<S2, Ix2>, abs_error: &mut ArrayBase<S3, Ix2>, rel_error: &mut ArrayBase<S4, Ix2>)
+    where T: Float,
+          S1: Data<Elem=T>,
+          S2: DataMut<Elem=T>,
+          S3: DataMut<Elem=T>,
+          S4: DataMut<Elem=T>,
+{
+    let (n, _) = a.dim();
+
+    let mut verified = ExpmVerified::new(n);
+    verified.expm(a, b, abs_error, rel_error);
+}
+
+#[cfg(test)]
+mod tests {
+    use ndarray::prelude::*;
+
+    #[test]
+    fn bounds_enclose_exact_exponential() {
+        // exp([[λ, 1], [0, μ]]) = [[e^λ, (e^λ - e^μ)/(λ - μ)], [0, e^μ]]
+        let (lambda, mu) = (-0.75f64, 1.5f64);
+        let a = arr2(&[[lambda, 1.0], [0.0, mu]]);
+        let exact = arr2(&[[lambda.exp(), (lambda.exp() - mu.exp()) / (lambda - mu)], [0.0, mu.exp()]]);
+
+        let mut b = Array2::<f64>::zeros((2, 2));
+        let mut abs_error = Array2::<f64>::zeros((2, 2));
+        let mut rel_error = Array2::<f64>::zeros((2, 2));
+        crate::expm_verified(&a, &mut b, &mut abs_error, &mut rel_error);
+
+        for ((&x, &y), &error) in b.iter().zip(exact.iter()).zip(abs_error.iter()) {
+            // The reference itself carries a few rounding errors.
+            assert!((x - y).abs() <= error + 4.0 * f64::EPSILON * y.abs());
+            assert!(error <= 1e-13 * y.abs().max(1.0));
+        }
+        assert!(rel_error[(1, 0)].is_infinite());
+        assert!(rel_error[(0, 1)] < 1e-13);
+    }
+
+    #[test]
+    fn bounds_are_tight_for_moderate_norms() {
+        let a = arr2(&[[-1.0, 2.0, 0.5], [0.5, -3.0, 1.0], [0.25, 0.25, -0.5]]);
+        let mut b = Array2::<f64>::zeros((3, 3));
+        let mut abs_error = Array2::<f64>::zeros((3, 3));
+        let mut rel_error = Array2::<f64>::zeros((3, 3));
+
+        let mut verified = super::ExpmVerified::new(3);
+        verified.expm(&a, &mut b, &mut abs_error, &mut rel_error);
+        let (lower, upper) = verified.enclosure();
+
+        for (((&x, &l), &u), &r) in b.iter().zip(lower.iter()).zip(upper.iter()).zip(rel_error.iter()) {
+            assert!(l <= u);
+            assert!(r < 1e-12, "relative error bound {}", r);
+            assert!(x >= l - 1e-12 && x <= u + 1e-12);
+        }
+    }
+}